@@ -0,0 +1,8 @@
+//! Run with `cargo +nightly fuzz run tokenize` in `fuzz/`, the checks themselves live in
+//! `papyrus_lib::fuzz` so `cargo test --features fuzzing` can smoke test them.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| papyrus_lib::fuzz::tokenize(data));
@@ -0,0 +1,181 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr, Variant};
+
+/// Derives `ParseTag` and `Display` for an enum of tags.
+///
+/// Every variant has a single field holding the tag's payload, whose kind is given by
+/// `#[papyrus(payload = "…")]`, either `"string"` or an integer type like `"u64"`. Tags are
+/// named after their variant in lowercase, unless `name = "…"` says otherwise, and display as
+/// `name:payload`.
+///
+/// ```
+/// use papyrus_derive::PapyrusTag;
+/// use papyrus_lib::{Token, Tokenizer};
+///
+/// #[derive(Clone, Debug, PartialEq, PapyrusTag)]
+/// enum Shop {
+///     #[papyrus(payload = "string")]
+///     Sku(String),
+///     #[papyrus(name = "order", payload = "u64")]
+///     OrderId(u64),
+/// }
+///
+/// let tokens = Tokenizer::default().tokenize_as::<Shop>("[order: 7]").unwrap();
+/// assert_eq!(tokens, [Token::Tag(Shop::OrderId(7))]);
+/// assert_eq!(Shop::Sku("A-1".to_string()).to_string(), "sku:A-1");
+/// ```
+#[proc_macro_derive(PapyrusTag, attributes(papyrus))]
+pub fn derive_papyrus_tag(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+enum Payload {
+    String,
+    Integer,
+}
+
+struct TagVariant<'a> {
+    variant: &'a Variant,
+    name: String,
+    payload: Payload,
+}
+
+const INTEGERS: [&str; 12] = [
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "PapyrusTag can only be derived for enums",
+        ));
+    };
+
+    let mut variants: Vec<TagVariant> = Vec::new();
+    for variant in &data.variants {
+        let variant = parse_variant(variant)?;
+        if variants.iter().any(|known| known.name == variant.name) {
+            return Err(syn::Error::new_spanned(
+                variant.variant,
+                format!("duplicate tag name `{}`", variant.name),
+            ));
+        }
+        variants.push(variant);
+    }
+
+    let parse_arms = variants.iter().map(|v| {
+        let (ident, name) = (&v.variant.ident, &v.name);
+        let value = match v.payload {
+            Payload::String => quote! {
+                ::core::result::Result::Ok(Self::#ident(::core::convert::From::from(payload)))
+            },
+            Payload::Integer => quote! {
+                payload
+                    .parse()
+                    .map(Self::#ident)
+                    .map_err(::papyrus_lib::TagParseErr::CaptureParseErr)
+            },
+        };
+        quote!(#name => #value,)
+    });
+    let display_arms = variants.iter().map(|v| {
+        let (ident, name) = (&v.variant.ident, &v.name);
+        let format = format!("{name}:{{}}");
+        quote!(Self::#ident(payload) => ::core::write!(f, #format, payload),)
+    });
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::papyrus_lib::ParseTag for #ident #ty_generics #where_clause {
+            fn parse_tag(
+                body: &str,
+            ) -> ::core::result::Result<Self, ::papyrus_lib::TagParseErr> {
+                let unknown = || {
+                    ::papyrus_lib::TagParseErr::UnknownTag(
+                        ::papyrus_lib::__private::format!("[{}]", body),
+                    )
+                };
+                let (name, payload) = body.trim().split_once(':').ok_or_else(unknown)?;
+                let payload = payload.trim();
+                if payload.is_empty() {
+                    return ::core::result::Result::Err(::papyrus_lib::TagParseErr::NoCaptures);
+                }
+                match name {
+                    #(#parse_arms)*
+                    _ => ::core::result::Result::Err(unknown()),
+                }
+            }
+        }
+
+        impl #impl_generics ::core::fmt::Display for #ident #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                match self {
+                    #(#display_arms)*
+                }
+            }
+        }
+    })
+}
+
+fn parse_variant(variant: &Variant) -> syn::Result<TagVariant<'_>> {
+    let mut name = None;
+    let mut payload = None;
+    for attr in variant
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("papyrus"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                let lit: LitStr = meta.value()?.parse()?;
+                let value = lit.value();
+                if value.is_empty() || value.contains(|c: char| c == ':' || c.is_whitespace()) {
+                    return Err(syn::Error::new_spanned(lit, "invalid tag name"));
+                }
+                name = Some(value);
+            } else if meta.path.is_ident("payload") {
+                let lit: LitStr = meta.value()?.parse()?;
+                payload = Some(match lit.value().as_str() {
+                    "string" => Payload::String,
+                    ty if INTEGERS.contains(&ty) => Payload::Integer,
+                    ty => {
+                        return Err(syn::Error::new_spanned(
+                            &lit,
+                            format!(
+                                "unsupported payload type `{ty}`, expected \"string\" or an \
+                                 integer type"
+                            ),
+                        ))
+                    }
+                });
+            } else {
+                return Err(meta.error("expected `name` or `payload`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    if !matches!(&variant.fields, Fields::Unnamed(fields) if fields.unnamed.len() == 1) {
+        return Err(syn::Error::new_spanned(
+            variant,
+            "tag variants need a single unnamed field for their payload",
+        ));
+    }
+    let Some(payload) = payload else {
+        return Err(syn::Error::new_spanned(
+            variant,
+            "missing `#[papyrus(payload = \"…\")]`",
+        ));
+    };
+    Ok(TagVariant {
+        variant,
+        name: name.unwrap_or_else(|| variant.ident.to_string().to_lowercase()),
+        payload,
+    })
+}
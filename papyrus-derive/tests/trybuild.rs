@@ -0,0 +1,7 @@
+#[test]
+pub fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass.rs");
+    t.compile_fail("tests/ui/duplicate_name.rs");
+    t.compile_fail("tests/ui/unsupported_payload.rs");
+}
@@ -0,0 +1,11 @@
+use papyrus_derive::PapyrusTag;
+
+#[derive(Clone, PapyrusTag)]
+enum Shop {
+    #[papyrus(payload = "u64")]
+    Order(u64),
+    #[papyrus(name = "order", payload = "string")]
+    LegacyOrder(String),
+}
+
+fn main() {}
@@ -0,0 +1,33 @@
+use papyrus_derive::PapyrusTag;
+use papyrus_lib::{TagParseErr, Token, TokenizeErr, Tokenizer, UnknownTags};
+
+#[derive(Clone, Debug, PartialEq, PapyrusTag)]
+enum Shop {
+    #[papyrus(name = "sku", payload = "string")]
+    Product(String),
+    #[papyrus(payload = "u32")]
+    Order(u32),
+}
+
+fn main() {
+    let tokenizer = Tokenizer::builder().unknown_tags(UnknownTags::Text).build();
+    assert_eq!(
+        tokenizer.tokenize_as::<Shop>("[sku: A-1 ] in [order:7], [user:1]"),
+        Ok(vec![
+            Token::Tag(Shop::Product("A-1".to_string())),
+            Token::Text(" in ".to_string()),
+            Token::Tag(Shop::Order(7)),
+            Token::Text(", [user:1]".to_string()),
+        ])
+    );
+    assert!(matches!(
+        Tokenizer::default().tokenize_as::<Shop>("[order:x]"),
+        Err(TokenizeErr::TagErr(_, TagParseErr::CaptureParseErr(_)))
+    ));
+    assert!(matches!(
+        Tokenizer::default().tokenize_as::<Shop>("[sku:]"),
+        Err(TokenizeErr::TagErr(_, TagParseErr::NoCaptures))
+    ));
+    assert_eq!(Shop::Product("A-1".to_string()).to_string(), "sku:A-1");
+    assert_eq!(Shop::Order(7).to_string(), "order:7");
+}
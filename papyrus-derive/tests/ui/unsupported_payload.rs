@@ -0,0 +1,9 @@
+use papyrus_derive::PapyrusTag;
+
+#[derive(Clone, PapyrusTag)]
+enum Shop {
+    #[papyrus(payload = "f64")]
+    Price(f64),
+}
+
+fn main() {}
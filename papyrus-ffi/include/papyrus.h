@@ -0,0 +1,53 @@
+#ifndef PAPYRUS_H
+#define PAPYRUS_H
+
+/* Generated by cbindgen from papyrus-ffi, don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define PAPYRUS_OK 0
+
+#define PAPYRUS_NULL_POINTER 1
+
+#define PAPYRUS_INVALID_UTF8 2
+
+#define PAPYRUS_PARSE_ERROR 3
+
+#define PAPYRUS_INVALID_RESOLVER 4
+
+#define PAPYRUS_PANIC 5
+
+/**
+ * Tokenizes `input`, writing the tokens in their serde JSON form to `out_json`.
+ *
+ * # Safety
+ *
+ * `input` must be null or a valid nul terminated string, and `out_json` null or valid for
+ * writes.
+ */
+int papyrus_tokenize_json(const char *input, char **out_json);
+
+/**
+ * Renders `input` as HTML to `out`, looking tags up in `resolver_json`, an object from tags
+ * like `"user:5"` to `{"label": …, "url": …}`. A null `resolver_json` resolves nothing.
+ *
+ * # Safety
+ *
+ * `input` and `resolver_json` must be null or valid nul terminated strings, and `out` null
+ * or valid for writes.
+ */
+int papyrus_render_html(const char *input, const char *resolver_json, char **out);
+
+/**
+ * Releases a string returned by this library, null is ignored.
+ *
+ * # Safety
+ *
+ * `s` must be null or a string returned by this library that wasn't freed yet.
+ */
+void papyrus_free(char *s);
+
+#endif  /* PAPYRUS_H */
@@ -0,0 +1,118 @@
+//! A C ABI for rendering papyrus markup from other runtimes.
+//!
+//! Every function returns one of the `PAPYRUS_*` status codes. Strings handed out through
+//! `out` parameters are owned by the caller and must be released with [`papyrus_free`].
+
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+};
+
+use papyrus_lib::{render_html, Document, Resolved, Tag};
+use serde::Deserialize;
+
+pub const PAPYRUS_OK: c_int = 0;
+pub const PAPYRUS_NULL_POINTER: c_int = 1;
+pub const PAPYRUS_INVALID_UTF8: c_int = 2;
+pub const PAPYRUS_PARSE_ERROR: c_int = 3;
+pub const PAPYRUS_INVALID_RESOLVER: c_int = 4;
+pub const PAPYRUS_PANIC: c_int = 5;
+
+/// Tokenizes `input`, writing the tokens in their serde JSON form to `out_json`.
+///
+/// # Safety
+///
+/// `input` must be null or a valid nul terminated string, and `out_json` null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn papyrus_tokenize_json(
+    input: *const c_char,
+    out_json: *mut *mut c_char,
+) -> c_int {
+    guard(out_json, || {
+        let input = read(input)?;
+        let document = Document::parse(input).map_err(|_| PAPYRUS_PARSE_ERROR)?;
+        Ok(serde_json::to_string(document.tokens()).expect("tokens always serialize"))
+    })
+}
+
+/// Renders `input` as HTML to `out`, looking tags up in `resolver_json`, an object from tags
+/// like `"user:5"` to `{"label": …, "url": …}`. A null `resolver_json` resolves nothing.
+///
+/// # Safety
+///
+/// `input` and `resolver_json` must be null or valid nul terminated strings, and `out` null
+/// or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn papyrus_render_html(
+    input: *const c_char,
+    resolver_json: *const c_char,
+    out: *mut *mut c_char,
+) -> c_int {
+    guard(out, || {
+        let input = read(input)?;
+        let resolver_map: HashMap<String, Entry> = if resolver_json.is_null() {
+            HashMap::new()
+        } else {
+            serde_json::from_str(read(resolver_json)?).map_err(|_| PAPYRUS_INVALID_RESOLVER)?
+        };
+        let document = Document::parse(input).map_err(|_| PAPYRUS_PARSE_ERROR)?;
+
+        let resolver = |tag: &Tag| {
+            resolver_map.get(&tag.to_string()).map(|entry| Resolved {
+                label: entry.label.clone(),
+                url: entry.url.clone(),
+            })
+        };
+        Ok(render_html(document.tokens(), &resolver))
+    })
+}
+
+/// Releases a string returned by this library, null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that wasn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn papyrus_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    label: String,
+    url: Option<String>,
+}
+
+unsafe fn read<'a>(s: *const c_char) -> Result<&'a str, c_int> {
+    if s.is_null() {
+        return Err(PAPYRUS_NULL_POINTER);
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| PAPYRUS_INVALID_UTF8)
+}
+
+// Runs `f`, hands its string out through `out` and turns panics into `PAPYRUS_PANIC`, as
+// unwinding across the C boundary is undefined behavior.
+unsafe fn guard(out: *mut *mut c_char, f: impl FnOnce() -> Result<String, c_int>) -> c_int {
+    if out.is_null() {
+        return PAPYRUS_NULL_POINTER;
+    }
+    *out = ptr::null_mut();
+    // Inputs can't contain nul, so a nul in the output came from a label or url of the
+    // resolver.
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        f().and_then(|s| CString::new(s).map_err(|_| PAPYRUS_INVALID_RESOLVER))
+    }));
+    match result {
+        Ok(Ok(s)) => {
+            *out = s.into_raw();
+            PAPYRUS_OK
+        }
+        Ok(Err(code)) => code,
+        Err(_) => PAPYRUS_PANIC,
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    ffi::{c_char, CStr},
+    ptr,
+};
+
+use papyrus_ffi::*;
+
+fn take(s: *mut c_char) -> String {
+    let string = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+    unsafe { papyrus_free(s) };
+    string
+}
+
+#[test]
+pub fn tokenize_json() {
+    let mut out = ptr::null_mut();
+    let code = unsafe { papyrus_tokenize_json(c"hi [user:5]".as_ptr(), &mut out) };
+    assert_eq!(code, PAPYRUS_OK);
+    assert_eq!(take(out), r#"[{"Text":"hi "},{"Tag":"user:5"}]"#);
+
+    let code = unsafe { papyrus_tokenize_json(c"[usr:5]".as_ptr(), &mut out) };
+    assert_eq!(code, PAPYRUS_PARSE_ERROR);
+    assert!(out.is_null());
+}
+
+#[test]
+pub fn render_html_with_resolver() {
+    let mut out = ptr::null_mut();
+    let code = unsafe {
+        papyrus_render_html(
+            c"hi [user:1] [user:2]".as_ptr(),
+            cr#"{"user:1": {"label": "alice", "url": "/u/alice"}}"#.as_ptr(),
+            &mut out,
+        )
+    };
+    assert_eq!(code, PAPYRUS_OK);
+    assert_eq!(
+        take(out),
+        "hi <a class=\"mention\" data-user-id=\"1\" href=\"/u/alice\">@alice</a> \
+         <span class=\"mention unresolved\" data-user-id=\"2\">[user:2]</span>"
+    );
+
+    let code = unsafe { papyrus_render_html(c"hi".as_ptr(), c"{".as_ptr(), &mut out) };
+    assert_eq!(code, PAPYRUS_INVALID_RESOLVER);
+    let code = unsafe {
+        papyrus_render_html(
+            c"[user:1]".as_ptr(),
+            cr#"{"user:1": {"label": "a\u0000b"}}"#.as_ptr(),
+            &mut out,
+        )
+    };
+    assert_eq!(code, PAPYRUS_INVALID_RESOLVER);
+}
+
+#[test]
+pub fn invalid_arguments() {
+    let mut out = ptr::null_mut();
+    let invalid = [b'h', 0xff, b'i', 0];
+    let code = unsafe { papyrus_tokenize_json(invalid.as_ptr().cast(), &mut out) };
+    assert_eq!(code, PAPYRUS_INVALID_UTF8);
+    assert_eq!(
+        unsafe { papyrus_tokenize_json(ptr::null(), &mut out) },
+        PAPYRUS_NULL_POINTER
+    );
+    assert_eq!(
+        unsafe { papyrus_render_html(c"hi".as_ptr(), ptr::null(), ptr::null_mut()) },
+        PAPYRUS_NULL_POINTER
+    );
+    unsafe { papyrus_free(ptr::null_mut()) };
+}
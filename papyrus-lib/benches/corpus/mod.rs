@@ -0,0 +1,114 @@
+//! Generated inputs for the benchmarks, so they're reproducible without being committed.
+
+// Every benchmark includes this module, but none of them uses all of it.
+#![allow(dead_code)]
+
+pub const KB: usize = 1 << 10;
+pub const MB: usize = 1 << 20;
+
+const WORDS: [&str; 16] = [
+    "the", "quick", "brown", "fox", "jumps", "over", "lazy", "dog", "again", "and", "papyrus",
+    "tokens", "are", "cheap", "to", "scan",
+];
+
+// A xorshift generator with a fixed seed, so every run sees the same input.
+struct Rng(u32);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as usize % n
+    }
+}
+
+fn generate(len: usize, mut piece: impl FnMut(&mut Rng, &mut String)) -> String {
+    let mut rng = Rng(0x2545f491);
+    let mut out = String::with_capacity(len + 64);
+    while out.len() < len {
+        piece(&mut rng, &mut out);
+    }
+    out
+}
+
+/// Sentences without any markup, a line break every few of them.
+pub fn prose(len: usize) -> String {
+    generate(len, |rng, out| {
+        for _ in 0..4 + rng.below(8) {
+            out.push_str(WORDS[rng.below(WORDS.len())]);
+            out.push(' ');
+        }
+        out.push_str(if rng.below(4) == 0 { ".\n" } else { ". " });
+    })
+}
+
+/// Prose like [`prose`], with an article link about every 2 KB.
+pub fn sparse(len: usize) -> String {
+    generate(len, |rng, out| {
+        for _ in 0..4 + rng.below(8) {
+            out.push_str(WORDS[rng.below(WORDS.len())]);
+            out.push(' ');
+        }
+        if rng.below(32) == 0 {
+            out.push_str(&format!("[article:{}] ", rng.below(100_000)));
+        }
+        out.push_str(if rng.below(4) == 0 { ".\n" } else { ". " });
+    })
+}
+
+/// Short chat lines, most of them mentioning someone or linking an article.
+pub fn chat(len: usize) -> String {
+    generate(len, |rng, out| {
+        for _ in 0..1 + rng.below(4) {
+            match rng.below(4) {
+                0 => out.push_str(&format!("[user:{}] ", rng.below(10_000))),
+                1 => out.push_str(&format!("[article:{}] ", rng.below(100_000))),
+                _ => {
+                    out.push_str(WORDS[rng.below(WORDS.len())]);
+                    out.push(' ');
+                }
+            }
+        }
+        out.push('\n');
+    })
+}
+
+/// Chat lines like [`chat`], but their tags have names the tokenizer doesn't know.
+pub fn unknown_tags(len: usize) -> String {
+    const NAMES: [&str; 4] = ["issue", "channel", "team", "commit"];
+    generate(len, |rng, out| {
+        for _ in 0..1 + rng.below(4) {
+            match rng.below(2) {
+                0 => {
+                    let name = NAMES[rng.below(NAMES.len())];
+                    out.push_str(&format!("[{name}:{}] ", rng.below(100_000)));
+                }
+                _ => {
+                    out.push_str(WORDS[rng.below(WORDS.len())]);
+                    out.push(' ');
+                }
+            }
+        }
+        out.push('\n');
+    })
+}
+
+/// Brackets that are never closed, each one a tag running to the end of the input.
+pub fn unclosed(len: usize) -> String {
+    generate(len, |rng, out| {
+        out.push('[');
+        out.push_str(WORDS[rng.below(WORDS.len())]);
+    })
+}
+
+/// Tags with bodies of `body` bytes, which are all rejected as unknown.
+pub fn long_tags(len: usize, body: usize) -> String {
+    generate(len, |rng, out| {
+        out.push('[');
+        while out.len() % body != 0 {
+            out.push_str(WORDS[rng.below(WORDS.len())]);
+        }
+        out.push_str("] ");
+    })
+}
@@ -0,0 +1,28 @@
+//! Rendering throughput of already tokenized input, see `benches/README.md`.
+
+mod corpus;
+
+use corpus::{chat, prose, MB};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use papyrus_lib::{render_html, render_plain, tokenize_lossy, Resolved, Tag};
+
+fn render(c: &mut Criterion) {
+    let resolver = |_: &Tag| None::<Resolved>;
+    let inputs = [("tag_free_1mb", prose(MB)), ("tag_dense_1mb", chat(MB))];
+
+    let mut group = c.benchmark_group("render");
+    for (name, input) in &inputs {
+        let tokens = tokenize_lossy(input);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(format!("html_{name}"), |b| {
+            b.iter(|| render_html(black_box(&tokens), &resolver))
+        });
+        group.bench_function(format!("plain_{name}"), |b| {
+            b.iter(|| render_plain(black_box(&tokens), &resolver))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+//! Tokenizing throughput, see `benches/README.md` for comparing runs.
+
+mod corpus;
+
+use corpus::{chat, long_tags, prose, sparse, unclosed, unknown_tags, KB, MB};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use papyrus_lib::{progress, scan_tags, TokenIter, TokenizerOptions};
+
+fn scan(c: &mut Criterion) {
+    let inputs = [
+        ("tag_free_1kb", prose(KB)),
+        ("tag_free_1mb", prose(MB)),
+        ("tag_dense_1mb", chat(MB)),
+        ("unknown_tags_1mb", unknown_tags(MB)),
+        ("unclosed_1mb", unclosed(MB)),
+        ("long_tags_1mb", long_tags(MB, 64 * KB)),
+    ];
+
+    let mut group = c.benchmark_group("scan");
+    for (name, input) in &inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(*name, |b| {
+            b.iter(|| TokenIter::new(black_box(&input)).count())
+        });
+    }
+    let options = TokenizerOptions {
+        emit_breaks: true,
+        ..Default::default()
+    };
+    let (_, chat) = &inputs[2];
+    group.bench_function("tag_dense_1mb_breaks", |b| {
+        b.iter(|| TokenIter::with_options(black_box(&chat), options).count())
+    });
+    // Should be within noise of `tag_dense_1mb`.
+    group.bench_function("tag_dense_1mb_progress", |b| {
+        b.iter(|| {
+            let tokens = TokenIter::new(black_box(&chat));
+            progress(tokens, 64 * KB, Some(chat.len()), |bytes, total| {
+                black_box((bytes, total));
+            })
+            .count()
+        })
+    });
+    group.finish();
+
+    // Only the tags, against all tokens of the same input.
+    let mut group = c.benchmark_group("scan_tags");
+    for (name, input) in [("tag_sparse_1mb", &sparse(MB)), ("tag_dense_1mb", chat)] {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(format!("tokens_{name}"), |b| {
+            b.iter(|| TokenIter::new(black_box(&input)).count())
+        });
+        group.bench_function(format!("tags_{name}"), |b| {
+            b.iter(|| scan_tags(black_box(input)).count())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -0,0 +1,103 @@
+use alloc::format;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+#[cfg(feature = "tag-blocks")]
+use crate::BlockKind;
+use crate::{Tag, Token};
+
+// Characters the tokenizer treats specially, mixed with multi-byte ones, so generated text
+// hits escapes, tag boundaries and char boundaries far more often than random strings would.
+const INTERESTING: [char; 10] = ['[', ']', '\\', '\n', ' ', ':', 'a', 'ü', '€', '🦀'];
+
+fn text(u: &mut Unstructured) -> Result<String> {
+    let len = u.arbitrary_len::<u32>()?;
+    (0..len)
+        .map(|_| {
+            Ok(if u.ratio(3, 4)? {
+                *u.choose(&INTERESTING)?
+            } else {
+                u.arbitrary()?
+            })
+        })
+        .collect()
+}
+
+// Only built-in tags, custom and keyed ones don't survive a roundtrip without their
+// tokenizer.
+impl<'a> Arbitrary<'a> for Tag {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=6)? {
+            0 => Tag::User(u.arbitrary()?),
+            1 => Tag::Article(u.arbitrary()?),
+            #[cfg(feature = "tag-blocks")]
+            2 => Tag::Quote(u.arbitrary()?),
+            #[cfg(feature = "tag-blocks")]
+            3 => Tag::Spoiler,
+            4 => Tag::Link(format!("https://example.com/{}", u.arbitrary::<u32>()?)),
+            5 => Tag::Var(format!("v{}", u.arbitrary::<u32>()?)),
+            #[cfg(feature = "tag-blocks")]
+            _ => Tag::Close(*u.choose(&[BlockKind::Spoiler, BlockKind::Quote])?),
+            // More mentions in place of the block tags.
+            #[cfg(not(feature = "tag-blocks"))]
+            _ => Tag::User(u.arbitrary()?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Token {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=4)? {
+            0 => Token::Text(text(u)?),
+            1 => Token::Tag(u.arbitrary()?),
+            2 => Token::LineBreak,
+            3 => Token::ParagraphBreak,
+            _ => Token::Highlight(text(u)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::Unstructured;
+
+    use crate::*;
+
+    // Runs `check` against token streams generated from a fixed stream of pseudo random bytes.
+    fn for_each_stream(mut check: impl FnMut(Vec<Token>)) {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..500 {
+            let bytes = (0..512)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect::<Vec<_>>();
+            check(Unstructured::new(&bytes).arbitrary().unwrap());
+        }
+    }
+
+    #[test]
+    pub fn arbitrary_source_roundtrip() {
+        for_each_stream(|tokens| {
+            let source = to_source(&tokens);
+            let doc = Document::parse(&source).unwrap();
+            assert_eq!(doc.to_source(), source);
+            assert_eq!(
+                tags(doc.tokens()).collect::<Vec<_>>(),
+                tags(&tokens).collect::<Vec<_>>()
+            );
+        });
+    }
+
+    #[test]
+    pub fn arbitrary_render_never_panics() {
+        for_each_stream(|tokens| {
+            render_html(&tokens, &|_: &Tag| None);
+            render_plain(&tokens, &|_: &Tag| None);
+            paragraphs(tokens);
+        });
+    }
+}
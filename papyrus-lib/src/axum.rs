@@ -0,0 +1,238 @@
+//! Papyrus markup in axum handlers: [`ValidatedMarkup`] extracts a posted document, rejecting
+//! it with its diagnostics, and [`RenderedHtml`] responds with tokens rendered as HTML.
+//!
+//! ```
+//! use axum::{routing::post, Router};
+//! use papyrus_lib::axum::{RenderedHtml, ValidatedMarkup};
+//! use papyrus_lib::{Resolved, Tag};
+//!
+//! fn resolve(tag: &Tag) -> Option<Resolved> {
+//!     None
+//! }
+//!
+//! async fn preview(markup: ValidatedMarkup) -> RenderedHtml<fn(&Tag) -> Option<Resolved>> {
+//!     RenderedHtml::new(markup.tokens, resolve)
+//! }
+//!
+//! let app: Router = Router::new().route("/preview", post(preview));
+//! ```
+
+use std::fmt::Display;
+
+use axum::{
+    extract::{rejection::StringRejection, FromRequest, Request},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::{render_html, Message, Resolver, Severity, Token, Tokenizer};
+
+/// Markup posted as the request body, extracted only if it tokenizes with the default
+/// [`Tokenizer`] and has no malformed tags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatedMarkup {
+    pub source: String,
+    pub tokens: Vec<Token>,
+}
+
+impl<S: Send + Sync> FromRequest<S> for ValidatedMarkup {
+    type Rejection = MarkupRejection;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let source = String::from_request(request, state)
+            .await
+            .map_err(MarkupRejection::Body)?;
+        let tokenizer = Tokenizer::default();
+        let diagnostics = tokenizer.validate(&source);
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            let messages = diagnostics.iter().map(|d| d.message()).collect();
+            return Err(MarkupRejection::Invalid(messages));
+        }
+        match tokenizer.tokenize(&source) {
+            Ok(tokens) => Ok(Self { source, tokens }),
+            Err(e) => Err(MarkupRejection::Invalid(vec![e.message()])),
+        }
+    }
+}
+
+/// Why a [`ValidatedMarkup`] couldn't be extracted.
+///
+/// Invalid markup is rejected with `422 Unprocessable Entity` and a JSON body listing what's
+/// wrong, like
+/// `{"diagnostics":[{"line":1,"column":4,"severity":"Error","code":"unknown_tag","message":"unknown tag [usr:1]"}]}`.
+#[derive(Debug)]
+pub enum MarkupRejection {
+    /// The body isn't text.
+    Body(StringRejection),
+    Invalid(Vec<Message>),
+}
+
+impl Display for MarkupRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Body(rejection) => write!(f, "{rejection}"),
+            Self::Invalid(messages) => match messages.first() {
+                Some(first) => write!(f, "invalid markup: {first}"),
+                None => f.write_str("invalid markup"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for MarkupRejection {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Body(rejection) => Some(rejection),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Problem {
+    line: Option<u16>,
+    column: Option<u32>,
+    severity: Severity,
+    code: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct Problems {
+    diagnostics: Vec<Problem>,
+}
+
+impl IntoResponse for MarkupRejection {
+    fn into_response(self) -> Response {
+        let messages = match self {
+            Self::Body(rejection) => return rejection.into_response(),
+            Self::Invalid(messages) => messages,
+        };
+        let diagnostics = messages
+            .into_iter()
+            .map(|message| {
+                let position = message.position;
+                let message = Message {
+                    position: None,
+                    ..message
+                };
+                Problem {
+                    line: position.map(|p| p.line()),
+                    column: position.map(|p| p.column()),
+                    severity: message.severity,
+                    message: message.to_string(),
+                    code: message.code,
+                }
+            })
+            .collect();
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(Problems { diagnostics }),
+        )
+            .into_response()
+    }
+}
+
+/// Tokens rendered by [`render_html`] as a `text/html; charset=utf-8` response.
+#[derive(Clone, Debug)]
+pub struct RenderedHtml<R> {
+    pub tokens: Vec<Token>,
+    pub resolver: R,
+}
+
+impl<R: Resolver> RenderedHtml<R> {
+    pub fn new(tokens: Vec<Token>, resolver: R) -> Self {
+        Self { tokens, resolver }
+    }
+}
+
+impl<R: Resolver> IntoResponse for RenderedHtml<R> {
+    fn into_response(self) -> Response {
+        Html(render_html(&self.tokens, &self.resolver)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::{axum::*, Resolved, Tag};
+
+    fn resolve(tag: &Tag) -> Option<Resolved> {
+        matches!(tag, Tag::User(1)).then(|| Resolved {
+            label: "alice".to_string(),
+            url: Some("/u/alice".to_string()),
+        })
+    }
+
+    async fn preview(markup: ValidatedMarkup) -> RenderedHtml<fn(&Tag) -> Option<Resolved>> {
+        RenderedHtml::new(markup.tokens, resolve)
+    }
+
+    async fn post_markup(body: &str) -> (StatusCode, Option<String>, String) {
+        let app = Router::new().route("/preview", post(preview));
+        let request = Request::post("/preview")
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    pub async fn axum_renders_valid_markup() {
+        let (status, content_type, body) = post_markup("hi [user:1] <3").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.as_deref(), Some("text/html; charset=utf-8"));
+        assert_eq!(
+            body,
+            "hi <a class=\"mention\" data-user-id=\"1\" href=\"/u/alice\">@alice</a> &lt;3"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn axum_rejects_invalid_markup() {
+        let (status, content_type, body) = post_markup("ok\n[usr:1] [user:5x]").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({
+                "diagnostics": [
+                    {
+                        "line": 2,
+                        "column": 1,
+                        "severity": "Error",
+                        "code": "unknown_tag",
+                        "message": "unknown tag [usr:1]",
+                    },
+                    {
+                        "line": 2,
+                        "column": 9,
+                        "severity": "Error",
+                        "code": "capture_parse_err",
+                        "message": "\"5x\": invalid id: invalid digit found in string",
+                    },
+                ]
+            })
+        );
+    }
+}
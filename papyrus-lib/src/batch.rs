@@ -0,0 +1,69 @@
+use rayon::prelude::*;
+
+use crate::{tokenize_lossy, Token, TokenIter, TokenizeErr};
+
+/// Tokenizes many documents in parallel, returning their results in the order of `inputs`.
+pub fn tokenize_batch(inputs: &[&str]) -> Vec<Result<Vec<Token>, TokenizeErr>> {
+    inputs
+        .par_iter()
+        .map(|input| TokenIter::new(*input).collect())
+        .collect()
+}
+
+/// Like [`tokenize_batch`], but tokenizing as leniently as [`tokenize_lossy`].
+pub fn tokenize_batch_lossy(inputs: &[&str]) -> Vec<Vec<Token>> {
+    inputs
+        .par_iter()
+        .map(|input| tokenize_lossy(input))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn documents() -> Vec<String> {
+        let pieces = [
+            "hi ",
+            "[user:1]",
+            "[article:22] ",
+            "[bad]",
+            "\\[",
+            "\n",
+            "é",
+        ];
+        let mut state = 0x2545f491u32;
+        (0..300)
+            .map(|_| {
+                (0..state % 40)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 17;
+                        state ^= state << 5;
+                        pieces[state as usize % pieces.len()]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn batch_matches_sequential() {
+        let documents = documents();
+        let inputs = documents.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(
+            tokenize_batch(&inputs),
+            inputs
+                .iter()
+                .map(|input| TokenIter::new(*input).collect())
+                .collect::<Vec<Result<Vec<_>, _>>>()
+        );
+        assert_eq!(
+            tokenize_batch_lossy(&inputs),
+            inputs
+                .iter()
+                .map(|input| tokenize_lossy(input))
+                .collect::<Vec<_>>()
+        );
+    }
+}
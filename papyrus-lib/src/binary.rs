@@ -0,0 +1,89 @@
+//! A compact binary form of token streams, for caching parsed documents.
+//!
+//! Every encoding starts with a format version byte, bumped whenever the layout changes.
+
+use alloc::{vec, vec::Vec};
+use core::fmt::Display;
+
+use crate::Token;
+
+pub const FORMAT_VERSION: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Empty,
+    UnsupportedVersion(u8),
+    Malformed(postcard::Error),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::Empty => write!(f, "empty input"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported format version {v}"),
+            DecodeError::Malformed(e) => write!(f, "malformed input: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+pub fn encode(tokens: &[Token]) -> Vec<u8> {
+    postcard::to_extend(tokens, vec![FORMAT_VERSION]).expect("tokens always serialize")
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<Token>, DecodeError> {
+    let (&version, rest) = bytes.split_first().ok_or(DecodeError::Empty)?;
+    if version != FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    postcard::from_bytes(rest).map_err(DecodeError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::binary::*;
+    use crate::*;
+
+    fn tokens() -> Vec<Token> {
+        vec![
+            Token::Text("hi ".to_string()),
+            Token::Tag(Tag::User(5)),
+            Token::LineBreak,
+            Token::Tag(Tag::Article(9)),
+            Token::ParagraphBreak,
+            Token::Highlight("ünï".to_string()),
+        ]
+    }
+
+    #[test]
+    pub fn binary_roundtrip() {
+        let bytes = encode(&tokens());
+        assert_eq!(bytes[0], FORMAT_VERSION);
+        assert_eq!(decode(&bytes), Ok(tokens()));
+        assert_eq!(decode(&encode(&[])), Ok(vec![]));
+    }
+
+    #[test]
+    pub fn binary_version_mismatch() {
+        let mut bytes = encode(&tokens());
+        bytes[0] = FORMAT_VERSION + 1;
+        assert_eq!(
+            decode(&bytes),
+            Err(DecodeError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+        assert_eq!(decode(&[]), Err(DecodeError::Empty));
+    }
+
+    #[test]
+    pub fn binary_truncated() {
+        let bytes = encode(&tokens());
+        for len in 1..bytes.len() {
+            assert!(matches!(
+                decode(&bytes[..len]),
+                Err(DecodeError::Malformed(_))
+            ));
+        }
+    }
+}
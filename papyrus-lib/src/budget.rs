@@ -0,0 +1,230 @@
+use core::fmt::Display;
+
+use crate::{visit::TokenRef, Token};
+
+/// Why a [`Budget`] stopped reading tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Truncated {
+    /// The next token would have taken the tokens past [`Budget::max_bytes`].
+    MaxBytes,
+    /// There were more tokens than [`Budget::max_tokens`] allows.
+    MaxTokens,
+    /// The callback of [`Budget::cancel_when`] asked to stop.
+    Cancelled,
+}
+
+impl Display for Truncated {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::MaxBytes => "byte budget exhausted",
+            Self::MaxTokens => "token budget exhausted",
+            Self::Cancelled => "cancelled",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Truncated {}
+
+/// Limits on how many tokens are read, see [`Budget::limit`].
+///
+/// The budget is spent by everything it limits, so input fed to a
+/// [`PushParser`](crate::PushParser) in chunks is limited as a whole.
+///
+/// ```
+/// use papyrus_lib::{Budget, PushParser, Truncated};
+///
+/// let mut budget = Budget::new().max_tokens(3);
+/// let mut parser = PushParser::new();
+/// let mut tokens = Vec::new();
+/// for chunk in ["a [user:1] b ", "[user:2] c"] {
+///     tokens.extend(budget.limit(parser.feed(chunk)));
+/// }
+/// assert_eq!(tokens.len(), 3);
+/// assert_eq!(budget.truncated(), Some(Truncated::MaxTokens));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Budget<F = fn() -> bool> {
+    max_bytes: Option<usize>,
+    max_tokens: Option<usize>,
+    cancel: Option<(usize, F)>,
+    bytes: usize,
+    tokens: usize,
+    truncated: Option<Truncated>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Budget {
+    /// A budget without limits.
+    pub fn new() -> Self {
+        Self {
+            max_bytes: None,
+            max_tokens: None,
+            cancel: None,
+            bytes: 0,
+            tokens: 0,
+            truncated: None,
+        }
+    }
+}
+
+impl<F: Fn() -> bool> Budget<F> {
+    /// Stops before the token that would take the tokens past `max` bytes in their serialized
+    /// form, which for tokens of a canonical source is the input they were read from.
+    pub fn max_bytes(mut self, max: usize) -> Self {
+        self.max_bytes = Some(max);
+        self
+    }
+
+    /// Stops after `max` tokens, counting errors.
+    pub fn max_tokens(mut self, max: usize) -> Self {
+        self.max_tokens = Some(max);
+        self
+    }
+
+    /// Stops once `cancel` returns true, which is asked after every `every` tokens, like if a
+    /// deadline passed.
+    ///
+    /// Panics if `every` is 0.
+    pub fn cancel_when<G: Fn() -> bool>(self, every: usize, cancel: G) -> Budget<G> {
+        assert!(
+            every > 0,
+            "cancellation has to be checked every 1 or more tokens"
+        );
+        Budget {
+            max_bytes: self.max_bytes,
+            max_tokens: self.max_tokens,
+            cancel: Some((every, cancel)),
+            bytes: self.bytes,
+            tokens: self.tokens,
+            truncated: self.truncated,
+        }
+    }
+
+    /// Why the budget stopped reading tokens, `None` while it hasn't.
+    pub fn truncated(&self) -> Option<Truncated> {
+        self.truncated
+    }
+
+    /// The tokens and errors of `tokens` until the budget runs out, after which nothing is
+    /// read from any stream it limits.
+    pub fn limit<I, E>(&mut self, tokens: I) -> Limit<'_, I::IntoIter, F>
+    where
+        I: IntoIterator<Item = Result<Token, E>>,
+    {
+        Limit {
+            tokens: tokens.into_iter(),
+            budget: self,
+        }
+    }
+
+    fn spend<E>(&mut self, item: &Result<Token, E>) -> Result<(), Truncated> {
+        let len = match item {
+            Ok(token) => TokenRef::from(token).source_len(),
+            Err(_) => 0,
+        };
+        if self.max_tokens.is_some_and(|max| self.tokens >= max) {
+            return Err(Truncated::MaxTokens);
+        }
+        if self.max_bytes.is_some_and(|max| self.bytes + len > max) {
+            return Err(Truncated::MaxBytes);
+        }
+        self.tokens += 1;
+        self.bytes += len;
+        Ok(())
+    }
+}
+
+/// A token stream limited by a [`Budget`], see [`Budget::limit`].
+#[derive(Debug)]
+pub struct Limit<'b, I, F> {
+    tokens: I,
+    budget: &'b mut Budget<F>,
+}
+
+impl<I, E, F> Iterator for Limit<'_, I, F>
+where
+    I: Iterator<Item = Result<Token, E>>,
+    F: Fn() -> bool,
+{
+    type Item = Result<Token, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let budget = &mut *self.budget;
+        if budget.truncated.is_some() {
+            return None;
+        }
+        let item = self.tokens.next()?;
+        if let Err(truncated) = budget.spend(&item) {
+            budget.truncated = Some(truncated);
+            return None;
+        }
+        if let Some((every, cancel)) = &budget.cancel {
+            if budget.tokens.is_multiple_of(*every) && cancel() {
+                budget.truncated = Some(Truncated::Cancelled);
+            }
+        }
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::*;
+
+    #[test]
+    pub fn budget_max_tokens() {
+        let input = "[user:1] and ".repeat(10_000);
+        let mut budget = Budget::new().max_tokens(5);
+        let tokens = budget
+            .limit(TokenIter::new(&input))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(to_source(&tokens), "[user:1] and [user:1] and [user:1]");
+        assert_eq!(budget.truncated(), Some(Truncated::MaxTokens));
+        assert_eq!(budget.limit(TokenIter::new("more")).count(), 0);
+
+        let mut budget = Budget::new().max_tokens(2);
+        assert_eq!(budget.limit(TokenIter::new("[user:1] a")).count(), 2);
+        assert_eq!(budget.truncated(), None);
+    }
+
+    #[test]
+    pub fn budget_max_bytes() {
+        let mut budget = Budget::new().max_bytes(20);
+        let tokens: Vec<_> = budget
+            .limit(TokenIter::new("[user:1] and [user:2] ok"))
+            .collect();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(budget.truncated(), Some(Truncated::MaxBytes));
+        assert_eq!(Truncated::MaxBytes.to_string(), "byte budget exhausted");
+    }
+
+    #[test]
+    pub fn budget_cancelled() {
+        let polls = Cell::new(0);
+        let mut budget = Budget::new().cancel_when(10, || {
+            polls.set(polls.get() + 1);
+            polls.get() == 3
+        });
+        let input = "[user:1] ".repeat(1_000);
+        let mut parser = PushParser::new();
+        let mut tokens = 0;
+        for chunk in input.as_bytes().chunks(100) {
+            tokens += budget
+                .limit(parser.feed(core::str::from_utf8(chunk).unwrap()))
+                .count();
+        }
+        assert_eq!(tokens, 30);
+        assert_eq!(polls.get(), 3);
+        assert_eq!(budget.truncated(), Some(Truncated::Cancelled));
+    }
+}
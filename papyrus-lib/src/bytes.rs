@@ -0,0 +1,123 @@
+use core::ops::Range;
+
+use crate::{
+    scan::{tag_range, text_content, Scanned, Scanner},
+    BorrowedToken, Position, TokenizeErr, Tokenizer, TokenizerOptions,
+};
+
+/// Tokenizes bytes that weren't validated as UTF-8.
+///
+/// Only the text and tags actually read have to be valid, a token containing invalid bytes
+/// is skipped and reported as [`TokenizeErr::InvalidUtf8`] at the line of the first invalid
+/// byte.
+pub struct ByteTokenIter<'a> {
+    bytes: &'a [u8],
+    scanner: Scanner,
+}
+
+impl<'a> ByteTokenIter<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::with_options(bytes, TokenizerOptions::default())
+    }
+
+    pub fn with_options(bytes: &'a [u8], options: TokenizerOptions) -> Self {
+        Self {
+            bytes,
+            scanner: Scanner::new(options.into()),
+        }
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.scanner.offset()
+    }
+}
+
+impl<'a> Iterator for ByteTokenIter<'a> {
+    type Item = Result<BorrowedToken<'a>, TokenizeErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (lexeme, position) = self.scanner.next_lexeme(self.bytes)?;
+        Some(token_from_bytes(
+            self.scanner.config(),
+            self.bytes,
+            lexeme,
+            position,
+        ))
+    }
+}
+
+// Materializes a lexeme of input that wasn't validated as UTF-8.
+pub(crate) fn token_from_bytes<'a>(
+    config: &Tokenizer,
+    bytes: &'a [u8],
+    lexeme: Scanned,
+    position: Position,
+) -> Result<BorrowedToken<'a>, TokenizeErr> {
+    let str = |range: Range<usize>| {
+        let bytes = &bytes[range];
+        core::str::from_utf8(bytes).map_err(|e| {
+            TokenizeErr::InvalidUtf8(config.advance(position, &bytes[..e.valid_up_to()]))
+        })
+    };
+    match lexeme {
+        Scanned::LineBreak => Ok(BorrowedToken::LineBreak),
+        Scanned::ParagraphBreak => Ok(BorrowedToken::ParagraphBreak),
+        Scanned::Tag(body) => {
+            str(tag_range(body, bytes.len())).and_then(|raw| config.tag(raw, position))
+        }
+        Scanned::Text(range, escaped) => {
+            let text = str(range)?;
+            text_content(text, escaped, config, position).map(BorrowedToken::Text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn tokenize(bytes: &[u8]) -> Vec<Result<Token, TokenizeErr>> {
+        ByteTokenIter::new(bytes)
+            .map(|t| t.map(Token::from))
+            .collect()
+    }
+
+    #[test]
+    pub fn bytes_multibyte_text() {
+        let input = "héllo [user:1] wörld 🦀 \\[x\\]\n[article:2]";
+        assert_eq!(
+            tokenize(input.as_bytes()),
+            TokenIter::new(input).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    pub fn bytes_invalid_in_text() {
+        assert_eq!(
+            tokenize(b"ok [user:1] a\nb\xffc [user:2]"),
+            vec![
+                Ok(Token::Text("ok ".to_string())),
+                Ok(Token::Tag(Tag::User(1))),
+                Err(TokenizeErr::InvalidUtf8(Position::new(2).with_column(2))),
+                Ok(Token::Tag(Tag::User(2))),
+            ]
+        );
+        // A multi-byte char cut short.
+        assert_eq!(
+            tokenize(b"\xc3"),
+            vec![Err(TokenizeErr::InvalidUtf8(1.into()))]
+        );
+    }
+
+    #[test]
+    pub fn bytes_invalid_in_tag() {
+        assert_eq!(
+            tokenize(b"a\n[user:\xe2\x82]b"),
+            vec![
+                Ok(Token::Text("a\n".to_string())),
+                Err(TokenizeErr::InvalidUtf8(Position::new(2).with_column(7))),
+                Ok(Token::Text("b".to_string())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Caching the tokens of documents that are rendered over and over.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{Token, TokenizeErr, Tokenizer};
+
+struct Entry {
+    source: Box<str>,
+    tokenizer: Tokenizer,
+    tokens: Arc<Vec<Token>>,
+    used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<u64, Entry>,
+    clock: u64,
+}
+
+/// A bounded cache of tokenized documents, evicting the least recently used one when full.
+///
+/// Entries are keyed by the source and the [`Tokenizer`] it was tokenized with, so the
+/// same source tokenized differently is cached separately. Inputs that fail to tokenize
+/// aren't cached.
+pub struct TokenCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl TokenCache {
+    /// # Panics
+    ///
+    /// If `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "token cache capacity must not be zero");
+        Self {
+            capacity,
+            entries: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The tokens of `source`, tokenizing it only if they aren't cached yet.
+    pub fn get(&self, tokenizer: &Tokenizer, source: &str) -> Result<Arc<Vec<Token>>, TokenizeErr> {
+        self.get_with(tokenizer, source, || tokenizer.tokenize(source))
+    }
+
+    fn get_with(
+        &self,
+        tokenizer: &Tokenizer,
+        source: &str,
+        tokenize: impl FnOnce() -> Result<Vec<Token>, TokenizeErr>,
+    ) -> Result<Arc<Vec<Token>>, TokenizeErr> {
+        let key = key(tokenizer, source);
+        {
+            let mut entries = self.lock();
+            entries.clock += 1;
+            let clock = entries.clock;
+            if let Some(entry) = entries.map.get_mut(&key) {
+                if entry.tokenizer == *tokenizer && *entry.source == *source {
+                    entry.used = clock;
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return Ok(entry.tokens.clone());
+                }
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Tokenize without holding the lock, other lookups don't have to wait for it.
+        let tokens = Arc::new(tokenize()?);
+        let mut entries = self.lock();
+        if entries.map.len() >= self.capacity && !entries.map.contains_key(&key) {
+            let oldest = entries.map.iter().min_by_key(|(_, entry)| entry.used);
+            if let Some(&oldest) = oldest.map(|(key, _)| key) {
+                entries.map.remove(&oldest);
+            }
+        }
+        let used = entries.clock;
+        entries.map.insert(
+            key,
+            Entry {
+                source: source.into(),
+                tokenizer: tokenizer.clone(),
+                tokens: tokens.clone(),
+                used,
+            },
+        );
+        Ok(tokens)
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.lock().map.clear();
+    }
+
+    // A panic while holding the lock can't leave the entries inconsistent.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn key(tokenizer: &Tokenizer, source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    tokenizer.hash(&mut hasher);
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, sync::Arc};
+
+    use crate::{cache::TokenCache, *};
+
+    #[test]
+    pub fn cache_tokenizes_once() {
+        let cache = TokenCache::new(4);
+        let tokenizer = Tokenizer::default();
+        let count = Cell::new(0);
+        let counting = |source: &str| {
+            cache.get_with(&tokenizer, source, || {
+                count.set(count.get() + 1);
+                tokenizer.tokenize(source)
+            })
+        };
+        let first = counting("hi [user:1]").unwrap();
+        for _ in 0..10 {
+            assert!(Arc::ptr_eq(&counting("hi [user:1]").unwrap(), &first));
+        }
+        assert_eq!(count.get(), 1);
+        assert_eq!((cache.hits(), cache.misses()), (10, 1));
+
+        // Errors aren't cached.
+        assert!(counting("[bad]").is_err());
+        assert!(counting("[bad]").is_err());
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    pub fn cache_keys_on_tokenizer() {
+        let cache = TokenCache::new(4);
+        let strict = Tokenizer::default();
+        let lenient = Tokenizer::builder().unknown_tags(UnknownTags::Text).build();
+        assert!(cache.get(&strict, "[x]").is_err());
+        assert_eq!(
+            *cache.get(&lenient, "[x]").unwrap(),
+            vec![Token::Text("[x]".to_string())]
+        );
+        let breaks = Tokenizer::builder().emit_breaks(true).build();
+        assert_eq!(cache.get(&strict, "a\nb").unwrap().len(), 1);
+        assert_eq!(cache.get(&breaks, "a\nb").unwrap().len(), 3);
+        assert_eq!(cache.misses(), 4);
+    }
+
+    #[test]
+    pub fn cache_evicts_least_recently_used() {
+        let cache = TokenCache::new(2);
+        let tokenizer = Tokenizer::default();
+        cache.get(&tokenizer, "a").unwrap();
+        cache.get(&tokenizer, "b").unwrap();
+        cache.get(&tokenizer, "a").unwrap();
+        cache.get(&tokenizer, "c").unwrap();
+        assert_eq!(cache.len(), 2);
+        cache.get(&tokenizer, "a").unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (2, 3));
+        cache.get(&tokenizer, "b").unwrap();
+        assert_eq!(cache.misses(), 4);
+    }
+}
@@ -0,0 +1,100 @@
+use alloc::{format, string::String};
+use core::fmt::Write;
+
+use crate::{BorrowedToken, Position, Tag, TagParseErr, Token, TokenizeErr, Tokenizer};
+
+/// Rewrites every tag of the input in its canonical form, leaving everything else byte for
+/// byte as it is.
+///
+/// Canonical tags have a lowercase name, no whitespace, ids without leading zeros, and
+/// aliases replaced by the name they stand for. Names are only matched ignoring case if they
+/// don't parse as they are.
+///
+/// ```
+/// assert_eq!(
+///     papyrus_lib::format("hi  [ User: 05 ], see [article:009 ]").unwrap(),
+///     "hi  [user:5], see [article:9]"
+/// );
+/// ```
+pub fn format(input: &str) -> Result<String, TokenizeErr> {
+    Tokenizer::default().format(input)
+}
+
+/// Whether [`format`] would leave the input as it is.
+pub fn check_format(input: &str) -> Result<bool, TokenizeErr> {
+    Tokenizer::default().check_format(input)
+}
+
+pub(crate) fn format_with(tokenizer: &Tokenizer, input: &str) -> Result<String, TokenizeErr> {
+    let mut out = String::with_capacity(input.len());
+    let (open, close) = (char::from(tokenizer.open), char::from(tokenizer.close));
+    for token in tokenizer.iter(input).spanned() {
+        let (token, span) = match token {
+            Ok(token) => token,
+            Err(TokenizeErr::TagErr(position, TagParseErr::UnknownTag(raw))) => {
+                match lowercase_tag(tokenizer, &raw) {
+                    Some(tag) => write!(out, "{open}{tag}{close}").unwrap(),
+                    None => {
+                        let e = TagParseErr::UnknownTag(raw);
+                        return Err(TokenizeErr::TagErr(position, e));
+                    }
+                }
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        match token {
+            Token::Tag(tag) => write!(out, "{open}{tag}{close}").unwrap(),
+            _ => out.push_str(&input[span.range()]),
+        }
+    }
+    Ok(out)
+}
+
+// The tag with its name lowercased, if that parses.
+fn lowercase_tag(tokenizer: &Tokenizer, raw: &str) -> Option<Tag> {
+    let lowered = match raw.split_once(':') {
+        Some((name, payload)) => format!("{}:{payload}", name.to_lowercase()),
+        None => raw.to_lowercase(),
+    };
+    // Positions only end up in errors, which are dropped.
+    match tokenizer.tag(&lowered, Position::new(1)) {
+        Ok(BorrowedToken::Tag(tag)) => Some(tag),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn format_messy_document() {
+        let tokenizer = Tokenizer::builder().alias("u", "user").unwrap().build();
+        let input =
+            "Thanks  [ User: 05 ],\t[u:5] and [user:5 ]!\n\n  \\[not a tag\\] [article:0012]";
+        let formatted = tokenizer.format(input).unwrap();
+        assert_eq!(
+            formatted,
+            "Thanks  [user:5],\t[user:5] and [user:5]!\n\n  \\[not a tag\\] [article:12]"
+        );
+        assert_eq!(tokenizer.format(&formatted).as_ref(), Ok(&formatted));
+        assert_eq!(tokenizer.check_format(input), Ok(false));
+        assert_eq!(tokenizer.check_format(&formatted), Ok(true));
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn format_keeps_text() {
+        let input = " \t a\\b [quote:1]\r\n\n  x\\\\ [/quote] ";
+        assert_eq!(format(input).as_deref(), Ok(input));
+        assert_eq!(check_format(""), Ok(true));
+        assert_eq!(
+            format("ok [Nope:1]"),
+            Err(TokenizeErr::TagErr(
+                Position::new(1).with_column(4),
+                TagParseErr::UnknownTag("[Nope:1]".to_string())
+            ))
+        );
+    }
+}
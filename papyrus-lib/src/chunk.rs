@@ -0,0 +1,163 @@
+use alloc::string::{String, ToString};
+
+use crate::Token;
+
+// How far back from the limit a split looks for whitespace to split after.
+const LOOK_BACK: usize = 16;
+
+/// A token stream with long text cut into pieces, see [`chunk_text`].
+#[derive(Clone, Debug)]
+pub struct ChunkText<I> {
+    tokens: I,
+    max_len: usize,
+    // The text being cut and how much of it was given out.
+    text: String,
+    cut: usize,
+}
+
+/// Cuts text tokens longer than `max_len` bytes into consecutive text tokens of at most
+/// `max_len` bytes, for consumers that buffer a token at a time. [`normalize`](crate::normalize)
+/// merges them back.
+///
+/// A cut is never inside a character and is moved back to just after whitespace if there is
+/// some shortly before the limit.
+///
+/// Panics if `max_len` is less than 4, the longest a character can be.
+///
+/// ```
+/// use papyrus_lib::{chunk_text, normalize, Token, TokenIter};
+///
+/// let input = "a long line of text";
+/// let tokens = chunk_text(TokenIter::new(input), 8).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(tokens, ["a long ", "line of ", "text"].map(|s| Token::Text(s.to_string())));
+/// assert_eq!(normalize(tokens), [Token::Text(input.to_string())]);
+/// ```
+pub fn chunk_text<T, E, I>(tokens: I, max_len: usize) -> ChunkText<I>
+where
+    I: Iterator<Item = Result<Token<T>, E>>,
+{
+    assert!(
+        max_len >= 4,
+        "text can only be cut into pieces of 4 or more bytes"
+    );
+    ChunkText {
+        tokens,
+        max_len,
+        text: String::new(),
+        cut: 0,
+    }
+}
+
+impl<I> ChunkText<I> {
+    fn next_piece(&mut self) -> Option<String> {
+        let rest = &self.text[self.cut..];
+        if rest.is_empty() {
+            return None;
+        }
+        let len = piece_len(rest, self.max_len);
+        let piece = rest[..len].to_string();
+        self.cut += len;
+        Some(piece)
+    }
+}
+
+// The length of the piece to cut from the front of `text`.
+fn piece_len(text: &str, max_len: usize) -> usize {
+    if text.len() <= max_len {
+        return text.len();
+    }
+    let mut end = max_len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut start = end.saturating_sub(LOOK_BACK);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let window = &text[start..end];
+    match window.rfind(char::is_whitespace) {
+        Some(i) => {
+            let space = window[i..].chars().next().unwrap();
+            start + i + space.len_utf8()
+        }
+        None => end,
+    }
+}
+
+impl<T, E, I> Iterator for ChunkText<I>
+where
+    I: Iterator<Item = Result<Token<T>, E>>,
+{
+    type Item = Result<Token<T>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(piece) = self.next_piece() {
+            return Some(Ok(Token::Text(piece)));
+        }
+        match self.tokens.next()? {
+            Ok(Token::Text(text)) if text.len() > self.max_len => {
+                self.text = text;
+                self.cut = 0;
+                self.next_piece().map(|piece| Ok(Token::Text(piece)))
+            }
+            item => Some(item),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (low, _) = self.tokens.size_hint();
+        let pending = usize::from(self.cut < self.text.len());
+        (low + pending, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn chunked(input: &str, max_len: usize) -> Vec<Token> {
+        chunk_text(TokenIter::new(input), max_len)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn texts(tokens: &[Token]) -> Vec<&str> {
+        tokens.iter().filter_map(Token::as_text).collect()
+    }
+
+    #[test]
+    pub fn chunk_text_at_limit() {
+        let input = "abcdefghij[user:1]0123";
+        let tokens = chunked(input, 4);
+        assert_eq!(texts(&tokens), ["abcd", "efgh", "ij", "0123"]);
+        assert_eq!(tokens[3], Token::Tag(Tag::User(1)));
+
+        // Whitespace shortly before the limit is preferred.
+        assert_eq!(
+            texts(&chunked("hello world again", 10)),
+            ["hello ", "world ", "again"]
+        );
+    }
+
+    #[test]
+    pub fn chunk_text_keeps_characters() {
+        // "é" takes bytes 3 and 4, so a cut at 4 moves back to 3.
+        let tokens = chunked("abcédef", 4);
+        assert_eq!(texts(&tokens), ["abc", "éde", "f"]);
+        let tokens = chunked(&"日本語".repeat(10), 5);
+        assert!(tokens.iter().all(|t| t.as_text().unwrap().len() == 3));
+    }
+
+    #[test]
+    pub fn chunk_text_normalizes_back() {
+        let input = "some text, [user:1] then more text over the limit: 日本語 ünïcödé\n\nend";
+        for max_len in 4..20 {
+            let tokens = chunked(input, max_len);
+            assert!(tokens
+                .iter()
+                .filter_map(Token::as_text)
+                .all(|text| text.len() <= max_len));
+            assert_eq!(normalize(tokens), tokenize_lossy(input), "{max_len}");
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+#[cfg(feature = "tag-blocks")]
+use crate::BlockKind;
+use crate::{visit::TokenRef, Tag, Token};
+
+/// A [`Token`] taking at most 24 bytes, for keeping many of them in memory.
+///
+/// Text is a `Box<str>`, without the spare capacity a `String` may have, and the common tags
+/// are stored inline. The others, which carry strings of their own, are boxed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[repr(u8)]
+pub enum CompactToken {
+    Text(Box<str>),
+    Highlight(Box<str>),
+    LineBreak,
+    ParagraphBreak,
+    User(usize),
+    Article(usize),
+    #[cfg(feature = "tag-blocks")]
+    Quote(usize),
+    #[cfg(feature = "tag-blocks")]
+    Spoiler,
+    #[cfg(feature = "tag-blocks")]
+    Close(BlockKind),
+    /// Custom tags, keys and links.
+    Tag(Box<Tag>),
+}
+
+impl From<Token> for CompactToken {
+    fn from(token: Token) -> Self {
+        match token {
+            Token::Text(text) => Self::Text(text.into()),
+            Token::Highlight(text) => Self::Highlight(text.into()),
+            Token::LineBreak => Self::LineBreak,
+            Token::ParagraphBreak => Self::ParagraphBreak,
+            Token::Tag(Tag::User(id)) => Self::User(id),
+            Token::Tag(Tag::Article(id)) => Self::Article(id),
+            #[cfg(feature = "tag-blocks")]
+            Token::Tag(Tag::Quote(id)) => Self::Quote(id),
+            #[cfg(feature = "tag-blocks")]
+            Token::Tag(Tag::Spoiler) => Self::Spoiler,
+            #[cfg(feature = "tag-blocks")]
+            Token::Tag(Tag::Close(kind)) => Self::Close(kind),
+            Token::Tag(tag) => Self::Tag(Box::new(tag)),
+        }
+    }
+}
+
+impl From<CompactToken> for Token {
+    fn from(token: CompactToken) -> Self {
+        match token {
+            CompactToken::Text(text) => Token::Text(text.into()),
+            CompactToken::Highlight(text) => Token::Highlight(text.into()),
+            CompactToken::LineBreak => Token::LineBreak,
+            CompactToken::ParagraphBreak => Token::ParagraphBreak,
+            CompactToken::User(id) => Token::Tag(Tag::User(id)),
+            CompactToken::Article(id) => Token::Tag(Tag::Article(id)),
+            #[cfg(feature = "tag-blocks")]
+            CompactToken::Quote(id) => Token::Tag(Tag::Quote(id)),
+            #[cfg(feature = "tag-blocks")]
+            CompactToken::Spoiler => Token::Tag(Tag::Spoiler),
+            #[cfg(feature = "tag-blocks")]
+            CompactToken::Close(kind) => Token::Tag(Tag::Close(kind)),
+            CompactToken::Tag(tag) => Token::Tag(*tag),
+        }
+    }
+}
+
+/// Tokens with all their text in a single buffer, each taking 16 bytes plus its text.
+///
+/// Tags that carry strings are kept in a table of their own. Iterating yields [`TokenRef`]s,
+/// so the tokens can be walked and rendered without converting them back.
+///
+/// ```
+/// use papyrus_lib::{render_plain, tokenize_lossy, CompactDocument, Tag};
+///
+/// let tokens = tokenize_lossy("hi [user:1], see [link:https://a.com]");
+/// let compact = CompactDocument::from(tokens.clone());
+/// assert_eq!(render_plain(compact.iter(), &|_: &Tag| None), "hi [user:1], see https://a.com");
+/// assert_eq!(compact.to_tokens(), tokens);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompactDocument {
+    text: String,
+    tokens: Vec<Packed>,
+    tags: Vec<Tag>,
+}
+
+// A token of a `CompactDocument`, with text as the start and length of it in the buffer
+// and tags carrying strings as their index in the table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum Packed {
+    Text(u32, u32),
+    Highlight(u32, u32),
+    LineBreak,
+    ParagraphBreak,
+    User(usize),
+    Article(usize),
+    #[cfg(feature = "tag-blocks")]
+    Quote(usize),
+    #[cfg(feature = "tag-blocks")]
+    Spoiler,
+    #[cfg(feature = "tag-blocks")]
+    Close(BlockKind),
+    Tag(u32),
+}
+
+impl CompactDocument {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a token.
+    ///
+    /// # Panics
+    ///
+    /// If the text or the number of tags carrying strings outgrows `u32`.
+    pub fn push(&mut self, token: Token) {
+        let mut text = |text: &str| {
+            self.text.push_str(text);
+            let end = u32::try_from(self.text.len()).expect("the text is limited to 4 GiB");
+            (end - text.len() as u32, text.len() as u32)
+        };
+        let packed = match token {
+            Token::Text(t) => {
+                let (start, len) = text(&t);
+                Packed::Text(start, len)
+            }
+            Token::Highlight(t) => {
+                let (start, len) = text(&t);
+                Packed::Highlight(start, len)
+            }
+            Token::LineBreak => Packed::LineBreak,
+            Token::ParagraphBreak => Packed::ParagraphBreak,
+            Token::Tag(Tag::User(id)) => Packed::User(id),
+            Token::Tag(Tag::Article(id)) => Packed::Article(id),
+            #[cfg(feature = "tag-blocks")]
+            Token::Tag(Tag::Quote(id)) => Packed::Quote(id),
+            #[cfg(feature = "tag-blocks")]
+            Token::Tag(Tag::Spoiler) => Packed::Spoiler,
+            #[cfg(feature = "tag-blocks")]
+            Token::Tag(Tag::Close(kind)) => Packed::Close(kind),
+            Token::Tag(tag) => {
+                let index = u32::try_from(self.tags.len()).expect("too many tags");
+                self.tags.push(tag);
+                Packed::Tag(index)
+            }
+        };
+        self.tokens.push(packed);
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<TokenRef<'_>> {
+        self.tokens.get(index).map(|&packed| self.unpack(packed))
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = TokenRef<'_>> + '_ {
+        self.tokens.iter().map(|&packed| self.unpack(packed))
+    }
+
+    pub fn to_tokens(&self) -> Vec<Token> {
+        self.iter().map(TokenRef::into_owned).collect()
+    }
+
+    /// Drops the spare capacity of the buffers, once all tokens are pushed.
+    pub fn shrink_to_fit(&mut self) {
+        self.text.shrink_to_fit();
+        self.tokens.shrink_to_fit();
+        self.tags.shrink_to_fit();
+    }
+
+    fn unpack(&self, packed: Packed) -> TokenRef<'_> {
+        let text = |start: u32, len: u32| &self.text[start as usize..(start + len) as usize];
+        match packed {
+            Packed::Text(start, len) => TokenRef::Text(text(start, len)),
+            Packed::Highlight(start, len) => TokenRef::Highlight(text(start, len)),
+            Packed::LineBreak => TokenRef::LineBreak,
+            Packed::ParagraphBreak => TokenRef::ParagraphBreak,
+            Packed::User(id) => TokenRef::Tag(Tag::User(id)),
+            Packed::Article(id) => TokenRef::Tag(Tag::Article(id)),
+            #[cfg(feature = "tag-blocks")]
+            Packed::Quote(id) => TokenRef::Tag(Tag::Quote(id)),
+            #[cfg(feature = "tag-blocks")]
+            Packed::Spoiler => TokenRef::Tag(Tag::Spoiler),
+            #[cfg(feature = "tag-blocks")]
+            Packed::Close(kind) => TokenRef::Tag(Tag::Close(kind)),
+            Packed::Tag(index) => TokenRef::Tag(self.tags[index as usize].clone()),
+        }
+    }
+}
+
+impl Extend<Token> for CompactDocument {
+    fn extend<I: IntoIterator<Item = Token>>(&mut self, tokens: I) {
+        for token in tokens {
+            self.push(token);
+        }
+    }
+}
+
+impl FromIterator<Token> for CompactDocument {
+    fn from_iter<I: IntoIterator<Item = Token>>(tokens: I) -> Self {
+        let mut document = Self::new();
+        document.extend(tokens);
+        document.shrink_to_fit();
+        document
+    }
+}
+
+impl From<Vec<Token>> for CompactDocument {
+    fn from(tokens: Vec<Token>) -> Self {
+        tokens.into_iter().collect()
+    }
+}
+
+impl From<&CompactDocument> for Vec<Token> {
+    fn from(document: &CompactDocument) -> Self {
+        document.to_tokens()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::size_of;
+
+    use crate::{compact::Packed, *};
+
+    #[cfg(feature = "tag-blocks")]
+    fn tokens() -> Vec<Token> {
+        vec![
+            Token::Text("héllo ".to_string()),
+            Token::Tag(Tag::User(1)),
+            Token::LineBreak,
+            Token::Highlight("found".to_string()),
+            Token::Tag(Tag::Custom {
+                name: "ticket".to_string(),
+                value: "PROJ-1".to_string(),
+            }),
+            Token::ParagraphBreak,
+            Token::Tag(Tag::Quote(usize::MAX)),
+            Token::Text(String::new()),
+            Token::Tag(Tag::Link("https://a.com".to_string())),
+            Token::Tag(Tag::Close(BlockKind::Quote)),
+            Token::Tag(Tag::Spoiler),
+            Token::Tag(Tag::UserKey("alice".to_string())),
+            Token::Text("bye".to_string()),
+        ]
+    }
+
+    #[test]
+    pub fn compact_sizes() {
+        assert!(size_of::<CompactToken>() <= 24);
+        assert!(size_of::<CompactToken>() < size_of::<Token>());
+        assert!(size_of::<Packed>() <= 16);
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn compact_roundtrip() {
+        let tokens = tokens();
+        let compact = tokens
+            .iter()
+            .cloned()
+            .map(CompactToken::from)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            compact[4],
+            CompactToken::Tag(Box::new(tokens[4].as_tag().unwrap().clone()))
+        );
+        assert_eq!(
+            compact.into_iter().map(Token::from).collect::<Vec<_>>(),
+            tokens
+        );
+
+        let document = CompactDocument::from(tokens.clone());
+        assert_eq!(document.len(), tokens.len());
+        assert_eq!(document.get(3), Some(TokenRef::Highlight("found")));
+        assert_eq!(document.get(7), Some(TokenRef::Text("")));
+        assert_eq!(document.get(13), None);
+        assert_eq!(Vec::from(&document), tokens);
+        assert_eq!(to_source(&document.to_tokens()), to_source(&tokens));
+        assert!(CompactDocument::new().is_empty());
+    }
+}
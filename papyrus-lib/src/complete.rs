@@ -0,0 +1,120 @@
+use crate::{
+    scan::{Scanned, Scanner},
+    Span, Tokenizer,
+};
+
+/// An unclosed tag being typed, see [`completion_context`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartialTag<'a> {
+    /// What's typed of the name, without leading whitespace.
+    pub name_prefix: &'a str,
+    /// What's typed after the colon, without leading whitespace, if there is one.
+    pub payload_prefix: Option<&'a str>,
+    /// From the opening bracket up to the cursor.
+    pub span: Span,
+}
+
+/// Finds the tag the cursor at byte `cursor` is typing, if any.
+///
+/// The input before the cursor is scanned like the tokenizer does, so escaped brackets don't
+/// start a tag. A tag the cursor is in counts as closed, and thus doesn't need completing,
+/// if the next bracket after the cursor closes it.
+///
+/// ```
+/// use papyrus_lib::{completion_context, PartialTag, Span};
+///
+/// assert_eq!(
+///     completion_context("hello [us", 9),
+///     Some(PartialTag { name_prefix: "us", payload_prefix: None, span: Span::new(6, 9) })
+/// );
+/// ```
+pub fn completion_context(input: &str, cursor: usize) -> Option<PartialTag<'_>> {
+    Tokenizer::default().completion_context(input, cursor)
+}
+
+pub(crate) fn partial_tag<'a>(
+    config: &Tokenizer,
+    input: &'a str,
+    cursor: usize,
+) -> Option<PartialTag<'a>> {
+    let before = input.get(..cursor)?;
+    let mut scanner = Scanner::new(config.clone());
+    let mut last = None;
+    while let Some((lexeme, _)) = scanner.next_lexeme(before.as_bytes()) {
+        last = Some(lexeme);
+    }
+    // The body of a tag closed before the cursor ends before its closing bracket, only that
+    // of an open one runs up to the cursor.
+    let Some(Scanned::Tag(body)) = last else {
+        return None;
+    };
+    if body.end != cursor {
+        return None;
+    }
+    let after = &input.as_bytes()[cursor..];
+    if memchr::memchr2(config.open, config.close, after).is_some_and(|i| after[i] == config.close) {
+        return None;
+    }
+    let typed = &input[body.start..cursor];
+    let (name, payload) = match typed.split_once(':') {
+        Some((name, payload)) => (name, Some(payload.trim_start())),
+        None => (typed, None),
+    };
+    Some(PartialTag {
+        name_prefix: name.trim_start(),
+        payload_prefix: payload,
+        span: Span::new(body.start - 1, cursor),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn partial<'a>(
+        name_prefix: &'a str,
+        payload_prefix: Option<&'a str>,
+        span: Span,
+    ) -> PartialTag<'a> {
+        PartialTag {
+            name_prefix,
+            payload_prefix,
+            span,
+        }
+    }
+
+    #[test]
+    pub fn completion_in_name_and_payload() {
+        assert_eq!(
+            completion_context("hello [us", 9),
+            Some(partial("us", None, Span::new(6, 9)))
+        );
+        assert_eq!(
+            completion_context("[user:1] hi [ user: 4 and more", 21),
+            Some(partial("user", Some("4"), Span::new(12, 21)))
+        );
+        assert_eq!(
+            completion_context("see [article:", 13),
+            Some(partial("article", Some(""), Span::new(4, 13)))
+        );
+        assert_eq!(
+            completion_context("then [ later [user:1]", 6),
+            Some(partial("", None, Span::new(5, 6)))
+        );
+    }
+
+    #[test]
+    pub fn completion_outside_open_tags() {
+        // Plain text, escaped brackets, and before a tag's opening bracket.
+        assert_eq!(completion_context("hello there", 5), None);
+        assert_eq!(completion_context(r"hello \[us", 10), None);
+        assert_eq!(completion_context("hi [user:1]", 3), None);
+        // Inside or right after a closed tag.
+        assert_eq!(completion_context("hi [user:1] there", 7), None);
+        assert_eq!(completion_context("hi [user:1] there", 11), None);
+        assert_eq!(completion_context("hi [us]", 6), None);
+        // Not a character boundary, or past the end.
+        assert_eq!(completion_context("[é", 2), None);
+        assert_eq!(completion_context("[us", 4), None);
+    }
+}
@@ -0,0 +1,122 @@
+use alloc::string::String;
+
+/// What the tokenizer does with control characters in text, see
+/// [`TokenizerBuilder::control_chars`](crate::TokenizerBuilder::control_chars).
+///
+/// These are the C0 and C1 controls and DEL, except `\n`, `\t` and `\r`, which are always
+/// kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ControlChars {
+    #[default]
+    Keep,
+    /// Removes them, with ANSI escape sequences like `\x1b[31m` removed as a whole. Their `[`
+    /// doesn't open a tag.
+    Strip,
+    /// Fails with [`TokenizeErr::ControlChar`](crate::TokenizeErr::ControlChar) at the first.
+    Error,
+}
+
+pub(crate) fn is_control(c: char) -> bool {
+    c.is_control() && !matches!(c, '\n' | '\t' | '\r')
+}
+
+// The first control character and where it is.
+pub(crate) fn find_control(text: &str) -> Option<(usize, char)> {
+    text.char_indices().find(|&(_, c)| is_control(c))
+}
+
+pub(crate) fn strip_controls(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if !is_control(c) {
+            out.push(c);
+            continue;
+        }
+        // A CSI sequence, `ESC [` or its C1 form, then parameter and intermediate bytes up
+        // to a final byte. One cut short at the end of the text is removed all the same.
+        let csi = c == '\u{9b}' || (c == '\x1b' && chars.clone().next() == Some('['));
+        if !csi {
+            continue;
+        }
+        if c == '\x1b' {
+            chars.next();
+        }
+        let mut rest = chars.clone();
+        while let Some(c) = rest.next() {
+            match c {
+                '\x30'..='\x3f' | '\x20'..='\x2f' => chars = rest.clone(),
+                '\x40'..='\x7e' => {
+                    chars = rest;
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn tokenizer(control_chars: ControlChars) -> Tokenizer {
+        Tokenizer::builder().control_chars(control_chars).build()
+    }
+
+    #[test]
+    pub fn control_chars_policies() {
+        let input = "a\0b [user:1] c";
+        assert_eq!(
+            tokenizer(ControlChars::Keep).tokenize(input).unwrap()[0],
+            Token::Text("a\0b ".to_string())
+        );
+        assert_eq!(
+            tokenizer(ControlChars::Strip).tokenize(input).unwrap()[0],
+            Token::Text("ab ".to_string())
+        );
+        let error = tokenizer(ControlChars::Error).tokenize(input).unwrap_err();
+        assert_eq!(
+            error,
+            TokenizeErr::ControlChar(Position::new(1).with_column(2), '\0')
+        );
+        assert_eq!(
+            error.message().to_string(),
+            "control character U+0000 at line 1"
+        );
+
+        // Escapes and tags before it move it along.
+        let error = tokenizer(ControlChars::Error)
+            .tokenize("[user:1]\n\\[x\x0b")
+            .unwrap_err();
+        assert_eq!(error.position(), Position::new(2).with_column(4));
+    }
+
+    #[test]
+    pub fn control_chars_strip_ansi() {
+        let strip = tokenizer(ControlChars::Strip);
+        assert_eq!(
+            strip
+                .tokenize("\x1b[1;31mred\x1b[0m and \u{9b}4mlined\x1b[")
+                .unwrap(),
+            [Token::Text("red and lined".to_string())]
+        );
+        // Only CSI sequences are known, other escapes lose just the escape.
+        assert_eq!(
+            strip.tokenize("\x1b]0;title\x07 \x1bc").unwrap(),
+            [Token::Text("]0;title c".to_string())]
+        );
+    }
+
+    #[test]
+    pub fn control_chars_keep_whitespace() {
+        let input = "a\tb\r\nc\n\nd";
+        for control_chars in [ControlChars::Keep, ControlChars::Strip, ControlChars::Error] {
+            assert_eq!(
+                tokenizer(control_chars).tokenize(input).unwrap(),
+                [Token::Text(input.to_string())]
+            );
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use alloc::collections::VecDeque;
+
+use crate::{visit::TokenRef, Span, Tag, TagKind, Token};
+
+/// A cursor over tokens, for parsers that need to look further ahead than the next token or
+/// to backtrack.
+///
+/// Tokens are pulled from the iterator only as far as they're looked at, and spans are those
+/// of the tokens' serialized form, like [`walk`](crate::walk) gives.
+///
+/// ```
+/// use papyrus_lib::{tokenize_lossy, Tag, TagKind, Token, TokenCursor};
+///
+/// let mut cursor = TokenCursor::new(tokenize_lossy("hi [user:1] [article:2]"));
+/// assert_eq!(cursor.peek_n(3), Some(&Token::Tag(Tag::Article(2))));
+/// assert_eq!(cursor.eat_text(), Some("hi "));
+///
+/// let checkpoint = cursor.checkpoint();
+/// assert_eq!(cursor.eat_tag(TagKind::User), Some(&Tag::User(1)));
+/// assert_eq!(cursor.eat_tag(TagKind::User), None);
+/// cursor.rollback(checkpoint);
+/// assert_eq!(cursor.offset(), 3);
+/// assert_eq!(cursor.bump(), Some(&Token::Tag(Tag::User(1))));
+/// ```
+#[derive(Debug)]
+pub struct TokenCursor<I: Iterator<Item = Token>> {
+    iter: I,
+    /// Tokens pulled from the iterator, from the oldest one a checkpoint can return to.
+    buffer: VecDeque<(Token, Span)>,
+    /// The index of the next token in the buffer.
+    pos: usize,
+    /// Where the token after the last one in the buffer starts.
+    end: usize,
+    checkpoints: usize,
+}
+
+/// A position of a [`TokenCursor`] to return to, see [`TokenCursor::checkpoint`].
+#[must_use = "keeping tokens for a checkpoint needs it to be rolled back or committed"]
+#[derive(Debug)]
+pub struct Checkpoint {
+    // The buffer isn't drained while there are checkpoints, so this stays valid.
+    pos: usize,
+}
+
+impl<I: Iterator<Item = Token>> TokenCursor<I> {
+    pub fn new(tokens: impl IntoIterator<Item = Token, IntoIter = I>) -> Self {
+        Self {
+            iter: tokens.into_iter(),
+            buffer: VecDeque::new(),
+            pos: 0,
+            end: 0,
+            checkpoints: 0,
+        }
+    }
+
+    /// The token `n` tokens ahead, so `peek_n(0)` is the next one. `None` past the end.
+    pub fn peek_n(&mut self, n: usize) -> Option<&Token> {
+        while self.buffer.len() <= self.pos + n {
+            let token = self.iter.next()?;
+            let start = self.end;
+            self.end += TokenRef::from(&token).source_len();
+            self.buffer.push_back((token, Span::new(start, self.end)));
+        }
+        Some(&self.buffer[self.pos + n].0)
+    }
+
+    pub fn peek(&mut self) -> Option<&Token> {
+        self.peek_n(0)
+    }
+
+    /// Moves past the next token and returns it.
+    pub fn bump(&mut self) -> Option<&Token> {
+        self.peek()?;
+        if self.checkpoints == 0 {
+            // Nothing can return to the bumped tokens anymore.
+            self.buffer.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.pos += 1;
+        Some(&self.buffer[self.pos - 1].0)
+    }
+
+    /// Bumps the next token if it's text, returning its content.
+    pub fn eat_text(&mut self) -> Option<&str> {
+        match self.peek()? {
+            Token::Text(_) => self.bump()?.as_text(),
+            _ => None,
+        }
+    }
+
+    /// Bumps the next token if it's a tag of the given kind.
+    pub fn eat_tag(&mut self, kind: TagKind) -> Option<&Tag> {
+        match self.peek()? {
+            Token::Tag(tag) if tag.kind() == kind => self.bump()?.as_tag(),
+            _ => None,
+        }
+    }
+
+    /// Where the next token starts, or the end of the tokens so far if there's none.
+    pub fn offset(&self) -> usize {
+        match self.buffer.get(self.pos) {
+            Some((_, span)) => span.start,
+            None => self.end,
+        }
+    }
+
+    /// The span of the token bumped last, an empty one at the start if there's none.
+    pub fn span(&self) -> Span {
+        match self.pos.checked_sub(1) {
+            Some(last) => self.buffer[last].1,
+            None => Span::new(self.offset(), self.offset()),
+        }
+    }
+
+    /// Remembers the position, so [`TokenCursor::rollback`] can return to it. Tokens bumped
+    /// after it are kept until it's rolled back or committed.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints += 1;
+        Checkpoint { pos: self.pos }
+    }
+
+    /// Returns to the checkpoint, so the tokens bumped since come again.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.pos = checkpoint.pos;
+        self.checkpoints -= 1;
+    }
+
+    /// Gives up on returning to the checkpoint.
+    pub fn commit(&mut self, _checkpoint: Checkpoint) {
+        self.checkpoints -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn cursor(s: &str) -> TokenCursor<std::vec::IntoIter<Token>> {
+        TokenCursor::new(tokenize_lossy(s))
+    }
+
+    #[test]
+    pub fn cursor_lookahead_past_end() {
+        let mut cursor = cursor("a [user:1]");
+        assert_eq!(cursor.peek_n(2), None);
+        assert_eq!(cursor.peek_n(1), Some(&Token::Tag(Tag::User(1))));
+        assert_eq!(cursor.bump(), Some(&Token::Text("a ".to_string())));
+        assert_eq!(cursor.bump(), Some(&Token::Tag(Tag::User(1))));
+        assert_eq!(cursor.span(), Span::new(2, 10));
+        assert_eq!(cursor.peek_n(0), None);
+        assert_eq!(cursor.bump(), None);
+        assert_eq!(cursor.offset(), 10);
+        assert_eq!(cursor.eat_text(), None);
+    }
+
+    #[test]
+    pub fn cursor_rollback() {
+        let mut cursor = cursor("[user:1] x [article:2] y");
+        assert_eq!(cursor.eat_tag(TagKind::Article), None);
+        assert_eq!(cursor.eat_tag(TagKind::User), Some(&Tag::User(1)));
+        let outer = cursor.checkpoint();
+        assert_eq!(cursor.eat_text(), Some(" x "));
+        let inner = cursor.checkpoint();
+        assert_eq!(cursor.eat_tag(TagKind::Article), Some(&Tag::Article(2)));
+        assert_eq!(cursor.eat_text(), Some(" y"));
+        cursor.rollback(inner);
+        assert_eq!(cursor.offset(), 11);
+        assert_eq!(cursor.peek_n(1), Some(&Token::Text(" y".to_string())));
+        cursor.rollback(outer);
+        assert_eq!(cursor.span(), Span::new(0, 8));
+        assert_eq!(cursor.eat_text(), Some(" x "));
+        let checkpoint = cursor.checkpoint();
+        cursor.bump();
+        cursor.commit(checkpoint);
+        assert_eq!(cursor.eat_text(), Some(" y"));
+        assert_eq!(cursor.bump(), None);
+    }
+}
@@ -0,0 +1,180 @@
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::Display;
+
+use crate::{normalize, Tag, Token};
+
+/// The changes between two token streams, as computed by [`diff`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TokenDiff {
+    pub added_tags: Vec<Tag>,
+    pub removed_tags: Vec<Tag>,
+    pub text_changes: Vec<TextChange>,
+}
+
+/// A region of text that was replaced, either side may be empty for pure insertions and
+/// deletions.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TextChange {
+    pub old: String,
+    pub new: String,
+}
+
+impl TokenDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_tags.is_empty() && self.removed_tags.is_empty() && self.text_changes.is_empty()
+    }
+}
+
+impl Display for TokenDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for tag in &self.added_tags {
+            writeln!(f, "added [{tag}]")?;
+        }
+        for tag in &self.removed_tags {
+            writeln!(f, "removed [{tag}]")?;
+        }
+        for change in &self.text_changes {
+            writeln!(f, "changed {:?} to {:?}", change.old, change.new)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares two token streams token by token.
+///
+/// Both sides are normalized first, so only differences that survive tokenizing are
+/// reported, which means re-spelling a tag like `[user: 05]` as `[user:5]` isn't a change.
+/// Consecutive changed tokens are grouped, and the text within a group becomes one
+/// [`TextChange`].
+pub fn diff<I, J>(old: I, new: J) -> TokenDiff
+where
+    I: IntoIterator<Item = Token>,
+    J: IntoIterator<Item = Token>,
+{
+    let old = normalize(old);
+    let new = normalize(new);
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = TokenDiff::default();
+    let mut hunk = Hunk::default();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            hunk.finish(&mut result);
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            hunk.add(&new[j], &mut result.added_tags, true);
+            j += 1;
+        } else {
+            hunk.add(&old[i], &mut result.removed_tags, false);
+            i += 1;
+        }
+    }
+    hunk.finish(&mut result);
+
+    result
+}
+
+// The text of a run of consecutive changed tokens.
+#[derive(Default)]
+struct Hunk {
+    old: String,
+    new: String,
+}
+
+impl Hunk {
+    fn add(&mut self, token: &Token, tags: &mut Vec<Tag>, added: bool) {
+        let text = if added { &mut self.new } else { &mut self.old };
+        match token {
+            Token::Tag(tag) => tags.push(tag.clone()),
+            Token::Text(t) | Token::Highlight(t) => text.push_str(t),
+            Token::LineBreak => text.push('\n'),
+            Token::ParagraphBreak => text.push_str("\n\n"),
+        }
+    }
+
+    fn finish(&mut self, result: &mut TokenDiff) {
+        if !self.old.is_empty() || !self.new.is_empty() {
+            result.text_changes.push(TextChange {
+                old: core::mem::take(&mut self.old),
+                new: core::mem::take(&mut self.new),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::tokenize, *};
+
+    #[test]
+    pub fn diff_added_mention() {
+        let diff = diff(tokenize("hi all "), tokenize("hi all [user:5]"));
+        assert_eq!(
+            diff,
+            TokenDiff {
+                added_tags: vec![Tag::User(5)],
+                ..Default::default()
+            }
+        );
+        assert_eq!(diff.to_string(), "added [user:5]\n");
+    }
+
+    #[test]
+    pub fn diff_removed_mention() {
+        let diff = diff(
+            tokenize("thanks [user:1] and [user:2]!"),
+            tokenize("thanks [user:2]!"),
+        );
+        assert_eq!(diff.removed_tags, vec![Tag::User(1)]);
+        assert!(diff.added_tags.is_empty());
+        assert_eq!(
+            diff.text_changes,
+            vec![TextChange {
+                old: " and ".to_string(),
+                new: String::new()
+            }]
+        );
+    }
+
+    #[test]
+    pub fn diff_text_only() {
+        let diff = diff(
+            tokenize("see [article:1], it's good"),
+            tokenize("see [article: 01], it's great"),
+        );
+        assert_eq!(
+            diff,
+            TokenDiff {
+                text_changes: vec![TextChange {
+                    old: ", it's good".to_string(),
+                    new: ", it's great".to_string()
+                }],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            diff.to_string(),
+            "changed \", it's good\" to \", it's great\"\n"
+        );
+    }
+
+    #[test]
+    pub fn diff_identical() {
+        let tokens = tokenize("a [user:1] b [article:2]");
+        assert!(diff(tokens.clone(), tokens).is_empty());
+        assert!(diff(vec![], vec![]).is_empty());
+    }
+}
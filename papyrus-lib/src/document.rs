@@ -0,0 +1,1149 @@
+use alloc::{
+    string::{String, ToString},
+    vec::{self, Vec},
+};
+use core::{
+    fmt::{Display, Write},
+    ops::{Index, Range},
+    slice,
+};
+
+use crate::{
+    normalize,
+    scan::Scanner,
+    search::content_offset,
+    trace::{debug, span},
+    walk_spanned, FrontMatter, Position, Rendered, Resolver, Span, Tag, Token, TokenIter,
+    TokenVisitor, TokenizeErr, Tokenizer, TracedToken,
+};
+
+/// A token stream together with the source it was read from and the span of every token.
+///
+/// Documents that weren't parsed from markup use their serialized form as source. Spans
+/// always index the source as it was read, also after changing tokens in place.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Document {
+    source: String,
+    tokens: Vec<Token>,
+    spans: Vec<Span>,
+    front_matter: Option<FrontMatter>,
+}
+
+impl Document {
+    pub fn builder() -> DocumentBuilder {
+        DocumentBuilder::default()
+    }
+
+    pub fn parse(s: &str) -> Result<Self, TokenizeErr> {
+        let (tokens, spans) = TokenIter::new(s)
+            .spanned()
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        Ok(Self {
+            source: s.to_string(),
+            tokens,
+            spans,
+            front_matter: None,
+        })
+    }
+
+    /// Parses like [`Document::parse`], after taking out the [`FrontMatter`] the source starts
+    /// with, if any. Spans and positions still count from the start of the source.
+    ///
+    /// A block that isn't front matter, like one that is never closed, is read as part of the
+    /// body, [`FrontMatter::parse`] tells what's wrong with it. Edits keep reading the front
+    /// matter of documents that have one.
+    pub fn parse_front_matter(s: &str) -> Result<Self, TokenizeErr> {
+        let front_matter = match FrontMatter::parse(s) {
+            Ok(Some(front_matter)) => front_matter,
+            Ok(None) => return Self::parse(s),
+            Err(_error) => {
+                debug!(error = %_error, "read front matter as body");
+                return Self::parse(s);
+            }
+        };
+        let start = front_matter.span().end;
+        let position = Position::new(1).advanced(&s.as_bytes()[..start]);
+        let (tokens, spans) = TokenIter::resume(s, Scanner::at(start, Tokenizer::default()))
+            .with_position(position)
+            .spanned()
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        Ok(Self {
+            source: s.to_string(),
+            tokens,
+            spans,
+            front_matter: Some(front_matter),
+        })
+    }
+
+    /// Parses leniently, keeping tags that fail to parse as text exactly as written.
+    pub fn parse_lossy(s: &str) -> Self {
+        let trace = span!("parse_lossy", input_len = s.len(); tokens, errors);
+        let mut errors = 0;
+        let mut tokens = Vec::new();
+        let mut spans: Vec<Span> = Vec::new();
+        let mut iter = TokenIter::new(s);
+        loop {
+            let start = iter.byte_offset();
+            let token = match iter.next() {
+                Some(Ok(token)) => token,
+                Some(Err(_error)) => {
+                    errors += 1;
+                    debug!(error = %_error, start, end = iter.byte_offset(), "kept malformed tag as text");
+                    Token::Text(s[start..iter.byte_offset()].to_string())
+                }
+                None => break,
+            };
+            let span = Span::new(start, iter.byte_offset());
+            match (tokens.last_mut(), spans.last_mut(), token) {
+                (Some(Token::Text(last)), Some(last_span), Token::Text(text)) => {
+                    last.push_str(&text);
+                    last_span.end = span.end;
+                }
+                (_, _, token) => {
+                    tokens.push(token);
+                    spans.push(span);
+                }
+            }
+        }
+        trace.record("tokens", tokens.len());
+        trace.record("errors", errors);
+        Self {
+            source: s.to_string(),
+            tokens,
+            spans,
+            front_matter: None,
+        }
+    }
+
+    /// Joins documents, separating them by a paragraph break.
+    pub fn concat(parts: &[Document]) -> Self {
+        Self::concat_with(parts, &[Token::ParagraphBreak])
+    }
+
+    /// Joins documents, inserting `separator` between each of them.
+    ///
+    /// The source of the result is the serialized form of the joined tokens.
+    pub fn concat_with(parts: &[Document], separator: &[Token]) -> Self {
+        let mut tokens = Vec::new();
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                tokens.extend_from_slice(separator);
+            }
+            tokens.extend_from_slice(&part.tokens);
+        }
+        tokens.into()
+    }
+
+    /// Replaces the tokens covering the source byte `range` with `replacement`, an empty range
+    /// inserts at that offset.
+    ///
+    /// Text tokens partially covered by the range are cut at its boundaries, which may neither
+    /// split a tag nor a multi-byte character. Afterwards the source is re-serialized from the
+    /// tokens, so the spans stay consistent with it. Front matter is kept as it was, ranges
+    /// reaching into it are out of bounds.
+    pub fn splice<I: IntoIterator<Item = Token>>(
+        &mut self,
+        range: Range<usize>,
+        replacement: I,
+    ) -> Result<(), SliceError> {
+        let start = self
+            .front_matter
+            .as_ref()
+            .map_or(0, |front_matter| front_matter.span().end);
+        if range.start < start {
+            return Err(SliceError::OutOfBounds(range.start));
+        }
+        let [mut tokens, _, after] = self.cut(range)?;
+        tokens.extend(replacement);
+        tokens.extend(after);
+        let (source, tokens, spans) = Self::from(normalize(tokens)).into_parts();
+        self.source.truncate(start);
+        self.source.push_str(&source);
+        self.tokens = tokens;
+        self.spans = spans
+            .iter()
+            .map(|span| Span::new(span.start + start, span.end + start))
+            .collect();
+        Ok(())
+    }
+
+    /// Replaces the source byte `range` with `replacement` and retokenizes, like parsing the
+    /// edited source would.
+    ///
+    /// Only the tokens from the one ending at the edit up to the first one after it that
+    /// starts where it did before are scanned again, the rest are reused. On error the
+    /// document is left unchanged.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) -> Result<(), EditError> {
+        self.replace(range, replacement).map(|_| ())
+    }
+
+    /// Applies an [`Edit`] like [`Document::edit`] does, and returns the indices of the tokens
+    /// that changed.
+    ///
+    /// The tokens before those are as they were, the ones after are the old ones with their
+    /// spans moved by the edit. The range is empty, at the edit, if only that happened.
+    ///
+    /// ```
+    /// use papyrus_lib::{Document, Edit};
+    ///
+    /// let mut doc = Document::parse("hi [user:1], see [article:2]").unwrap();
+    /// assert_eq!(doc.apply_edit(Edit::insert(3, "@")), Ok(0..1));
+    /// assert_eq!(doc.source(), "hi @[user:1], see [article:2]");
+    /// assert_eq!(doc.apply_edit(Edit::delete(4..12)), Ok(0..1));
+    /// assert_eq!(doc.len(), 2);
+    /// ```
+    pub fn apply_edit(&mut self, edit: Edit) -> Result<Range<usize>, EditError> {
+        self.replace(edit.range, &edit.replacement)
+    }
+
+    // Edits the source, returning the range of retokenized tokens that aren't the same.
+    fn replace(
+        &mut self,
+        range: Range<usize>,
+        replacement: &str,
+    ) -> Result<Range<usize>, EditError> {
+        self.check_range(&range)?;
+        let mut source = String::with_capacity(self.source.len() + replacement.len());
+        source.push_str(&self.source[..range.start]);
+        source.push_str(replacement);
+        source.push_str(&self.source[range.end..]);
+        let reparse = |source: &str| match self.front_matter {
+            Some(_) => Self::parse_front_matter(source),
+            None => Self::parse(source),
+        };
+        if self
+            .front_matter
+            .as_ref()
+            .is_some_and(|front_matter| range.start < front_matter.span().end)
+        {
+            *self = reparse(&source)?;
+            return Ok(0..self.tokens.len());
+        }
+
+        // Scanning a token never looks past the first byte of the next one, so tokens ending
+        // before the edit come out the same.
+        let restart = self.spans.partition_point(|span| span.end < range.start);
+        let offset = self
+            .spans
+            .get(restart)
+            .map_or(range.start, |span| span.start);
+        let edit_end = range.start + replacement.len();
+        let shift = |span: &Span| {
+            Span::new(
+                span.start + edit_end - range.end,
+                span.end + edit_end - range.end,
+            )
+        };
+
+        let mut iter =
+            TokenIter::resume(&source, Scanner::at(offset, Tokenizer::default())).spanned();
+        let mut tokens = self.tokens[..restart].to_vec();
+        let mut spans = self.spans[..restart].to_vec();
+        let resume = loop {
+            // Once scanning reaches where an old token after the edit starts, the rest of the
+            // input reads as before.
+            let offset = iter.0.byte_offset();
+            if offset >= edit_end {
+                let old = offset - edit_end + range.end;
+                let index = self.spans.partition_point(|span| span.start < old);
+                if self.spans.get(index).is_some_and(|span| span.start == old) {
+                    break index;
+                }
+            }
+            match iter.next() {
+                Some(Ok((token, span))) => {
+                    tokens.push(token);
+                    spans.push(span);
+                }
+                // Positions are counted from the restart, the full parse gets them right.
+                Some(Err(_)) => return Err(reparse(&source).map(|_| ()).unwrap_err().into()),
+                None => break self.tokens.len(),
+            }
+        };
+        // Scanning again from the token ending at the edit may read some of it as before.
+        let mut changed = restart..tokens.len();
+        while changed.start < changed.end.min(resume)
+            && tokens[changed.start] == self.tokens[changed.start]
+            && spans[changed.start] == self.spans[changed.start]
+        {
+            changed.start += 1;
+        }
+        let moved = |i: usize| resume - (tokens.len() - i);
+        while changed.end > changed.start
+            && moved(changed.end) > restart
+            && tokens[changed.end - 1] == self.tokens[moved(changed.end) - 1]
+            && spans[changed.end - 1] == shift(&self.spans[moved(changed.end) - 1])
+        {
+            changed.end -= 1;
+        }
+
+        tokens.extend_from_slice(&self.tokens[resume..]);
+        spans.extend(self.spans[resume..].iter().map(shift));
+        *self = Self {
+            source,
+            tokens,
+            spans,
+            front_matter: self.front_matter.take(),
+        };
+        Ok(changed)
+    }
+
+    /// Appends text, merging it into the last token if that's text too.
+    ///
+    /// Like all appending, this leaves the source of the tokens before as it is and adds the
+    /// serialized form of the new ones. Only a last token whose source would read differently
+    /// with more after it, like text ending in a `\` or a tag missing its `]`, is serialized
+    /// again first.
+    pub fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.close_last();
+        let start = self.source.len();
+        write_source(&mut self.source, &Token::Text(text.to_string()));
+        match (self.tokens.last_mut(), self.spans.last_mut()) {
+            (Some(Token::Text(last)), Some(span)) => {
+                last.push_str(text);
+                span.end = self.source.len();
+            }
+            _ => {
+                self.tokens.push(Token::Text(text.to_string()));
+                self.spans.push(Span::new(start, self.source.len()));
+            }
+        }
+    }
+
+    pub fn push_tag(&mut self, tag: Tag) {
+        self.push(Token::Tag(tag));
+    }
+
+    /// Appends a token, text the way [`Document::push_text`] does.
+    pub fn push(&mut self, token: Token) {
+        if let Token::Text(text) = &token {
+            return self.push_text(text);
+        }
+        self.close_last();
+        let start = self.source.len();
+        write_source(&mut self.source, &token);
+        self.tokens.push(token);
+        self.spans.push(Span::new(start, self.source.len()));
+    }
+
+    // Rewrites the source of the last token if appending could change what it reads as.
+    fn close_last(&mut self) {
+        let (Some(token), Some(span)) = (self.tokens.last(), self.spans.last_mut()) else {
+            return;
+        };
+        let source = &self.source[span.range()];
+        let open = match token {
+            Token::Text(_) => source.bytes().rev().take_while(|&b| b == b'\\').count() % 2 == 1,
+            Token::Tag(_) => !source.ends_with(']'),
+            _ => false,
+        };
+        if open {
+            self.source.truncate(span.start);
+            write_source(&mut self.source, token);
+            span.end = self.source.len();
+        }
+    }
+
+    /// Removes the token at `index` along with its source, and returns it.
+    ///
+    /// Text on both sides of it is merged, so the tokens after it may move back by two.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Token {
+        let token = self.tokens.remove(index);
+        let span = self.spans.remove(index);
+        self.source.replace_range(span.range(), "");
+        for later in &mut self.spans[index..] {
+            *later = Span::new(later.start - span.len(), later.end - span.len());
+        }
+        if let (Some(Token::Text(_)), Some(Token::Text(_))) = (
+            index.checked_sub(1).and_then(|i| self.tokens.get(i)),
+            self.tokens.get(index),
+        ) {
+            let Token::Text(text) = self.tokens.remove(index) else {
+                unreachable!()
+            };
+            let end = self.spans.remove(index).end;
+            if let Token::Text(last) = &mut self.tokens[index - 1] {
+                last.push_str(&text);
+            }
+            self.spans[index - 1].end = end;
+        }
+        token
+    }
+
+    /// Composes the content of text tokens into Unicode NFC, see [`nfc`](crate::nfc).
+    ///
+    /// The source and spans are kept, so they still index the bytes as written, which the
+    /// tokens don't necessarily parse back from anymore.
+    #[cfg(feature = "unicode-normalization")]
+    pub fn nfc(&mut self) {
+        use unicode_normalization::UnicodeNormalization;
+
+        for token in &mut self.tokens {
+            if let Token::Text(text) = token {
+                *text = text.nfc().collect();
+            }
+        }
+    }
+
+    /// Returns the tokens covering the source byte `range`, cutting text tokens at its edges.
+    ///
+    /// Fails if the range would split a tag or a multi-byte character.
+    pub fn slice(&self, range: Range<usize>) -> Result<Vec<Token>, SliceError> {
+        let [_, inside, _] = self.cut(range)?;
+        Ok(inside)
+    }
+
+    // Splits the tokens into those before, inside, and after the source byte range.
+    fn cut(&self, range: Range<usize>) -> Result<[Vec<Token>; 3], SliceError> {
+        self.check_range(&range)?;
+
+        let mut parts = [Vec::new(), Vec::new(), Vec::new()];
+        for (token, span) in self.spanned_tokens() {
+            if span.end <= range.start {
+                parts[0].push(token.clone());
+            } else if span.start >= range.end {
+                parts[2].push(token.clone());
+            } else if span.start >= range.start && span.end <= range.end {
+                parts[1].push(token.clone());
+            } else if let Some(text) = token.as_text() {
+                let token_source = &self.source[span.range()];
+                let to_content = |offset: usize| {
+                    content_offset(
+                        token_source,
+                        offset.clamp(span.start, span.end) - span.start,
+                    )
+                };
+                let (start, end) = (to_content(range.start), to_content(range.end));
+                for (part, text) in
+                    parts
+                        .iter_mut()
+                        .zip([&text[..start], &text[start..end], &text[end..]])
+                {
+                    if !text.is_empty() {
+                        part.push(Token::Text(text.to_string()));
+                    }
+                }
+            } else {
+                return Err(SliceError::SplitsTag(span));
+            }
+        }
+        Ok(parts)
+    }
+
+    fn check_range(&self, range: &Range<usize>) -> Result<(), SliceError> {
+        for offset in [range.start, range.end] {
+            if offset > self.source.len() || range.start > range.end {
+                return Err(SliceError::OutOfBounds(offset));
+            }
+            if !self.source.is_char_boundary(offset) {
+                return Err(SliceError::NotCharBoundary(offset));
+            }
+        }
+        Ok(())
+    }
+
+    /// The front matter read by [`Document::parse_front_matter`].
+    pub fn front_matter(&self) -> Option<&FrontMatter> {
+        self.front_matter.as_ref()
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The source span of every token, in the same order as [`Document::tokens`].
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Finds the token whose span contains the byte `offset`.
+    ///
+    /// Spans are half-open, so on the boundary between two tokens the one starting there wins.
+    /// Offsets at or past the end of the source have no token.
+    pub fn token_at(&self, offset: usize) -> Option<(&Token, Span)> {
+        let index = self.spans.partition_point(|span| span.end <= offset);
+        let span = *self.spans.get(index)?;
+        (span.start <= offset).then(|| (&self.tokens[index], span))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// The tokens in order, like iterating over `&doc` does.
+    pub fn iter(&self) -> slice::Iter<'_, Token> {
+        self.tokens.iter()
+    }
+
+    pub(crate) fn spanned_tokens(&self) -> impl Iterator<Item = (&Token, Span)> {
+        self.tokens.iter().zip(self.spans.iter().copied())
+    }
+
+    /// The tokens with their spans as their origin, for [`crate::Pipeline::apply_traced`].
+    pub fn traced_tokens(&self) -> Vec<TracedToken> {
+        self.spanned_tokens()
+            .map(|(token, span)| TracedToken::from((token.clone(), span)))
+            .collect()
+    }
+
+    /// The tokens rendering as HTML when formatted, or as plain text with
+    /// [`Rendered::as_plain`], without building a `String` first.
+    pub fn renderable<'a, R: Resolver + ?Sized>(&'a self, resolver: &'a R) -> Rendered<'a, R> {
+        Rendered::new(&self.tokens, resolver)
+    }
+
+    pub fn into_tokens(self) -> Vec<Token> {
+        self.tokens
+    }
+
+    pub(crate) fn into_parts(self) -> (String, Vec<Token>, Vec<Span>) {
+        (self.source, self.tokens, self.spans)
+    }
+
+    pub fn to_source(&self) -> String {
+        to_source(&self.tokens)
+    }
+
+    /// Walks the tokens with their spans into the document's source.
+    pub fn walk<V: TokenVisitor + ?Sized>(&self, visitor: &mut V) {
+        walk_spanned(self.spanned_tokens(), visitor)
+    }
+}
+
+impl<'a> IntoIterator for &'a Document {
+    type Item = &'a Token;
+    type IntoIter = slice::Iter<'a, Token>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Document {
+    type Item = Token;
+    type IntoIter = vec::IntoIter<Token>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tokens.into_iter()
+    }
+}
+
+impl Extend<Token> for Document {
+    fn extend<I: IntoIterator<Item = Token>>(&mut self, tokens: I) {
+        for token in tokens {
+            self.push(token);
+        }
+    }
+}
+
+impl Index<usize> for Document {
+    type Output = Token;
+
+    fn index(&self, index: usize) -> &Token {
+        &self.tokens[index]
+    }
+}
+
+impl From<Vec<Token>> for Document {
+    fn from(tokens: Vec<Token>) -> Self {
+        let mut source = String::new();
+        let spans = tokens
+            .iter()
+            .map(|token| {
+                let start = source.len();
+                write_source(&mut source, token);
+                Span::new(start, source.len())
+            })
+            .collect();
+        Self {
+            source,
+            tokens,
+            spans,
+            front_matter: None,
+        }
+    }
+}
+
+/// A change to the source of a [`Document`], replacing the byte `range` with `replacement`, like
+/// an operation of a collaborative editor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl Edit {
+    pub fn insert(offset: usize, text: &str) -> Self {
+        Self {
+            range: offset..offset,
+            replacement: text.to_string(),
+        }
+    }
+
+    pub fn delete(range: Range<usize>) -> Self {
+        Self {
+            range,
+            replacement: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum SliceError {
+    OutOfBounds(usize),
+    NotCharBoundary(usize),
+    SplitsTag(Span),
+}
+
+impl Display for SliceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::OutOfBounds(offset) => write!(f, "offset {offset} is out of bounds"),
+            Self::NotCharBoundary(offset) => write!(f, "offset {offset} is inside a character"),
+            Self::SplitsTag(span) => {
+                write!(f, "range splits the tag at {}..{}", span.start, span.end)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SliceError {}
+
+/// Why [`Document::edit`] failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+    Slice(SliceError),
+    Tokenize(TokenizeErr),
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Slice(e) => write!(f, "{e}"),
+            Self::Tokenize(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EditError {}
+
+impl From<SliceError> for EditError {
+    fn from(e: SliceError) -> Self {
+        Self::Slice(e)
+    }
+}
+
+impl From<TokenizeErr> for EditError {
+    fn from(e: TokenizeErr) -> Self {
+        Self::Tokenize(e)
+    }
+}
+
+/// Builds a [`Document`] token by token, so callers never have to assemble markup by hand.
+///
+/// Adjacent text is merged into a single token, which is also what the tokenizer produces
+/// when reading the serialized document back.
+#[derive(Debug, Default)]
+pub struct DocumentBuilder {
+    tokens: Vec<Token>,
+}
+
+impl DocumentBuilder {
+    pub fn text(mut self, text: &str) -> Self {
+        if text.is_empty() {
+            return self;
+        }
+        match self.tokens.last_mut() {
+            Some(Token::Text(last)) => last.push_str(text),
+            _ => self.tokens.push(Token::Text(text.to_string())),
+        }
+        self
+    }
+
+    pub fn tag(mut self, tag: Tag) -> Self {
+        self.tokens.push(Token::Tag(tag));
+        self
+    }
+
+    pub fn user(self, id: usize) -> Self {
+        self.tag(Tag::User(id))
+    }
+
+    pub fn article(self, id: usize) -> Self {
+        self.tag(Tag::Article(id))
+    }
+
+    pub fn build(self) -> Document {
+        self.tokens.into()
+    }
+}
+
+/// Serializes tokens back into markup, escaping text so it reads back as the same tokens.
+pub fn to_source(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        write_source(&mut out, token);
+    }
+    out
+}
+
+// Appends a token's serialized form, see `to_source`.
+fn write_source(out: &mut String, token: &Token) {
+    match token {
+        Token::Text(text) | Token::Highlight(text) => {
+            for c in text.chars() {
+                if matches!(c, '[' | ']' | '\\') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+        }
+        Token::Tag(tag) => write!(out, "[{tag}]").unwrap(),
+        Token::LineBreak => out.push('\n'),
+        Token::ParagraphBreak => out.push_str("\n\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn build_roundtrip() {
+        let doc = Document::builder()
+            .text("hey ")
+            .user(5)
+            .text(", see ")
+            .article(9)
+            .build();
+        assert_eq!(doc.to_source(), "hey [user:5], see [article:9]");
+        assert_eq!(Document::parse(&doc.to_source()), Ok(doc));
+    }
+
+    #[test]
+    pub fn document_as_collection() {
+        let doc = Document::parse("a [user:1] b [article:2]").unwrap();
+        assert_eq!(doc.len(), 4);
+        assert!(!doc.is_empty() && Document::default().is_empty());
+        assert_eq!(doc[1], Token::Tag(Tag::User(1)));
+        assert_eq!(doc.iter().len(), 4);
+        assert_eq!(
+            doc.iter()
+                .rev()
+                .filter_map(Token::as_tag)
+                .collect::<Vec<_>>(),
+            [&Tag::Article(2), &Tag::User(1)]
+        );
+        let mut texts = Vec::new();
+        for token in &doc {
+            texts.extend(token.as_text());
+        }
+        assert_eq!(texts, ["a ", " b "]);
+        assert_eq!(doc.into_iter().last(), Some(Token::Tag(Tag::Article(2))));
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds: the len is 2 but the index is 2")]
+    pub fn document_index_out_of_bounds() {
+        let _ = &Document::parse("a [user:1]").unwrap()[2];
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn push_and_remove() {
+        let mut doc = Document::parse("note: [user: 1] was").unwrap();
+        doc.push_text(" rude");
+        doc.push_text(" [again]");
+        assert_eq!(doc.len(), 3);
+        assert_eq!(doc[2], Token::Text(" was rude [again]".to_string()));
+        doc.push_tag(Tag::Article(2));
+        doc.extend([Token::Text(" in ".to_string()), Token::Tag(Tag::Quote(3))]);
+        assert_eq!(
+            doc.source(),
+            r"note: [user: 1] was rude \[again\][article:2] in [quote:3]"
+        );
+        assert_eq!(Document::parse(doc.source()).as_ref(), Ok(&doc));
+
+        assert_eq!(doc.remove(3), Token::Tag(Tag::Article(2)));
+        assert_eq!(doc.remove(1), Token::Tag(Tag::User(1)));
+        assert_eq!(
+            doc.tokens(),
+            &[
+                Token::Text("note:  was rude [again] in ".to_string()),
+                Token::Tag(Tag::Quote(3)),
+            ]
+        );
+        assert_eq!(doc.source(), r"note:  was rude \[again\] in [quote:3]");
+        assert_eq!(Document::parse(doc.source()).as_ref(), Ok(&doc));
+        assert_eq!(doc.remove(1), Token::Tag(Tag::Quote(3)));
+        assert_eq!(doc.to_source(), doc.source());
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    pub fn nfc_keeps_spans() {
+        let source = "cafe\u{301} [user:1] ne\u{301}e";
+        let mut doc = Document::parse(source).unwrap();
+        doc.nfc();
+        assert_eq!(
+            doc.tokens(),
+            &[
+                Token::Text("caf\u{e9} ".to_string()),
+                Token::Tag(Tag::User(1)),
+                Token::Text(" n\u{e9}e".to_string()),
+            ]
+        );
+        assert_eq!(doc.source(), source);
+        assert_eq!(doc.spans()[2], Span::new(15, 21));
+        assert_eq!(&source[doc.spans()[2].range()], " ne\u{301}e");
+        assert_eq!(doc.token_at(19), Some((&doc[2], Span::new(15, 21))));
+        assert_eq!(
+            nfc(Document::parse(source).unwrap().into_tokens()),
+            doc.tokens()
+        );
+    }
+
+    #[test]
+    pub fn push_closes_last_token() {
+        let mut doc = Document::parse("a\\").unwrap();
+        doc.push_tag(Tag::User(3));
+        assert_eq!(doc.source(), r"a\\[user:3]");
+        assert_eq!(Document::parse(doc.source()).as_ref(), Ok(&doc));
+
+        let mut doc = Document::parse("]\\").unwrap();
+        doc.push_text("[user:2]");
+        assert_eq!(doc.tokens(), &[Token::Text(r"]\[user:2]".to_string())]);
+        assert_eq!(Document::parse(doc.source()).as_ref(), Ok(&doc));
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn push_closes_last_tag() {
+        let mut doc = Document::parse("1a\\[article:2][spoiler\n").unwrap();
+        doc.push_text("user:");
+        assert_eq!(doc.source(), r"1a\[article:2][spoiler]user:");
+        assert_eq!(Document::parse(doc.source()).as_ref(), Ok(&doc));
+    }
+
+    #[test]
+    pub fn build_escapes_text() {
+        let doc = Document::builder()
+            .text("not a [user:1] tag, ")
+            .text(r"nor C:\[x]")
+            .user(2)
+            .build();
+        assert_eq!(
+            doc.tokens(),
+            &[
+                Token::Text(r"not a [user:1] tag, nor C:\[x]".to_string()),
+                Token::Tag(Tag::User(2))
+            ]
+        );
+        assert_eq!(
+            doc.to_source(),
+            r"not a \[user:1\] tag, nor C:\\\[x\][user:2]"
+        );
+        assert_eq!(Document::parse(&doc.to_source()), Ok(doc));
+    }
+
+    #[test]
+    pub fn concat_three() {
+        let parts = [
+            Document::parse("hi [user:1]").unwrap(),
+            Document::parse_lossy("[bad] stays"),
+            Document::parse("[article:2] \\[end\\]").unwrap(),
+        ];
+        let doc = Document::concat(&parts);
+        assert_eq!(
+            doc.tokens(),
+            &[
+                Token::Text("hi ".to_string()),
+                Token::Tag(Tag::User(1)),
+                Token::ParagraphBreak,
+                Token::Text("[bad] stays".to_string()),
+                Token::ParagraphBreak,
+                Token::Tag(Tag::Article(2)),
+                Token::Text(" [end]".to_string()),
+            ]
+        );
+        assert_eq!(
+            doc.source(),
+            "hi [user:1]\n\n\\[bad\\] stays\n\n[article:2] \\[end\\]"
+        );
+        assert_eq!(doc.source(), doc.to_source());
+
+        let doc = Document::concat_with(&parts, &[Token::Text(" | ".to_string())]);
+        assert_eq!(
+            doc.source(),
+            "hi [user:1] | \\[bad\\] stays | [article:2] \\[end\\]"
+        );
+        assert_eq!(Document::concat(&[]), Document::default());
+    }
+
+    #[test]
+    pub fn splice_text_run() {
+        let mut doc = Document::parse("hello world [user:1]").unwrap();
+        doc.splice(
+            6..6,
+            [Token::Tag(Tag::User(2)), Token::Text(" and ".to_string())],
+        )
+        .unwrap();
+        assert_eq!(doc.source(), "hello [user:2] and world [user:1]");
+
+        doc.splice(19..24, [Token::Text("everyone".to_string())])
+            .unwrap();
+        assert_eq!(
+            doc.tokens(),
+            &[
+                Token::Text("hello ".to_string()),
+                Token::Tag(Tag::User(2)),
+                Token::Text(" and everyone ".to_string()),
+                Token::Tag(Tag::User(1)),
+            ]
+        );
+        assert_eq!(Document::parse(doc.source()), Ok(doc));
+    }
+
+    #[test]
+    pub fn splice_keeps_front_matter() {
+        let mut doc = Document::parse_front_matter("---\ntitle: x\n---\nhello [user:1]").unwrap();
+        doc.splice(23..23, [Token::Tag(Tag::User(2))]).unwrap();
+        assert_eq!(doc.source(), "---\ntitle: x\n---\nhello [user:2][user:1]");
+        assert_eq!(doc.front_matter().unwrap().get("title"), Some("x"));
+        assert_eq!(doc.spans()[1], Span::new(23, 31));
+        assert_eq!(
+            Document::parse_front_matter(doc.source()).as_ref(),
+            Ok(&doc)
+        );
+        assert_eq!(doc.splice(4..5, []), Err(SliceError::OutOfBounds(4)));
+    }
+
+    #[test]
+    pub fn splice_errors() {
+        let mut doc = Document::parse("aé [user:1]").unwrap();
+        assert_eq!(
+            doc.splice(5..6, []),
+            Err(SliceError::SplitsTag(Span::new(4, 12)))
+        );
+        assert_eq!(doc.splice(2..2, []), Err(SliceError::NotCharBoundary(2)));
+        assert_eq!(doc.splice(0..13, []), Err(SliceError::OutOfBounds(13)));
+        assert_eq!(doc.splice(0..12, []), Ok(()));
+        assert_eq!(doc, Document::default());
+    }
+
+    #[test]
+    pub fn slice_inside_text() {
+        let doc = Document::parse(r"[user:1] a \[long\] text").unwrap();
+        assert_eq!(
+            doc.slice(11..21),
+            Ok(vec![Token::Text("[long] t".to_string())])
+        );
+        assert_eq!(doc.slice(12..12), Ok(vec![]));
+    }
+
+    #[test]
+    pub fn slice_across_tag() {
+        let doc = Document::parse("first [user:1] second [article:2]").unwrap();
+        assert_eq!(
+            doc.slice(2..17),
+            Ok(vec![
+                Token::Text("rst ".to_string()),
+                Token::Tag(Tag::User(1)),
+                Token::Text(" se".to_string()),
+            ])
+        );
+        assert_eq!(doc.slice(0..doc.source().len()), Ok(doc.tokens().to_vec()));
+    }
+
+    #[test]
+    pub fn slice_bisects_tag() {
+        let doc = Document::parse("first [user:1] second").unwrap();
+        assert_eq!(
+            doc.slice(2..8),
+            Err(SliceError::SplitsTag(Span::new(6, 14)))
+        );
+        assert_eq!(
+            doc.slice(13..16),
+            Err(SliceError::SplitsTag(Span::new(6, 14)))
+        );
+    }
+
+    #[test]
+    pub fn token_at_offsets() {
+        let doc = Document::parse("hi  there [user:1][article:2]").unwrap();
+        let text = (&Token::Text("hi  there ".to_string()), Span::new(0, 10));
+        let user = (&Token::Tag(Tag::User(1)), Span::new(10, 18));
+        let article = (&Token::Tag(Tag::Article(2)), Span::new(18, 29));
+        assert_eq!(doc.token_at(0), Some(text));
+        assert_eq!(doc.token_at(3), Some(text));
+        assert_eq!(doc.token_at(9), Some(text));
+        assert_eq!(doc.token_at(10), Some(user));
+        assert_eq!(doc.token_at(17), Some(user));
+        assert_eq!(doc.token_at(18), Some(article));
+        assert_eq!(doc.token_at(28), Some(article));
+        assert_eq!(doc.token_at(29), None);
+        assert_eq!(doc.token_at(100), None);
+        assert_eq!(Document::default().token_at(0), None);
+        assert_eq!(doc.spans(), &[text.1, user.1, article.1]);
+    }
+
+    #[test]
+    pub fn parse_lossy_keeps_bad_tags() {
+        let doc = Document::parse_lossy("a [bad] b [user:1][user:x]");
+        assert_eq!(
+            doc.tokens(),
+            &[
+                Token::Text("a [bad] b ".to_string()),
+                Token::Tag(Tag::User(1)),
+                Token::Text("[user:x]".to_string()),
+            ]
+        );
+        assert_eq!(
+            tokenize_lossy("[bad]"),
+            vec![Token::Text("[bad]".to_string())]
+        );
+    }
+
+    #[test]
+    pub fn build_skips_empty_text() {
+        let doc = Document::builder().text("").user(1).text("").build();
+        assert_eq!(doc.tokens(), &[Token::Tag(Tag::User(1))]);
+    }
+
+    #[test]
+    pub fn edit_matches_parse() {
+        let pieces = [
+            "a",
+            "é",
+            " ",
+            "\n",
+            "[user:1]",
+            "[article:22]",
+            "[",
+            "]",
+            "\\",
+            "user",
+            ":3",
+            "",
+        ];
+        let mut state = 0x2545f491u32;
+        let mut next = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as usize % n
+        };
+        let mut doc = Document::parse("hello [user:1] wörld, see [article:2]\\[x\\]\n").unwrap();
+        for _ in 0..5000 {
+            let source = doc.source();
+            let boundary = |mut offset: usize| {
+                while !source.is_char_boundary(offset) {
+                    offset += 1;
+                }
+                offset
+            };
+            let start = boundary(next(source.len() + 1));
+            let range = start..boundary((start + next(4)).min(source.len()));
+            let replacement = (0..next(3))
+                .map(|_| pieces[next(pieces.len())])
+                .collect::<String>();
+
+            let mut source = source.to_string();
+            source.replace_range(range.clone(), &replacement);
+            let before = Document::parse(doc.source()).unwrap();
+            match Document::parse(&source) {
+                Ok(expected) => {
+                    let edit = Edit { range, replacement };
+                    let changed = doc.apply_edit(edit).unwrap();
+                    assert_eq!(doc, expected, "{source:?}");
+                    let tail = doc.len() - changed.end;
+                    assert_eq!(
+                        doc.tokens()[..changed.start],
+                        before.tokens()[..changed.start]
+                    );
+                    assert_eq!(
+                        doc.tokens()[changed.end..],
+                        before.tokens()[before.len() - tail..]
+                    );
+                }
+                Err(e) => {
+                    assert_eq!(doc.edit(range, &replacement), Err(EditError::Tokenize(e)));
+                    assert_eq!(doc, before);
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn edit_reuses_tail() {
+        let mut doc = Document::parse("ab [user:1] cd [article:2]").unwrap();
+        doc.edit(1..1, "[article:3]").unwrap();
+        assert_eq!(
+            doc.spans(),
+            &[
+                Span::new(0, 1),
+                Span::new(1, 12),
+                Span::new(12, 14),
+                Span::new(14, 22),
+                Span::new(22, 26),
+                Span::new(26, 37)
+            ]
+        );
+        assert_eq!(
+            doc.edit(0..40, ""),
+            Err(EditError::Slice(SliceError::OutOfBounds(40)))
+        );
+    }
+
+    #[test]
+    pub fn apply_edit_reports_changes() {
+        let source = "hello world [user:1] and [article:2] bye";
+        let check = |edit: Edit, changed: Range<usize>| {
+            let mut doc = Document::parse(source).unwrap();
+            let mut edited = source.to_string();
+            edited.replace_range(edit.range.clone(), &edit.replacement);
+            assert_eq!(doc.apply_edit(edit), Ok(changed));
+            assert_eq!(doc, Document::parse(&edited).unwrap());
+            doc
+        };
+
+        // Inside a text token.
+        let doc = check(Edit::insert(6, "big "), 0..1);
+        assert_eq!(doc.spans()[1], Span::new(16, 24));
+        // A whole tag, merging the text on both sides of it.
+        let doc = check(Edit::delete(12..20), 0..1);
+        assert_eq!(doc[0], Token::Text("hello world  and ".to_string()));
+        assert_eq!(doc[1], Token::Tag(Tag::Article(2)));
+        // A new tag in the middle of text, which still ends the same after it.
+        let doc = check(Edit::insert(37, "[user:3] "), 4..6);
+        assert_eq!(doc[5], Token::Tag(Tag::User(3)));
+        assert_eq!(doc.len(), 7);
+        // Nothing but moving the tokens after it.
+        let mut doc = Document::parse("[user:1][article:2]").unwrap();
+        assert_eq!(doc.apply_edit(Edit::insert(8, "")), Ok(1..1));
+    }
+}
@@ -0,0 +1,102 @@
+use alloc::string::{String, ToString};
+use core::fmt::Write;
+
+use crate::{Document, Token};
+
+impl Document {
+    /// A line per token with its span, like `6..14 user 5`, for snapshot tests and debugging.
+    ///
+    /// Text and highlights are quoted with Rust's escapes, tags are their name and payload,
+    /// and breaks are `line_break` and `paragraph_break`.
+    ///
+    /// The format is meant to stay as it is, so changing it is a breaking change for
+    /// everyone's snapshots.
+    ///
+    /// ```
+    /// use papyrus_lib::Document;
+    ///
+    /// let doc = Document::parse("hello [user:5]\n[article:2]").unwrap();
+    /// assert_eq!(
+    ///     doc.dump(),
+    ///     "0..6 text \"hello \"\n6..14 user 5\n14..15 text \"\\n\"\n15..26 article 2\n"
+    /// );
+    /// ```
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        for (token, span) in self.tokens().iter().zip(self.spans()) {
+            write!(out, "{}..{} ", span.start, span.end).unwrap();
+            write_token(&mut out, token);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Writes a line of [`Document::dump`] without the span and newline.
+pub(crate) fn write_token(out: &mut String, token: &Token) {
+    match token {
+        Token::Text(text) => write!(out, "text \"{}\"", text.escape_debug()),
+        Token::Highlight(text) => write!(out, "highlight \"{}\"", text.escape_debug()),
+        Token::Tag(tag) => {
+            let tag = tag.to_string();
+            match tag.split_once(':') {
+                Some((name, payload)) => write!(out, "{name} {payload}"),
+                None => out.write_str(&tag),
+            }
+        }
+        Token::LineBreak => out.write_str("line_break"),
+        Token::ParagraphBreak => out.write_str("paragraph_break"),
+    }
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    // Inputs covering every kind of token, escapes, errors kept as text and multi-byte text.
+    const CORPUS: [(&str, &str); 6] = [
+        ("mentions", "Thanks [user:1], see [article:9]!\n"),
+        ("escapes", r"C:\ \\ \[not a tag\] [user: 12]"),
+        (
+            "lossy",
+            "fine [user:1]\nbad [usr:2] and [article:x] [user:3",
+        ),
+        (
+            "blocks",
+            "[quote:4]said [spoiler]it[/spoiler][/quote] https://a.com",
+        ),
+        ("unicode", "für [user:1] 🦀\n\n[article:2]"),
+        ("empty", ""),
+    ];
+
+    #[test]
+    pub fn dump_corpus() {
+        for (name, input) in CORPUS {
+            if cfg!(not(feature = "tag-blocks")) && name == "blocks" {
+                continue;
+            }
+            let doc = Document::parse_lossy(input);
+            insta::assert_snapshot!(name, doc.dump(), input);
+        }
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn dump_breaks_and_highlights() {
+        let tokens = vec![
+            Token::Highlight("hi".to_string()),
+            Token::LineBreak,
+            Token::Tag(Tag::Link("https://a.com".to_string())),
+            Token::ParagraphBreak,
+            Token::Tag(Tag::Close(BlockKind::Quote)),
+        ];
+        insta::assert_snapshot!(Document::from(tokens).dump(), @r###"
+        0..2 highlight "hi"
+        2..3 line_break
+        3..23 link https://a.com
+        23..25 paragraph_break
+        25..33 /quote
+        "###);
+    }
+}
@@ -0,0 +1,151 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{tokenize_lossy, Token};
+
+/// Bytes of the input that couldn't be decoded and became U+FFFD, see
+/// [`tokenize_lossy_bytes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EncodingIssue {
+    /// Where the bytes start in the input.
+    pub offset: usize,
+    pub len: usize,
+}
+
+/// Tokenizes bytes as UTF-8 like [`tokenize_lossy`], replacing each invalid sequence with
+/// U+FFFD and reporting where it was.
+///
+/// ```
+/// use papyrus_lib::{tokenize_lossy_bytes, EncodingIssue, Tag, Token};
+///
+/// let (tokens, issues) = tokenize_lossy_bytes(b"caf\xe9 [user:1]");
+/// assert_eq!(tokens[0], Token::Text("caf\u{fffd} ".to_string()));
+/// assert_eq!(tokens[1], Token::Tag(Tag::User(1)));
+/// assert_eq!(issues, [EncodingIssue { offset: 3, len: 1 }]);
+/// ```
+pub fn tokenize_lossy_bytes(bytes: &[u8]) -> (Vec<Token>, Vec<EncodingIssue>) {
+    let mut text = String::with_capacity(bytes.len());
+    let mut issues = Vec::new();
+    let mut offset = 0;
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        offset += chunk.valid().len();
+        let invalid = chunk.invalid().len();
+        if invalid > 0 {
+            text.push(char::REPLACEMENT_CHARACTER);
+            issues.push(EncodingIssue {
+                offset,
+                len: invalid,
+            });
+            offset += invalid;
+        }
+    }
+    (tokenize_lossy(&text), issues)
+}
+
+/// Like [`tokenize_lossy_bytes`], but decoding the bytes from `encoding` first, like
+/// `encoding_rs::WINDOWS_1252` for old Latin-1 posts.
+///
+/// A byte order mark of `encoding` is skipped, others are kept.
+///
+/// ```
+/// use papyrus_lib::{tokenize_encoded, Token};
+///
+/// let (tokens, issues) = tokenize_encoded(b"caf\xe9", encoding_rs::WINDOWS_1252);
+/// assert_eq!(tokens, [Token::Text("café".to_string())]);
+/// assert!(issues.is_empty());
+/// ```
+#[cfg(feature = "encoding")]
+pub fn tokenize_encoded(
+    bytes: &[u8],
+    encoding: &'static encoding_rs::Encoding,
+) -> (Vec<Token>, Vec<EncodingIssue>) {
+    use encoding_rs::DecoderResult;
+
+    let mut decoder = encoding.new_decoder_with_bom_removal();
+    let mut text = String::with_capacity(
+        decoder
+            .max_utf8_buffer_length_without_replacement(bytes.len())
+            .unwrap_or(bytes.len()),
+    );
+    let mut issues = Vec::new();
+    let mut read = 0;
+    loop {
+        let (result, consumed) =
+            decoder.decode_to_string_without_replacement(&bytes[read..], &mut text, true);
+        read += consumed;
+        match result {
+            DecoderResult::InputEmpty => break,
+            DecoderResult::OutputFull => text.reserve(bytes.len() - read + 4),
+            DecoderResult::Malformed(len, after) => {
+                let (len, after) = (usize::from(len), usize::from(after));
+                text.push(char::REPLACEMENT_CHARACTER);
+                issues.push(EncodingIssue {
+                    offset: read - after - len,
+                    len,
+                });
+            }
+        }
+    }
+    (tokenize_lossy(&text), issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn lossy_bytes_in_text() {
+        let (tokens, issues) = tokenize_lossy_bytes(b"ok [user:1] a\nb\xff\xfec");
+        assert_eq!(
+            tokens,
+            [
+                Token::Text("ok ".to_string()),
+                Token::Tag(Tag::User(1)),
+                Token::Text(" a\nb\u{fffd}\u{fffd}c".to_string()),
+            ]
+        );
+        assert_eq!(
+            issues,
+            [
+                EncodingIssue { offset: 15, len: 1 },
+                EncodingIssue { offset: 16, len: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn lossy_bytes_in_tag() {
+        // The tag doesn't parse anymore, so it's kept as text.
+        let (tokens, issues) = tokenize_lossy_bytes(b"a [user:\xe2\x82] [user:2]");
+        assert_eq!(
+            tokens,
+            [
+                Token::Text("a [user:\u{fffd}] ".to_string()),
+                Token::Tag(Tag::User(2)),
+            ]
+        );
+        assert_eq!(issues, [EncodingIssue { offset: 8, len: 2 }]);
+        assert_eq!(tokenize_lossy_bytes(b"[user:1]").1, []);
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    pub fn encoded_windows_1252() {
+        let (tokens, issues) = tokenize_encoded(
+            b"Caf\xe9 \x93ol\xe9\x94 by [user:7]",
+            encoding_rs::WINDOWS_1252,
+        );
+        assert_eq!(
+            tokens,
+            [
+                Token::Text("Café “olé” by ".to_string()),
+                Token::Tag(Tag::User(7)),
+            ]
+        );
+        assert_eq!(issues, []);
+
+        let (tokens, issues) = tokenize_encoded(b"a\xffb", encoding_rs::UTF_8);
+        assert_eq!(tokens, [Token::Text("a\u{fffd}b".to_string())]);
+        assert_eq!(issues, [EncodingIssue { offset: 1, len: 1 }]);
+    }
+}
@@ -0,0 +1,175 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+use core::fmt::Display;
+
+use crate::{Position, Span};
+
+/// The metadata block a document may start with, `key: value` lines between two `---` lines,
+/// see [`Document::parse_front_matter`](crate::Document::parse_front_matter).
+///
+/// Keys and values are trimmed, blank lines skipped, and a key given twice has the last
+/// value.
+///
+/// ```
+/// use papyrus_lib::{FrontMatter, Span};
+///
+/// let input = "---\ntitle: Launch day\ncategory: 5\n---\nWe're live!";
+/// let front_matter = FrontMatter::parse(input).unwrap().unwrap();
+/// assert_eq!(front_matter.get("title"), Some("Launch day"));
+/// assert_eq!(front_matter.span(), Span::new(0, 38));
+/// assert_eq!(FrontMatter::parse("We're live!"), Ok(None));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrontMatter {
+    fields: BTreeMap<String, String>,
+    span: Span,
+}
+
+/// Why a block starting like [`FrontMatter`] isn't one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrontMatterErr {
+    /// There's no `---` line closing the block opened at the position.
+    Unterminated(Position),
+    /// The line isn't blank and has no key before a colon.
+    InvalidLine(Position),
+}
+
+impl Display for FrontMatterErr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Unterminated(p) => write!(f, "unterminated front matter at {p}"),
+            Self::InvalidLine(p) => write!(f, "front matter line without a key at {p}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FrontMatterErr {}
+
+impl FrontMatter {
+    /// Reads the block at the start of `input`, `None` if its first line isn't `---`.
+    pub fn parse(input: &str) -> Result<Option<Self>, FrontMatterErr> {
+        let mut lines = input.split_inclusive('\n');
+        if lines.next().is_none_or(|line| !is_delimiter(line)) {
+            return Ok(None);
+        }
+        let mut end = input.len() - lines.clone().map(str::len).sum::<usize>();
+        let mut fields = BTreeMap::new();
+        for (i, line) in lines.enumerate() {
+            end += line.len();
+            if is_delimiter(line) {
+                let span = Span::new(0, end);
+                return Ok(Some(Self { fields, span }));
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let position = Position::new(u16::try_from(i + 2).unwrap_or(u16::MAX));
+            match line.split_once(':') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    fields.insert(key.trim().to_string(), value.trim().to_string());
+                }
+                _ => return Err(FrontMatterErr::InvalidLine(position)),
+            }
+        }
+        Err(FrontMatterErr::Unterminated(Position::new(1)))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    pub fn fields(&self) -> &BTreeMap<String, String> {
+        &self.fields
+    }
+
+    /// The block in the input, including both `---` lines and the line break after them.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+fn is_delimiter(line: &str) -> bool {
+    line.trim_end() == "---"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn front_matter_parsed() {
+        let input =
+            "---\r\ntitle: Launch day\n\ncategory : 5\nurl: https://a.com\n---\nhi [user:1]";
+        let doc = Document::parse_front_matter(input).unwrap();
+        let front_matter = doc.front_matter().unwrap();
+        assert_eq!(front_matter.get("category"), Some("5"));
+        assert_eq!(front_matter.get("url"), Some("https://a.com"));
+        assert_eq!(front_matter.fields().len(), 3);
+        assert_eq!(
+            doc.tokens(),
+            [Token::Text("hi ".to_string()), Token::Tag(Tag::User(1))]
+        );
+        assert_eq!(doc.spans()[0], Span::new(60, 63));
+        assert_eq!(doc.source(), input);
+
+        let doc = Document::parse_front_matter("---\n---\n").unwrap();
+        assert!(doc.front_matter().unwrap().fields().is_empty());
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    pub fn front_matter_absent() {
+        for input in ["hi [user:1]\n---\na: b\n---\n", " ---\na: b\n---\n", ""] {
+            let doc = Document::parse_front_matter(input).unwrap();
+            assert_eq!(doc.front_matter(), None);
+            assert_eq!(doc, Document::parse(input).unwrap());
+        }
+        // Only asked for, it's text.
+        let doc = Document::parse("---\na: b\n---\n").unwrap();
+        assert_eq!(doc.front_matter(), None);
+        assert_eq!(doc.len(), 1);
+    }
+
+    #[test]
+    pub fn front_matter_malformed() {
+        let input = "---\ntitle: Launch day\n\n[user:1]";
+        assert_eq!(
+            FrontMatter::parse(input),
+            Err(FrontMatterErr::Unterminated(Position::new(1)))
+        );
+        let doc = Document::parse_front_matter(input).unwrap();
+        assert_eq!(doc.front_matter(), None);
+        assert_eq!(doc, Document::parse(input).unwrap());
+
+        let input = "---\ntitle: Launch day\nno key\n---\n";
+        assert_eq!(
+            FrontMatter::parse(input),
+            Err(FrontMatterErr::InvalidLine(Position::new(3)))
+        );
+        assert_eq!(
+            FrontMatter::parse("---\n: 1\n---").unwrap_err().to_string(),
+            "front matter line without a key at line 2"
+        );
+    }
+
+    #[test]
+    pub fn front_matter_body_positions() {
+        let input = "---\ntitle: x\n---\nfirst\nsee [user:5x]";
+        let error = Document::parse_front_matter(input).unwrap_err();
+        assert_eq!(error, Document::parse(input).unwrap_err());
+        assert_eq!(error.position(), Position::new(5).with_column(5));
+
+        let mut doc = Document::parse_front_matter("---\ntitle: x\n---\nhi [user:1]").unwrap();
+        doc.edit(19..19, " there").unwrap();
+        assert_eq!(doc.tokens()[0], Token::Text("hi there ".to_string()));
+        assert_eq!(doc.front_matter().unwrap().get("title"), Some("x"));
+        doc.edit(11..12, "y").unwrap();
+        assert_eq!(doc.front_matter().unwrap().get("title"), Some("y"));
+        assert_eq!(doc.spans()[1], Span::new(26, 34));
+        let error = doc.edit(34..34, "\n[usr:1]").unwrap_err();
+        assert_eq!(error.to_string(), "UnknownTag(\"[usr:1]\") at line 5");
+    }
+}
@@ -0,0 +1,81 @@
+//! The checks the fuzz targets in `fuzz/` run on every input.
+
+use crate::{
+    to_source, tokenize_lossy, validate, ByteTokenIter, Document, TokenIter, TokenizerOptions,
+};
+
+/// Tokenizes arbitrary bytes every way there is, panicking if the results disagree.
+///
+/// The serialized form of the lenient tokens has to read back as the same tokens, and
+/// reading from the back has to find the same tokens as reading from the front.
+pub fn tokenize(data: &[u8]) {
+    ByteTokenIter::new(data).for_each(drop);
+
+    let input = String::from_utf8_lossy(data);
+    validate(&input);
+
+    let tokens = tokenize_lossy(&input);
+    let source = to_source(&tokens);
+    match Document::parse(&source) {
+        Ok(doc) => assert_eq!(doc.tokens(), tokens, "{source:?}"),
+        Err(e) => panic!("{source:?} doesn't read back: {e}"),
+    }
+
+    for (emit_breaks, normalize_newlines) in
+        [(false, false), (true, false), (false, true), (true, true)]
+    {
+        let options = TokenizerOptions {
+            emit_breaks,
+            normalize_newlines,
+            ..Default::default()
+        };
+        let mut forward = TokenIter::with_options(&*input, options)
+            .spanned()
+            .collect::<Vec<_>>();
+        forward.reverse();
+        assert_eq!(
+            TokenIter::with_options(&*input, options)
+                .spanned()
+                .rev()
+                .collect::<Vec<_>>(),
+            forward
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuzz;
+
+    #[test]
+    pub fn fuzz_smoke() {
+        let alphabet: [&[u8]; 12] = [
+            b"[",
+            b"]",
+            b"\\",
+            b"\n",
+            b" ",
+            b"a",
+            b"user:",
+            b"article:",
+            b"1",
+            "é".as_bytes(),
+            b"\xff",
+            b"\t",
+        ];
+        let mut state = 0x9e3779b9u32;
+        let mut next = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as usize % n
+        };
+        for _ in 0..10_000 {
+            let data = (0..next(24))
+                .flat_map(|_| alphabet[next(alphabet.len())])
+                .copied()
+                .collect::<Vec<_>>();
+            fuzz::tokenize(&data);
+        }
+    }
+}
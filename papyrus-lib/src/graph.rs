@@ -0,0 +1,268 @@
+//! Who-mentions-whom analytics over many documents.
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+
+use crate::{tags, tokenize_lossy, Tag, TagKind, Token};
+
+/// Weighted edges from authors to the users they mention.
+///
+/// Documents are tokenized leniently, so a malformed tag only loses itself, never the
+/// mentions around it.
+#[derive(Debug, Default)]
+pub struct MentionGraph {
+    edges: BTreeMap<(u64, u64), usize>,
+}
+
+impl MentionGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ingest(&mut self, author: u64, input: &str) {
+        for tag in tags(&tokenize_lossy(input)) {
+            if let Tag::User(user) = tag {
+                *self.edges.entry((author, *user as u64)).or_default() += 1;
+            }
+        }
+    }
+
+    /// Every `(author, mentioned user, count)` edge, ordered by author and then user.
+    pub fn edges(&self) -> impl Iterator<Item = (u64, u64, usize)> + '_ {
+        self.edges
+            .iter()
+            .map(|(&(author, user), &count)| (author, user, count))
+    }
+}
+
+impl<'a> Extend<(u64, &'a str)> for MentionGraph {
+    fn extend<I: IntoIterator<Item = (u64, &'a str)>>(&mut self, documents: I) {
+        for (author, input) in documents {
+            self.ingest(author, input);
+        }
+    }
+}
+
+impl<'a> FromIterator<(u64, &'a str)> for MentionGraph {
+    fn from_iter<I: IntoIterator<Item = (u64, &'a str)>>(documents: I) -> Self {
+        let mut graph = Self::new();
+        graph.extend(documents);
+        graph
+    }
+}
+
+/// How [`cooccurrence_with`] counts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CooccurrenceOptions {
+    /// The kinds of tags to count pairs of, all if empty.
+    pub kinds: Vec<TagKind>,
+    /// Pairs in fewer documents than this are dropped, 1 by default.
+    pub min_count: usize,
+    /// Once there are more pairs than this, those below `min_count` so far are dropped,
+    /// unbounded by default.
+    ///
+    /// A pair dropped early starts counting from zero if it comes up again, so with a bound
+    /// the counts of rare pairs may come out too low.
+    pub max_pairs: usize,
+}
+
+impl Default for CooccurrenceOptions {
+    fn default() -> Self {
+        Self {
+            kinds: Vec::new(),
+            min_count: 1,
+            max_pairs: usize::MAX,
+        }
+    }
+}
+
+/// How many documents contain both tags of each unordered pair, see [`cooccurrence`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CooccurrenceMatrix {
+    // The smaller tag first.
+    pairs: BTreeMap<(Tag, Tag), usize>,
+}
+
+impl CooccurrenceMatrix {
+    /// In how many documents both tags are, in either order.
+    pub fn count(&self, a: &Tag, b: &Tag) -> usize {
+        let key = if a <= b { (a, b) } else { (b, a) };
+        self.pairs
+            .get(&(key.0.clone(), key.1.clone()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Every pair with its count, ordered by the smaller tag of each and then the other.
+    pub fn pairs(&self) -> impl Iterator<Item = (&Tag, &Tag, usize)> + '_ {
+        self.pairs.iter().map(|((a, b), &count)| (a, b, count))
+    }
+
+    /// The `k` tags in the most documents together with `tag`, with their counts, the most
+    /// first and ties in tag order.
+    pub fn top_partners(&self, tag: &Tag, k: usize) -> Vec<(&Tag, usize)> {
+        let mut partners: Vec<_> = self
+            .pairs
+            .iter()
+            .filter_map(|((a, b), &count)| {
+                let partner = if a == tag { b } else { a };
+                (a == tag || b == tag).then_some((partner, count))
+            })
+            .collect();
+        partners.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+        partners.truncate(k);
+        partners
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+/// Counts which tags are in the same documents, with the default [`CooccurrenceOptions`].
+///
+/// A tag counts once per document, however often it's in it.
+///
+/// ```
+/// use papyrus_lib::{graph::cooccurrence, tokenize_lossy, Tag};
+///
+/// let docs = [tokenize_lossy("[article:1] [article:2]"), tokenize_lossy("[article:2] [article:1] [user:3]")];
+/// let matrix = cooccurrence(docs.iter().map(Vec::as_slice));
+/// assert_eq!(matrix.count(&Tag::Article(2), &Tag::Article(1)), 2);
+/// assert_eq!(matrix.count(&Tag::Article(1), &Tag::User(3)), 1);
+/// ```
+pub fn cooccurrence<'a, I: IntoIterator<Item = &'a [Token]>>(docs: I) -> CooccurrenceMatrix {
+    cooccurrence_with(docs, &CooccurrenceOptions::default())
+}
+
+/// Like [`cooccurrence`], with the given options.
+pub fn cooccurrence_with<'a, I: IntoIterator<Item = &'a [Token]>>(
+    docs: I,
+    options: &CooccurrenceOptions,
+) -> CooccurrenceMatrix {
+    let mut pairs = BTreeMap::new();
+    for tokens in docs {
+        let tags: BTreeSet<&Tag> = tags(tokens)
+            .filter(|tag| options.kinds.is_empty() || options.kinds.contains(&tag.kind()))
+            .collect();
+        for (i, &a) in tags.iter().enumerate() {
+            for &b in tags.iter().skip(i + 1) {
+                *pairs.entry((a.clone(), b.clone())).or_default() += 1;
+            }
+        }
+        if pairs.len() > options.max_pairs {
+            pairs.retain(|_, count| *count >= options.min_count);
+        }
+    }
+    pairs.retain(|_, count| *count >= options.min_count);
+    CooccurrenceMatrix { pairs }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::*;
+
+    #[test]
+    pub fn graph_weighted_edges() {
+        let graph = [
+            (1, "hey [user:2], did [user:3] see [article:9]?"),
+            (2, "[user:1] yes"),
+            (1, "[user:2] again"),
+        ]
+        .into_iter()
+        .collect::<MentionGraph>();
+        assert_eq!(
+            graph.edges().collect::<Vec<_>>(),
+            vec![(1, 2, 2), (1, 3, 1), (2, 1, 1)]
+        );
+    }
+
+    #[test]
+    pub fn graph_malformed_tag() {
+        let mut graph = MentionGraph::new();
+        graph.ingest(7, "[user:1] [usr:2] [user:x] [user:3]");
+        graph.ingest(7, "[user:1]");
+        assert_eq!(
+            graph.edges().collect::<Vec<_>>(),
+            vec![(7, 1, 2), (7, 3, 1)]
+        );
+    }
+
+    #[test]
+    pub fn cooccurrence_pairs() {
+        let docs = [
+            "[article:1] and [article:2], by [user:7]",
+            "[article:2] [article:3] [article:1] [article:1]",
+            "[article:2] [article:3] [user:7] [user:8]",
+        ]
+        .map(tokenize_lossy);
+        let docs = || docs.iter().map(Vec::as_slice);
+
+        let all = cooccurrence(docs());
+        assert_eq!(all.count(&Tag::Article(1), &Tag::Article(2)), 2);
+        assert_eq!(all.count(&Tag::Article(3), &Tag::Article(2)), 2);
+        assert_eq!(all.count(&Tag::Article(1), &Tag::Article(3)), 1);
+        assert_eq!(all.count(&Tag::User(7), &Tag::User(8)), 1);
+        assert_eq!(all.count(&Tag::User(7), &Tag::Article(2)), 2);
+        assert_eq!(all.count(&Tag::Article(1), &Tag::Article(1)), 0);
+        assert_eq!(all.len(), 9);
+
+        let articles = CooccurrenceOptions {
+            kinds: vec![TagKind::Article],
+            min_count: 2,
+            ..Default::default()
+        };
+        let matrix = cooccurrence_with(docs(), &articles);
+        assert_eq!(
+            matrix.pairs().collect::<Vec<_>>(),
+            [
+                (&Tag::Article(1), &Tag::Article(2), 2),
+                (&Tag::Article(2), &Tag::Article(3), 2)
+            ]
+        );
+    }
+
+    #[test]
+    pub fn cooccurrence_top_partners() {
+        let docs = [
+            "[article:1] [article:2] [article:3]",
+            "[article:1] [article:3]",
+            "[article:1] [article:3] [article:4]",
+            "[article:2] [article:4]",
+        ]
+        .map(tokenize_lossy);
+        let matrix = cooccurrence(docs.iter().map(Vec::as_slice));
+        assert_eq!(
+            matrix.top_partners(&Tag::Article(1), 2),
+            [(&Tag::Article(3), 3), (&Tag::Article(2), 1)]
+        );
+        assert_eq!(
+            matrix.top_partners(&Tag::Article(4), 5),
+            [
+                (&Tag::Article(1), 1),
+                (&Tag::Article(2), 1),
+                (&Tag::Article(3), 1)
+            ]
+        );
+        assert!(matrix.top_partners(&Tag::User(1), 3).is_empty());
+
+        // The three pairs of the first document are pruned right away, so the pair in the
+        // first three documents only counts from the second.
+        let bounded = CooccurrenceOptions {
+            min_count: 2,
+            max_pairs: 2,
+            ..Default::default()
+        };
+        let matrix = cooccurrence_with(docs.iter().map(Vec::as_slice), &bounded);
+        assert_eq!(
+            matrix.pairs().collect::<Vec<_>>(),
+            [(&Tag::Article(1), &Tag::Article(3), 2)]
+        );
+    }
+}
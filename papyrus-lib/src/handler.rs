@@ -0,0 +1,222 @@
+use crate::{
+    scan::{tag_range, text_content, Scanned, Scanner},
+    BorrowedToken, ControlChars, Span, Tag, TokenizeErr, Tokenizer,
+};
+
+/// Whether [`scan`] goes on after a callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Stop,
+}
+
+/// Callbacks for [`scan`], which all continue by default.
+///
+/// Text comes in slices of the input, so text containing escapes is split into several
+/// pieces around the backslashes, and errors don't stop the scan unless `on_error` says so.
+pub trait ScanHandler {
+    fn on_text(&mut self, _text: &str, _span: Span) -> Flow {
+        Flow::Continue
+    }
+
+    fn on_tag(&mut self, _tag: &Tag, _span: Span) -> Flow {
+        Flow::Continue
+    }
+
+    fn on_line_break(&mut self, _span: Span) -> Flow {
+        Flow::Continue
+    }
+
+    fn on_paragraph_break(&mut self, _span: Span) -> Flow {
+        Flow::Continue
+    }
+
+    fn on_error(&mut self, _error: &TokenizeErr) -> Flow {
+        Flow::Continue
+    }
+}
+
+/// Tokenizes the input in one pass, passing every token to `handler` instead of collecting
+/// them, until it returns [`Flow::Stop`], which is what this returns then.
+///
+/// ```
+/// use papyrus_lib::{scan, Flow, ScanHandler, Span, Tag};
+///
+/// struct FirstMention(Option<usize>);
+///
+/// impl ScanHandler for FirstMention {
+///     fn on_tag(&mut self, tag: &Tag, _span: Span) -> Flow {
+///         match tag {
+///             Tag::User(id) => {
+///                 self.0 = Some(*id);
+///                 Flow::Stop
+///             }
+///             _ => Flow::Continue,
+///         }
+///     }
+/// }
+///
+/// let mut first = FirstMention(None);
+/// scan("see [article:1], [user:2] and [user:3]", &mut first);
+/// assert_eq!(first.0, Some(2));
+/// ```
+pub fn scan<H: ScanHandler + ?Sized>(input: &str, handler: &mut H) -> Flow {
+    Tokenizer::default().scan(input, handler)
+}
+
+pub(crate) fn drive<H: ScanHandler + ?Sized>(
+    tokenizer: &Tokenizer,
+    input: &str,
+    handler: &mut H,
+) -> Flow {
+    let mut scanner = Scanner::new(tokenizer.clone());
+    loop {
+        let start = scanner.offset();
+        let Some((lexeme, position)) = scanner.next_lexeme(input.as_bytes()) else {
+            return Flow::Continue;
+        };
+        let span = Span::new(start, scanner.offset());
+        let flow = match lexeme {
+            Scanned::LineBreak => handler.on_line_break(span),
+            Scanned::ParagraphBreak => handler.on_paragraph_break(span),
+            Scanned::Text(range, escaped)
+                if tokenizer.options.normalize_newlines && input[range.clone()].contains('\r')
+                    || tokenizer.options.control_chars != ControlChars::Keep =>
+            {
+                match text_content(&input[range.clone()], escaped, tokenizer, position) {
+                    Ok(text) => handler.on_text(&text, range.into()),
+                    Err(e) => handler.on_error(&e),
+                }
+            }
+            Scanned::Text(range, false) => handler.on_text(&input[range.clone()], range.into()),
+            Scanned::Text(range, true) => {
+                unescaped(input, range.start, range.end, tokenizer, handler)
+            }
+            Scanned::Tag(body) => {
+                match tokenizer.tag::<Tag>(&input[tag_range(body, input.len())], position) {
+                    Ok(BorrowedToken::Tag(tag)) => handler.on_tag(&tag, span),
+                    Ok(BorrowedToken::Text(text)) => handler.on_text(&text, span),
+                    Ok(BorrowedToken::LineBreak) => handler.on_line_break(span),
+                    Ok(BorrowedToken::ParagraphBreak) => handler.on_paragraph_break(span),
+                    Err(e) => handler.on_error(&e),
+                }
+            }
+        };
+        if flow == Flow::Stop {
+            return Flow::Stop;
+        }
+    }
+}
+
+// Passes escaped text as the pieces between the backslashes of its escapes.
+fn unescaped<H: ScanHandler + ?Sized>(
+    input: &str,
+    start: usize,
+    end: usize,
+    tokenizer: &Tokenizer,
+    handler: &mut H,
+) -> Flow {
+    let bytes = input.as_bytes();
+    let escapable = |b: u8| b == tokenizer.open || b == tokenizer.close || b == b'\\';
+    let mut piece = start;
+    let mut i = start;
+    while i < end {
+        if bytes[i] == b'\\' && i + 1 < end && escapable(bytes[i + 1]) {
+            if piece < i && handler.on_text(&input[piece..i], Span::new(piece, i)) == Flow::Stop {
+                return Flow::Stop;
+            }
+            piece = i + 1;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    if piece < end {
+        return handler.on_text(&input[piece..end], Span::new(piece, end));
+    }
+    Flow::Continue
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[derive(Default)]
+    struct Collect {
+        tokens: Vec<Token>,
+        spans: Vec<Span>,
+    }
+
+    impl ScanHandler for Collect {
+        fn on_text(&mut self, text: &str, span: Span) -> Flow {
+            self.spans.push(span);
+            match self.tokens.last_mut() {
+                Some(Token::Text(last)) => last.push_str(text),
+                _ => self.tokens.push(Token::Text(text.to_string())),
+            }
+            Flow::Continue
+        }
+
+        fn on_tag(&mut self, tag: &Tag, span: Span) -> Flow {
+            self.spans.push(span);
+            self.tokens.push(Token::Tag(tag.clone()));
+            Flow::Continue
+        }
+
+        fn on_line_break(&mut self, span: Span) -> Flow {
+            self.spans.push(span);
+            self.tokens.push(Token::LineBreak);
+            Flow::Continue
+        }
+
+        fn on_paragraph_break(&mut self, span: Span) -> Flow {
+            self.spans.push(span);
+            self.tokens.push(Token::ParagraphBreak);
+            Flow::Continue
+        }
+    }
+
+    #[test]
+    pub fn scan_matches_tokenize() {
+        let input = "hi [user:12] wörld \\[x\\] C:\\ \\\\\n\n[article: 3] [user:\n1]\nbye";
+        for emit_breaks in [false, true] {
+            let tokenizer = Tokenizer::builder().emit_breaks(emit_breaks).build();
+            let mut collect = Collect::default();
+            assert_eq!(tokenizer.scan(input, &mut collect), Flow::Continue);
+            assert_eq!(Ok(collect.tokens), tokenizer.tokenize(input));
+            assert!(collect.spans.windows(2).all(|w| w[0].end <= w[1].start));
+        }
+    }
+
+    #[test]
+    pub fn scan_stops() {
+        struct FirstTag(Vec<String>);
+
+        impl ScanHandler for FirstTag {
+            fn on_text(&mut self, text: &str, _span: Span) -> Flow {
+                self.0.push(text.to_string());
+                Flow::Continue
+            }
+
+            fn on_tag(&mut self, tag: &Tag, _span: Span) -> Flow {
+                self.0.push(tag.to_string());
+                Flow::Stop
+            }
+
+            fn on_error(&mut self, error: &TokenizeErr) -> Flow {
+                self.0.push(error.to_string());
+                Flow::Continue
+            }
+        }
+
+        let mut first = FirstTag(Vec::new());
+        assert_eq!(
+            scan("a [bad] b [user:1] c [user:2]", &mut first),
+            Flow::Stop
+        );
+        assert_eq!(
+            first.0,
+            ["a ", "UnknownTag(\"[bad]\") at line 1", " b ", "user:1"]
+        );
+    }
+}
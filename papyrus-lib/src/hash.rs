@@ -0,0 +1,141 @@
+use core::fmt::Write;
+
+use crate::{visit::TokenRef, ParseTag, TokenSource};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// Ends a run of text or a tag. It's never part of UTF-8, so it can't be confused with content.
+const END: u8 = 0xff;
+
+/// A hash of what the tokens mean, stable across releases, for cache keys and ETags.
+///
+/// It's the 64-bit FNV-1a of the [`normalize`](crate::normalize)d tokens, each written as a
+/// kind byte followed by its content and a `0xff` byte: `t` and the text, `h` and the
+/// highlighted text, `l` and `p` with no content for line and paragraph breaks, and `g` and
+/// the tag as it's written between brackets, like `user:5`. Inputs differing only in how
+/// their tags are spelled, like whitespace inside them or aliases, hash the same, while
+/// escaping and breaks count as they mean something. Changing any of this is a breaking
+/// change.
+///
+/// ```
+/// use papyrus_lib::{content_hash, tokenize_lossy};
+///
+/// assert_eq!(
+///     content_hash(&tokenize_lossy("hi [ user: 05 ]")),
+///     content_hash(&tokenize_lossy("hi [user:5]"))
+/// );
+/// assert_ne!(
+///     content_hash(&tokenize_lossy("hi [user:5]")),
+///     content_hash(&tokenize_lossy("hi [user:6]"))
+/// );
+/// ```
+pub fn content_hash<'a, T, S>(tokens: S) -> u64
+where
+    T: ParseTag,
+    S: TokenSource<'a, T>,
+{
+    let mut hasher = Fnv(FNV_OFFSET_BASIS);
+    // Whether a run of text is open, so adjacent text hashes like the single token
+    // normalizing would merge it into.
+    let mut in_text = false;
+    for token in tokens.token_refs() {
+        if let TokenRef::Text(text) = token {
+            if !text.is_empty() && !in_text {
+                hasher.write(b"t");
+                in_text = true;
+            }
+            hasher.write(text.as_bytes());
+            continue;
+        }
+        if in_text {
+            hasher.write(&[END]);
+            in_text = false;
+        }
+        match token {
+            TokenRef::Text(_) => unreachable!(),
+            TokenRef::Highlight(text) => {
+                hasher.write(b"h");
+                hasher.write(text.as_bytes());
+            }
+            TokenRef::Tag(tag) => {
+                hasher.write(b"g");
+                write!(hasher, "{tag}").unwrap();
+            }
+            TokenRef::LineBreak => hasher.write(b"l"),
+            TokenRef::ParagraphBreak => hasher.write(b"p"),
+        }
+        hasher.write(&[END]);
+    }
+    if in_text {
+        hasher.write(&[END]);
+    }
+    hasher.0
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+impl Write for Fnv {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn hash(input: &str) -> u64 {
+        content_hash(&tokenize_lossy(input))
+    }
+
+    #[test]
+    pub fn content_hash_fixed() {
+        assert_eq!(content_hash(&[] as &[Token]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash("hello"), 0x3cbb_8edc_e7c9_d8da);
+        assert_eq!(hash("hi [user:1], see [article:22]"), 0x9755_a29e_e256_ec0d);
+        #[cfg(feature = "tag-blocks")]
+        assert_eq!(
+            content_hash(&[
+                Token::Tag(Tag::Spoiler),
+                Token::LineBreak,
+                Token::Highlight("x".to_string()),
+                Token::ParagraphBreak,
+                Token::Tag(Tag::Close(BlockKind::Spoiler)),
+            ]),
+            0x73df_9dd7_4ad4_9029
+        );
+    }
+
+    #[test]
+    pub fn content_hash_ignores_spelling() {
+        let tokenizer = Tokenizer::builder().alias("u", "user").unwrap().build();
+        let aliased = tokenizer.tokenize("a [u: 01 ]b").unwrap();
+        assert_eq!(content_hash(&aliased), hash("a [user:1]b"));
+        let split: [Token; 3] = [
+            Token::Text("a".to_string()),
+            Token::Text(String::new()),
+            Token::Text(" b".to_string()),
+        ];
+        assert_eq!(content_hash(&split), hash("a b"));
+
+        assert_ne!(hash("a [user:1]b"), hash("a [user:2]b"));
+        assert_ne!(hash("a [user:1]"), hash("a [article:1]"));
+        assert_ne!(hash("ab"), hash("a\\[b"));
+        let highlighted: [Token; 2] = [
+            Token::Text("a".to_string()),
+            Token::Highlight("b".to_string()),
+        ];
+        assert_ne!(content_hash(&highlighted), hash("ab"));
+    }
+}
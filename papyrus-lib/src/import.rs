@@ -0,0 +1,531 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Display, num::ParseIntError};
+
+use crate::{normalize, tokenizer::BUILTIN_NAMES, RenderTag, Tag, TagParseErr, Token};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    /// A `<` without a closing `>`.
+    UnterminatedTag(usize),
+    /// A mention or article anchor without a closing `</a>`, or an element of
+    /// [`from_rendered_html`] without its closing tag.
+    UnclosedAnchor(usize),
+    InvalidId(usize, ParseIntError),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ImportError::UnterminatedTag(offset) => write!(f, "unterminated tag at byte {offset}"),
+            ImportError::UnclosedAnchor(offset) => write!(f, "unclosed anchor at byte {offset}"),
+            ImportError::InvalidId(offset, e) => write!(f, "invalid id at byte {offset}: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ImportError {}
+
+/// Converts markup of the old editor into tokens.
+///
+/// Anchors with a `data-user-id` or `data-article-id` become tags, whatever their label is.
+/// Paragraphs become paragraph breaks and `<br>`s line breaks. Any other markup is stripped,
+/// keeping its text.
+pub fn from_legacy_html(input: &str) -> Result<Vec<Token>, ImportError> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find('<') {
+        decode_entities(&mut text, &rest[..start]);
+        let offset = input.len() - rest.len() + start;
+        let end = rest[start..]
+            .find('>')
+            .ok_or(ImportError::UnterminatedTag(offset))?;
+        let element = Element::parse(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+
+        let mut flush = |tokens: &mut Vec<Token>, token| {
+            tokens.push(Token::Text(core::mem::take(&mut text)));
+            tokens.push(token);
+        };
+        match element.name.as_str() {
+            "a" if !element.closing => {
+                let tag = match (
+                    element.attribute("data-user-id"),
+                    element.attribute("data-article-id"),
+                ) {
+                    (Some(id), _) => Tag::User(parse_id(id, offset)?),
+                    (None, Some(id)) => Tag::Article(parse_id(id, offset)?),
+                    (None, None) => continue,
+                };
+                let (_, close) =
+                    find_closing(rest, "a").ok_or(ImportError::UnclosedAnchor(offset))?;
+                rest = &rest[close..];
+                flush(&mut tokens, Token::Tag(tag));
+            }
+            "p" if !element.closing => flush(&mut tokens, Token::ParagraphBreak),
+            "br" => flush(&mut tokens, Token::LineBreak),
+            _ => {}
+        }
+    }
+    decode_entities(&mut text, rest);
+    tokens.push(Token::Text(text));
+
+    Ok(trim_paragraphs(normalize(tokens)))
+}
+
+/// Converts the output of [`render_html`](crate::render_html) back into tokens.
+///
+/// Tags are read from the class and data attribute of their element, ignoring the label, so
+/// resolved and unresolved tags come back alike. Elements whose attributes don't make a tag
+/// the tokenizer would read, like links that aren't http(s), are stripped. A `<br>` and the
+/// newline after it become a line break and two of them a paragraph break, and `<mark>`s
+/// highlights. Any other markup is stripped, keeping its text. The tokens are normalized.
+///
+/// ```
+/// use papyrus_lib::{from_rendered_html, render_html, tokenize_lossy, Tag};
+///
+/// let tokens = tokenize_lossy("hi [user:1] & [article:2]");
+/// let html = render_html(&tokens, &|_: &Tag| None);
+/// assert_eq!(from_rendered_html(&html), Ok(tokens));
+/// ```
+pub fn from_rendered_html(input: &str) -> Result<Vec<Token>, ImportError> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find('<') {
+        decode_entities(&mut text, &rest[..start]);
+        let offset = input.len() - rest.len() + start;
+        let end = rest[start..]
+            .find('>')
+            .ok_or(ImportError::UnterminatedTag(offset))?;
+        let element = Element::parse(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+        if element.closing {
+            continue;
+        }
+
+        let token = match element.name.as_str() {
+            "a" | "span" => {
+                let Some(tag) = rendered_tag(&element, offset)? else {
+                    continue;
+                };
+                let (_, close) =
+                    find_closing(rest, &element.name).ok_or(ImportError::UnclosedAnchor(offset))?;
+                rest = &rest[close..];
+                Token::Tag(tag)
+            }
+            "mark" => {
+                let (content, close) =
+                    find_closing(rest, "mark").ok_or(ImportError::UnclosedAnchor(offset))?;
+                let mut highlight = String::new();
+                decode_entities(&mut highlight, &rest[..content]);
+                rest = &rest[close..];
+                Token::Highlight(highlight)
+            }
+            "br" => {
+                rest = rest.strip_prefix('\n').unwrap_or(rest);
+                if text.is_empty() && tokens.last() == Some(&Token::LineBreak) {
+                    tokens.pop();
+                    Token::ParagraphBreak
+                } else {
+                    Token::LineBreak
+                }
+            }
+            _ => continue,
+        };
+        if !text.is_empty() {
+            tokens.push(Token::Text(core::mem::take(&mut text)));
+        }
+        tokens.push(token);
+    }
+    decode_entities(&mut text, rest);
+    tokens.push(Token::Text(text));
+
+    Ok(normalize(tokens))
+}
+
+// The tag an element of `render_html` stands for, by its first class.
+fn rendered_tag(element: &Element, offset: usize) -> Result<Option<Tag>, ImportError> {
+    let class = element.attribute("class").unwrap_or_default();
+    let data = |name| {
+        element.attribute(name).map(|value| {
+            let mut decoded = String::new();
+            decode_entities(&mut decoded, value);
+            decoded
+        })
+    };
+    // Ids that aren't numbers are keys.
+    let id_or_key = |id: String, kind: fn(usize) -> Tag, key: fn(String) -> Tag| match id
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+    {
+        true => parse_id(&id, offset).map(kind),
+        false => Ok(key(id)),
+    };
+    let tag = match class.split_whitespace().next() {
+        Some("mention") => match data("data-user-id") {
+            Some(id) => id_or_key(id, Tag::User, Tag::UserKey)?,
+            None => return Ok(None),
+        },
+        Some("article") => match data("data-article-id") {
+            Some(id) => id_or_key(id, Tag::Article, Tag::ArticleKey)?,
+            None => return Ok(None),
+        },
+        #[cfg(feature = "tag-blocks")]
+        Some("quote") => match data("data-article-id") {
+            Some(id) => Tag::Quote(parse_id(&id, offset)?),
+            None => return Ok(None),
+        },
+        // Anything else is read like the tokenizer reads its body, so only tags it could have
+        // given come back, not `javascript:` links or names with spaces.
+        Some("link") => match data("data-url") {
+            Some(url) => return Ok(parsed(&format!("link:{url}"), "link")),
+            None => return Ok(None),
+        },
+        Some("var") => match data("data-var") {
+            Some(name) => return Ok(parsed(&format!("var:{name}"), "var")),
+            None => return Ok(None),
+        },
+        Some(class @ ("tag" | "spoiler")) => match data("data-tag") {
+            Some(body) => match body.parse::<Tag>() {
+                Ok(tag) if tag.class() == class => tag,
+                Err(TagParseErr::UnknownTag(_)) if class == "tag" => match custom_tag(&body) {
+                    Some(tag) => tag,
+                    None => return Ok(None),
+                },
+                _ => return Ok(None),
+            },
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(tag))
+}
+
+// The tag of the body, if it's one of the class.
+fn parsed(body: &str, class: &str) -> Option<Tag> {
+    body.parse::<Tag>().ok().filter(|tag| tag.class() == class)
+}
+
+// A custom tag the body could be the markup of. Names of built-in tags aren't free for
+// them, even those whose feature is off.
+fn custom_tag(body: &str) -> Option<Tag> {
+    let (name, value) = body.split_once(':')?;
+    let valid = !name.is_empty()
+        && !name.contains(char::is_whitespace)
+        && !BUILTIN_NAMES.contains(&name)
+        && !value.contains(['[', ']']);
+    valid.then(|| Tag::Custom {
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+// Where the content of the element whose opening tag preceded `rest` ends, and the offset
+// just past its closing tag.
+fn find_closing(rest: &str, name: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    while let Some(start) = rest[offset..].find("</") {
+        let start = offset + start;
+        let end = start + rest[start..].find('>')?;
+        if rest[start + 2..end].trim().eq_ignore_ascii_case(name) {
+            return Some((start, end + 1));
+        }
+        offset = end;
+    }
+    None
+}
+
+fn parse_id(id: &str, offset: usize) -> Result<usize, ImportError> {
+    id.trim()
+        .parse()
+        .map_err(|e| ImportError::InvalidId(offset, e))
+}
+
+// Drops the whitespace HTML puts around paragraphs, and paragraph breaks not separating two
+// paragraphs.
+fn trim_paragraphs(tokens: Vec<Token>) -> Vec<Token> {
+    let mut out: Vec<Token> = Vec::new();
+    for token in tokens {
+        match token {
+            Token::ParagraphBreak => {
+                if let Some(Token::Text(text)) = out.last_mut() {
+                    text.truncate(text.trim_end().len());
+                    if text.is_empty() {
+                        out.pop();
+                    }
+                }
+                if !matches!(out.last(), None | Some(Token::ParagraphBreak)) {
+                    out.push(Token::ParagraphBreak);
+                }
+            }
+            Token::Text(text) if matches!(out.last(), None | Some(Token::ParagraphBreak)) => {
+                let text = text.trim_start();
+                if !text.is_empty() {
+                    out.push(Token::Text(text.to_string()));
+                }
+            }
+            token => out.push(token),
+        }
+    }
+    if let Some(Token::Text(text)) = out.last_mut() {
+        text.truncate(text.trim_end().len());
+        if text.is_empty() {
+            out.pop();
+        }
+    }
+    if out.last() == Some(&Token::ParagraphBreak) {
+        out.pop();
+    }
+    out
+}
+
+fn decode_entities(out: &mut String, text: &str) {
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                entity => {
+                    let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => entity.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+}
+
+// The name and attributes of a tag, from the markup between its angle brackets.
+struct Element<'a> {
+    name: String,
+    closing: bool,
+    attributes: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Element<'a> {
+    fn parse(markup: &'a str) -> Self {
+        let (closing, markup) = match markup.strip_prefix('/') {
+            Some(markup) => (true, markup),
+            None => (false, markup),
+        };
+        let markup = markup.trim_end_matches('/');
+        let name_end = markup
+            .find(|c: char| c.is_whitespace())
+            .unwrap_or(markup.len());
+
+        let mut attributes = Vec::new();
+        let mut rest = markup[name_end..].trim_start();
+        while !rest.is_empty() {
+            let name_end = rest
+                .find(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let name = &rest[..name_end];
+            rest = rest[name_end..].trim_start();
+
+            let mut value = "";
+            if let Some(after) = rest.strip_prefix('=') {
+                let after = after.trim_start();
+                let (v, remaining) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = after[1..].find(quote).map_or(after.len(), |e| e + 1);
+                        (&after[1..end], after.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = after
+                            .find(|c: char| c.is_whitespace())
+                            .unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+                value = v;
+                rest = remaining.trim_start();
+            }
+            if !name.is_empty() {
+                attributes.push((name, value));
+            }
+        }
+
+        Self {
+            name: markup[..name_end].to_ascii_lowercase(),
+            closing,
+            attributes,
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::text, *};
+
+    #[test]
+    pub fn import_nested_mention() {
+        assert_eq!(
+            from_legacy_html(
+                "<p><b>thanks <a class=\"mention\" data-user-id=\"5\"><i>@alice</i></a></b>, \
+                 see <a data-article-id='9' href=\"/a/9\">Launch &amp; more</a>!</p>"
+            ),
+            Ok(vec![
+                text("thanks "),
+                Token::Tag(Tag::User(5)),
+                text(", see "),
+                Token::Tag(Tag::Article(9)),
+                text("!"),
+            ])
+        );
+    }
+
+    #[test]
+    pub fn import_plain_anchor() {
+        assert_eq!(
+            from_legacy_html("go <a class=\"mention\" href=\"/u/bob\">@bob</a> &lt;3"),
+            Ok(vec![text("go @bob <3")])
+        );
+    }
+
+    #[test]
+    pub fn import_paragraphs() {
+        assert_eq!(
+            from_legacy_html("<p>one<br>line</p>\n<p> two [x] </p>\n"),
+            Ok(vec![
+                text("one"),
+                Token::LineBreak,
+                text("line"),
+                Token::ParagraphBreak,
+                text("two [x]"),
+            ])
+        );
+        assert_eq!(from_legacy_html("just text"), Ok(vec![text("just text")]));
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn import_rendered() {
+        let ticket = Tag::Custom {
+            name: "ticket".to_string(),
+            value: "A&B:1".to_string(),
+        };
+        let tokens = vec![
+            text("<hi> "),
+            Token::Tag(Tag::User(1)),
+            text(" & "),
+            Token::Tag(Tag::UserKey("ann".to_string())),
+            Token::LineBreak,
+            Token::Tag(Tag::Quote(9)),
+            Token::Tag(ticket.clone()),
+            Token::ParagraphBreak,
+            Token::Highlight("'found'".to_string()),
+            Token::Tag(Tag::Link("https://a.com/?a=1&b=2".to_string())),
+            Token::Tag(Tag::Spoiler),
+            text("\n"),
+            Token::Tag(Tag::Var("order_id".to_string())),
+            Token::Tag(Tag::Close(BlockKind::Spoiler)),
+            Token::Tag(Tag::ArticleKey("x\"y".to_string())),
+        ];
+        let resolver = testing::MapResolver::new()
+            .link(Tag::User(1), "</a>", "/u/1")
+            .label(ticket, "[ticket]");
+        let html = render_html(&tokens, &resolver);
+        assert_eq!(from_rendered_html(&html), Ok(tokens));
+    }
+
+    #[test]
+    pub fn import_rendered_checks_tags() {
+        for html in [
+            "<a class=\"link\" data-url=\"javascript:alert(1)\">x</a>",
+            "<span class=\"var\" data-var=\"a b]\">x</span>",
+            "<span class=\"tag\" data-tag=\"if:x y\">x</span>",
+            "<span class=\"tag\" data-tag=\"user:1\">x</span>",
+            "<span class=\"tag\" data-tag=\"quote:ab\">x</span>",
+            "<span class=\"tag\" data-tag=\"my tag:1\">x</span>",
+            "<span class=\"tag\" data-tag=\"ticket:a]b\">x</span>",
+            "<span class=\"spoiler\" data-tag=\"list\">x</span>",
+        ] {
+            assert_eq!(from_rendered_html(html), Ok(vec![text("x")]), "{html}");
+        }
+        let html = "<a class=\"link\" data-url=\"https://a.com\">x</a>\
+                    <span class=\"tag\" data-tag=\"ticket:1\">x</span>";
+        let tokens = from_rendered_html(html).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Tag(Tag::Link("https://a.com".to_string())),
+                Token::Tag(Tag::Custom {
+                    name: "ticket".to_string(),
+                    value: "1".to_string()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn import_rendered_unknown_markup() {
+        assert_eq!(
+            from_rendered_html(
+                "<p>a <b>bold</b> <a href=\"/x\">link</a> <span class=\"mention\">@x</span> \
+                 <span class=\"tag\" data-tag=\"nope\">[nope]</span>&amp;</p><br>"
+            ),
+            Ok(vec![text("a bold link @x [nope]&"), Token::LineBreak])
+        );
+        assert_eq!(
+            from_rendered_html("<mark>a"),
+            Err(ImportError::UnclosedAnchor(0))
+        );
+        #[cfg(feature = "tag-blocks")]
+        assert!(matches!(
+            from_rendered_html("<span class=\"quote\" data-article-id=\"a\"></span>"),
+            Err(ImportError::InvalidId(0, _))
+        ));
+    }
+
+    #[test]
+    pub fn import_errors() {
+        assert_eq!(
+            from_legacy_html("a <b"),
+            Err(ImportError::UnterminatedTag(2))
+        );
+        assert_eq!(
+            from_legacy_html("a <a data-user-id=\"1\">@x"),
+            Err(ImportError::UnclosedAnchor(2))
+        );
+        assert!(matches!(
+            from_legacy_html("<a data-user-id=\"x\">@x</a>"),
+            Err(ImportError::InvalidId(0, _))
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Where tags are referenced across many documents.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{Document, Span, Tag, Token};
+
+/// How a document is identified in a [`CorpusIndex`].
+pub type DocId = u64;
+
+/// An inverted index from tags to the documents and spans referencing them.
+///
+/// Documents are tokenized leniently, like [`MentionGraph`](crate::graph::MentionGraph)
+/// does. Tags are ordered by kind, so those of a kind are next to each other, and the
+/// occurrences of a tag are sorted by document and then span.
+#[derive(Debug, Default)]
+pub struct CorpusIndex {
+    occurrences: BTreeMap<Tag, Vec<(DocId, Span)>>,
+    /// The distinct tags of every document, to find its occurrences when it's replaced.
+    documents: BTreeMap<DocId, Vec<Tag>>,
+}
+
+impl CorpusIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indexes the tags of `input`, replacing those a document with the same id had.
+    pub fn add(&mut self, doc: DocId, input: &str) {
+        self.remove(doc);
+        let mut tags = Vec::new();
+        for (token, span) in Document::parse_lossy(input).spanned_tokens() {
+            let Token::Tag(tag) = token else { continue };
+            let occurrences = self.occurrences.entry(tag.clone()).or_default();
+            let at = occurrences.partition_point(|&(other, _)| other <= doc);
+            occurrences.insert(at, (doc, span));
+            tags.push(tag.clone());
+        }
+        tags.sort();
+        tags.dedup();
+        self.documents.insert(doc, tags);
+    }
+
+    /// Drops a document from the index, returning whether it was in it.
+    pub fn remove(&mut self, doc: DocId) -> bool {
+        let Some(tags) = self.documents.remove(&doc) else {
+            return false;
+        };
+        for tag in tags {
+            if let Some(occurrences) = self.occurrences.get_mut(&tag) {
+                occurrences.retain(|&(other, _)| other != doc);
+                if occurrences.is_empty() {
+                    self.occurrences.remove(&tag);
+                }
+            }
+        }
+        true
+    }
+
+    /// Every reference to `tag`, ordered by document and then span.
+    pub fn occurrences(&self, tag: &Tag) -> &[(DocId, Span)] {
+        self.occurrences.get(tag).map_or(&[], Vec::as_slice)
+    }
+
+    /// The documents referencing `tag`, in ascending order.
+    pub fn documents(&self, tag: &Tag) -> impl Iterator<Item = DocId> + '_ {
+        let occurrences = self.occurrences(tag);
+        occurrences
+            .iter()
+            .enumerate()
+            .filter(move |&(i, &(doc, _))| i == 0 || occurrences[i - 1].0 != doc)
+            .map(|(_, &(doc, _))| doc)
+    }
+
+    pub fn documents_mentioning_user(&self, id: usize) -> impl Iterator<Item = DocId> + '_ {
+        self.documents(&Tag::User(id))
+    }
+
+    pub fn documents_mentioning_article(&self, id: usize) -> impl Iterator<Item = DocId> + '_ {
+        self.documents(&Tag::Article(id))
+    }
+
+    /// The indexed tags, grouped by kind, with the number of references to each.
+    pub fn tags(&self) -> impl Iterator<Item = (&Tag, usize)> {
+        self.occurrences
+            .iter()
+            .map(|(tag, occurrences)| (tag, occurrences.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{index::*, *};
+
+    fn corpus() -> CorpusIndex {
+        let mut index = CorpusIndex::new();
+        index.add(3, "see [article:9] by [user:1], and [article:9] again");
+        index.add(1, "[user:1] wrote [article:2]");
+        index.add(2, "[usr:1] [user:2] [article:x] on [article:9]");
+        index
+    }
+
+    #[test]
+    pub fn index_queries() {
+        let index = corpus();
+        assert_eq!(
+            index.occurrences(&Tag::Article(9)),
+            &[
+                (2, Span::new(32, 43)),
+                (3, Span::new(4, 15)),
+                (3, Span::new(33, 44)),
+            ]
+        );
+        assert_eq!(
+            index.documents(&Tag::Article(9)).collect::<Vec<_>>(),
+            [2, 3]
+        );
+        assert_eq!(
+            index.documents_mentioning_user(1).collect::<Vec<_>>(),
+            [1, 3]
+        );
+        assert_eq!(
+            index.documents_mentioning_article(2).collect::<Vec<_>>(),
+            [1]
+        );
+        assert_eq!(index.occurrences(&Tag::User(5)), &[]);
+        assert_eq!(
+            index.tags().collect::<Vec<_>>(),
+            [
+                (&Tag::User(1), 2),
+                (&Tag::User(2), 1),
+                (&Tag::Article(2), 1),
+                (&Tag::Article(9), 3),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn index_replace_document() {
+        let mut index = corpus();
+        index.add(3, "now only [user:2]");
+        assert_eq!(index.documents(&Tag::Article(9)).collect::<Vec<_>>(), [2]);
+        assert_eq!(
+            index.occurrences(&Tag::User(2)),
+            &[(2, Span::new(8, 16)), (3, Span::new(9, 17))]
+        );
+        assert!(index.remove(2));
+        assert!(!index.remove(2));
+        assert_eq!(index.occurrences(&Tag::Article(9)), &[]);
+        assert_eq!(
+            index.tags().collect::<Vec<_>>(),
+            [
+                (&Tag::User(1), 1),
+                (&Tag::User(2), 1),
+                (&Tag::Article(2), 1)
+            ]
+        );
+    }
+}
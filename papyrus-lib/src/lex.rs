@@ -0,0 +1,224 @@
+use alloc::vec::Vec;
+use core::{iter::FusedIterator, ops::Range};
+
+use crate::{
+    scan::{Scanned, Scanner},
+    Span, Tokenizer,
+};
+
+/// A piece of the markup as written, see [`lex`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lexeme {
+    OpenBracket,
+    /// What's before the colon of a tag, or all of it without one.
+    TagName,
+    Colon,
+    /// What's after the colon of a tag.
+    Payload,
+    CloseBracket,
+    /// Text outside of tags, escapes included as written.
+    Text,
+    Newline,
+    /// Spaces around the name and payload of a tag, and blank lines, which aren't text.
+    Whitespace,
+}
+
+/// Splits markup into its lexemes with their spans, which cover the input without gaps.
+///
+/// ```
+/// use papyrus_lib::{lex, Lexeme};
+///
+/// let input = "hi [user: 1]";
+/// let lexemes = lex(input).map(|(lexeme, _)| lexeme).collect::<Vec<_>>();
+/// assert_eq!(
+///     lexemes,
+///     [
+///         Lexeme::Text,
+///         Lexeme::OpenBracket,
+///         Lexeme::TagName,
+///         Lexeme::Colon,
+///         Lexeme::Whitespace,
+///         Lexeme::Payload,
+///         Lexeme::CloseBracket,
+///     ]
+/// );
+/// let source = lex(input).map(|(_, span)| &input[span.start..span.end]).collect::<String>();
+/// assert_eq!(source, input);
+/// ```
+pub fn lex(input: &str) -> Lexemes<'_> {
+    Tokenizer::default().lex(input)
+}
+
+/// The iterator returned by [`lex`] and [`Tokenizer::lex`].
+#[derive(Clone)]
+pub struct Lexemes<'a> {
+    source: &'a str,
+    scanner: Scanner,
+    /// The rest of the lexemes of the last token scanned, in reverse.
+    pending: Vec<(Lexeme, Span)>,
+}
+
+impl<'a> Lexemes<'a> {
+    pub(crate) fn new(source: &'a str, scanner: Scanner) -> Self {
+        Self {
+            source,
+            scanner,
+            pending: Vec::new(),
+        }
+    }
+
+    // Pushes a lexeme, splitting newlines out of it.
+    fn push(&mut self, lexeme: Lexeme, range: Range<usize>) {
+        let mut start = range.start;
+        for newline in memchr::memchr_iter(b'\n', &self.source.as_bytes()[range.clone()]) {
+            let newline = range.start + newline;
+            if start < newline {
+                self.pending.push((lexeme, Span::new(start, newline)));
+            }
+            self.pending
+                .push((Lexeme::Newline, Span::new(newline, newline + 1)));
+            start = newline + 1;
+        }
+        if start < range.end {
+            self.pending.push((lexeme, Span::new(start, range.end)));
+        }
+    }
+
+    // Pushes a tag's name or payload, with the whitespace around it.
+    fn push_trimmed(&mut self, lexeme: Lexeme, range: Range<usize>) {
+        let text = &self.source[range.clone()];
+        let start = range.start + (text.len() - text.trim_start().len());
+        let end = range.end - (text.len() - text.trim_end().len());
+        let end = end.max(start);
+        self.push(Lexeme::Whitespace, range.start..start);
+        self.push(lexeme, start..end);
+        self.push(Lexeme::Whitespace, end..range.end);
+    }
+
+    fn push_tag(&mut self, body: Range<usize>) {
+        self.push(Lexeme::OpenBracket, body.start - 1..body.start);
+        let colon = memchr::memchr(b':', &self.source.as_bytes()[body.clone()]);
+        match colon.map(|colon| body.start + colon) {
+            Some(colon) => {
+                self.push_trimmed(Lexeme::TagName, body.start..colon);
+                self.push(Lexeme::Colon, colon..colon + 1);
+                self.push_trimmed(Lexeme::Payload, colon + 1..body.end);
+            }
+            None => self.push_trimmed(Lexeme::TagName, body.clone()),
+        }
+        if body.end < self.source.len() {
+            self.push(Lexeme::CloseBracket, body.end..body.end + 1);
+        }
+    }
+}
+
+impl Iterator for Lexemes<'_> {
+    type Item = (Lexeme, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_empty() {
+            let start = self.scanner.offset();
+            let (scanned, _) = self.scanner.next_lexeme(self.source.as_bytes())?;
+            match scanned {
+                Scanned::Tag(body) => self.push_tag(body),
+                Scanned::Text(range, _) => self.push(Lexeme::Text, range),
+                Scanned::LineBreak | Scanned::ParagraphBreak => {
+                    self.push(Lexeme::Whitespace, start..self.scanner.offset());
+                }
+            }
+            self.pending.reverse();
+        }
+        self.pending.pop()
+    }
+}
+
+impl FusedIterator for Lexemes<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn lexemes(input: &str) -> Vec<(Lexeme, &str)> {
+        lex(input)
+            .map(|(lexeme, span)| (lexeme, &input[span.start..span.end]))
+            .collect()
+    }
+
+    #[test]
+    pub fn lex_tags() {
+        use Lexeme::*;
+        assert_eq!(
+            lexemes("a\\[ [ user :  12 ]\n[bad][x:"),
+            [
+                (Text, "a\\[ "),
+                (OpenBracket, "["),
+                (Whitespace, " "),
+                (TagName, "user"),
+                (Whitespace, " "),
+                (Colon, ":"),
+                (Whitespace, "  "),
+                (Payload, "12"),
+                (Whitespace, " "),
+                (CloseBracket, "]"),
+                (Newline, "\n"),
+                (OpenBracket, "["),
+                (TagName, "bad"),
+                (CloseBracket, "]"),
+                (OpenBracket, "["),
+                (TagName, "x"),
+                (Colon, ":"),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn lex_breaks() {
+        use Lexeme::*;
+        let tokenizer = Tokenizer::builder().emit_breaks(true).build();
+        let input = "a\n \n[user:\n1]";
+        assert_eq!(
+            tokenizer
+                .lex(input)
+                .map(|(lexeme, span)| (lexeme, &input[span.start..span.end]))
+                .collect::<Vec<_>>(),
+            [
+                (Text, "a"),
+                (Newline, "\n"),
+                (Whitespace, " "),
+                (Newline, "\n"),
+                (OpenBracket, "["),
+                (TagName, "user"),
+                (Colon, ":"),
+                (Newline, "\n"),
+                (Payload, "1"),
+                (CloseBracket, "]"),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn lex_lossless() {
+        let inputs = [
+            "",
+            "[",
+            "]",
+            "[]",
+            "[:]",
+            "[ : ]",
+            "hi [user:1] wörld 🦀 \\[x\\] C:\\ [usr:1] ]\n\n[article: 3]\n bye",
+            "[user:\n1]\n\n  \n[y",
+        ];
+        for input in inputs {
+            for emit_breaks in [false, true] {
+                let tokenizer = Tokenizer::builder().emit_breaks(emit_breaks).build();
+                let mut end = 0;
+                for (_, span) in tokenizer.lex(input) {
+                    assert_eq!(span.start, end, "{input:?}");
+                    assert!(span.end > span.start, "{input:?}");
+                    end = span.end;
+                }
+                assert_eq!(end, input.len(), "{input:?}");
+            }
+        }
+    }
+}
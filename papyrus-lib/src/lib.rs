@@ -1,72 +1,902 @@
-use std::{
-    borrow::BorrowMut,
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    cmp::Ordering,
     fmt::{Debug, Display},
-    iter::Peekable,
+    iter::FusedIterator,
+    marker::PhantomData,
     num::ParseIntError,
-    str::{Chars, FromStr},
+    ops::Range,
+    str::FromStr,
 };
 
-use regex::Regex;
+use scan::{tag_range, text_content, Scanned, Scanner};
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impl;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "rayon")]
+mod batch;
+#[cfg(feature = "binary")]
+pub mod binary;
+mod budget;
+mod bytes;
+#[cfg(feature = "cache")]
+pub mod cache;
+mod canonical;
+mod chunk;
+mod compact;
+mod complete;
+mod control;
+mod cursor;
+mod diff;
+mod document;
+mod dump;
+mod encoding;
+mod front_matter;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+pub mod graph;
+mod handler;
+mod hash;
+mod import;
+pub mod index;
+mod lex;
+mod line;
+pub mod lint;
+#[cfg(feature = "markdown")]
+pub mod markdown;
+#[cfg(feature = "unicode")]
+mod measure;
+mod message;
+mod meter;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod notify;
+mod owned;
+mod policy;
+mod progress;
+#[cfg(test)]
+mod properties;
+mod provenance;
+mod push;
+#[cfg(feature = "std")]
+mod read;
+mod render;
+#[cfg(feature = "rkyv")]
+mod rkyv_impl;
+mod roundtrip;
+mod scan;
+#[cfg(feature = "schemars")]
+mod schema;
+mod search;
+mod sentence;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod snippet;
+mod social;
+mod split;
+#[cfg(feature = "sqlx")]
+pub mod sqlx;
+mod stats;
+#[cfg(feature = "tokio")]
+mod stream;
+#[cfg(feature = "std")]
+mod substitute;
+mod tag_scan;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod tokenizer;
+mod trace;
+mod transform;
+#[cfg(feature = "tag-blocks")]
+mod tree;
+mod validate;
+mod visit;
+
+// What the code `papyrus_macros` and `papyrus_derive` expand to refers to, so it works without `std` too.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::{format, string::String, vec};
+}
 
-#[derive(Debug, PartialEq, Eq)]
+#[cfg(feature = "rayon")]
+pub use batch::{tokenize_batch, tokenize_batch_lossy};
+pub use budget::{Budget, Limit, Truncated};
+pub use bytes::ByteTokenIter;
+pub use canonical::{check_format, format};
+pub use chunk::{chunk_text, ChunkText};
+pub use compact::{CompactDocument, CompactToken};
+pub use complete::{completion_context, PartialTag};
+pub use control::ControlChars;
+pub use cursor::{Checkpoint, TokenCursor};
+pub use diff::{diff, TextChange, TokenDiff};
+pub use document::{to_source, Document, DocumentBuilder, Edit, EditError, SliceError};
+#[cfg(feature = "encoding")]
+pub use encoding::tokenize_encoded;
+pub use encoding::{tokenize_lossy_bytes, EncodingIssue};
+pub use front_matter::{FrontMatter, FrontMatterErr};
+pub use handler::{scan, Flow, ScanHandler};
+pub use hash::content_hash;
+pub use import::{from_legacy_html, from_rendered_html, ImportError};
+pub use lex::{lex, Lexeme, Lexemes};
+pub use line::{lines, Lines};
+#[cfg(feature = "unicode")]
+pub use measure::{measure, Measure, TagWidth};
+#[cfg(feature = "fluent")]
+pub use message::format_fluent;
+pub use message::{Message, Problem};
+#[cfg(feature = "mmap")]
+pub use mmap::{tokenize_file, MapError, MappedFile, MappedTokens};
+pub use notify::{notification_targets, NotifyOptions};
+pub use owned::OwnedTokenIter;
+pub use policy::{check, PolicyRule, PolicyViolation, TagKind, TagPolicy};
+pub use progress::{progress, BytesRead, Progress};
+pub use provenance::{Origin, TracedToken};
+pub use push::PushParser;
+#[cfg(feature = "std")]
+pub use read::{ReadErr, ReadTokenIter};
+#[cfg(feature = "tag-blocks")]
+pub use render::render_tree_html;
+pub use render::{
+    render_html, render_html_with, render_plain, HtmlOptions, RenderTag, Rendered, RenderedHtml,
+    RenderedPlain, Resolved, Resolver,
+};
+pub use roundtrip::{verify_roundtrip, Divergence, RoundtripReport};
+pub use search::{find_text, highlight};
+pub use sentence::sentences;
+pub use snippet::{contexts, TagContext};
+pub use social::{convert_social, HandleResolver};
+pub use split::paragraphs;
+pub use stats::{estimate_len, exceeds, reading_time, word_count, LenModel};
+#[cfg(feature = "tokio")]
+pub use stream::TokenStream;
+#[cfg(feature = "std")]
+pub use substitute::{substitute, MissingVar};
+pub use tag_scan::{count_tags, count_tokens, has_any_tag, scan_tags, TagScan};
+pub use tokenizer::{
+    PayloadKind, RegisterError, Tokenizer, TokenizerBuilder, UnknownTagAction, UnknownTags,
+};
+pub use transform::{
+    autolink, flatten, map_text, normalize, redact, tags, typography, typography_with, Autolink,
+    Flatten, MapText, Normalize, Pipeline, Redact, Transform, Typography, TypographyOptions,
+};
+#[cfg(feature = "unicode-normalization")]
+pub use transform::{nfc, Nfc};
+#[cfg(feature = "tag-blocks")]
+pub use tree::{
+    evaluate, parse_tree, parse_tree_with, unwrap_blocks, BlockKind, Node, TreeError, TreeOptions,
+    Unwrap, UnwrapPolicy,
+};
+pub use validate::{validate, Diagnostic, Severity};
+pub use visit::{walk, walk_spanned, TokenRef, TokenSource, TokenVisitor};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum TagParseErr {
     NoCaptures,
     CaptureNotFound,
-    CaptureParseErr(ParseIntError),
+    CaptureParseErr(
+        #[cfg_attr(feature = "serde", serde(with = "serde_impl::parse_int_err"))]
+        #[cfg_attr(feature = "schemars", schemars(with = "schema::ParseIntErrKind"))]
+        ParseIntError,
+    ),
     UnknownTag(String),
+    /// A tag named with an alias, see [`TokenizerBuilder::deprecate_aliases`]. Only ever a
+    /// warning, as the tag parses like one named `canonical`.
+    DeprecatedAlias {
+        alias: String,
+        canonical: String,
+    },
+    /// The payload doesn't match the pattern set with [`TokenizerBuilder::payload_pattern`].
+    InvalidPayload(String),
+    /// A part of the payload that doesn't parse, like the id of `[user:5x]`, see
+    /// [`TokenizeErr::invalid_part`] for where it is in the input.
+    InvalidPart {
+        /// The body of the tag, without its delimiters.
+        body: String,
+        /// The part within the body.
+        span: Span,
+        /// What's wrong with the part.
+        error: Box<TagParseErr>,
+    },
+    /// A closing bracket in text without a tag opening it, like what's left of `[user:5]`
+    /// after deleting its start. Only ever a warning, as it reads as text.
+    UnmatchedClose,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum Token {
+/// A piece of a document.
+///
+/// With the `serde` feature, tokens use serde's externally tagged representation, e.g.
+/// `{"Text":"hi "}`, `{"Tag":"user:5"}` and `"LineBreak"`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub enum Token<T = Tag> {
     Text(String),
-    Tag(Tag),
+    Tag(T),
+    LineBreak,
+    ParagraphBreak,
+    /// Text marked as matching a search, never produced by the tokenizer itself.
+    Highlight(String),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl<T> Token<T> {
+    /// The content of a text token.
+    ///
+    /// ```
+    /// use papyrus_lib::{Tag, Token};
+    ///
+    /// assert_eq!(Token::<Tag>::Text("hi".to_string()).as_text(), Some("hi"));
+    /// assert_eq!(Token::Tag(Tag::User(1)).as_text(), None);
+    /// ```
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Token::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// ```
+    /// use papyrus_lib::{Tag, Token};
+    ///
+    /// assert_eq!(Token::Tag(Tag::User(1)).as_tag(), Some(&Tag::User(1)));
+    /// assert_eq!(Token::<Tag>::LineBreak.as_tag(), None);
+    /// ```
+    pub fn as_tag(&self) -> Option<&T> {
+        match self {
+            Token::Tag(tag) => Some(tag),
+            _ => None,
+        }
+    }
+
+    /// Whether the token is text. Highlights aren't, even though they hold text too.
+    ///
+    /// ```
+    /// use papyrus_lib::{Tag, Token};
+    ///
+    /// assert!(Token::<Tag>::Text("hi".to_string()).is_text());
+    /// assert!(!Token::<Tag>::Highlight("hi".to_string()).is_text());
+    /// ```
+    pub fn is_text(&self) -> bool {
+        matches!(self, Token::Text(_))
+    }
+
+    /// ```
+    /// use papyrus_lib::{Tag, Token};
+    ///
+    /// assert!(Token::Tag(Tag::User(1)).is_tag());
+    /// assert!(!Token::<Tag>::ParagraphBreak.is_tag());
+    /// ```
+    pub fn is_tag(&self) -> bool {
+        matches!(self, Token::Tag(_))
+    }
+
+    /// ```
+    /// use papyrus_lib::{Tag, Token};
+    ///
+    /// assert_eq!(Token::<Tag>::Text("hi".to_string()).into_text(), Some("hi".to_string()));
+    /// ```
+    pub fn into_text(self) -> Option<String> {
+        match self {
+            Token::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// ```
+    /// use papyrus_lib::{Tag, Token};
+    ///
+    /// assert_eq!(Token::Tag(Tag::Article(2)).into_tag(), Some(Tag::Article(2)));
+    /// ```
+    pub fn into_tag(self) -> Option<T> {
+        match self {
+            Token::Tag(tag) => Some(tag),
+            _ => None,
+        }
+    }
+}
+
+/// A token borrowing its text from the source it was read from, see [`TokenIter::borrowed`].
+///
+/// Text containing escapes is owned, as its content differs from its source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BorrowedToken<'a, T = Tag> {
+    Text(Cow<'a, str>),
+    Tag(T),
+    LineBreak,
+    ParagraphBreak,
+}
+
+impl<'a, T> BorrowedToken<'a, T> {
+    pub fn into_owned(self) -> Token<T> {
+        match self {
+            BorrowedToken::Text(text) => Token::Text(text.into_owned()),
+            BorrowedToken::Tag(tag) => Token::Tag(tag),
+            BorrowedToken::LineBreak => Token::LineBreak,
+            BorrowedToken::ParagraphBreak => Token::ParagraphBreak,
+        }
+    }
+}
+
+impl<'a, T> From<BorrowedToken<'a, T>> for Token<T> {
+    fn from(token: BorrowedToken<'a, T>) -> Self {
+        token.into_owned()
+    }
+}
+
+/// A tag, written as `[name:payload]` in markup, or `[name]` for tags without a payload.
+///
+/// Block tags like `[spoiler]` open a block that a `Tag::Close` like `[/spoiler]` closes,
+/// see `parse_tree`. They're only there with the `tag-blocks` feature, which is on by
+/// default; without it their names are unknown tags.
+///
+/// `Display` produces the inner form without brackets, e.g. `user:5`, which is also what
+/// `FromStr` accepts and, with the `serde` feature, how tags are serialized.
+///
+/// Tags order by their [`TagKind`], in the order of its variants and with spoilers before
+/// quotes, then by payload. Numeric ids come before keys and openers before their closers.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+// rkyv's derive uses `ArchivedTag` internally, hence the rename.
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(archived = "ArchivedTagValue", check_bytes)
+)]
 pub enum Tag {
     User(usize),
     Article(usize),
+    /// A tag registered with [`TokenizerBuilder::register`], holding what its parser returned.
+    ///
+    /// `FromStr` and deserializing don't know about registered tags, so they reject these.
+    Custom {
+        name: String,
+        value: String,
+    },
+    /// Opens a block hiding its content until it's revealed.
+    #[cfg(feature = "tag-blocks")]
+    Spoiler,
+    /// Opens a block quoting the article of the given id.
+    #[cfg(feature = "tag-blocks")]
+    Quote(usize),
+    /// Closes the innermost open block, which has to be of this kind.
+    #[cfg(feature = "tag-blocks")]
+    Close(BlockKind),
+    /// A mention of a user whose id isn't numeric, see [`TokenizerBuilder::payload_pattern`].
+    ///
+    /// Like custom tags, `FromStr` and deserializing reject these.
+    UserKey(String),
+    /// An article whose id isn't numeric, like [`Tag::UserKey`].
+    ArticleKey(String),
+    /// A link to an `http` or `https` url, see [`autolink`].
+    Link(String),
+    /// A placeholder for the value of a variable, see [`substitute`].
+    Var(String),
+    /// Opens a block shown only if the condition of the given name holds, see [`evaluate`].
+    #[cfg(feature = "tag-blocks")]
+    If(String),
+    /// Starts the part of an [`Tag::If`] block shown if its condition doesn't hold.
+    #[cfg(feature = "tag-blocks")]
+    Else,
+    /// Opens a list of [`Tag::Item`] blocks, written `[list:ordered]` if it's numbered.
+    #[cfg(feature = "tag-blocks")]
+    List {
+        ordered: bool,
+    },
+    /// Opens a block of an item of the [`Tag::List`] right around it.
+    #[cfg(feature = "tag-blocks")]
+    Item,
+    /// Opens a table of [`Tag::Row`] blocks.
+    #[cfg(feature = "tag-blocks")]
+    Table,
+    /// Opens a row of [`Tag::Cell`] blocks, written `[row:header]` if it's a header row.
+    #[cfg(feature = "tag-blocks")]
+    Row {
+        header: bool,
+    },
+    /// Opens a cell of the [`Tag::Row`] right around it.
+    #[cfg(feature = "tag-blocks")]
+    Cell,
+}
+
+impl Tag {
+    // The kind's place in the order, and the variant's among those of the kind.
+    fn rank(&self) -> (u8, u8) {
+        match self {
+            Tag::User(_) => (0, 0),
+            Tag::UserKey(_) => (0, 1),
+            Tag::Article(_) => (1, 0),
+            Tag::ArticleKey(_) => (1, 1),
+            Tag::Link(_) => (2, 0),
+            Tag::Var(_) => (3, 0),
+            Tag::Custom { .. } => (4, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Spoiler => (5, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(BlockKind::Spoiler) => (5, 1),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Quote(_) => (6, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(BlockKind::Quote) => (6, 1),
+            #[cfg(feature = "tag-blocks")]
+            Tag::If(_) => (7, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Else => (7, 1),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(BlockKind::If) => (7, 2),
+            #[cfg(feature = "tag-blocks")]
+            Tag::List { .. } => (8, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(BlockKind::List) => (8, 1),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Item => (9, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(BlockKind::Item) => (9, 1),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Table => (10, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(BlockKind::Table) => (10, 1),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Row { .. } => (11, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(BlockKind::Row) => (11, 1),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Cell => (12, 0),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(BlockKind::Cell) => (12, 1),
+        }
+    }
+}
+
+impl Ord for Tag {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank()
+            .cmp(&other.rank())
+            .then_with(|| match (self, other) {
+                (Tag::User(a), Tag::User(b)) | (Tag::Article(a), Tag::Article(b)) => a.cmp(b),
+                (Tag::UserKey(a), Tag::UserKey(b))
+                | (Tag::ArticleKey(a), Tag::ArticleKey(b))
+                | (Tag::Link(a), Tag::Link(b))
+                | (Tag::Var(a), Tag::Var(b)) => a.cmp(b),
+                #[cfg(feature = "tag-blocks")]
+                (Tag::Quote(a), Tag::Quote(b)) => a.cmp(b),
+                #[cfg(feature = "tag-blocks")]
+                (Tag::If(a), Tag::If(b)) => a.cmp(b),
+                #[cfg(feature = "tag-blocks")]
+                (Tag::List { ordered: a }, Tag::List { ordered: b })
+                | (Tag::Row { header: a }, Tag::Row { header: b }) => a.cmp(b),
+                (
+                    Tag::Custom { name, value },
+                    Tag::Custom {
+                        name: other_name,
+                        value: other_value,
+                    },
+                ) => (name, value).cmp(&(other_name, other_value)),
+                // The same variant without payload.
+                _ => Ordering::Equal,
+            })
+    }
+}
+
+impl PartialOrd for Tag {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl FromStr for Tag {
     type Err = TagParseErr;
+
+    // Parses `name:id`, allowing whitespace around the tag and after the colon. The name
+    // picks the kind before anything else is looked at, so unknown tags fail early.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static::lazy_static! {
-            static ref USER_RE: Regex = Regex::new(r"user:\s*(?P<id>\d+)").unwrap();
-            static ref ARTICLE_RE: Regex = Regex::new(r"article:\s*(?P<id>\d+)").unwrap();
+        let unknown = || TagParseErr::UnknownTag(format!("[{s}]"));
+        let Some((name, payload)) = s.trim().split_once(':') else {
+            #[cfg(feature = "tag-blocks")]
+            return match s.trim() {
+                "spoiler" => Ok(Tag::Spoiler),
+                "else" => Ok(Tag::Else),
+                "list" => Ok(Tag::List { ordered: false }),
+                "item" => Ok(Tag::Item),
+                "table" => Ok(Tag::Table),
+                "row" => Ok(Tag::Row { header: false }),
+                "cell" => Ok(Tag::Cell),
+                name => name
+                    .strip_prefix('/')
+                    .and_then(BlockKind::from_name)
+                    .map(Tag::Close)
+                    .ok_or_else(unknown),
+            };
+            #[cfg(not(feature = "tag-blocks"))]
+            return Err(unknown());
+        };
+        if name == "link" {
+            let url = payload.trim();
+            let valid = (url.starts_with("http://") || url.starts_with("https://"))
+                && !url.contains(char::is_whitespace);
+            return match valid {
+                true => Ok(Tag::Link(url.into())),
+                false => Err(TagParseErr::InvalidPayload(url.into())),
+            };
         }
-        if let Some(cap) = USER_RE.captures(s) {
-            return Ok(Tag::User(
-                cap.name("id")
-                    .ok_or(TagParseErr::CaptureNotFound)?
-                    .as_str()
-                    .parse()
-                    .map_err(|e| TagParseErr::CaptureParseErr(e))?,
-            ));
+        #[cfg(feature = "tag-blocks")]
+        if let "list" | "row" = name {
+            return match (name, payload.trim()) {
+                ("list", "ordered") => Ok(Tag::List { ordered: true }),
+                ("row", "header") => Ok(Tag::Row { header: true }),
+                (_, payload) => Err(TagParseErr::InvalidPayload(payload.into())),
+            };
+        }
+        let named: Option<fn(String) -> Tag> = match name {
+            "var" => Some(Tag::Var),
+            #[cfg(feature = "tag-blocks")]
+            "if" => Some(Tag::If),
+            _ => None,
+        };
+        if let Some(named) = named {
+            let payload = payload.trim();
+            if !is_var_name(payload) {
+                return Err(TagParseErr::InvalidPayload(payload.into()));
+            }
+            return Ok(named(payload.into()));
+        }
+        let kind: fn(usize) -> Tag = match name {
+            "user" => Tag::User,
+            "article" => Tag::Article,
+            #[cfg(feature = "tag-blocks")]
+            "quote" => Tag::Quote,
+            _ => return Err(unknown()),
+        };
+        let id = parse_id(payload).ok_or_else(unknown)?;
+        id.map(kind)
+            .map_err(|e| TagParseErr::invalid_part(s, payload.trim_start(), e))
+    }
+}
+
+/// A type of tag that token streams can be made of, [`Tag`] unless another one is picked with
+/// [`Tokenizer::iter_as`].
+pub trait ParseTag: Clone + Display {
+    /// Parses the body of a tag, without its delimiters. Tags of names the type doesn't
+    /// know have to fail with [`TagParseErr::UnknownTag`], so [`UnknownTags`] applies to
+    /// them.
+    fn parse_tag(body: &str) -> Result<Self, TagParseErr>;
+
+    /// The tag parsed by a parser registered with [`TokenizerBuilder::register`], `None` if
+    /// the type can't hold it.
+    fn from_custom(_name: String, _value: String) -> Option<Self> {
+        None
+    }
+
+    /// `tag` as this type, for tags a [`TokenizerBuilder::on_unknown_tag`] hook puts in place
+    /// of unknown ones. `None` if the type can't hold it.
+    fn from_tag(_tag: Tag) -> Option<Self> {
+        None
+    }
+
+    /// Passes the tag to `visitor`, [`TokenVisitor::visit_tag`] unless the type has more
+    /// specific methods.
+    fn accept<V: TokenVisitor<Self> + ?Sized>(&self, visitor: &mut V, span: Span) {
+        visitor.visit_tag(self, span)
+    }
+}
+
+impl ParseTag for Tag {
+    fn parse_tag(body: &str) -> Result<Self, TagParseErr> {
+        body.parse()
+    }
+
+    fn from_custom(name: String, value: String) -> Option<Self> {
+        Some(Tag::Custom { name, value })
+    }
+
+    fn from_tag(tag: Tag) -> Option<Self> {
+        Some(tag)
+    }
+
+    fn accept<V: TokenVisitor<Self> + ?Sized>(&self, visitor: &mut V, span: Span) {
+        match self {
+            Tag::User(id) => visitor.visit_user(*id, span),
+            Tag::Article(id) => visitor.visit_article(*id, span),
+            _ => visitor.visit_tag(self, span),
         }
+    }
+}
+
+// Names of variables and conditions are ASCII letters, digits and underscores.
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
 
-        if let Some(cap) = ARTICLE_RE.captures(s) {
-            return Ok(Tag::Article(
-                cap.name("id")
-                    .ok_or(TagParseErr::CaptureNotFound)?
-                    .as_str()
-                    .parse()
-                    .map_err(|e| TagParseErr::CaptureParseErr(e))?,
-            ));
+// `None` if the payload doesn't even start like an id.
+fn parse_id(payload: &str) -> Option<Result<usize, TagParseErr>> {
+    let id = payload.trim_start();
+    id.starts_with(|c: char| c.is_ascii_digit())
+        .then(|| id.parse().map_err(TagParseErr::CaptureParseErr))
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::User(id) => write!(f, "user:{id}"),
+            Self::Article(id) => write!(f, "article:{id}"),
+            Self::Custom { name, value } => write!(f, "{name}:{value}"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Spoiler => f.write_str("spoiler"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Quote(id) => write!(f, "quote:{id}"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Close(kind) => write!(f, "/{}", kind.name()),
+            Self::UserKey(key) => write!(f, "user:{key}"),
+            Self::ArticleKey(key) => write!(f, "article:{key}"),
+            Self::Link(url) => write!(f, "link:{url}"),
+            Self::Var(name) => write!(f, "var:{name}"),
+            #[cfg(feature = "tag-blocks")]
+            Self::If(condition) => write!(f, "if:{condition}"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Else => f.write_str("else"),
+            #[cfg(feature = "tag-blocks")]
+            Self::List { ordered: false } => f.write_str("list"),
+            #[cfg(feature = "tag-blocks")]
+            Self::List { ordered: true } => f.write_str("list:ordered"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Item => f.write_str("item"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Table => f.write_str("table"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Row { header: false } => f.write_str("row"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Row { header: true } => f.write_str("row:header"),
+            #[cfg(feature = "tag-blocks")]
+            Self::Cell => f.write_str("cell"),
         }
+    }
+}
+
+impl From<Tag> for String {
+    fn from(tag: Tag) -> Self {
+        tag.to_string()
+    }
+}
+
+impl TryFrom<String> for Tag {
+    type Error = TagParseErr;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
 
-        Err(TagParseErr::UnknownTag(format!("[{s}]")))
+impl Display for TagParseErr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NoCaptures => write!(f, "no captures"),
+            Self::CaptureNotFound => write!(f, "capture not found"),
+            Self::CaptureParseErr(e) => write!(f, "invalid id: {e}"),
+            Self::UnknownTag(tag) => write!(f, "unknown tag {tag}"),
+            Self::DeprecatedAlias { alias, canonical } => {
+                write!(f, "{alias} is deprecated, use {canonical}")
+            }
+            Self::InvalidPayload(payload) => write!(f, "invalid payload {payload:?}"),
+            Self::InvalidPart { body, span, error } => {
+                write!(f, "{:?}: {error}", &body[span.range()])
+            }
+            Self::UnmatchedClose => write!(f, "closing bracket without a tag opening it"),
+        }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for TagParseErr {}
+
+impl TagParseErr {
+    /// A [`TagParseErr::InvalidPart`] for `part`, which has to be a slice of `body`. Parsers
+    /// registered with [`TokenizerBuilder::register`] pass their payload as `body`, which the
+    /// tokenizer then replaces by that of the whole tag.
+    ///
+    /// ```
+    /// use papyrus_lib::{Span, TagParseErr};
+    ///
+    /// let body = "user:5x";
+    /// let TagParseErr::InvalidPart { span, .. } =
+    ///     TagParseErr::invalid_part(body, &body[5..], TagParseErr::NoCaptures)
+    /// else {
+    ///     unreachable!()
+    /// };
+    /// assert_eq!(span, Span::new(5, 7));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `part` isn't a slice of `body`.
+    pub fn invalid_part(body: &str, part: &str, error: TagParseErr) -> Self {
+        let start = (part.as_ptr() as usize)
+            .checked_sub(body.as_ptr() as usize)
+            .filter(|start| start + part.len() <= body.len())
+            .expect("the invalid part has to be a slice of the body");
+        Self::InvalidPart {
+            body: body.into(),
+            span: Span::new(start, start + part.len()),
+            error: Box::new(error),
+        }
+    }
+
+    // The error with an invalid part moved into `body`, `start` mapping where it starts.
+    pub(crate) fn moved_into(self, body: &str, start: impl FnOnce(usize) -> usize) -> Self {
+        match self {
+            Self::InvalidPart { span, error, .. } => {
+                let start = start(span.start);
+                Self::InvalidPart {
+                    body: body.into(),
+                    span: Span::new(start, start + span.len()),
+                    error,
+                }
+            }
+            e => e,
+        }
+    }
+
+    /// The kind of error in snake case, like `unknown_tag`, for metrics and logs.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NoCaptures => "no_captures",
+            Self::CaptureNotFound => "capture_not_found",
+            Self::CaptureParseErr(_) => "capture_parse_err",
+            Self::UnknownTag(_) => "unknown_tag",
+            Self::DeprecatedAlias { .. } => "deprecated_alias",
+            Self::InvalidPayload(_) => "invalid_payload",
+            // What's wrong matters more than that it's only a part.
+            Self::InvalidPart { error, .. } => error.code(),
+            Self::UnmatchedClose => "unmatched_close",
+        }
+    }
+}
+
+/// Where in the input the reported item starts, like the `[` of a tag or the first invalid
+/// byte, as 1-based line and column. Columns count chars, lines saturate at `u16::MAX`.
+///
+/// `Display` writes the line only, see [`Position::display`] for other forms.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Position {
     line: u16,
+    column: u32,
 }
 
 impl Position {
+    /// The start of the line.
     pub fn new(line: u16) -> Self {
-        Self { line }
+        Self { line, column: 1 }
+    }
+
+    pub fn with_column(self, column: u32) -> Self {
+        Self { column, ..self }
+    }
+
+    pub fn line(&self) -> u16 {
+        self.line
+    }
+
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+
+    /// Formats the position like `style` says.
+    ///
+    /// ```
+    /// use papyrus_lib::{Position, PositionStyle};
+    ///
+    /// let position = Position::new(3).with_column(17);
+    /// assert_eq!(position.to_string(), "line 3");
+    /// assert_eq!(position.display(PositionStyle::LineColumn).to_string(), "3:17");
+    /// assert_eq!(position.display(PositionStyle::Labeled).to_string(), "L3:C17");
+    /// ```
+    pub fn display(self, style: PositionStyle) -> DisplayPosition {
+        DisplayPosition {
+            position: self,
+            style,
+        }
+    }
+
+    // The position after `bytes`, which follow this one in the input.
+    pub(crate) fn advanced(self, bytes: &[u8]) -> Self {
+        // Counting the bytes that don't continue a char also counts invalid UTF-8 bytewise.
+        let chars = |bytes: &[u8]| bytes.iter().filter(|&&b| (b as i8) >= -0x40).count();
+        let saturate = |n: usize| u32::try_from(n).unwrap_or(u32::MAX);
+        match memchr::memrchr(b'\n', bytes) {
+            Some(last) => {
+                let lines = memchr::memchr_iter(b'\n', bytes).count();
+                Self {
+                    line: self
+                        .line
+                        .saturating_add(u16::try_from(lines).unwrap_or(u16::MAX)),
+                    column: saturate(chars(&bytes[last + 1..])).saturating_add(1),
+                }
+            }
+            None => Self {
+                column: self.column.saturating_add(saturate(chars(bytes))),
+                ..self
+            },
+        }
+    }
+
+    // Like `advanced`, but also ending lines at a `\r` that isn't followed by a `\n`.
+    pub(crate) fn advanced_newlines(self, bytes: &[u8]) -> Self {
+        let mut lines = 0usize;
+        let mut last = None;
+        for i in memchr::memchr2_iter(b'\n', b'\r', bytes) {
+            if bytes[i] == b'\n' || bytes.get(i + 1) != Some(&b'\n') {
+                lines += 1;
+                last = Some(i);
+            }
+        }
+        match last {
+            Some(last) => Self {
+                line: self
+                    .line
+                    .saturating_add(u16::try_from(lines).unwrap_or(u16::MAX)),
+                column: 1,
+            }
+            .advanced(&bytes[last + 1..]),
+            None => self.advanced(bytes),
+        }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&self.display(PositionStyle::Line), f)
+    }
+}
+
+/// How [`Position::display`] formats a position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PositionStyle {
+    /// `line 3`, like `Display` does.
+    #[default]
+    Line,
+    /// `3:17`, like compilers and editors.
+    LineColumn,
+    /// `L3:C17`.
+    Labeled,
+}
+
+/// A position formatted in a [`PositionStyle`], see [`Position::display`].
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayPosition {
+    position: Position,
+    style: PositionStyle,
+}
+
+impl Display for DisplayPosition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let Position { line, column } = self.position;
+        match self.style {
+            PositionStyle::Line => write!(f, "line {line}"),
+            PositionStyle::LineColumn => write!(f, "{line}:{column}"),
+            PositionStyle::Labeled => write!(f, "L{line}:C{column}"),
+        }
     }
 }
 
@@ -76,106 +906,400 @@ impl From<u16> for Position {
     }
 }
 
-pub struct TokenIter<'a> {
-    iter: Peekable<Chars<'a>>,
-    position: Position,
+/// A byte range into the source a token was read from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
 }
 
-impl Display for Position {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "line {}", self.line) // 1 has to be subtracted here, since the Position points at what's next.
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Self::new(range.start, range.end)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TokenizerOptions {
+    /// Emit `Token::LineBreak` for newlines and `Token::ParagraphBreak` for blank lines
+    /// instead of keeping them inside text tokens.
+    pub emit_breaks: bool,
+    /// Read `\r\n` and a lone `\r` as newlines too, turning them into `\n` in text. Spans
+    /// and [`TokenIter::byte_offset`] still refer to the input as it is.
+    pub normalize_newlines: bool,
+    /// What to do with control characters in text.
+    pub control_chars: ControlChars,
+}
+
+/// Tokenizes a str, into tokens of [`Tag`]s unless created by [`Tokenizer::iter_as`].
+pub struct TokenIter<'a, T = Tag> {
+    /// The part of the input not yet read from the back.
+    source: &'a str,
+    scanner: Scanner,
+    /// The line at the end of `source`, once reading from the back started.
+    back_line: Option<usize>,
+    /// Lexemes found at the end of `source` that weren't read yet.
+    back_lexemes: Vec<(usize, Scanned)>,
+    tag: PhantomData<fn() -> T>,
+}
+
+// Derived, it would require tags to be `Clone`.
+impl<T> Clone for TokenIter<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            source: self.source,
+            scanner: self.scanner.clone(),
+            back_line: self.back_line,
+            back_lexemes: self.back_lexemes.clone(),
+            tag: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for TokenIter<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let rest = &self.source[self.byte_offset()..];
+        let preview = match rest.char_indices().nth(24) {
+            Some((end, _)) => format!("{}…", &rest[..end]),
+            None => rest.to_string(),
+        };
+        f.debug_struct("TokenIter")
+            .field("position", &self.scanner.position())
+            .field("offset", &self.byte_offset())
+            .field("rest", &preview)
+            .finish()
     }
 }
 
 impl<'a> TokenIter<'a> {
-    pub fn new<S: Into<&'a str>>(s: S) -> Self {
+    /// Tokenizes anything borrowing as a `str`, like a literal, a `&String` or a `&Cow<str>`.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use papyrus_lib::TokenIter;
+    ///
+    /// let owned = String::from("hi [user:1]");
+    /// let cow: Cow<str> = Cow::Borrowed("hi [user:1]");
+    /// let boxed: Box<str> = "hi [user:1]".into();
+    /// assert_eq!(TokenIter::new("hi [user:1]").count(), 2);
+    /// assert_eq!(TokenIter::new(&owned).count(), 2);
+    /// assert_eq!(TokenIter::new(&cow).count(), 2);
+    /// assert_eq!(TokenIter::new(cow.as_ref()).count(), 2);
+    /// assert_eq!(TokenIter::new(&boxed).count(), 2);
+    /// ```
+    pub fn new<S: AsRef<str> + ?Sized>(s: &'a S) -> Self {
+        Self::with_options(s, TokenizerOptions::default())
+    }
+
+    pub fn with_options<S: AsRef<str> + ?Sized>(s: &'a S, options: TokenizerOptions) -> Self {
+        Self::resume(s.as_ref(), Scanner::new(options.into()))
+    }
+}
+
+impl<'a, T> TokenIter<'a, T> {
+    pub(crate) fn resume(source: &'a str, scanner: Scanner) -> Self {
         TokenIter {
-            iter: s.into().chars().peekable(),
-            position: Position::new(1),
+            source,
+            scanner,
+            back_line: None,
+            back_lexemes: Vec::new(),
+            tag: PhantomData,
         }
     }
+
+    /// Turns this iterator into one that also yields the source span of every token.
+    pub fn spanned(self) -> SpannedTokenIter<'a, T> {
+        SpannedTokenIter(self)
+    }
+
+    /// Turns this iterator into one yielding tokens that borrow their text from the source.
+    pub fn borrowed(self) -> BorrowedTokenIter<'a, T> {
+        BorrowedTokenIter(self)
+    }
+
+    /// How many bytes of the input were read, so the next token starts there.
+    pub fn byte_offset(&self) -> usize {
+        self.scanner.offset()
+    }
+
+    /// The position the next token starts at.
+    pub fn position(&self) -> Position {
+        self.scanner.position()
+    }
+
+    /// The input not read yet, from [`TokenIter::byte_offset`] on.
+    ///
+    /// Tokenizing it with [`TokenIter::with_position`] set to [`TokenIter::position`] goes on
+    /// like this iterator would, also after an error, as long as tags aren't restricted to
+    /// [`TokenizerBuilder::word_boundary`] or [`TokenizerBuilder::line_start_tags`], which
+    /// depend on what's before.
+    ///
+    /// ```
+    /// use papyrus_lib::{Position, TokenIter};
+    ///
+    /// let mut iter = TokenIter::new("hi [user:1]\n[usr:2]");
+    /// assert_eq!(iter.by_ref().take(2).count(), 2);
+    /// assert_eq!(iter.remainder(), "\n[usr:2]");
+    ///
+    /// let mut rest = TokenIter::new(iter.remainder()).with_position(iter.position());
+    /// let error = rest.find_map(Result::err).unwrap();
+    /// assert_eq!(error.position(), Position::new(2));
+    /// ```
+    pub fn remainder(&self) -> &'a str {
+        &self.source[self.byte_offset()..]
+    }
+
+    /// Counts positions from `position` instead of the start of the first line, for input
+    /// that's the rest of a larger one, see [`TokenIter::remainder`].
+    ///
+    /// Only meant to be called before reading any tokens.
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.scanner.set_position(position);
+        self
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl<'a, T: ParseTag> TokenIter<'a, T> {
+    fn next_borrowed(&mut self) -> Option<Result<BorrowedToken<'a, T>, TokenizeErr>> {
+        let (lexeme, position) = self.scanner.next_lexeme(self.source.as_bytes())?;
+        Some(self.token(lexeme, position))
+    }
+
+    fn next_back_borrowed(&mut self) -> Option<Result<BorrowedToken<'a, T>, TokenizeErr>> {
+        let bytes = self.source.as_bytes();
+        if self.byte_offset() >= bytes.len() {
+            return None;
+        }
+        if self.back_lexemes.is_empty() {
+            self.back_lexemes = self.scanner.last_lexemes(bytes);
+        }
+        let (start, lexeme) = self.back_lexemes.pop()?;
+        let back_line = self
+            .back_line
+            .unwrap_or_else(|| 1 + self.scanner.line_ends(bytes));
+        let line = back_line - self.scanner.line_ends(&bytes[start..]);
+        let line_start = self.scanner.line_start(&bytes[..start]);
+        let position = self.scanner.config().advance(
+            Position::new(line.try_into().unwrap_or(u16::MAX)),
+            &bytes[line_start..start],
+        );
+        let token = self.token(lexeme, position);
+        self.source = &self.source[..start];
+        self.back_line = Some(line);
+        Some(token)
+    }
+
+    fn token(
+        &self,
+        lexeme: Scanned,
+        position: Position,
+    ) -> Result<BorrowedToken<'a, T>, TokenizeErr> {
+        let source = self.source;
+        let config = self.scanner.config();
+        Ok(match lexeme {
+            Scanned::LineBreak => BorrowedToken::LineBreak,
+            Scanned::ParagraphBreak => BorrowedToken::ParagraphBreak,
+            Scanned::Tag(body) => {
+                return config.tag(&source[tag_range(body, source.len())], position)
+            }
+            Scanned::Text(range, escaped) => {
+                BorrowedToken::Text(text_content(&source[range], escaped, config, position)?)
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum TokenizeErr {
     TagErr(Position, TagParseErr),
+    InvalidUtf8(Position),
+    /// A tag still open at the end of the input, reported by [`PushParser::finish`].
+    UnclosedTag(Position),
+    /// A tag longer than [`TokenizerBuilder::max_tag_len`] allows.
+    TagTooLong(Position),
+    /// A control character in text, with [`ControlChars::Error`].
+    ControlChar(Position, char),
+}
+
+impl TokenizeErr {
+    pub fn position(&self) -> Position {
+        match self {
+            Self::TagErr(p, _)
+            | Self::InvalidUtf8(p)
+            | Self::UnclosedTag(p)
+            | Self::TagTooLong(p)
+            | Self::ControlChar(p, _) => *p,
+        }
+    }
+
+    /// Where the part of a [`TagParseErr::InvalidPart`] starts, and the part, so it can be
+    /// pointed out exactly.
+    ///
+    /// ```
+    /// use papyrus_lib::{Position, TokenIter};
+    ///
+    /// let error = TokenIter::new("see [user:12x]").find_map(Result::err).unwrap();
+    /// assert_eq!(
+    ///     error.invalid_part(),
+    ///     Some((Position::new(1).with_column(11), "12x"))
+    /// );
+    /// ```
+    pub fn invalid_part(&self) -> Option<(Position, &str)> {
+        let Self::TagErr(position, TagParseErr::InvalidPart { body, span, .. }) = self else {
+            return None;
+        };
+        // Delimiters are a single ASCII char.
+        let start = position
+            .with_column(position.column.saturating_add(1))
+            .advanced(&body.as_bytes()[..span.start]);
+        Some((start, &body[span.range()]))
+    }
+
+    /// Like [`TagParseErr::code`], with that of the tag's error for [`TokenizeErr::TagErr`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TagErr(_, e) => e.code(),
+            Self::InvalidUtf8(_) => "invalid_utf8",
+            Self::UnclosedTag(_) => "unclosed_tag",
+            Self::TagTooLong(_) => "tag_too_long",
+            Self::ControlChar(..) => "control_char",
+        }
+    }
 }
 
 impl Display for TokenizeErr {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::TagErr(p, e) => write!(f, "{e:?} at {p}"),
+            Self::InvalidUtf8(p) => write!(f, "invalid UTF-8 at {p}"),
+            Self::UnclosedTag(p) => write!(f, "unclosed tag at {p}"),
+            Self::TagTooLong(p) => write!(f, "tag too long at {p}"),
+            Self::ControlChar(p, c) => write!(f, "control character {c:?} at {p}"),
         }
     }
 }
 
-impl<'a> Iterator for TokenIter<'a> {
-    type Item = Result<Token, TokenizeErr>;
+#[cfg(feature = "std")]
+impl std::error::Error for TokenizeErr {}
+
+impl<'a, T: ParseTag> Iterator for TokenIter<'a, T> {
+    type Item = Result<Token<T>, TokenizeErr>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(next) = self.iter.next() {
-            if next == '\n' {
-                self.position.line += 1;
-            }
-
-            return Some(match next {
-                '[' => self
-                    .iter
-                    .borrow_mut()
-                    .take_while(|p| *p != ']')
-                    .collect::<String>()
-                    .parse::<Tag>()
-                    .map_err(|e| TokenizeErr::TagErr(self.position, e))
-                    .map(|tag| Token::Tag(tag)),
-
-                c => {
-                    let mut text = String::from(c);
-                    while let Some(peek) = self.iter.peek() {
-                        if *peek == '[' {
-                            break;
-                        }
-                        // We can use unrwap here since we checked if there is a next character via iter.peek
-                        text.push(self.iter.next().unwrap())
-                    }
-                    Ok(Token::Text(text))
-                }
-            });
-        }
+        self.next_borrowed()
+            .map(|token| token.map(BorrowedToken::into_owned))
+    }
 
-        None
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Every token consumes at least a byte of the source.
+        (0, Some(self.source.len() - self.byte_offset()))
+    }
+}
+
+impl<T: ParseTag> DoubleEndedIterator for TokenIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.next_back_borrowed()
+            .map(|token| token.map(BorrowedToken::into_owned))
+    }
+}
+
+impl<T: ParseTag> FusedIterator for TokenIter<'_, T> {}
+
+/// Tokenizes leniently: tags that fail to parse are kept as text, exactly as written.
+pub fn tokenize_lossy(s: &str) -> Vec<Token> {
+    Document::parse_lossy(s).into_tokens()
+}
+
+#[derive(Clone, Debug)]
+pub struct BorrowedTokenIter<'a, T = Tag>(TokenIter<'a, T>);
+
+impl<'a, T: ParseTag> Iterator for BorrowedTokenIter<'a, T> {
+    type Item = Result<BorrowedToken<'a, T>, TokenizeErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_borrowed()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T: ParseTag> DoubleEndedIterator for BorrowedTokenIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back_borrowed()
+    }
+}
+
+impl<T: ParseTag> FusedIterator for BorrowedTokenIter<'_, T> {}
+
+#[derive(Clone, Debug)]
+pub struct SpannedTokenIter<'a, T = Tag>(TokenIter<'a, T>);
+
+impl<'a, T: ParseTag> Iterator for SpannedTokenIter<'a, T> {
+    type Item = Result<(Token<T>, Span), TokenizeErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.0.byte_offset();
+        let token = self.0.next()?;
+        Some(token.map(|token| (token, Span::new(start, self.0.byte_offset()))))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
     }
 }
 
+impl<T: ParseTag> DoubleEndedIterator for SpannedTokenIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let end = self.0.source.len();
+        let token = self.0.next_back()?;
+        Some(token.map(|token| (token, Span::new(self.0.source.len(), end))))
+    }
+}
+
+impl<T: ParseTag> FusedIterator for SpannedTokenIter<'_, T> {}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
 
     #[test]
     pub fn parse_user() {
-        assert_eq!(
-            TokenIter::new("[user:0]").collect::<Vec<_>>(),
-            vec![Ok(Token::Tag(Tag::User(0)))]
-        );
+        assert_tokens!("[user:0]", [user 0]);
     }
 
     #[test]
     pub fn parse_article() {
-        assert_eq!(
-            TokenIter::new("[article:0]").collect::<Vec<_>>(),
-            vec![Ok(Token::Tag(Tag::Article(0)))]
-        );
+        assert_tokens!("[article:0]", [article 0]);
     }
 
     #[test]
     pub fn line_err() {
-        let tag = "\n[unknown]";
-        assert_eq!(
-            TokenIter::new(tag).collect::<Vec<_>>(),
-            vec![Err(TokenizeErr::TagErr(
-                2.into(),
-                TagParseErr::UnknownTag(tag[1..].to_string())
-            ))]
-        )
+        assert_tokens!("\n[unknown]", [text "\n", err unknown_tag @ line 2]);
     }
 
     #[test]
@@ -189,4 +1313,595 @@ mod tests {
             ))]
         )
     }
+
+    #[test]
+    pub fn parse_breaks() {
+        let input = "a\nb \n \n\n[user:1] c\n";
+        assert_eq!(
+            TokenIter::new(input).collect::<Vec<_>>(),
+            vec![
+                Ok(Token::Text("a\nb \n \n\n".to_string())),
+                Ok(Token::Tag(Tag::User(1))),
+                Ok(Token::Text(" c\n".to_string())),
+            ]
+        );
+        assert_tokens!(
+            input,
+            TokenizerOptions { emit_breaks: true, ..Default::default() },
+            [text "a", line_break, text "b ", paragraph_break, user 1, text " c", line_break]
+        );
+    }
+
+    #[test]
+    pub fn line_err_breaks() {
+        assert_tokens!(
+            "\n\n[unknown]",
+            TokenizerOptions { emit_breaks: true, ..Default::default() },
+            [paragraph_break, err unknown_tag @ line 3]
+        );
+    }
+
+    #[test]
+    pub fn normalize_newlines() {
+        let input = "one\r\ntwo\rthree\n[user:1]\r\n\r\nfour \\[\r[unknown]";
+        let options = TokenizerOptions {
+            normalize_newlines: true,
+            ..Default::default()
+        };
+        let tokens = TokenIter::with_options(input, options)
+            .spanned()
+            .collect::<Vec<_>>();
+        let text = |s: &str| Token::Text(s.to_string());
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|t| t.as_ref().map(|(token, _)| token))
+                .collect::<Vec<_>>(),
+            [
+                Ok(&text("one\ntwo\nthree\n")),
+                Ok(&Token::Tag(Tag::User(1))),
+                Ok(&text("\n\nfour [\n")),
+                Err(&TokenizeErr::TagErr(
+                    Position::new(7),
+                    TagParseErr::UnknownTag("[unknown]".to_string())
+                )),
+            ]
+        );
+        let spans = tokens.iter().filter_map(|t| Some(t.as_ref().ok()?.1));
+        assert_eq!(
+            spans.map(|span| &input[span.range()]).collect::<Vec<_>>(),
+            ["one\r\ntwo\rthree\n", "[user:1]", "\r\n\r\nfour \\[\r"]
+        );
+        let mut backward = TokenIter::with_options(input, options)
+            .spanned()
+            .rev()
+            .collect::<Vec<_>>();
+        backward.reverse();
+        assert_eq!(backward, tokens);
+
+        let options = TokenizerOptions {
+            emit_breaks: true,
+            ..options
+        };
+        assert_tokens!(
+            input,
+            options,
+            [
+                text "one", line_break, text "two", line_break, text "three", line_break,
+                user 1, paragraph_break, text "four [", line_break, err unknown_tag @ line 7
+            ]
+        );
+        let mut backward = TokenIter::with_options(input, options)
+            .rev()
+            .collect::<Vec<_>>();
+        backward.reverse();
+        assert_eq!(
+            backward,
+            TokenIter::with_options(input, options).collect::<Vec<_>>()
+        );
+
+        // Without the option, a lone `\r` doesn't end a line.
+        let error = TokenIter::new(input).find_map(Result::err).unwrap();
+        assert_eq!(error.position(), Position::new(5).with_column(9));
+    }
+
+    #[test]
+    pub fn parse_spans() {
+        let input = "hé [user:1]\\[x\n\n[article:22]";
+        assert_eq!(
+            TokenIter::new(input).spanned().collect::<Vec<_>>(),
+            vec![
+                Ok((Token::Text("hé ".to_string()), Span::new(0, 4))),
+                Ok((Token::Tag(Tag::User(1)), Span::new(4, 12))),
+                Ok((Token::Text("[x\n\n".to_string()), Span::new(12, 17))),
+                Ok((Token::Tag(Tag::Article(22)), Span::new(17, 29))),
+            ]
+        );
+        assert_eq!(
+            TokenIter::with_options(
+                input,
+                TokenizerOptions {
+                    emit_breaks: true,
+                    ..Default::default()
+                }
+            )
+            .spanned()
+            .map(|t| t.unwrap().1)
+            .collect::<Vec<_>>(),
+            vec![
+                Span::new(0, 4),
+                Span::new(4, 12),
+                Span::new(12, 15),
+                Span::new(15, 17),
+                Span::new(17, 29)
+            ]
+        );
+    }
+
+    #[test]
+    pub fn tag_display_roundtrip() {
+        for id in [0, 1, 7, 42, 1000, 123456789, usize::MAX] {
+            for tag in [Tag::User(id), Tag::Article(id)] {
+                assert_eq!(Tag::from_str(&tag.to_string()), Ok(tag));
+            }
+        }
+        assert_eq!(Tag::User(5).to_string(), "user:5");
+        assert_eq!(Tag::Article(9).to_string(), "article:9");
+    }
+
+    #[cfg(not(feature = "tag-blocks"))]
+    #[test]
+    pub fn block_tags_disabled() {
+        for body in [
+            "spoiler",
+            "/quote",
+            "quote:1",
+            "if:beta",
+            "list:ordered",
+            "row",
+            "cell",
+        ] {
+            assert_eq!(
+                Tag::from_str(body),
+                Err(TagParseErr::UnknownTag(format!("[{body}]")))
+            );
+        }
+        assert_eq!(
+            TokenIter::new("a [spoiler]").collect::<Vec<_>>(),
+            [
+                Ok(Token::Text("a ".to_string())),
+                Err(TokenizeErr::TagErr(
+                    Position::new(1).with_column(3),
+                    TagParseErr::UnknownTag("[spoiler]".to_string())
+                )),
+            ]
+        );
+        #[cfg(feature = "serde")]
+        assert!(serde_json::from_str::<Tag>("\"spoiler\"").is_err());
+    }
+
+    #[test]
+    pub fn tags_as_keys() {
+        let mut counts = std::collections::HashMap::new();
+        for token in tokenize_lossy("[user:1] [article:2] [user:1] [user:3] [user:1]") {
+            if let Some(tag) = token.into_tag() {
+                *counts.entry(tag).or_insert(0) += 1;
+            }
+        }
+        assert_eq!(counts[&Tag::User(1)], 3);
+        assert_eq!(counts[&Tag::Article(2)], 1);
+        assert_eq!(counts.len(), 3);
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn tags_sort_by_kind_then_payload() {
+        let mut tags = vec![
+            Tag::Close(BlockKind::Quote),
+            Tag::Quote(2),
+            Tag::Close(BlockKind::Spoiler),
+            Tag::Link("https://b.com".to_string()),
+            Tag::UserKey("alice".to_string()),
+            Tag::Article(1),
+            Tag::Spoiler,
+            Tag::User(10),
+            Tag::Custom {
+                name: "emoji".to_string(),
+                value: "wave".to_string(),
+            },
+            Tag::User(9),
+            Tag::Var("name".to_string()),
+            Tag::Link("https://a.com".to_string()),
+        ];
+        tags.sort();
+        assert_eq!(
+            tags.iter().map(Tag::to_string).collect::<Vec<_>>(),
+            [
+                "user:9",
+                "user:10",
+                "user:alice",
+                "article:1",
+                "link:https://a.com",
+                "link:https://b.com",
+                "var:name",
+                "emoji:wave",
+                "spoiler",
+                "/spoiler",
+                "quote:2",
+                "/quote"
+            ]
+        );
+    }
+
+    #[test]
+    pub fn errors_clone() {
+        let error = Tokenizer::default().tokenize("a\n[user:x]").unwrap_err();
+        let stashed = error.clone();
+        assert_eq!(stashed, error);
+        assert_eq!(stashed.to_string(), error.to_string());
+        let diagnostics = validate("[usr:1]");
+        assert_eq!(diagnostics.clone(), diagnostics);
+    }
+
+    #[test]
+    pub fn parse_escaped() {
+        assert_tokens!(r"\[user:0\] C:\ \\", [text r"[user:0] C:\ \"]);
+    }
+
+    #[test]
+    pub fn borrowed_slices_input() {
+        let input = "héllo [user:1] wörld\n[article:2]\\[x\\]";
+        let range = input.as_bytes().as_ptr_range();
+        let tokens = TokenIter::new(input)
+            .borrowed()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        for token in &tokens[..3] {
+            let BorrowedToken::Text(Cow::Borrowed(text)) = token else {
+                continue;
+            };
+            let text_range = text.as_bytes().as_ptr_range();
+            assert!(range.start <= text_range.start && text_range.end <= range.end);
+        }
+        assert!(matches!(
+            &tokens[0],
+            BorrowedToken::Text(Cow::Borrowed("héllo "))
+        ));
+        assert!(matches!(
+            &tokens[2],
+            BorrowedToken::Text(Cow::Borrowed(" wörld\n"))
+        ));
+        assert_eq!(
+            tokens[4],
+            BorrowedToken::Text(Cow::Owned("[x]".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn borrowed_matches_owned() {
+        let corpus = [
+            "[user:0]",
+            "\n[unknown]",
+            "a [user:1] b\n\n [article: 02]\n",
+            "unterminated [user:1",
+            "[usr:1] and [user:x]",
+            r"\[user:0\] C:\ \\",
+            "ünï\r\n\t\n[user:1]\\",
+            "",
+        ];
+        for input in corpus {
+            for emit_breaks in [false, true] {
+                let options = TokenizerOptions {
+                    emit_breaks,
+                    ..Default::default()
+                };
+                assert_eq!(
+                    TokenIter::with_options(input, options)
+                        .borrowed()
+                        .map(|t| t.map(Token::from))
+                        .collect::<Vec<_>>(),
+                    TokenIter::with_options(input, options).collect::<Vec<_>>(),
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn tag_parse_table() {
+        let unknown = |s: &str| Err(TagParseErr::UnknownTag(format!("[{s}]")));
+        let invalid = |s: &str| {
+            let id = &s[5..];
+            let e = TagParseErr::CaptureParseErr(id.parse::<usize>().unwrap_err());
+            Err(TagParseErr::invalid_part(s, id, e))
+        };
+        let cases = [
+            ("user:5", Ok(Tag::User(5))),
+            ("article:05", Ok(Tag::Article(5))),
+            ("user:  5", Ok(Tag::User(5))),
+            ("user:\t5", Ok(Tag::User(5))),
+            (" user:5 ", Ok(Tag::User(5))),
+            ("\narticle:1", Ok(Tag::Article(1))),
+            ("user :5", unknown("user :5")),
+            ("User:5", unknown("User:5")),
+            ("xuser:5", unknown("xuser:5")),
+            ("see user:5", unknown("see user:5")),
+            ("user:5:6", invalid("user:5:6")),
+            ("user:5x", invalid("user:5x")),
+            ("user:5 6", invalid("user:5 6")),
+            ("user:+5", unknown("user:+5")),
+            ("user:-5", unknown("user:-5")),
+            ("user:", unknown("user:")),
+            ("user", unknown("user")),
+            ("", unknown("")),
+            (
+                "user:99999999999999999999999",
+                invalid("user:99999999999999999999999"),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(input.parse::<Tag>(), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    pub fn line_err_after_text() {
+        let line = |input: &str, emit_breaks| {
+            TokenIter::with_options(
+                input,
+                TokenizerOptions {
+                    emit_breaks,
+                    ..Default::default()
+                },
+            )
+            .find_map(Result::err)
+            .map(|e| e.position())
+        };
+        for emit_breaks in [false, true] {
+            assert_eq!(line("a\nb\n\n[usr:1]", emit_breaks), Some(4.into()));
+            assert_eq!(
+                line("[user:\n1] \\[\n\\] [x]", emit_breaks),
+                Some(Position::new(3).with_column(4))
+            );
+            assert_eq!(line("[x] \n", emit_breaks), Some(1.into()));
+        }
+    }
+
+    #[test]
+    pub fn resume_from_remainder() {
+        let inputs = [
+            "hi [user:1], see [article:2]\\[x\\] ok",
+            "ab\n\n  [usr:1] c\n[user:x]\n\n[article:3] ü [",
+            "[x][y]\n]",
+            "",
+        ];
+        for (input, emit_breaks) in inputs.into_iter().flat_map(|i| [(i, false), (i, true)]) {
+            let options = TokenizerOptions {
+                emit_breaks,
+                ..Default::default()
+            };
+            let all: Vec<_> = TokenIter::with_options(input, options).collect();
+            for taken in 0..=all.len() {
+                let mut iter = TokenIter::with_options(input, options);
+                let mut tokens: Vec<_> = iter.by_ref().take(taken).collect();
+                assert_eq!(&input[iter.byte_offset()..], iter.remainder(), "{input:?}");
+                let rest = TokenIter::with_options(iter.remainder(), options)
+                    .with_position(iter.position());
+                tokens.extend(rest);
+                assert_eq!(tokens, all, "{input:?} after {taken}");
+            }
+        }
+        let mut iter = TokenIter::new("a\n[usr:1]b");
+        assert!(iter.nth(1).unwrap().is_err());
+        assert_eq!(
+            (iter.remainder(), iter.byte_offset(), iter.position()),
+            ("b", 9, Position::new(2).with_column(8))
+        );
+    }
+
+    #[test]
+    pub fn positions_of_errors() {
+        let at = |line, column| Position::new(line).with_column(column);
+        fn first_err(
+            mut iter: impl Iterator<Item = Result<Token, TokenizeErr>>,
+        ) -> Option<Position> {
+            iter.find_map(Result::err).map(|e| e.position())
+        }
+        for emit_breaks in [false, true] {
+            let iter = |input| {
+                TokenIter::with_options(
+                    input,
+                    TokenizerOptions {
+                        emit_breaks,
+                        ..Default::default()
+                    },
+                )
+            };
+            assert_eq!(first_err(iter("[x] b")), Some(at(1, 1)));
+            assert_eq!(first_err(iter("ab\n[x]")), Some(at(2, 1)));
+            assert_eq!(first_err(iter("ab\n\n  \n[x]")), Some(at(4, 1)));
+            assert_eq!(first_err(iter("ü\nab [x]")), Some(at(2, 4)));
+            assert_eq!(first_err(iter("ab\n xy[x]").rev()), Some(at(2, 4)));
+        }
+        let mut parser = PushParser::new();
+        parser.feed("a\nb [us").for_each(drop);
+        assert_eq!(first_err(parser.finish()), Some(at(2, 3)));
+        assert_eq!(
+            ByteTokenIter::new(b"\nab\xc3").find_map(Result::err),
+            Some(TokenizeErr::InvalidUtf8(at(2, 3)))
+        );
+        assert_eq!(
+            at(2, 3).display(PositionStyle::LineColumn).to_string(),
+            "2:3"
+        );
+    }
+
+    #[test]
+    pub fn clone_mid_iteration() {
+        let mut iter = TokenIter::new("a [user:1] b [article:2] c");
+        iter.next();
+        let lookahead = iter.clone();
+        assert_eq!(lookahead.collect::<Vec<_>>(), iter.collect::<Vec<_>>());
+    }
+
+    #[test]
+    pub fn size_hint_bounds() {
+        let input = "x[user:1][user:2]\\[é\\]\n\n[article:3]";
+        for emit_breaks in [false, true] {
+            let mut iter = TokenIter::with_options(
+                input,
+                TokenizerOptions {
+                    emit_breaks,
+                    ..Default::default()
+                },
+            );
+            loop {
+                let (lower, upper) = iter.size_hint();
+                let remaining = iter.clone().count();
+                assert!(lower <= remaining && remaining <= upper.unwrap());
+                if iter.next().is_none() {
+                    break;
+                }
+            }
+            assert_eq!(iter.size_hint(), (0, Some(0)));
+            assert!(iter.next().is_none());
+        }
+    }
+
+    #[test]
+    pub fn debug_preview() {
+        let mut iter = TokenIter::new("first\n[user:1] and then a long tail of text");
+        iter.next();
+        assert_eq!(
+            format!("{iter:?}"),
+            "TokenIter { position: Position { line: 2, column: 1 }, offset: 6, \
+             rest: \"[user:1] and then a long…\" }"
+        );
+    }
+
+    const REVERSE_CORPUS: [&str; 14] = [
+        "[user:0]",
+        "\n[unknown]",
+        "a [user:1] b\n\n [article: 02]\n",
+        "unterminated [user:1",
+        "[usr:1] and [user:x]",
+        r"\[user:0\] C:\ \\",
+        "ünï\r\n\t\n[user:1]\\",
+        r"[x\\[a] \\[b] \[c] [d\] e]",
+        "]]] a]b[user:1]]c[[user:2]",
+        "x\n \n\n\ny\n[user:\n3]\n\n",
+        r"\\\[user:1] \\\\[user:2]",
+        "[][]]\\[\\]",
+        "\\",
+        "",
+    ];
+
+    #[test]
+    pub fn scan_tags_matches_tokens() {
+        let tokenizers = [
+            Tokenizer::default(),
+            Tokenizer::builder().max_tag_len(8).build(),
+            Tokenizer::builder().unknown_tags(UnknownTags::Text).build(),
+            Tokenizer::builder().delimiters('{', '}').build(),
+        ];
+        let braces = [
+            "{user:1} {usr} [user:2] \\{article:123456789}",
+            "{x}{user:3",
+        ];
+        for input in REVERSE_CORPUS.into_iter().chain(braces) {
+            for tokenizer in &tokenizers {
+                let tags = tokenizer
+                    .iter(input)
+                    .spanned()
+                    .filter_map(|token| match token {
+                        Ok((Token::Tag(tag), span)) => Some(Ok((tag, span))),
+                        Ok(_) => None,
+                        Err(e) => Some(Err(e)),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    tokenizer.scan_tags(input).collect::<Vec<_>>(),
+                    tags,
+                    "{input:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn counts_match_tokens() {
+        for input in REVERSE_CORPUS {
+            assert_eq!(
+                count_tokens(input),
+                tokenize_lossy(input).len(),
+                "{input:?}"
+            );
+            let tokens = Tokenizer::default().tokenize(input);
+            let tags = tokens.map(|tokens| tokens.iter().filter(|t| t.is_tag()).count());
+            assert_eq!(count_tags(input), tags, "{input:?}");
+            let any = TokenIter::new(input).any(|token| matches!(token, Ok(Token::Tag(_))));
+            assert_eq!(has_any_tag(input), any, "{input:?}");
+        }
+
+        // Finding the tag at the start doesn't scan the rest, which takes a while.
+        let input = format!("[user:1]{}", "[unknown] ".repeat(200_000));
+        let start = std::time::Instant::now();
+        assert!(has_any_tag(&input));
+        let early = start.elapsed();
+        let start = std::time::Instant::now();
+        assert_eq!(count_tokens(&input), 2);
+        assert!(early * 100 < start.elapsed(), "{early:?}");
+    }
+
+    #[test]
+    pub fn rev_matches_forward() {
+        for input in REVERSE_CORPUS {
+            for emit_breaks in [false, true] {
+                let options = TokenizerOptions {
+                    emit_breaks,
+                    ..Default::default()
+                };
+                let mut forward = TokenIter::with_options(input, options)
+                    .spanned()
+                    .collect::<Vec<_>>();
+                forward.reverse();
+                assert_eq!(
+                    TokenIter::with_options(input, options)
+                        .spanned()
+                        .rev()
+                        .collect::<Vec<_>>(),
+                    forward,
+                    "{input:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn interleaved_next_back() {
+        for input in REVERSE_CORPUS {
+            for emit_breaks in [false, true] {
+                let options = TokenizerOptions {
+                    emit_breaks,
+                    ..Default::default()
+                };
+                let forward = TokenIter::with_options(input, options).collect::<Vec<_>>();
+                for pattern in 0..16u32 {
+                    let mut iter = TokenIter::with_options(input, options);
+                    let (mut front, mut back) = (Vec::new(), Vec::new());
+                    for step in 0.. {
+                        let pushed = match pattern >> (step % 4) & 1 {
+                            0 => iter.next().map(|t| front.push(t)),
+                            _ => iter.next_back().map(|t| back.push(t)),
+                        };
+                        if pushed.is_none() {
+                            break;
+                        }
+                    }
+                    assert!(iter.next().is_none() && iter.next_back().is_none());
+                    front.extend(back.into_iter().rev());
+                    assert_eq!(front, forward, "{input:?} {pattern}");
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,185 @@
+use alloc::{string::String, vec::Vec};
+use core::mem;
+
+use crate::Token;
+
+/// Groups a token stream by source line, see [`lines`].
+#[derive(Clone, Debug)]
+pub struct Lines<I> {
+    tokens: I,
+    line: u32,
+    current: Vec<Token>,
+    // What's left of a text or highlight token split at a newline, with where it starts.
+    rest: Option<(Token, usize)>,
+    // The line after a paragraph break, which is empty.
+    blank: bool,
+    // Whether anything is on the current line, which the end of the tokens then ends.
+    started: bool,
+}
+
+/// The tokens on every line, numbered from 1.
+///
+/// Text and highlights spanning several lines are split at the newlines, which are dropped
+/// along with the `\r` of a `\r\n`, as are breaks. Tags belong to the line their opening
+/// bracket is on. Empty lines are yielded with no tokens, and like [`str::lines`] a trailing
+/// newline doesn't start another line.
+///
+/// ```
+/// use papyrus_lib::{lines, tokenize_lossy, Tag, Token};
+///
+/// let lines = lines(tokenize_lossy("hi\n\n[user:1] there")).collect::<Vec<_>>();
+/// assert_eq!(
+///     lines,
+///     [
+///         (1, vec![Token::Text("hi".into())]),
+///         (2, vec![]),
+///         (3, vec![Token::Tag(Tag::User(1)), Token::Text(" there".into())]),
+///     ]
+/// );
+/// ```
+pub fn lines<I: IntoIterator<Item = Token>>(tokens: I) -> Lines<I::IntoIter> {
+    Lines {
+        tokens: tokens.into_iter(),
+        line: 0,
+        current: Vec::new(),
+        rest: None,
+        blank: false,
+        started: false,
+    }
+}
+
+impl<I> Lines<I> {
+    fn finish(&mut self) -> (u32, Vec<Token>) {
+        self.line += 1;
+        self.started = false;
+        (self.line, mem::take(&mut self.current))
+    }
+
+    fn push_text(&mut self, token: &Token, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let text = String::from(text);
+        self.current.push(match token {
+            Token::Highlight(_) => Token::Highlight(text),
+            _ => Token::Text(text),
+        });
+        self.started = true;
+    }
+}
+
+impl<I: Iterator<Item = Token>> Iterator for Lines<I> {
+    type Item = (u32, Vec<Token>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((token, start)) = self.rest.take() {
+                let (Token::Text(text) | Token::Highlight(text)) = &token else {
+                    unreachable!("only text is split")
+                };
+                match text[start..].find('\n') {
+                    Some(i) => {
+                        let line = &text[start..start + i];
+                        self.push_text(&token, line.strip_suffix('\r').unwrap_or(line));
+                        self.rest = Some((token, start + i + 1));
+                        return Some(self.finish());
+                    }
+                    None => self.push_text(&token, &text[start..]),
+                }
+            }
+            if mem::take(&mut self.blank) {
+                return Some(self.finish());
+            }
+            match self.tokens.next() {
+                Some(token @ (Token::Text(_) | Token::Highlight(_))) => {
+                    self.rest = Some((token, 0));
+                }
+                Some(Token::LineBreak) => return Some(self.finish()),
+                Some(Token::ParagraphBreak) => {
+                    self.blank = true;
+                    return Some(self.finish());
+                }
+                Some(Token::Tag(tag)) => {
+                    self.current.push(Token::Tag(tag));
+                    self.started = true;
+                }
+                None if self.started => return Some(self.finish()),
+                None => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::text, *};
+
+    #[test]
+    pub fn lines_split_text() {
+        assert_eq!(
+            lines(tokenize_lossy(
+                "one [user:1] two\nthree\r\nfour [article:2]"
+            ))
+            .collect::<Vec<_>>(),
+            [
+                (
+                    1,
+                    vec![text("one "), Token::Tag(Tag::User(1)), text(" two")]
+                ),
+                (2, vec![text("three")]),
+                (3, vec![text("four "), Token::Tag(Tag::Article(2))]),
+            ]
+        );
+        assert_eq!(
+            lines([Token::Highlight("a\nb".to_string())]).collect::<Vec<_>>(),
+            [
+                (1, vec![Token::Highlight("a".to_string())]),
+                (2, vec![Token::Highlight("b".to_string())])
+            ]
+        );
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn lines_tag_at_line_start() {
+        assert_eq!(
+            lines(Document::parse("see\n[user:1] here").unwrap()).collect::<Vec<_>>(),
+            [
+                (1, vec![text("see")]),
+                (2, vec![Token::Tag(Tag::User(1)), text(" here")]),
+            ]
+        );
+        let tokens = TokenIter::with_options(
+            "a\n[quote:1]\n\nb",
+            TokenizerOptions {
+                emit_breaks: true,
+                ..Default::default()
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(
+            lines(tokens).collect::<Vec<_>>(),
+            [
+                (1, vec![text("a")]),
+                (2, vec![Token::Tag(Tag::Quote(1))]),
+                (3, vec![]),
+                (4, vec![text("b")]),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn lines_trailing_newline() {
+        let numbers = |input: &str| {
+            lines(tokenize_lossy(input))
+                .map(|(line, tokens)| (line, tokens.len()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(numbers("a\n"), [(1, 1)]);
+        assert_eq!(numbers("a\n\n"), [(1, 1), (2, 0)]);
+        assert_eq!(numbers("\n"), [(1, 0)]);
+        assert_eq!(numbers(""), []);
+        assert_eq!(numbers("[user:1]"), [(1, 1)]);
+    }
+}
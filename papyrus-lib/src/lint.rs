@@ -0,0 +1,671 @@
+//! Style checks on parsed tokens, beyond the hard errors of [`crate::validate`].
+//!
+//! ```
+//! use papyrus_lib::{lint::Linter, Severity, Tokenizer, UnknownTagAction};
+//!
+//! let tokens = Tokenizer::builder()
+//!     .on_unknown_tag(|_, _| UnknownTagAction::AsText)
+//!     .build()
+//!     .tokenize("[user:0] see [usr:2]")
+//!     .unwrap();
+//! let linter = Linter::builtin().severity("likely-typo", Severity::Error);
+//! let diagnostics = linter.check(&tokens);
+//! assert_eq!(diagnostics[0].to_string(), "0..8: error: [user:0] has id 0 (zero-id)");
+//! assert_eq!(diagnostics[1].rule, "likely-typo");
+//! ```
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+
+#[cfg(feature = "tag-blocks")]
+use crate::BlockKind;
+use crate::{
+    tokenizer::BUILTIN_NAMES, visit::TokenRef, walk, Severity, Span, Tag, Token, TokenVisitor,
+};
+
+/// Something a [`LintRule`] found, with the span of the tokens in their serialized form, like
+/// [`walk`] gives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The name of the rule, filled in by the [`Linter`].
+    pub rule: String,
+    pub span: Span,
+    /// The rule's own severity, unless the [`Linter`] overrides it.
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new<M: Into<String>>(span: Span, message: M) -> Self {
+        Self {
+            rule: String::new(),
+            span,
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}..{}: ", self.span.start, self.span.end)?;
+        if self.severity == Severity::Error {
+            f.write_str("error: ")?;
+        }
+        write!(f, "{} ({})", self.message, self.rule)
+    }
+}
+
+pub trait LintRule {
+    /// The name overrides refer to the rule by, in kebab case.
+    fn name(&self) -> &str;
+
+    /// What the rule's diagnostics are reported as, unless overridden.
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>);
+}
+
+/// Runs rules over tokens, see [`Linter::check`].
+#[derive(Default)]
+pub struct Linter {
+    rules: Vec<Box<dyn LintRule + Send + Sync>>,
+    overrides: Vec<(String, Severity)>,
+}
+
+impl Linter {
+    /// A linter without any rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A linter with the built-in style rules, and at most 20 mentions.
+    ///
+    /// The quotas, like [`MaxTags`], depend on what a platform can fan out, so they aren't
+    /// part of it. [`TableColumns`] is one of them with the `tag-blocks` feature.
+    pub fn builtin() -> Self {
+        let linter = Self::new()
+            .rule(TooManyMentions(20))
+            .rule(RepeatedMention)
+            .rule(LikelyTypo)
+            .rule(ZeroId);
+        #[cfg(feature = "tag-blocks")]
+        let linter = linter.rule(TableColumns);
+        linter
+    }
+
+    pub fn rule<R: LintRule + Send + Sync + 'static>(mut self, rule: R) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Reports the diagnostics of the rule called `name` as `severity`.
+    pub fn severity(mut self, name: &str, severity: Severity) -> Self {
+        self.overrides.retain(|(rule, _)| rule != name);
+        self.overrides.push((name.to_string(), severity));
+        self
+    }
+
+    /// The names of the rules, in the order they run.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|rule| rule.name())
+    }
+
+    /// Runs every rule, returning their diagnostics ordered by where they start.
+    pub fn check(&self, tokens: &[Token]) -> Vec<Diagnostic> {
+        let mut diags = Vec::new();
+        for rule in &self.rules {
+            let start = diags.len();
+            rule.check(tokens, &mut diags);
+            let severity = self
+                .overrides
+                .iter()
+                .find(|(name, _)| name == rule.name())
+                .map_or(rule.severity(), |(_, severity)| *severity);
+            for diag in &mut diags[start..] {
+                diag.rule = rule.name().to_string();
+                diag.severity = severity;
+            }
+        }
+        diags.sort_by_key(|diag| diag.span.start);
+        diags
+    }
+}
+
+impl core::fmt::Debug for Linter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Linter")
+            .field("rules", &self.names().collect::<Vec<_>>())
+            .field("overrides", &self.overrides)
+            .finish()
+    }
+}
+
+// Calls `f` with every tag and its span.
+fn tags(tokens: &[Token], f: impl FnMut(&Tag, Span)) {
+    struct Tags<F>(F);
+
+    impl<F: FnMut(&Tag, Span)> TokenVisitor for Tags<F> {
+        fn visit_tag(&mut self, tag: &Tag, span: Span) {
+            (self.0)(tag, span)
+        }
+    }
+
+    walk(tokens, &mut Tags(f));
+}
+
+/// Reports the mention after the first `.0` ones.
+#[derive(Clone, Copy, Debug)]
+pub struct TooManyMentions(pub usize);
+
+impl LintRule for TooManyMentions {
+    fn name(&self) -> &str {
+        "too-many-mentions"
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        let mut count = 0;
+        tags(tokens, |tag, span| {
+            if let Tag::User(_) = tag {
+                count += 1;
+                if count == self.0 + 1 {
+                    let message = format!("more than {} mentions", self.0);
+                    diags.push(Diagnostic::new(span, message));
+                }
+            }
+        });
+    }
+}
+
+/// Reports a user mentioned again right after, with only whitespace in between.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RepeatedMention;
+
+impl LintRule for RepeatedMention {
+    fn name(&self) -> &str {
+        "repeated-mention"
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        struct Repeats<'d> {
+            last: Option<usize>,
+            diags: &'d mut Vec<Diagnostic>,
+        }
+
+        impl TokenVisitor for Repeats<'_> {
+            fn visit_text(&mut self, text: &str, _span: Span) {
+                if !text.trim().is_empty() {
+                    self.last = None;
+                }
+            }
+
+            fn visit_tag(&mut self, _tag: &Tag, _span: Span) {
+                self.last = None;
+            }
+
+            fn visit_user(&mut self, id: usize, span: Span) {
+                if self.last.replace(id) == Some(id) {
+                    let message = format!("[user:{id}] is mentioned twice in a row");
+                    self.diags.push(Diagnostic::new(span, message));
+                }
+            }
+        }
+
+        walk(tokens, &mut Repeats { last: None, diags });
+    }
+}
+
+/// Reports text looking like a misspelled built-in tag, like `[usr:1]` kept as text by
+/// [`crate::UnknownTagAction::AsText`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LikelyTypo;
+
+impl LintRule for LikelyTypo {
+    fn name(&self) -> &str {
+        "likely-typo"
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        struct Typos<'d>(&'d mut Vec<Diagnostic>);
+
+        impl TokenVisitor for Typos<'_> {
+            fn visit_text(&mut self, text: &str, span: Span) {
+                // Offsets into the text, moved past the escapes of its serialized form.
+                let serialized =
+                    |i: usize| span.start + i + text[..i].matches(['[', ']', '\\']).count();
+                let mut rest = 0;
+                while let Some(open) = text[rest..].find('[').map(|i| rest + i) {
+                    let Some(close) = text[open..].find(']').map(|i| open + i) else {
+                        break;
+                    };
+                    let body = &text[open + 1..close];
+                    let name = body.split(':').next().unwrap_or_default().trim();
+                    // Fewer edits than either name has chars, or every short name would be
+                    // close to `if`.
+                    let near = |builtin: &&str| {
+                        let distance = distance(name, builtin);
+                        let len = name.chars().count().min(builtin.len());
+                        distance > 0 && distance <= 2 && distance < len
+                    };
+                    if let Some(builtin) = BUILTIN_NAMES.into_iter().find(near) {
+                        let span = Span::new(serialized(open), serialized(close + 1));
+                        let message = format!("[{body}] looks like a misspelled {builtin} tag");
+                        self.0.push(Diagnostic::new(span, message));
+                    }
+                    rest = close + 1;
+                }
+            }
+
+            fn visit_highlight(&mut self, _text: &str, _span: Span) {}
+        }
+
+        walk(tokens, &mut Typos(diags));
+    }
+}
+
+// The Levenshtein distance between two names, by chars.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Reports tags with id 0, which no user or article has.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZeroId;
+
+impl LintRule for ZeroId {
+    fn name(&self) -> &str {
+        "zero-id"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        tags(tokens, |tag, span| {
+            if tag.id() == Some(0) {
+                diags.push(Diagnostic::new(span, format!("[{tag}] has id 0")));
+            }
+        });
+    }
+}
+
+/// Reports the tag after the first `.0` ones, of any kind.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxTags(pub usize);
+
+impl LintRule for MaxTags {
+    fn name(&self) -> &str {
+        "max-tags"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        let mut count = 0;
+        tags(tokens, |_, span| {
+            count += 1;
+            if count == self.0 + 1 {
+                diags.push(Diagnostic::new(span, format!("more than {} tags", self.0)));
+            }
+        });
+    }
+}
+
+/// Reports the first mention of a user after `.0` distinct ones were mentioned. Unlike
+/// [`TooManyMentions`], mentioning a user again doesn't count.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxMentionedUsers(pub usize);
+
+impl LintRule for MaxMentionedUsers {
+    fn name(&self) -> &str {
+        "max-mentioned-users"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        let mut users = BTreeSet::new();
+        let mut reported = false;
+        tags(tokens, |tag, span| {
+            if let Tag::User(id) = tag {
+                if users.insert(*id) && users.len() > self.0 && !reported {
+                    reported = true;
+                    let message = format!("more than {} users mentioned", self.0);
+                    diags.push(Diagnostic::new(span, message));
+                }
+            }
+        });
+    }
+}
+
+/// Reports the first tag over `.0` tags per 100 chars of text.
+///
+/// Documents count as at least 100 chars long, so a short reply with a single mention
+/// stays within any density above zero.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxTagDensity(pub usize);
+
+impl LintRule for MaxTagDensity {
+    fn name(&self) -> &str {
+        "max-tag-density"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        let chars: usize = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Text(text) | Token::Highlight(text) => Some(text.chars().count()),
+                _ => None,
+            })
+            .sum();
+        let allowed = self.0 * chars.max(100) / 100;
+        let mut count = 0;
+        tags(tokens, |_, span| {
+            count += 1;
+            if count == allowed + 1 {
+                let message = format!("more than {} tags per 100 chars", self.0);
+                diags.push(Diagnostic::new(span, message));
+            }
+        });
+    }
+}
+
+/// Reports documents longer than `.0` bytes in their serialized form, with the span of what's
+/// past the limit.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxLength(pub usize);
+
+impl LintRule for MaxLength {
+    fn name(&self) -> &str {
+        "max-length"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        let len = tokens
+            .iter()
+            .map(|token| TokenRef::from(token).source_len())
+            .sum();
+        if len > self.0 {
+            let message = format!("longer than {} bytes", self.0);
+            diags.push(Diagnostic::new(Span::new(self.0, len), message));
+        }
+    }
+}
+
+/// Reports table rows with another number of cells than the first row of their table.
+#[cfg(feature = "tag-blocks")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TableColumns;
+
+#[cfg(feature = "tag-blocks")]
+impl LintRule for TableColumns {
+    fn name(&self) -> &str {
+        "table-columns"
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        // Per open table, the cells of its first row, and the start and cells of the row
+        // being counted. Tables in cells are counted on their own.
+        let mut tables: Vec<(Option<usize>, usize, usize)> = Vec::new();
+        tags(tokens, |tag, span| match (tag, tables.last_mut()) {
+            (Tag::Table, _) => tables.push((None, 0, 0)),
+            (Tag::Close(BlockKind::Table), _) => drop(tables.pop()),
+            (Tag::Row { .. }, Some((_, start, cells))) => (*start, *cells) = (span.start, 0),
+            (Tag::Cell, Some((_, _, cells))) => *cells += 1,
+            (Tag::Close(BlockKind::Row), Some((first, start, cells))) => match *first {
+                None => *first = Some(*cells),
+                Some(columns) if columns != *cells => {
+                    let message =
+                        format!("row doesn't have the {columns} columns of the first row");
+                    diags.push(Diagnostic::new(Span::new(*start, span.end), message));
+                }
+                Some(_) => {}
+            },
+            _ => {}
+        });
+    }
+}
+
+/// Reports `[if]` blocks on conditions not in `.0`, all at once rather than only the first like
+/// [`crate::evaluate`] does when its condition doesn't know them.
+#[cfg(feature = "tag-blocks")]
+#[derive(Clone, Debug, Default)]
+pub struct UnknownCondition(pub BTreeSet<String>);
+
+#[cfg(feature = "tag-blocks")]
+impl LintRule for UnknownCondition {
+    fn name(&self) -> &str {
+        "unknown-condition"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+        tags(tokens, |tag, span| {
+            if let Tag::If(condition) = tag {
+                if !self.0.contains(condition) {
+                    let message = format!("unknown condition {condition}");
+                    diags.push(Diagnostic::new(span, message));
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lint::*;
+    #[cfg(feature = "tag-blocks")]
+    use crate::tokenize_lossy;
+    use crate::{to_source, TokenIter, Tokenizer, UnknownTagAction};
+
+    fn lint<R: LintRule + Send + Sync + 'static>(rule: R, input: &str) -> Vec<(String, Severity)> {
+        let tokens = Tokenizer::builder()
+            .on_unknown_tag(|_, _| UnknownTagAction::AsText)
+            .build()
+            .tokenize(input)
+            .unwrap();
+        let source = to_source(&tokens);
+        Linter::new()
+            .rule(rule)
+            .check(&tokens)
+            .into_iter()
+            .map(|d| (source[d.span.range()].to_string(), d.severity))
+            .collect()
+    }
+
+    #[test]
+    pub fn lint_too_many_mentions() {
+        assert_eq!(
+            lint(
+                TooManyMentions(2),
+                "[user:1] [user:2] [article:1] [user:3] [user:4]"
+            ),
+            [("[user:3]".to_string(), Severity::Warning)]
+        );
+        assert!(lint(TooManyMentions(2), "[user:1] [user:2]").is_empty());
+    }
+
+    #[test]
+    pub fn lint_repeated_mention() {
+        assert_eq!(
+            lint(
+                RepeatedMention,
+                "[user:1] [user:1][user:1] and [user:1] [user:2]"
+            ),
+            [
+                ("[user:1]".to_string(), Severity::Warning),
+                ("[user:1]".to_string(), Severity::Warning),
+            ]
+        );
+        assert!(lint(RepeatedMention, "[user:1] [article:1] [user:1]").is_empty());
+    }
+
+    #[test]
+    pub fn lint_likely_typo() {
+        assert_eq!(
+            lint(LikelyTypo, "ü [usr:1] [artcle: 2] [bold] [user:x"),
+            [
+                ("\\[usr:1\\]".to_string(), Severity::Warning),
+                ("\\[artcle: 2\\]".to_string(), Severity::Warning),
+            ]
+        );
+        assert_eq!(
+            lint(
+                LikelyTypo,
+                "[lnk:https://a.com] [itme] [tabel] [a] [1] [ok]"
+            ),
+            [
+                ("\\[lnk:https://a.com\\]".to_string(), Severity::Warning),
+                ("\\[itme\\]".to_string(), Severity::Warning),
+                ("\\[tabel\\]".to_string(), Severity::Warning),
+            ]
+        );
+        assert_eq!(distance("spoiler", "spoilr"), 1);
+    }
+
+    #[test]
+    pub fn lint_zero_id() {
+        assert_eq!(
+            lint(ZeroId, "[user:0] [user:10] [article:0]"),
+            [
+                ("[user:0]".to_string(), Severity::Error),
+                ("[article:0]".to_string(), Severity::Error),
+            ]
+        );
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn lint_table_columns() {
+        let input = "[table][row][cell]a[/cell][cell]b[/cell][/row][row][cell]c[/cell][/row]\
+            [row][cell][table][row][cell]d[/cell][/row][/table][/cell][cell][/cell][/row][/table]";
+        assert_eq!(
+            lint(TableColumns, input),
+            [("[row][cell]c[/cell][/row]".to_string(), Severity::Warning)]
+        );
+        let diagnostics = Linter::builtin().check(&tokenize_lossy(input));
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "46..71: row doesn't have the 2 columns of the first row (table-columns)"
+        );
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn lint_unknown_condition() {
+        let known = UnknownCondition(BTreeSet::from(["premium".to_string()]));
+        assert_eq!(
+            lint(
+                known,
+                "[if:premium][if:beta]new[/if][/if] [if:Premium][/if]"
+            ),
+            [
+                ("[if:beta]".to_string(), Severity::Error),
+                ("[if:Premium]".to_string(), Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn lint_quotas() {
+        let input = "[user:1] [user:1] [user:2] [article:3] [user:3]";
+        let error = |s: &str| vec![(s.to_string(), Severity::Error)];
+        assert_eq!(lint(MaxTags(3), input), error("[article:3]"));
+        assert_eq!(lint(MaxMentionedUsers(2), input), error("[user:3]"));
+        assert_eq!(lint(MaxTagDensity(2), input), error("[user:2]"));
+        assert_eq!(lint(MaxLength(40), input), error("user:3]"));
+
+        let compliant = Linter::new()
+            .rule(MaxTags(5))
+            .rule(MaxMentionedUsers(3))
+            .rule(MaxTagDensity(5))
+            .rule(MaxLength(47));
+        let tokens = Tokenizer::default().tokenize(input).unwrap();
+        assert!(compliant.check(&tokens).is_empty());
+        let text = "a".repeat(150);
+        let tokens = Tokenizer::default()
+            .tokenize(&format!("{text} [user:1] [user:2] [user:3] {text}"))
+            .unwrap();
+        assert!(Linter::new()
+            .rule(MaxTagDensity(1))
+            .check(&tokens)
+            .is_empty());
+        assert_eq!(Linter::new().rule(MaxTagDensity(0)).check(&tokens).len(), 1);
+    }
+
+    #[test]
+    pub fn lint_custom_rule() {
+        struct NoShouting;
+
+        impl LintRule for NoShouting {
+            fn name(&self) -> &str {
+                "no-shouting"
+            }
+
+            fn check(&self, tokens: &[Token], diags: &mut Vec<Diagnostic>) {
+                let mut offset = 0;
+                for token in tokens {
+                    let len = to_source(core::slice::from_ref(token)).len();
+                    if matches!(token, Token::Text(t) if t.contains("!!")) {
+                        diags.push(Diagnostic::new(Span::new(offset, offset + len), "shouting"));
+                    }
+                    offset += len;
+                }
+            }
+        }
+
+        let tokens = TokenIter::new("[user:0] hi!!")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let linter = Linter::builtin()
+            .rule(NoShouting)
+            .severity("no-shouting", Severity::Error)
+            .severity("zero-id", Severity::Warning);
+        let diagnostics = linter.check(&tokens);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "0..8: [user:0] has id 0 (zero-id)",
+                "8..13: error: shouting (no-shouting)"
+            ]
+        );
+    }
+}
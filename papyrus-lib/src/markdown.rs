@@ -0,0 +1,161 @@
+//! Papyrus tags inside Markdown, rendered in a single pulldown-cmark pipeline.
+
+use core::{mem, ops::Range};
+
+use pulldown_cmark::{Event, LinkType, Parser, Tag as MdTag, TagEnd};
+
+use crate::{render::Lookups, tokenize_lossy, Resolver, Tag, Token};
+
+/// Parses Markdown into events, turning the papyrus tags within its text into links.
+///
+/// Tags without a url become their label as text, unresolved tags keep their markup. Code
+/// spans, code blocks and the text of links and images are never scanned for tags, and
+/// brackets escaped in the Markdown, like `\[`, are text.
+pub fn events<'a, R: Resolver + ?Sized>(input: &'a str, resolver: &R) -> Vec<Event<'a>> {
+    let mut out = Vec::new();
+    let mut lookups = Lookups::new(resolver);
+    let mut exempt = 0usize;
+    // The text since the last other event, as papyrus markup.
+    let mut markup = String::new();
+    for (event, range) in Parser::new(input).into_offset_iter() {
+        if let (Event::Text(text), 0) = (&event, exempt) {
+            push_markup(&mut markup, input, text, range);
+            continue;
+        }
+        if !markup.is_empty() {
+            push_tokens(
+                &mut out,
+                tokenize_lossy(&mem::take(&mut markup)),
+                &mut lookups,
+            );
+        }
+        match event {
+            Event::Start(MdTag::CodeBlock(_) | MdTag::Link { .. } | MdTag::Image { .. }) => {
+                exempt += 1;
+                out.push(event);
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::Link | TagEnd::Image) => {
+                exempt -= 1;
+                out.push(event);
+            }
+            event => out.push(event),
+        }
+    }
+    if !markup.is_empty() {
+        push_tokens(&mut out, tokenize_lossy(&markup), &mut lookups);
+    }
+    out
+}
+
+// Appends the text of an event at `range` of the input, with the brackets and backslashes that
+// weren't written as such escaped. Markdown drops the backslash of an escape, but the escaped
+// char starts an event of its own.
+fn push_markup(markup: &mut String, input: &str, text: &str, range: Range<usize>) {
+    let escape = |markup: &mut String, c| {
+        if matches!(c, '[' | ']' | '\\') {
+            markup.push('\\');
+        }
+        markup.push(c);
+    };
+    // Entities, like `&#91;`.
+    if input[range.clone()] != *text {
+        text.chars().for_each(|c| escape(markup, c));
+        return;
+    }
+    let backslashes = input[..range.start]
+        .bytes()
+        .rev()
+        .take_while(|&b| b == b'\\')
+        .count();
+    let mut chars = text.chars();
+    if backslashes % 2 == 1 {
+        chars.next().into_iter().for_each(|c| escape(markup, c));
+    }
+    markup.push_str(chars.as_str());
+}
+
+fn push_tokens<R: Resolver + ?Sized>(
+    out: &mut Vec<Event>,
+    tokens: Vec<Token>,
+    lookups: &mut Lookups<R, Tag>,
+) {
+    for token in tokens {
+        match token {
+            Token::Text(text) | Token::Highlight(text) => out.push(Event::Text(text.into())),
+            Token::LineBreak | Token::ParagraphBreak => out.push(Event::SoftBreak),
+            Token::Tag(tag) => {
+                let Some(resolved) = lookups.resolve(&tag) else {
+                    out.push(Event::Text(format!("[{tag}]").into()));
+                    continue;
+                };
+                let label = match tag {
+                    Tag::User(_) => format!("@{}", resolved.label),
+                    _ => resolved.label,
+                };
+                let Some(url) = resolved.url else {
+                    out.push(Event::Text(label.into()));
+                    continue;
+                };
+                out.push(Event::Start(MdTag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: url.into(),
+                    title: "".into(),
+                    id: "".into(),
+                }));
+                out.push(Event::Text(label.into()));
+                out.push(Event::End(TagEnd::Link));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markdown::*;
+    use crate::{testing::MapResolver, *};
+
+    fn render(input: &str) -> String {
+        let resolver = MapResolver::new()
+            .link(Tag::User(1), "alice", "/u/alice")
+            .label(Tag::User(2), "bob");
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, events(input, &resolver).into_iter());
+        html
+    }
+
+    #[test]
+    pub fn markdown_paragraph_mention() {
+        assert_eq!(
+            render("hi *there* [user:1], [user:2] and [user:3]"),
+            "<p>hi <em>there</em> <a href=\"/u/alice\">@alice</a>, @bob and [user:3]</p>\n"
+        );
+    }
+
+    #[test]
+    pub fn markdown_code_untouched() {
+        assert_eq!(
+            render("see `[user:1]`\n\n```\n[user:1]\n```\n"),
+            "<p>see <code>[user:1]</code></p>\n<pre><code>[user:1]\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    pub fn markdown_escaped_brackets() {
+        assert_eq!(
+            render("\\[user:1\\] and &#91;user:1&#93; but \\\\[user:1]"),
+            "<p>[user:1] and [user:1] but \\<a href=\"/u/alice\">@alice</a></p>\n"
+        );
+        assert_eq!(
+            render("*\\[user:2]* C:\\x\\y \\[[user:2]"),
+            "<p><em>[user:2]</em> C:\\x\\y [@bob</p>\n"
+        );
+    }
+
+    #[test]
+    pub fn markdown_heading_mention() {
+        assert_eq!(
+            render("# Thanks [user:1]!\n"),
+            "<h1>Thanks <a href=\"/u/alice\">@alice</a>!</h1>\n"
+        );
+    }
+}
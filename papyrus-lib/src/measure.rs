@@ -0,0 +1,132 @@
+use alloc::string::String;
+use core::ops::AddAssign;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{render::write_plain_tag, Resolver, Token};
+
+/// The length of a token stream in different units, see [`measure`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Measure {
+    pub bytes: usize,
+    pub chars: usize,
+    /// User-perceived characters, like an emoji built from several code points.
+    pub graphemes: usize,
+    /// The estimated number of terminal columns, two for East Asian wide characters.
+    pub display_width: usize,
+}
+
+impl Measure {
+    fn of(text: &str) -> Self {
+        Self {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            graphemes: text.graphemes(true).count(),
+            display_width: text.width(),
+        }
+    }
+}
+
+impl AddAssign for Measure {
+    fn add_assign(&mut self, other: Self) {
+        self.bytes += other.bytes;
+        self.chars += other.chars;
+        self.graphemes += other.graphemes;
+        self.display_width += other.display_width;
+    }
+}
+
+/// How [`measure`] counts tags.
+#[derive(Clone, Copy)]
+pub enum TagWidth<'r> {
+    /// Every tag counts as this many of each unit, like a placeholder of ASCII characters.
+    Fixed(usize),
+    /// Tags count as what [`render_plain`](crate::render_plain) writes for them.
+    Label(&'r dyn Resolver),
+}
+
+/// Measures the text of a token stream, and its tags the way `tags` says.
+///
+/// Breaks count as their newlines, which take no columns.
+///
+/// ```
+/// use papyrus_lib::{measure, tokenize_lossy, Measure, TagWidth};
+///
+/// let tokens = tokenize_lossy("hi [user:1]");
+/// assert_eq!(
+///     measure(&tokens, TagWidth::Fixed(4)),
+///     Measure { bytes: 7, chars: 7, graphemes: 7, display_width: 7 }
+/// );
+/// ```
+pub fn measure<'a, I: IntoIterator<Item = &'a Token>>(tokens: I, tags: TagWidth) -> Measure {
+    let mut total = Measure::default();
+    for token in tokens {
+        total += match token {
+            Token::Text(text) | Token::Highlight(text) => Measure::of(text),
+            Token::Tag(tag) => match tags {
+                TagWidth::Fixed(n) => Measure {
+                    bytes: n,
+                    chars: n,
+                    graphemes: n,
+                    display_width: n,
+                },
+                TagWidth::Label(resolver) => {
+                    let mut label = String::new();
+                    write_plain_tag(&mut label, tag, resolver.resolve(tag)).unwrap();
+                    Measure::of(&label)
+                }
+            },
+            Token::LineBreak => Measure {
+                display_width: 0,
+                ..Measure::of("\n")
+            },
+            Token::ParagraphBreak => Measure {
+                display_width: 0,
+                ..Measure::of("\n\n")
+            },
+        };
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::tokenize, *};
+
+    #[test]
+    pub fn measure_combining_emoji_and_cjk() {
+        let measure = |s: &str| measure(&tokenize(s), TagWidth::Fixed(1));
+        assert_eq!(
+            measure("cafe\u{301}"),
+            Measure {
+                bytes: 6,
+                chars: 5,
+                graphemes: 4,
+                display_width: 4
+            }
+        );
+        let family = measure("\u{1f469}\u{200d}\u{1f469}\u{200d}\u{1f467}");
+        assert_eq!((family.chars, family.graphemes), (5, 1));
+        assert_eq!(
+            measure("\u{65e5}\u{672c}\u{8a9e} ok"),
+            Measure {
+                bytes: 12,
+                chars: 6,
+                graphemes: 6,
+                display_width: 9
+            }
+        );
+    }
+
+    #[test]
+    pub fn measure_tags() {
+        let tokens = tokenize("by [user:1] and [user:7]");
+        let resolver = testing::MapResolver::new().label(Tag::User(1), "\u{5c71}\u{7530}");
+        let labeled = measure(&tokens, TagWidth::Label(&resolver));
+        // `@山田` and the unresolved `[user:7]`.
+        assert_eq!(labeled.chars, 8 + 3 + 8);
+        assert_eq!(labeled.display_width, 8 + 5 + 8);
+        assert_eq!(measure(&tokens, TagWidth::Fixed(0)).bytes, "by  and ".len());
+    }
+}
@@ -0,0 +1,315 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+use core::fmt::Display;
+
+use crate::{Diagnostic, Position, Severity, Span, TagParseErr, TokenizeErr};
+
+/// An error as data, so it can be shown in the reader's language: its code, like
+/// [`TokenizeErr::code`], the values its message mentions, and where it is.
+///
+/// The args by code are `error` for `capture_parse_err`, `tag` for `unknown_tag`, `alias`
+/// and `canonical` for `deprecated_alias`, `payload` for `invalid_payload`, and `char` for
+/// `control_char`, plus `part` for errors of a [`TagParseErr::InvalidPart`].
+///
+/// `Display` writes the English message.
+///
+/// ```
+/// use papyrus_lib::TokenIter;
+///
+/// let error = TokenIter::new("hi\n[usr:1]").find_map(Result::err).unwrap();
+/// let message = error.message();
+/// assert_eq!(message.code, "unknown_tag");
+/// assert_eq!(message.args["tag"], "[usr:1]");
+/// assert_eq!(message.to_string(), "unknown tag [usr:1] at line 2");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Message {
+    pub code: String,
+    pub args: BTreeMap<String, String>,
+    pub position: Option<Position>,
+    pub severity: Severity,
+}
+
+impl Message {
+    fn new(code: &str, severity: Severity) -> Self {
+        Self {
+            code: code.to_string(),
+            args: BTreeMap::new(),
+            position: None,
+            severity,
+        }
+    }
+
+    fn arg(mut self, name: &str, value: impl Display) -> Self {
+        self.args.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let arg = |name: &str| self.args.get(name).map_or("", String::as_str);
+        if let Some(part) = self.args.get("part") {
+            write!(f, "{part:?}: ")?;
+        }
+        match self.code.as_str() {
+            "no_captures" => f.write_str("no captures")?,
+            "capture_not_found" => f.write_str("capture not found")?,
+            "capture_parse_err" => write!(f, "invalid id: {}", arg("error"))?,
+            "unknown_tag" => write!(f, "unknown tag {}", arg("tag"))?,
+            "deprecated_alias" => write!(
+                f,
+                "{} is deprecated, use {}",
+                arg("alias"),
+                arg("canonical")
+            )?,
+            "invalid_payload" => write!(f, "invalid payload {:?}", arg("payload"))?,
+            "invalid_utf8" => f.write_str("invalid UTF-8")?,
+            "unclosed_tag" => f.write_str("unclosed tag")?,
+            "tag_too_long" => f.write_str("tag too long")?,
+            "control_char" => write!(f, "control character {}", arg("char"))?,
+            "unmatched_close" => f.write_str("closing bracket without a tag opening it")?,
+            code => f.write_str(code)?,
+        }
+        match self.position {
+            Some(position) => write!(f, " at {position}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TagParseErr {
+    /// The error as a [`Message`], without a position.
+    pub fn message(&self) -> Message {
+        let message = Message::new(self.code(), Severity::Error);
+        match self {
+            Self::NoCaptures | Self::CaptureNotFound => message,
+            Self::UnmatchedClose => Message {
+                severity: Severity::Warning,
+                ..message
+            },
+            Self::CaptureParseErr(e) => message.arg("error", e),
+            Self::UnknownTag(tag) => message.arg("tag", tag),
+            Self::DeprecatedAlias { alias, canonical } => Message {
+                severity: Severity::Warning,
+                ..message.arg("alias", alias).arg("canonical", canonical)
+            },
+            Self::InvalidPayload(payload) => message.arg("payload", payload),
+            Self::InvalidPart { body, span, error } => {
+                error.message().arg("part", &body[span.range()])
+            }
+        }
+    }
+}
+
+impl TokenizeErr {
+    pub fn message(&self) -> Message {
+        let message = match self {
+            Self::TagErr(_, e) => e.message(),
+            Self::ControlChar(_, c) => {
+                let code_point = format_args!("U+{:04X}", u32::from(*c));
+                Message::new(self.code(), Severity::Error).arg("char", code_point)
+            }
+            _ => Message::new(self.code(), Severity::Error),
+        };
+        Message {
+            position: Some(self.position()),
+            ..message
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn message(&self) -> Message {
+        let line = u16::try_from(self.line).unwrap_or(u16::MAX);
+        let column = u32::try_from(self.column).unwrap_or(u32::MAX);
+        Message {
+            position: Some(Position::new(line).with_column(column)),
+            severity: self.severity,
+            ..self.error.message()
+        }
+    }
+}
+
+/// A [`TokenizeErr`] or [`Diagnostic`] in the one shape API responses use: its code, English
+/// message, severity, 1-based line and column in chars, span and the [`Message::args`] as
+/// `params`.
+///
+/// With `serde` it's `{"code", "message", "severity", "line", "column", "span", "params"}`,
+/// and stays so. A `TokenizeErr` only knows where it starts, so its span is `null`.
+///
+/// ```
+/// use papyrus_lib::{Problem, Severity, Span, Tokenizer};
+///
+/// let diagnostics = Tokenizer::default().validate("hi [usr:1]");
+/// let problem = Problem::from(&diagnostics[0]);
+/// assert_eq!(problem.message, "unknown tag [usr:1]");
+/// assert_eq!((problem.line, problem.column), (1, 4));
+/// assert_eq!(problem.span, Some(Span::new(3, 10)));
+/// assert_eq!(problem.params["tag"], "[usr:1]");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Problem {
+    pub code: String,
+    pub message: String,
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub span: Option<Span>,
+    pub params: BTreeMap<String, String>,
+}
+
+impl Problem {
+    fn new(message: Message, span: Option<Span>) -> Self {
+        let position = message.position.unwrap_or(Position::new(1));
+        // The line and column are fields of their own.
+        let text = Message {
+            position: None,
+            ..message.clone()
+        };
+        Self {
+            message: text.to_string(),
+            code: message.code,
+            severity: message.severity,
+            line: position.line().into(),
+            column: position.column() as usize,
+            span,
+            params: message.args,
+        }
+    }
+}
+
+impl From<&TokenizeErr> for Problem {
+    fn from(error: &TokenizeErr) -> Self {
+        Self::new(error.message(), None)
+    }
+}
+
+impl From<&Diagnostic> for Problem {
+    fn from(diagnostic: &Diagnostic) -> Self {
+        let mut problem = Self::new(diagnostic.message(), Some(diagnostic.span));
+        // Not clamped like a position is.
+        problem.line = diagnostic.line;
+        problem.column = diagnostic.column;
+        problem
+    }
+}
+
+/// Formats `message` with the message of `bundle` named like its code, falling back to
+/// English if there's none.
+///
+/// The message gets the args as variables, and `line` and `column` if it has a position.
+///
+/// ```
+/// use fluent::{FluentBundle, FluentResource};
+/// use papyrus_lib::{format_fluent, TokenIter};
+///
+/// let ftl = "unknown_tag = Unbekanntes Tag { $tag } in Zeile { $line }";
+/// let mut bundle = FluentBundle::new(vec!["de".parse().unwrap()]);
+/// bundle.add_resource(FluentResource::try_new(ftl.to_string()).unwrap()).unwrap();
+/// bundle.set_use_isolating(false);
+///
+/// let error = TokenIter::new("[usr:1]").find_map(Result::err).unwrap();
+/// assert_eq!(format_fluent(&bundle, &error.message()), "Unbekanntes Tag [usr:1] in Zeile 1");
+/// ```
+#[cfg(feature = "fluent")]
+pub fn format_fluent<R>(bundle: &fluent::FluentBundle<R>, message: &Message) -> String
+where
+    R: core::borrow::Borrow<fluent::FluentResource>,
+{
+    let Some(pattern) = bundle.get_message(&message.code).and_then(|m| m.value()) else {
+        return message.to_string();
+    };
+    let mut args = fluent::FluentArgs::new();
+    for (name, value) in &message.args {
+        args.set(name.as_str(), value.as_str());
+    }
+    if let Some(position) = message.position {
+        args.set("line", position.line());
+        args.set("column", position.column());
+    }
+    // Errors of the bundle, like variables it doesn't get, still leave a message.
+    let mut errors = alloc::vec::Vec::new();
+    bundle
+        .format_pattern(pattern, Some(&args), &mut errors)
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn error(input: &str) -> TokenizeErr {
+        TokenIter::new(input).find_map(Result::err).unwrap()
+    }
+
+    #[test]
+    pub fn message_english() {
+        let message = error("a\n[user:5x]").message();
+        assert_eq!(message.code, "capture_parse_err");
+        assert_eq!(message.args["part"], "5x");
+        assert_eq!(message.position, Some(Position::new(2)));
+        assert_eq!(
+            message.to_string(),
+            "\"5x\": invalid id: invalid digit found in string at line 2"
+        );
+        let invalid = ByteTokenIter::new(b"a\xff").find_map(Result::err).unwrap();
+        assert_eq!(invalid.message().to_string(), "invalid UTF-8 at line 1");
+
+        let tokenizer = Tokenizer::builder()
+            .alias("u", "user")
+            .unwrap()
+            .deprecate_aliases(true)
+            .build();
+        let warning = &tokenizer.validate("hi [u:1]")[0];
+        let message = warning.message();
+        assert_eq!(message.severity, Severity::Warning);
+        assert_eq!(message.position, Some(Position::new(1).with_column(4)));
+        assert_eq!(message.to_string(), "u is deprecated, use user at line 1");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    pub fn message_serde() {
+        let message = error("[usr:1]").message();
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": "unknown_tag",
+                "args": { "tag": "[usr:1]" },
+                "position": { "line": 1, "column": 1 },
+                "severity": "Error",
+            })
+        );
+        assert_eq!(serde_json::from_value::<Message>(json).unwrap(), message);
+    }
+
+    #[cfg(feature = "fluent")]
+    #[test]
+    pub fn message_fluent() {
+        use fluent::{FluentBundle, FluentResource};
+
+        let ftl = "unknown_tag = Unknown tag in test locale: { $tag } ({ $line }:{ $column })\n";
+        let mut bundle = FluentBundle::new(vec!["en-XA".parse().unwrap()]);
+        bundle
+            .add_resource(FluentResource::try_new(ftl.to_string()).unwrap())
+            .unwrap();
+        bundle.set_use_isolating(false);
+        assert_eq!(
+            format_fluent(&bundle, &error("hi [usr:1]").message()),
+            "Unknown tag in test locale: [usr:1] (1:4)"
+        );
+        // No message for the code.
+        assert_eq!(
+            format_fluent(&bundle, &error("a\n[user:5x]").message()),
+            "\"5x\": invalid id: invalid digit found in string at line 2"
+        );
+    }
+}
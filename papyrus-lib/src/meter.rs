@@ -0,0 +1,150 @@
+// Counters and histograms of the `metrics` feature, which compile to nothing without it.
+//
+// `documents_total` and `input_bytes` are labeled with the entry point as `op`,
+// `tokens_total` with the token's `kind`, the name of tags, and `errors_total` with the
+// error's `code`.
+
+#[cfg(feature = "metrics")]
+use alloc::string::ToString;
+
+use crate::{Token, TokenizeErr};
+
+/// A document passed to the `op` entry point, with its length if it's source.
+#[inline]
+pub(crate) fn document(_op: &'static str, _input_len: Option<usize>) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!("documents_total", "op" => _op).increment(1);
+        if let Some(len) = _input_len {
+            metrics::histogram!("input_bytes", "op" => _op).record(len as f64);
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn tokens<T: core::fmt::Display>(_tokens: &[Token<T>]) {
+    #[cfg(feature = "metrics")]
+    for token in _tokens {
+        let kind = match token {
+            Token::Text(_) => "text".to_string(),
+            Token::Highlight(_) => "highlight".to_string(),
+            Token::LineBreak => "line_break".to_string(),
+            Token::ParagraphBreak => "paragraph_break".to_string(),
+            Token::Tag(tag) => {
+                let mut tag = tag.to_string();
+                tag.truncate(tag.find(':').unwrap_or(tag.len()));
+                tag
+            }
+        };
+        metrics::counter!("tokens_total", "kind" => kind).increment(1);
+    }
+}
+
+#[inline]
+pub(crate) fn error(_code: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("errors_total", "code" => _code).increment(1);
+}
+
+#[inline]
+pub(crate) fn tokenize_err(error: &TokenizeErr) {
+    self::error(error.code());
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+
+    use crate::*;
+
+    // Keeps the value of every counter, and the number of values of every histogram,
+    // by their name and labels like `tokens_total{kind=user}`.
+    #[derive(Default)]
+    struct Stub(Mutex<BTreeMap<String, Arc<Value>>>);
+
+    #[derive(Default)]
+    struct Value(AtomicU64);
+
+    impl CounterFn for Value {
+        fn increment(&self, value: u64) {
+            self.0.fetch_add(value, Ordering::Relaxed);
+        }
+
+        fn absolute(&self, value: u64) {
+            self.0.store(value, Ordering::Relaxed);
+        }
+    }
+
+    impl HistogramFn for Value {
+        fn record(&self, _value: f64) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    impl Stub {
+        fn value(&self, key: &Key) -> Arc<Value> {
+            let labels = key
+                .labels()
+                .map(|label| format!("{}={}", label.key(), label.value()))
+                .collect::<Vec<_>>();
+            let name = format!("{}{{{}}}", key.name(), labels.join(","));
+            self.0.lock().unwrap().entry(name).or_default().clone()
+        }
+
+        fn get(&self, name: &str) -> u64 {
+            let values = self.0.lock().unwrap();
+            values.get(name).map_or(0, |v| v.0.load(Ordering::Relaxed))
+        }
+    }
+
+    impl Recorder for Stub {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.value(key))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.value(key))
+        }
+    }
+
+    #[test]
+    pub fn metrics_counted() {
+        let stub = Stub::default();
+        metrics::with_local_recorder(&stub, || {
+            let tokens = Tokenizer::default()
+                .tokenize("hi [user:1] [user:2]")
+                .unwrap();
+            Tokenizer::default().tokenize("[bad]").unwrap_err();
+            validate("[usr:1] [article:x]");
+            render_html(&tokens, &|_: &Tag| None);
+        });
+        assert_eq!(stub.get("documents_total{op=tokenize}"), 2);
+        assert_eq!(stub.get("documents_total{op=validate}"), 1);
+        assert_eq!(stub.get("documents_total{op=render_html}"), 1);
+        assert_eq!(stub.get("input_bytes{op=tokenize}"), 2);
+        assert_eq!(stub.get("tokens_total{kind=user}"), 2);
+        assert_eq!(stub.get("tokens_total{kind=text}"), 2);
+        assert_eq!(stub.get("errors_total{code=unknown_tag}"), 3);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use memmap2::Mmap;
+
+use crate::{BorrowedToken, ByteTokenIter, BytesRead, Position, Token, TokenizeErr};
+
+/// Why mapping or tokenizing a [`MappedFile`] failed.
+#[derive(Debug)]
+pub enum MapError {
+    /// The file couldn't be opened or mapped.
+    Io { path: PathBuf, error: io::Error },
+    /// The first invalid byte of a token, and its offset in the file.
+    InvalidUtf8 { position: Position, offset: usize },
+    /// Any other error of a token, with the offset of the token in the file.
+    Tokenize { error: TokenizeErr, offset: usize },
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::InvalidUtf8 { position, offset } => {
+                write!(f, "invalid UTF-8 at {position} (byte {offset})")
+            }
+            Self::Tokenize { error, offset } => write!(f, "{error} (byte {offset})"),
+        }
+    }
+}
+
+impl std::error::Error for MapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::InvalidUtf8 { .. } => None,
+            Self::Tokenize { error, .. } => Some(error),
+        }
+    }
+}
+
+/// A file mapped into memory, to tokenize it without reading it into a `String` first.
+///
+/// Like with any memory map, the file must not be changed while it's mapped, or the tokens
+/// borrowing from it change under them.
+///
+/// ```no_run
+/// use papyrus_lib::{BorrowedToken, MappedFile};
+///
+/// let file = MappedFile::open("corpus.txt")?;
+/// let tags = file.tokens().filter(|token| matches!(token, Ok(BorrowedToken::Tag(_)))).count();
+/// # Ok::<_, papyrus_lib::MapError>(())
+/// ```
+#[derive(Debug)]
+pub struct MappedFile {
+    map: Mmap,
+}
+
+impl MappedFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, MapError> {
+        let path = path.as_ref();
+        let io = |error| MapError::Io {
+            path: path.to_path_buf(),
+            error,
+        };
+        let file = File::open(path).map_err(io)?;
+        // SAFETY: The map is only ever read, and not changing the file while it's mapped is
+        // up to the caller, as documented above.
+        let map = unsafe { Mmap::map(&file) }.map_err(io)?;
+        Ok(Self { map })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Tokenizes the file, validating UTF-8 only as far as tokens are read, with tokens
+    /// borrowing their text from the map.
+    pub fn tokens(&self) -> MappedTokens<'_> {
+        MappedTokens {
+            bytes: &self.map,
+            iter: ByteTokenIter::new(&self.map),
+        }
+    }
+}
+
+/// The tokens of a [`MappedFile`], see [`MappedFile::tokens`].
+pub struct MappedTokens<'a> {
+    bytes: &'a [u8],
+    iter: ByteTokenIter<'a>,
+}
+
+impl<'a> Iterator for MappedTokens<'a> {
+    type Item = Result<BorrowedToken<'a>, MapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.iter.bytes_read();
+        Some(self.iter.next()?.map_err(|error| match error {
+            TokenizeErr::InvalidUtf8(position) => {
+                let token = &self.bytes[start..self.iter.bytes_read()];
+                let valid = core::str::from_utf8(token).map_or_else(|e| e.valid_up_to(), |_| 0);
+                MapError::InvalidUtf8 {
+                    position,
+                    offset: start + valid,
+                }
+            }
+            error => MapError::Tokenize {
+                error,
+                offset: start,
+            },
+        }))
+    }
+}
+
+impl BytesRead for MappedTokens<'_> {
+    fn bytes_read(&self) -> usize {
+        self.iter.bytes_read()
+    }
+}
+
+/// Tokenizes the file at `path` through a [`MappedFile`], failing at the first error.
+pub fn tokenize_file<P: AsRef<Path>>(path: P) -> Result<Vec<Token>, MapError> {
+    MappedFile::open(path)?
+        .tokens()
+        .map(|token| token.map(BorrowedToken::into_owned))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fmt::Write, fs, path::PathBuf};
+
+    use crate::*;
+
+    fn temp_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("papyrus-{name}-{}", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    pub fn mmap_matches_in_memory() {
+        let mut input = String::new();
+        for i in 0..60_000 {
+            writeln!(input, "line {i} by [user:{i}] on [article:{i}], \\[x\\] ü").unwrap();
+        }
+        assert!(input.len() > 2_000_000);
+        let path = temp_file("mmap", input.as_bytes());
+        let mapped = tokenize_file(&path);
+        fs::remove_file(&path).unwrap();
+        let expected = TokenIter::new(&input)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(mapped.unwrap(), expected);
+    }
+
+    #[test]
+    pub fn mmap_errors() {
+        let path = temp_file("mmap-invalid", b"ok [user:1]\ncaf\xe9 [article:2]");
+        let error = tokenize_file(&path).unwrap_err();
+        let tokens = MappedFile::open(&path).unwrap().tokens().count();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            error,
+            MapError::InvalidUtf8 {
+                position,
+                offset: 15
+            } if position == Position::new(2).with_column(4)
+        ));
+        assert_eq!(error.to_string(), "invalid UTF-8 at line 2 (byte 15)");
+        assert_eq!(tokens, 4);
+
+        let missing = std::env::temp_dir().join("papyrus-mmap-missing");
+        let error = MappedFile::open(&missing).unwrap_err();
+        assert!(matches!(&error, MapError::Io { path, .. } if *path == missing));
+    }
+}
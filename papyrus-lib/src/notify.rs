@@ -0,0 +1,143 @@
+use alloc::{collections::BTreeSet, vec::Vec};
+
+use crate::{tags, Tag, Token};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NotifyOptions {
+    /// A user that's never notified, usually the author mentioning themselves.
+    pub exclude: Option<u64>,
+    /// The maximum number of users to notify, further mentions are ignored.
+    pub max_targets: Option<usize>,
+}
+
+/// The users mentioned in a token stream, in order of their first mention and without
+/// duplicates.
+///
+/// `expand` gives the users a group mention stands for, in order. It's asked about every tag
+/// that isn't a user mention, and gives none for the tags that aren't groups.
+///
+/// ```
+/// use papyrus_lib::{notification_targets, tokenize_lossy, NotifyOptions, Tag};
+///
+/// let tokens = tokenize_lossy("[user:2] and [article:1]'s authors");
+/// let authors = |tag: &Tag| match tag {
+///     Tag::Article(1) => vec![1, 2],
+///     _ => Vec::new(),
+/// };
+/// assert_eq!(notification_targets(&tokens, NotifyOptions::default(), &authors), [2, 1]);
+/// ```
+pub fn notification_targets<'a, I, F>(tokens: I, options: NotifyOptions, expand: &F) -> Vec<u64>
+where
+    I: IntoIterator<Item = &'a Token>,
+    F: Fn(&Tag) -> Vec<u64> + ?Sized,
+{
+    let max_targets = options.max_targets.unwrap_or(usize::MAX);
+    let mut seen = BTreeSet::new();
+    let mut add = |targets: &mut Vec<u64>, user: u64| {
+        if targets.len() < max_targets && options.exclude != Some(user) && seen.insert(user) {
+            targets.push(user);
+        }
+    };
+    let mut targets = Vec::new();
+    for tag in tags(tokens) {
+        if targets.len() >= max_targets {
+            break;
+        }
+        match tag {
+            Tag::User(user) => add(&mut targets, *user as u64),
+            tag => {
+                for user in expand(tag) {
+                    add(&mut targets, user);
+                }
+            }
+        }
+    }
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::tokenize, *};
+
+    fn no_groups(_: &Tag) -> Vec<u64> {
+        Vec::new()
+    }
+
+    #[test]
+    pub fn targets_deduplicated() {
+        let tokens = tokenize("[user:3] [user:1] [article:3] [user:3] [user:2] [user:1]");
+        assert_eq!(
+            notification_targets(&tokens, NotifyOptions::default(), &no_groups),
+            vec![3, 1, 2]
+        );
+    }
+
+    #[test]
+    pub fn targets_exclude_author() {
+        let tokens = tokenize("[user:1] thanks to me, [user:2] and [user:1]");
+        let options = NotifyOptions {
+            exclude: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(notification_targets(&tokens, options, &no_groups), vec![2]);
+    }
+
+    #[test]
+    pub fn targets_capped() {
+        let tokens = tokenize("[user:5][user:5][user:4][user:3][user:2][user:1]");
+        let options = NotifyOptions {
+            exclude: Some(4),
+            max_targets: Some(3),
+        };
+        assert_eq!(
+            notification_targets(&tokens, options, &no_groups),
+            vec![5, 3, 2]
+        );
+        let options = NotifyOptions {
+            max_targets: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            notification_targets(&tokens, options, &no_groups),
+            Vec::<u64>::new()
+        );
+    }
+
+    #[test]
+    pub fn targets_expand_groups() {
+        let group = |name: &str| {
+            Token::Tag(Tag::Custom {
+                name: "group".to_string(),
+                value: name.to_string(),
+            })
+        };
+        let tokens = vec![
+            Token::Tag(Tag::User(2)),
+            group("devs"),
+            Token::Tag(Tag::Article(1)),
+            group("ops"),
+            Token::Tag(Tag::User(9)),
+        ];
+        let expand = |tag: &Tag| match tag {
+            Tag::Custom { name, value } if name == "group" => match value.as_str() {
+                "devs" => vec![1, 2, 3],
+                "ops" => vec![3, 4],
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+        let options = NotifyOptions {
+            exclude: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            notification_targets(&tokens, options, &expand),
+            [2, 1, 4, 9]
+        );
+        let options = NotifyOptions {
+            max_targets: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(notification_targets(&tokens, options, &expand), [2, 1, 3]);
+    }
+}
@@ -0,0 +1,129 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{iter::FusedIterator, marker::PhantomData, mem};
+
+use crate::{
+    scan::{Scanned, Scanner},
+    Token, TokenIter, TokenizeErr, TokenizerOptions,
+};
+
+/// A [`TokenIter`] that owns its source, so it can outlive whatever the source was read
+/// from.
+///
+/// It keeps the source in an `Arc`, so cloning the iterator doesn't copy it.
+#[derive(Clone)]
+pub struct OwnedTokenIter {
+    source: Arc<str>,
+    /// The end of the part of `source` not yet read from the back.
+    end: usize,
+    scanner: Scanner,
+    back_line: Option<usize>,
+    back_lexemes: Vec<(usize, Scanned)>,
+}
+
+impl OwnedTokenIter {
+    pub fn new<S: Into<Arc<str>>>(source: S) -> Self {
+        Self::with_options(source, TokenizerOptions::default())
+    }
+
+    pub fn with_options<S: Into<Arc<str>>>(source: S, options: TokenizerOptions) -> Self {
+        let source = source.into();
+        Self {
+            end: source.len(),
+            source,
+            scanner: Scanner::new(options.into()),
+            back_line: None,
+            back_lexemes: Vec::new(),
+        }
+    }
+
+    /// The whole source, including the tokens already read.
+    pub fn source(&self) -> &Arc<str> {
+        &self.source
+    }
+
+    // Borrows the source for a single step of a `TokenIter` resumed where this one stopped.
+    fn step<R>(&mut self, f: impl FnOnce(&mut TokenIter<'_>) -> R) -> R {
+        let mut iter = TokenIter {
+            source: &self.source[..self.end],
+            scanner: self.scanner.clone(),
+            back_line: self.back_line,
+            back_lexemes: mem::take(&mut self.back_lexemes),
+            tag: PhantomData,
+        };
+        let result = f(&mut iter);
+        self.end = iter.source.len();
+        self.scanner = iter.scanner;
+        self.back_line = iter.back_line;
+        self.back_lexemes = iter.back_lexemes;
+        result
+    }
+}
+
+impl From<String> for OwnedTokenIter {
+    fn from(source: String) -> Self {
+        Self::new(source)
+    }
+}
+
+impl From<Arc<str>> for OwnedTokenIter {
+    fn from(source: Arc<str>) -> Self {
+        Self::new(source)
+    }
+}
+
+impl Iterator for OwnedTokenIter {
+    type Item = Result<Token, TokenizeErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(|iter| iter.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.end - self.scanner.offset()))
+    }
+}
+
+impl DoubleEndedIterator for OwnedTokenIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(|iter| iter.next_back())
+    }
+}
+
+impl FusedIterator for OwnedTokenIter {}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io, path::Path, sync::Arc};
+
+    use crate::*;
+
+    fn read_and_tokenize(path: &Path) -> io::Result<OwnedTokenIter> {
+        fs::read_to_string(path).map(OwnedTokenIter::from)
+    }
+
+    #[test]
+    pub fn owned_from_file() {
+        let input = "hi [user:1]\n\n[article:2] [x] \\[y\\]";
+        let path = std::env::temp_dir().join(format!("papyrus-owned-{}", std::process::id()));
+        fs::write(&path, input).unwrap();
+        let tokens = read_and_tokenize(&path).unwrap().collect::<Vec<_>>();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(tokens, TokenIter::new(input).collect::<Vec<_>>());
+    }
+
+    #[test]
+    pub fn owned_both_ends() {
+        let input: Arc<str> = "a [user:1] b [x] c [article:2] d".into();
+        let expected = TokenIter::new(&*input).collect::<Vec<_>>();
+        let mut iter = OwnedTokenIter::new(input.clone());
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+        while let Some(token) = iter.next() {
+            front.push(token);
+            back.extend(iter.next_back());
+        }
+        front.extend(back.into_iter().rev());
+        assert_eq!(front, expected);
+        assert!(Arc::ptr_eq(iter.source(), &input));
+    }
+}
@@ -0,0 +1,329 @@
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "tag-blocks")]
+use crate::BlockKind;
+use crate::{walk, Span, Tag, TokenSource, TokenVisitor};
+
+/// What a tag is, without its payload.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TagKind {
+    User,
+    Article,
+    Link,
+    Var,
+    /// A custom tag of the given name.
+    Custom(String),
+    /// A block tag of the given kind, or its closer.
+    #[cfg(feature = "tag-blocks")]
+    Block(BlockKind),
+}
+
+impl Tag {
+    /// What the tag is, like [`TagKind::User`] for both [`Tag::User`] and [`Tag::UserKey`].
+    ///
+    /// ```
+    /// use papyrus_lib::{Tag, TagKind};
+    ///
+    /// assert_eq!(Tag::User(5).kind(), TagKind::User);
+    /// assert_eq!(Tag::UserKey("alice".to_string()).kind(), TagKind::User);
+    /// ```
+    // Matches every variant without a wildcard, so new ones have to pick their kind here.
+    pub fn kind(&self) -> TagKind {
+        match self {
+            Tag::User(_) | Tag::UserKey(_) => TagKind::User,
+            Tag::Article(_) | Tag::ArticleKey(_) => TagKind::Article,
+            Tag::Link(_) => TagKind::Link,
+            Tag::Var(_) => TagKind::Var,
+            Tag::Custom { name, .. } => TagKind::Custom(name.clone()),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Spoiler => TagKind::Block(BlockKind::Spoiler),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Quote(_) => TagKind::Block(BlockKind::Quote),
+            #[cfg(feature = "tag-blocks")]
+            Tag::If(_) | Tag::Else => TagKind::Block(BlockKind::If),
+            #[cfg(feature = "tag-blocks")]
+            Tag::List { .. } => TagKind::Block(BlockKind::List),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Item => TagKind::Block(BlockKind::Item),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Table => TagKind::Block(BlockKind::Table),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Row { .. } => TagKind::Block(BlockKind::Row),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Cell => TagKind::Block(BlockKind::Cell),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Close(kind) => TagKind::Block(*kind),
+        }
+    }
+
+    /// The numeric id of mentions, articles and quotes, `None` for other tags and keys.
+    ///
+    /// ```
+    /// use papyrus_lib::Tag;
+    ///
+    /// assert_eq!(Tag::Article(9).id(), Some(9));
+    /// assert_eq!(Tag::UserKey("alice".to_string()).id(), None);
+    /// ```
+    pub fn id(&self) -> Option<u64> {
+        match self {
+            Tag::User(id) | Tag::Article(id) => Some(*id as u64),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Quote(id) => Some(*id as u64),
+            _ => None,
+        }
+    }
+}
+
+/// Which tags a context allows, and how many of them, see [`check`].
+///
+/// ```
+/// use papyrus_lib::{TagKind, TagPolicy};
+///
+/// let comments = TagPolicy::allow([TagKind::User]).cap(TagKind::User, 10);
+/// let wiki = TagPolicy::default();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TagPolicy {
+    kinds: Kinds,
+    caps: Vec<(TagKind, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kinds {
+    Allow(Vec<TagKind>),
+    Deny(Vec<TagKind>),
+}
+
+impl Default for Kinds {
+    fn default() -> Self {
+        Self::Deny(Vec::new())
+    }
+}
+
+impl TagPolicy {
+    /// Allows only tags of the given kinds.
+    pub fn allow<I: IntoIterator<Item = TagKind>>(kinds: I) -> Self {
+        Self {
+            kinds: Kinds::Allow(kinds.into_iter().collect()),
+            caps: Vec::new(),
+        }
+    }
+
+    /// Allows tags of every kind but the given ones.
+    pub fn deny<I: IntoIterator<Item = TagKind>>(kinds: I) -> Self {
+        Self {
+            kinds: Kinds::Deny(kinds.into_iter().collect()),
+            caps: Vec::new(),
+        }
+    }
+
+    /// Allows at most `max` tags of `kind`, replacing its previous cap.
+    pub fn cap(mut self, kind: TagKind, max: usize) -> Self {
+        self.caps.retain(|(capped, _)| *capped != kind);
+        self.caps.push((kind, max));
+        self
+    }
+
+    /// Whether tags of `kind` are allowed at all, regardless of caps.
+    pub fn allows(&self, kind: &TagKind) -> bool {
+        match &self.kinds {
+            Kinds::Allow(kinds) => kinds.contains(kind),
+            Kinds::Deny(kinds) => !kinds.contains(kind),
+        }
+    }
+
+    /// The cap of `kind`, if it has one.
+    pub fn max(&self, kind: &TagKind) -> Option<usize> {
+        self.caps
+            .iter()
+            .find(|(capped, _)| capped == kind)
+            .map(|(_, max)| *max)
+    }
+}
+
+/// The rule of a [`TagPolicy`] a tag breaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolicyRule {
+    /// Its kind isn't allowed.
+    NotAllowed,
+    /// There are more tags of its kind than the cap allows, and this is one of the extra ones.
+    OverCap(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub tag: Tag,
+    /// The span of the tag in the serialized tokens, like [`walk`] gives.
+    pub span: Span,
+    pub rule: PolicyRule,
+}
+
+/// Finds every tag `policy` doesn't allow, in order.
+///
+/// Disallowed tags don't count towards caps, and of tags over a cap, the ones after the
+/// first `max` violate it. Closers and `[else]` are allowed like their blocks, but don't
+/// count.
+pub fn check<'a, S: TokenSource<'a>>(tokens: S, policy: &TagPolicy) -> Vec<PolicyViolation> {
+    let mut checker = Checker {
+        policy,
+        counts: Vec::new(),
+        violations: Vec::new(),
+    };
+    walk(tokens, &mut checker);
+    checker.violations
+}
+
+struct Checker<'p> {
+    policy: &'p TagPolicy,
+    counts: Vec<(TagKind, usize)>,
+    violations: Vec<PolicyViolation>,
+}
+
+impl TokenVisitor for Checker<'_> {
+    fn visit_tag(&mut self, tag: &Tag, span: Span) {
+        let kind = tag.kind();
+        // Else and closing tags are part of the block they're in, so they aren't counted.
+        #[cfg(feature = "tag-blocks")]
+        let counted = !matches!(tag, Tag::Else | Tag::Close(_));
+        #[cfg(not(feature = "tag-blocks"))]
+        let counted = true;
+        let rule = if !self.policy.allows(&kind) {
+            Some(PolicyRule::NotAllowed)
+        } else if let Some(max) = self.policy.max(&kind).filter(|_| counted) {
+            let count = match self.counts.iter_mut().find(|(counted, _)| *counted == kind) {
+                Some((_, count)) => count,
+                None => {
+                    self.counts.push((kind, 0));
+                    &mut self.counts.last_mut().unwrap().1
+                }
+            };
+            *count += 1;
+            (*count > max).then_some(PolicyRule::OverCap(max))
+        } else {
+            None
+        };
+        if let Some(rule) = rule {
+            self.violations.push(PolicyViolation {
+                tag: tag.clone(),
+                span,
+                rule,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::tokenize, *};
+
+    fn comments() -> TagPolicy {
+        TagPolicy::allow([TagKind::User]).cap(TagKind::User, 2)
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn every_tag_has_kind_and_id() {
+        let key = || "k".to_string();
+        let tags = [
+            (Tag::User(1), TagKind::User, Some(1)),
+            (Tag::UserKey(key()), TagKind::User, None),
+            (Tag::Article(2), TagKind::Article, Some(2)),
+            (Tag::ArticleKey(key()), TagKind::Article, None),
+            (Tag::Link(key()), TagKind::Link, None),
+            (Tag::Var(key()), TagKind::Var, None),
+            (
+                Tag::Custom {
+                    name: key(),
+                    value: key(),
+                },
+                TagKind::Custom(key()),
+                None,
+            ),
+            (Tag::Spoiler, TagKind::Block(BlockKind::Spoiler), None),
+            (Tag::Quote(3), TagKind::Block(BlockKind::Quote), Some(3)),
+            (Tag::If(key()), TagKind::Block(BlockKind::If), None),
+            (Tag::Else, TagKind::Block(BlockKind::If), None),
+            (
+                Tag::List { ordered: true },
+                TagKind::Block(BlockKind::List),
+                None,
+            ),
+            (Tag::Item, TagKind::Block(BlockKind::Item), None),
+            (Tag::Table, TagKind::Block(BlockKind::Table), None),
+            (
+                Tag::Row { header: true },
+                TagKind::Block(BlockKind::Row),
+                None,
+            ),
+            (Tag::Cell, TagKind::Block(BlockKind::Cell), None),
+            (
+                Tag::Close(BlockKind::Quote),
+                TagKind::Block(BlockKind::Quote),
+                None,
+            ),
+        ];
+        for (tag, kind, id) in tags {
+            // Stops compiling when a variant is added, which then belongs in the list above.
+            match tag {
+                Tag::User(_)
+                | Tag::UserKey(_)
+                | Tag::Article(_)
+                | Tag::ArticleKey(_)
+                | Tag::Link(_)
+                | Tag::Var(_)
+                | Tag::Custom { .. }
+                | Tag::Spoiler
+                | Tag::Quote(_)
+                | Tag::If(_)
+                | Tag::Else
+                | Tag::List { .. }
+                | Tag::Item
+                | Tag::Table
+                | Tag::Row { .. }
+                | Tag::Cell
+                | Tag::Close(_) => {}
+            }
+            assert_eq!((tag.kind(), tag.id()), (kind, id), "{tag}");
+        }
+    }
+
+    #[test]
+    pub fn check_disallowed() {
+        let input = "see [article:3], [user:1]";
+        assert_eq!(
+            check(&tokenize(input), &comments()),
+            [PolicyViolation {
+                tag: Tag::Article(3),
+                span: Span::new(4, 15),
+                rule: PolicyRule::NotAllowed,
+            }]
+        );
+        assert!(check(&tokenize(input), &TagPolicy::default()).is_empty());
+        let violations = check(&tokenize(input), &TagPolicy::deny([TagKind::User]));
+        assert_eq!(violations[0].tag, Tag::User(1));
+    }
+
+    #[test]
+    pub fn check_caps() {
+        let input = "[user:1] [article:1] [user:2] [user:1] [user:3]";
+        let violations = check(&tokenize(input), &comments());
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| (&input[v.span.range()], v.rule))
+                .collect::<Vec<_>>(),
+            [
+                ("[article:1]", PolicyRule::NotAllowed),
+                ("[user:1]", PolicyRule::OverCap(2)),
+                ("[user:3]", PolicyRule::OverCap(2)),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn check_compliant() {
+        let tokens = tokenize("thanks [user:1] and [user:2]!");
+        assert_eq!(check(&tokens, &comments()), []);
+    }
+}
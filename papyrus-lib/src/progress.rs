@@ -0,0 +1,142 @@
+#[cfg(feature = "std")]
+use std::io::BufRead;
+
+#[cfg(feature = "std")]
+use crate::ReadTokenIter;
+use crate::{ByteTokenIter, TokenIter};
+
+/// A token stream that knows how much of its input it read, see [`progress`].
+pub trait BytesRead {
+    fn bytes_read(&self) -> usize;
+}
+
+impl<T> BytesRead for TokenIter<'_, T> {
+    /// How much was read from the front.
+    fn bytes_read(&self) -> usize {
+        self.byte_offset()
+    }
+}
+
+impl BytesRead for ByteTokenIter<'_> {
+    fn bytes_read(&self) -> usize {
+        self.offset()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead> BytesRead for ReadTokenIter<R> {
+    fn bytes_read(&self) -> usize {
+        self.offset()
+    }
+}
+
+/// A token stream reporting its progress, see [`progress`].
+#[derive(Clone, Debug)]
+pub struct Progress<I, F> {
+    tokens: I,
+    hook: F,
+    every: usize,
+    total: Option<usize>,
+    // The bytes after which the hook is called next.
+    next: usize,
+    done: bool,
+}
+
+/// Calls `hook` with the bytes of input read so far and the `total` expected, each time
+/// another `every` bytes were read, and a last time once the tokens end.
+///
+/// Checking takes a comparison per token, so the hook is the only cost worth considering.
+///
+/// Panics if `every` is 0.
+///
+/// ```
+/// use papyrus_lib::{progress, TokenIter};
+///
+/// let input = "[user:1] hi ".repeat(1000);
+/// let mut done = 0;
+/// let tokens = progress(TokenIter::new(&input), 4096, Some(input.len()), |bytes, total| {
+///     eprintln!("{bytes} of {} bytes", total.unwrap());
+///     done = bytes;
+/// });
+/// assert_eq!(tokens.count(), 2000);
+/// assert_eq!(done, input.len());
+/// ```
+pub fn progress<I, F>(tokens: I, every: usize, total: Option<usize>, hook: F) -> Progress<I, F>
+where
+    I: Iterator + BytesRead,
+    F: FnMut(usize, Option<usize>),
+{
+    assert!(
+        every > 0,
+        "progress has to be reported every 1 or more bytes"
+    );
+    Progress {
+        tokens,
+        hook,
+        every,
+        total,
+        next: every,
+        done: false,
+    }
+}
+
+impl<I, F> Iterator for Progress<I, F>
+where
+    I: Iterator + BytesRead,
+    F: FnMut(usize, Option<usize>),
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.tokens.next();
+        let bytes = self.tokens.bytes_read();
+        if item.is_none() {
+            if !self.done {
+                self.done = true;
+                (self.hook)(bytes, self.total);
+            }
+        } else if bytes >= self.next {
+            (self.hook)(bytes, self.total);
+            self.next = (bytes / self.every + 1) * self.every;
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.tokens.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn progress_reported() {
+        let input = "see [user:1] and [article:22]\n".repeat(1000);
+        let mut calls = Vec::new();
+        let mut tokens = progress(TokenIter::new(&input), 1000, Some(input.len()), |b, t| {
+            calls.push((b, t))
+        });
+        assert_eq!(tokens.by_ref().count(), 4001);
+        assert_eq!(tokens.next(), None);
+        drop(tokens);
+
+        assert!(calls.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert!(calls.iter().all(|(_, total)| *total == Some(input.len())));
+        assert_eq!(calls.last(), Some(&(input.len(), Some(input.len()))));
+        // Every 1000 bytes, and once more at the end.
+        assert_eq!(calls.len(), input.len() / 1000 + 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    pub fn progress_of_reader() {
+        let input = "[user:1] ".repeat(100);
+        let mut calls = Vec::new();
+        let tokens = ReadTokenIter::new(input.as_bytes());
+        let count = progress(tokens, 450, None, |b, t| calls.push((b, t))).count();
+        assert_eq!(count, 200);
+        assert_eq!(calls, [(450, None), (900, None), (900, None)]);
+    }
+}
@@ -0,0 +1,121 @@
+//! Invariants of the tokenizer that hold for every input.
+
+use proptest::prelude::*;
+
+use crate::*;
+
+// Shrinks to few pieces, so counterexamples stay readable.
+fn markup() -> impl Strategy<Value = String> {
+    let piece = prop_oneof![
+        Just("["),
+        Just("]"),
+        Just(":"),
+        Just("\\"),
+        Just("\n"),
+        Just(" "),
+        Just("1"),
+        Just("42"),
+        Just("user"),
+        Just("article"),
+        Just("é"),
+        Just("🦀"),
+        Just("a"),
+    ];
+    prop::collection::vec(piece, 0..32).prop_map(|pieces| pieces.concat())
+}
+
+fn options() -> impl Strategy<Value = TokenizerOptions> {
+    any::<bool>().prop_map(|emit_breaks| TokenizerOptions {
+        emit_breaks,
+        ..Default::default()
+    })
+}
+
+proptest! {
+    #[test]
+    fn never_panics(input in any::<String>(), options in options()) {
+        TokenIter::with_options(&*input, options).for_each(drop);
+        TokenIter::with_options(&*input, options).borrowed().rev().for_each(drop);
+    }
+
+    #[test]
+    fn lossy_roundtrip(input in markup()) {
+        let tokens = tokenize_lossy(&input);
+        let doc = Document::parse(&to_source(&tokens));
+        prop_assert_eq!(doc.map(Document::into_tokens), Ok(normalize(tokens)));
+    }
+
+    #[test]
+    fn rendered_html_roundtrip(input in markup(), options in options()) {
+        // Malformed tags kept as text, like `tokenize_lossy` does.
+        let mut iter = TokenIter::with_options(&*input, options);
+        let mut tokens = Vec::new();
+        loop {
+            let start = iter.byte_offset();
+            match iter.next() {
+                Some(Ok(token)) => tokens.push(token),
+                Some(Err(_)) => tokens.push(Token::Text(input[start..iter.byte_offset()].into())),
+                None => break,
+            }
+        }
+        let resolver = |tag: &Tag| match tag {
+            Tag::User(1) => Some(Resolved { label: "<[user:2]>".into(), url: Some("/u/1".into()) }),
+            Tag::Article(_) => Some(Resolved { label: "a & b".into(), url: None }),
+            _ => None,
+        };
+        let html = render_html(&tokens, &resolver);
+        prop_assert_eq!(from_rendered_html(&html), Ok(normalize(tokens)));
+    }
+
+    #[test]
+    fn lexemes_reproduce_input(input in markup(), options in options()) {
+        let tokenizer = Tokenizer::builder().emit_breaks(options.emit_breaks).build();
+        let source = tokenizer
+            .lex(&input)
+            .map(|(_, span)| &input[span.start..span.end])
+            .collect::<String>();
+        prop_assert_eq!(source, input);
+    }
+
+    #[test]
+    fn spans_cover_input(input in markup(), options in options()) {
+        let mut iter = TokenIter::with_options(&*input, options);
+        let mut end = 0;
+        loop {
+            prop_assert_eq!(iter.byte_offset(), end);
+            if iter.next().is_none() {
+                break;
+            }
+            prop_assert!(iter.byte_offset() > end);
+            end = iter.byte_offset();
+        }
+        prop_assert_eq!(end, input.len());
+
+        let spans = Document::parse_lossy(&input).spans().to_vec();
+        prop_assert_eq!(spans.first().map_or(0, |span| span.start), 0);
+        for pair in spans.windows(2) {
+            prop_assert_eq!(pair[0].end, pair[1].start);
+        }
+        prop_assert_eq!(spans.last().map_or(0, |span| span.end), input.len());
+    }
+
+    #[test]
+    fn positions_non_decreasing(input in markup(), options in options()) {
+        let mut iter = TokenIter::with_options(&*input, options);
+        let mut line = 1;
+        loop {
+            let position = iter.scanner.position();
+            prop_assert!(position.line >= line);
+            let before = &input[..iter.byte_offset()];
+            prop_assert_eq!(usize::from(position.line()), 1 + before.matches('\n').count());
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            prop_assert_eq!(position.column() as usize, 1 + before[line_start..].chars().count());
+            line = position.line;
+            match iter.next() {
+                Some(Err(e)) => prop_assert_eq!(e.position(), position),
+                Some(Ok(_)) => {}
+                None => break,
+            }
+        }
+    }
+}
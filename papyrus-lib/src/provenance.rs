@@ -0,0 +1,229 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{Span, Token};
+
+/// Where a [`TracedToken`] came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+    /// Parsed from the span of the serialized tokens, like [`crate::walk`] gives.
+    Source(Span),
+    /// Made by the named pass of a [`Pipeline`](crate::Pipeline), from a token of the given
+    /// origin.
+    Transform {
+        name: &'static str,
+        from: Box<Origin>,
+    },
+}
+
+impl Origin {
+    /// The span of the source token it all started with.
+    pub fn source(&self) -> Span {
+        let mut origin = self;
+        loop {
+            match origin {
+                Self::Source(span) => return *span,
+                Self::Transform { from, .. } => origin = from,
+            }
+        }
+    }
+
+    /// The names of the passes the token went through, the last one first.
+    pub fn transforms(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let mut origin = self;
+        core::iter::from_fn(move || match origin {
+            Self::Source(_) => None,
+            Self::Transform { name, from } => {
+                origin = from;
+                Some(*name)
+            }
+        })
+    }
+
+    fn by(&self, name: &'static str) -> Self {
+        Self::Transform {
+            name,
+            from: Box::new(self.clone()),
+        }
+    }
+}
+
+/// A token with where it came from, for following tokens through a
+/// [`Pipeline::apply_traced`](crate::Pipeline::apply_traced).
+///
+/// Text merged from several tokens has the origin of the first of them.
+///
+/// ```
+/// use papyrus_lib::{Autolink, Document, Origin, Pipeline, Span, Tag, Token};
+///
+/// let doc = Document::parse("[user:1] see https://a.com").unwrap();
+/// let traced = Pipeline::new().then(Autolink).apply_traced(doc.traced_tokens());
+/// assert_eq!(traced[0].origin, Origin::Source(Span::new(0, 8)));
+/// assert_eq!(traced[2].token, Token::Tag(Tag::Link("https://a.com".to_string())));
+/// assert_eq!(traced[2].origin.transforms().collect::<Vec<_>>(), ["autolink"]);
+/// assert_eq!(traced[2].origin.source(), Span::new(8, 26));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracedToken {
+    pub token: Token,
+    pub origin: Origin,
+}
+
+impl From<(Token, Span)> for TracedToken {
+    fn from((token, span): (Token, Span)) -> Self {
+        Self {
+            token,
+            origin: Origin::Source(span),
+        }
+    }
+}
+
+// Runs a pass token by token, with `f` pushing what a token becomes. Tokens it leaves as
+// they are keep their origin, the others get one by the pass. The result is normalized.
+pub(crate) fn per_token<F>(
+    tokens: Vec<TracedToken>,
+    name: &'static str,
+    mut f: F,
+) -> Vec<TracedToken>
+where
+    F: FnMut(Token, &mut Vec<Token>),
+{
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut made = Vec::new();
+    for TracedToken { token, origin } in tokens {
+        let before = token.clone();
+        f(token, &mut made);
+        if let [token] = made.as_slice() {
+            if *token == before {
+                out.push(TracedToken {
+                    token: made.pop().unwrap(),
+                    origin,
+                });
+                continue;
+            }
+        }
+        out.extend(made.drain(..).map(|token| TracedToken {
+            token,
+            origin: origin.by(name),
+        }));
+    }
+    normalize(out)
+}
+
+// Matches the tokens a pass returned to the ones it got, in order, for passes that don't
+// trace themselves. Tokens it didn't find among those it got are taken as made by the pass
+// from the token after the last one found.
+pub(crate) fn realign(
+    tokens: Vec<TracedToken>,
+    name: &'static str,
+    apply: impl FnOnce(Vec<Token>) -> Vec<Token>,
+) -> Vec<TracedToken> {
+    let (input, origins): (Vec<_>, Vec<_>) = tokens
+        .into_iter()
+        .map(|traced| (traced.token, traced.origin))
+        .unzip();
+    let output = apply(input.clone());
+    let mut next = 0;
+    output
+        .into_iter()
+        .map(|token| {
+            let found = input[next..].iter().position(|t| *t == token);
+            let origin = match found {
+                Some(offset) => {
+                    next += offset + 1;
+                    origins[next - 1].clone()
+                }
+                // Tokens made from nothing start at the start.
+                None => origins
+                    .get(next)
+                    .or(origins.last())
+                    .map_or(Origin::Source(Span::default()), |origin| origin.by(name)),
+            };
+            TracedToken { token, origin }
+        })
+        .collect()
+}
+
+// `normalize` for traced tokens.
+fn normalize(tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+    let mut out: Vec<TracedToken> = vec![];
+    for traced in tokens {
+        match (&traced.token, out.last_mut()) {
+            (Token::Text(text), _) if text.is_empty() => {}
+            (
+                Token::Text(text),
+                Some(TracedToken {
+                    token: Token::Text(last),
+                    ..
+                }),
+            ) => last.push_str(text),
+            _ => out.push(traced),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn traced(input: &str) -> Vec<TracedToken> {
+        Document::parse(input).unwrap().traced_tokens()
+    }
+
+    fn untraced(tokens: Vec<TracedToken>) -> Vec<Token> {
+        tokens.into_iter().map(|traced| traced.token).collect()
+    }
+
+    #[test]
+    pub fn provenance_autolink() {
+        let input = "hi [user:1], see https://a.com/x and [user:2]";
+        let pipeline = Pipeline::new()
+            .then(Redact {
+                users: vec![2],
+                placeholder: "someone".to_string(),
+            })
+            .then(Autolink);
+        let tokens = traced(input);
+        let out = pipeline.apply_traced(tokens.clone());
+        assert_eq!(untraced(out.clone()), pipeline.apply(untraced(tokens)));
+
+        assert_eq!(out[0].origin, Origin::Source(Span::new(0, 3)));
+        assert_eq!(out[1].origin, Origin::Source(Span::new(3, 11)));
+        assert_eq!(
+            out[3],
+            TracedToken {
+                token: Token::Tag(Tag::Link("https://a.com/x".to_string())),
+                origin: Origin::Transform {
+                    name: "autolink",
+                    from: Box::new(Origin::Source(Span::new(11, 37))),
+                },
+            }
+        );
+        // The redacted mention became text, merged with the text before it before linking.
+        assert_eq!(out[4].token, Token::Text(" and someone".to_string()));
+        assert_eq!(out[4].origin.transforms().collect::<Vec<_>>(), ["autolink"]);
+    }
+
+    #[test]
+    pub fn provenance_of_other_passes() {
+        let tokens: Vec<TracedToken> = vec![
+            (Token::Text("a".to_string()), Span::new(0, 1)),
+            (Token::LineBreak, Span::new(1, 2)),
+            (Token::Tag(Tag::User(1)), Span::new(2, 10)),
+        ]
+        .into_iter()
+        .map(TracedToken::from)
+        .collect();
+        let out = Pipeline::new().then(Flatten).apply_traced(tokens.clone());
+        assert_eq!(out[0].token, Token::Text("a\n".to_string()));
+        assert_eq!(out[0].origin, Origin::Source(Span::new(0, 1)));
+
+        // Passes that don't trace themselves are matched up by their output.
+        let shout = |tokens: Vec<Token>| map_text(tokens, |text| text.to_uppercase());
+        let out = Pipeline::new().then(shout).apply_traced(tokens);
+        assert_eq!(out[0].origin.source(), Span::new(0, 1));
+        assert_eq!(out[0].origin.transforms().count(), 1);
+        assert_eq!(out[1].origin, Origin::Source(Span::new(1, 2)));
+        assert_eq!(out[2].origin, Origin::Source(Span::new(2, 10)));
+    }
+}
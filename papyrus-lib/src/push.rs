@@ -0,0 +1,167 @@
+use alloc::vec::Vec;
+
+use crate::{
+    bytes::token_from_bytes,
+    scan::{Scanned, Scanner},
+    BorrowedToken, Token, TokenizeErr, TokenizerOptions,
+};
+
+/// Tokenizes input that arrives in chunks, yielding each token as soon as it's complete.
+///
+/// Text is yielded once the next tag or break starts, so text or a tag at the end of a
+/// chunk is held back until later chunks or [`PushParser::finish`] complete it.
+pub struct PushParser {
+    buffer: Vec<u8>,
+    scanner: Scanner,
+}
+
+impl Default for PushParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PushParser {
+    pub fn new() -> Self {
+        Self::with_options(TokenizerOptions::default())
+    }
+
+    pub fn with_options(options: TokenizerOptions) -> Self {
+        Self {
+            buffer: Vec::new(),
+            scanner: Scanner::new(options.into()),
+        }
+    }
+
+    /// Appends a chunk of input, returning the tokens it completes.
+    pub fn feed(&mut self, chunk: &str) -> impl Iterator<Item = Result<Token, TokenizeErr>> + '_ {
+        self.push(chunk.as_bytes());
+        core::iter::from_fn(|| self.next_token(false))
+    }
+
+    // Appends bytes that may end within a char, which later chunks complete.
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        let consumed = self.scanner.offset();
+        self.buffer.drain(..consumed);
+        self.scanner.rebase(consumed);
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Ends the input, returning the tokens still pending. A tag not closed by then is an
+    /// [`TokenizeErr::UnclosedTag`].
+    pub fn finish(mut self) -> impl Iterator<Item = Result<Token, TokenizeErr>> {
+        core::iter::from_fn(move || self.next_token(true))
+    }
+
+    pub(crate) fn next_token(&mut self, eof: bool) -> Option<Result<Token, TokenizeErr>> {
+        let bytes = &self.buffer;
+        let (lexeme, position) = self.scanner.next_complete(bytes, eof)?;
+        if matches!(&lexeme, Scanned::Tag(body) if body.end == bytes.len()) {
+            return Some(Err(TokenizeErr::UnclosedTag(position)));
+        }
+        Some(
+            token_from_bytes(self.scanner.config(), bytes, lexeme, position)
+                .map(BorrowedToken::into_owned),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn push(chunks: &[&str]) -> Vec<Result<Token, TokenizeErr>> {
+        let mut parser = PushParser::new();
+        let mut tokens = Vec::new();
+        for chunk in chunks {
+            tokens.extend(parser.feed(chunk));
+        }
+        tokens.extend(parser.finish());
+        tokens
+    }
+
+    #[test]
+    pub fn push_every_split() {
+        let input = "hi [user:12] \\[ü\\] [article: 3]\nbye";
+        let expected = TokenIter::new(input).collect::<Vec<_>>();
+        for split in (0..=input.len()).filter(|i| input.is_char_boundary(*i)) {
+            let (a, b) = input.split_at(split);
+            assert_eq!(push(&[a, b]), expected, "split at {split}");
+        }
+    }
+
+    #[test]
+    pub fn push_split_newlines() {
+        let input = "a\r\nb\r\r\n[user:1]\rc";
+        let options = TokenizerOptions {
+            emit_breaks: true,
+            normalize_newlines: true,
+            ..Default::default()
+        };
+        let expected = TokenIter::with_options(input, options).collect::<Vec<_>>();
+        for split in 0..=input.len() {
+            let mut parser = PushParser::with_options(options);
+            let (a, b) = input.split_at(split);
+            let mut tokens = parser.feed(a).collect::<Vec<_>>();
+            tokens.extend(parser.feed(b));
+            tokens.extend(parser.finish());
+            assert_eq!(tokens, expected, "split at {split}");
+        }
+    }
+
+    #[test]
+    pub fn push_split_blank_lines() {
+        let inputs = [
+            "a\n \nb",
+            "a\n\t \n \n[user:1]\n",
+            "a\r\n \r\nb \n\n  c\r\r\n\rd",
+            "\n\n \n",
+        ];
+        for normalize_newlines in [false, true] {
+            let options = TokenizerOptions {
+                emit_breaks: true,
+                normalize_newlines,
+                ..Default::default()
+            };
+            for input in inputs {
+                let expected = TokenIter::with_options(input, options).collect::<Vec<_>>();
+                for split in 0..=input.len() {
+                    let mut parser = PushParser::with_options(options);
+                    let (a, b) = input.split_at(split);
+                    let mut tokens = parser.feed(a).collect::<Vec<_>>();
+                    tokens.extend(parser.feed(b));
+                    tokens.extend(parser.finish());
+                    assert_eq!(tokens, expected, "{input:?} split at {split}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn push_yields_early() {
+        let mut parser = PushParser::new();
+        assert_eq!(
+            parser.feed("hi [us").collect::<Vec<_>>(),
+            vec![Ok(Token::Text("hi ".to_string()))]
+        );
+        assert_eq!(
+            parser.feed("er:1] there").collect::<Vec<_>>(),
+            vec![Ok(Token::Tag(Tag::User(1)))]
+        );
+        assert_eq!(
+            parser.finish().collect::<Vec<_>>(),
+            vec![Ok(Token::Text(" there".to_string()))]
+        );
+    }
+
+    #[test]
+    pub fn push_dangling_bracket() {
+        assert_eq!(
+            push(&["a\n", "b ["]),
+            vec![
+                Ok(Token::Text("a\nb ".to_string())),
+                Err(TokenizeErr::UnclosedTag(Position::new(2).with_column(3)))
+            ]
+        );
+    }
+}
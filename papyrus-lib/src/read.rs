@@ -0,0 +1,204 @@
+use std::{
+    fmt::Display,
+    io::{self, BufRead},
+};
+
+use crate::{
+    bytes::token_from_bytes, scan::Scanner, BorrowedToken, Token, TokenizeErr, TokenizerOptions,
+};
+
+#[derive(Debug)]
+pub enum ReadErr {
+    Io(io::Error),
+    Tokenize(TokenizeErr),
+}
+
+impl Display for ReadErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadErr::Io(e) => write!(f, "{e}"),
+            ReadErr::Tokenize(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadErr {}
+
+impl From<io::Error> for ReadErr {
+    fn from(e: io::Error) -> Self {
+        ReadErr::Io(e)
+    }
+}
+
+impl From<TokenizeErr> for ReadErr {
+    fn from(e: TokenizeErr) -> Self {
+        ReadErr::Tokenize(e)
+    }
+}
+
+/// Tokenizes a reader incrementally, without reading it into memory first.
+///
+/// Only the token being read is buffered, so memory use is bound by the longest token
+/// rather than the input. Invalid UTF-8 is reported like [`crate::ByteTokenIter`] does.
+pub struct ReadTokenIter<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    scanner: Scanner,
+    eof: bool,
+    // The bytes dropped from the front of `buffer`.
+    dropped: usize,
+}
+
+impl<R: BufRead> ReadTokenIter<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, TokenizerOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: TokenizerOptions) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            scanner: Scanner::new(options.into()),
+            eof: false,
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.dropped + self.scanner.offset()
+    }
+
+    // Reads at least as much as is buffered past the scanner, so that rescanning a token
+    // after each refill stays linear overall.
+    fn fill(&mut self) -> io::Result<()> {
+        let want = (self.buffer.len() - self.scanner.offset()).max(1);
+        let mut read = 0;
+        while read < want {
+            let chunk = match self.reader.fill_buf() {
+                Ok(chunk) => chunk,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if chunk.is_empty() {
+                self.eof = true;
+                break;
+            }
+            let len = chunk.len();
+            self.buffer.extend_from_slice(chunk);
+            self.reader.consume(len);
+            read += len;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for ReadTokenIter<R> {
+    type Item = Result<Token, ReadErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let consumed = self.scanner.offset();
+        if consumed > self.buffer.len() / 2 {
+            self.buffer.drain(..consumed);
+            self.scanner.rebase(consumed);
+            self.dropped += consumed;
+        }
+
+        loop {
+            if let Some((lexeme, position)) = self.scanner.next_complete(&self.buffer, self.eof) {
+                return Some(
+                    token_from_bytes(self.scanner.config(), &self.buffer, lexeme, position)
+                        .map(BorrowedToken::into_owned)
+                        .map_err(ReadErr::Tokenize),
+                );
+            }
+            if self.eof {
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                return Some(Err(ReadErr::Io(e)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufReader, Cursor, Read};
+
+    use crate::*;
+
+    const CORPUS: [&str; 9] = [
+        "[user:0]",
+        "\n[unknown]",
+        "hello [user:1] wörld 🦀, see [article: 22]\n",
+        "a\n\n  \n[user:1]\nb\n \n",
+        "unterminated [user:1",
+        "ü\\[x\\] C:\\ \\\\ [usr:1] [user:x] ]",
+        "[x]\n[user:\n1]\n[y]",
+        "long text without any tags that spans many buffers of the reader",
+        "",
+    ];
+
+    #[test]
+    pub fn read_matches_in_memory() {
+        for input in CORPUS {
+            for emit_breaks in [false, true] {
+                let options = TokenizerOptions {
+                    emit_breaks,
+                    ..Default::default()
+                };
+                let reader = BufReader::with_capacity(7, Cursor::new(input));
+                assert_eq!(
+                    ReadTokenIter::with_options(reader, options)
+                        .map(|t| t.map_err(|e| match e {
+                            ReadErr::Tokenize(e) => e,
+                            ReadErr::Io(e) => panic!("{e}"),
+                        }))
+                        .collect::<Vec<_>>(),
+                    TokenIter::with_options(input, options).collect::<Vec<_>>(),
+                    "{input:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn read_breaks_across_buffers() {
+        for input in ["a\n \nb", "a\r\n\t\r\n \n[user:1]", "a\r \rb\n\n \n"] {
+            for normalize_newlines in [false, true] {
+                let options = TokenizerOptions {
+                    emit_breaks: true,
+                    normalize_newlines,
+                    ..Default::default()
+                };
+                let expected = TokenIter::with_options(input, options)
+                    .map(|t| t.unwrap())
+                    .collect::<Vec<_>>();
+                for capacity in 1..=input.len() {
+                    let reader = BufReader::with_capacity(capacity, Cursor::new(input));
+                    let tokens = ReadTokenIter::with_options(reader, options)
+                        .map(|t| t.unwrap())
+                        .collect::<Vec<_>>();
+                    assert_eq!(tokens, expected, "{input:?} read {capacity} at a time");
+                }
+            }
+        }
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    pub fn read_io_error() {
+        let reader = BufReader::new(Cursor::new("hi [user:1]").chain(Failing));
+        let tokens = ReadTokenIter::new(reader).take(3).collect::<Vec<_>>();
+        assert!(matches!(&tokens[0], Ok(Token::Text(t)) if t == "hi "));
+        assert!(matches!(&tokens[1], Ok(Token::Tag(Tag::User(1)))));
+        assert!(matches!(&tokens[2], Err(ReadErr::Io(e)) if e.to_string() == "disk on fire"));
+    }
+}
@@ -0,0 +1,940 @@
+#[cfg(feature = "tag-blocks")]
+use alloc::vec;
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use core::{
+    fmt::{Display, Write},
+    ops::Range,
+};
+
+#[cfg(feature = "tag-blocks")]
+use crate::Node;
+use crate::{meter, trace::span, walk, ParseTag, Span, Tag, Token, TokenSource, TokenVisitor};
+
+/// What a tag renders as.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resolved {
+    pub label: String,
+    pub url: Option<String>,
+}
+
+/// Looks up the label and link of tags while rendering.
+pub trait Resolver<T = Tag> {
+    fn resolve(&self, tag: &T) -> Option<Resolved>;
+
+    /// Whether a render may reuse what a tag resolved to for the same tag later on, so each
+    /// tag is looked up once per render. Resolvers whose result depends on the occurrence
+    /// return `false` to be asked every time.
+    fn memoize(&self) -> bool {
+        true
+    }
+}
+
+impl<T, F: Fn(&T) -> Option<Resolved>> Resolver<T> for F {
+    fn resolve(&self, tag: &T) -> Option<Resolved> {
+        self(tag)
+    }
+}
+
+/// How a type of tag renders, see [`render_html`].
+///
+/// Tags are ordered so a render can look up each distinct tag once.
+pub trait RenderTag: ParseTag + Ord {
+    /// The class of the tag's element, like `mention`.
+    fn class(&self) -> &str;
+
+    /// The name and value of the data attribute identifying the tag.
+    fn data(&self) -> (&str, &dyn Display);
+
+    /// What the label is prefixed with, like the `@` of mentions.
+    fn prefix(&self) -> &str {
+        ""
+    }
+
+    /// What the tag renders as when the resolver doesn't know it.
+    fn fallback(&self) -> Option<Resolved> {
+        None
+    }
+}
+
+impl RenderTag for Tag {
+    fn class(&self) -> &str {
+        match self {
+            Tag::User(_) | Tag::UserKey(_) => "mention",
+            Tag::Article(_) | Tag::ArticleKey(_) => "article",
+            Tag::Custom { .. } => "tag",
+            Tag::Link(_) => "link",
+            Tag::Var(_) => "var",
+            #[cfg(feature = "tag-blocks")]
+            Tag::If(_)
+            | Tag::Else
+            | Tag::List { .. }
+            | Tag::Item
+            | Tag::Table
+            | Tag::Row { .. }
+            | Tag::Cell
+            | Tag::Close(_) => "tag",
+            #[cfg(feature = "tag-blocks")]
+            Tag::Spoiler => "spoiler",
+            #[cfg(feature = "tag-blocks")]
+            Tag::Quote(_) => "quote",
+        }
+    }
+
+    fn data(&self) -> (&str, &dyn Display) {
+        match self {
+            Tag::User(id) => ("data-user-id", id),
+            Tag::Article(id) => ("data-article-id", id),
+            Tag::UserKey(key) => ("data-user-id", key),
+            Tag::ArticleKey(key) => ("data-article-id", key),
+            Tag::Link(url) => ("data-url", url),
+            Tag::Var(name) => ("data-var", name),
+            Tag::Custom { .. } => ("data-tag", self),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Quote(id) => ("data-article-id", id),
+            #[cfg(feature = "tag-blocks")]
+            Tag::Spoiler
+            | Tag::If(_)
+            | Tag::Else
+            | Tag::List { .. }
+            | Tag::Item
+            | Tag::Table
+            | Tag::Row { .. }
+            | Tag::Cell
+            | Tag::Close(_) => ("data-tag", self),
+        }
+    }
+
+    fn prefix(&self) -> &str {
+        match self {
+            Tag::User(_) | Tag::UserKey(_) => "@",
+            _ => "",
+        }
+    }
+
+    // Links are clickable without a resolver knowing them.
+    fn fallback(&self) -> Option<Resolved> {
+        match self {
+            Tag::Link(url) => Some(Resolved {
+                label: url.clone(),
+                url: Some(url.clone()),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Renders tokens as inline HTML.
+///
+/// Mentions become `<a class="mention" data-user-id="…">`, articles
+/// `<a class="article" data-article-id="…">`, links `<a class="link" data-url="…">`, variables
+/// `<a class="var" data-var="…">` and
+/// custom tags `<a class="tag" data-tag="…">`, or `<span>`s when they have no url. Tags the
+/// resolver doesn't know keep their markup as label and get an additional `unresolved` class.
+/// Other tag types than [`Tag`] render as their [`RenderTag`] implementation says.
+///
+/// Line breaks render as a `<br>` and paragraph breaks as two, so the HTML can go inside any
+/// element.
+///
+/// ```
+/// use papyrus_lib::{render_html, Tag, Token};
+///
+/// let tokens = [Token::Text("one".into()), Token::ParagraphBreak, Token::Text("two".into())];
+/// assert_eq!(render_html(&tokens, &|_: &Tag| None), "one<br>\n<br>\ntwo");
+/// ```
+pub fn render_html<'a, T, S, R>(tokens: S, resolver: &R) -> String
+where
+    T: RenderTag,
+    S: TokenSource<'a, T>,
+    R: Resolver<T> + ?Sized,
+{
+    let trace = span!("render_html"; output_len);
+    meter::document("render_html", None);
+    let mut out = String::new();
+    write_html(tokens, resolver, &mut out).unwrap();
+    trace.record("output_len", out.len());
+    out
+}
+
+/// What [`render_html_with`] adds to the HTML of [`render_html`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HtmlOptions {
+    /// Fills [`RenderedHtml::source_map`].
+    pub source_map: bool,
+    /// Gives tag elements a `data-papyrus-span="start..end"` attribute with the span of their
+    /// tag.
+    pub span_attributes: bool,
+    /// Gives tag elements an `id` for deep links, like `mention-user-5-3` for the third
+    /// mention of user 5, see [`RenderedHtml::anchors`].
+    pub anchors: bool,
+}
+
+/// HTML rendered by [`render_html_with`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenderedHtml {
+    pub html: String,
+    /// With [`HtmlOptions::source_map`], the range of `html` each token rendered as, with the
+    /// span of the token in the serialized tokens, like [`walk`] gives, in order.
+    pub source_map: Vec<(Range<usize>, Span)>,
+    /// With [`HtmlOptions::anchors`], the ids given to tag elements, in order.
+    ///
+    /// An id is the class of the element and the tag, with anything but ASCII alphanumerics,
+    /// `-` and `_` replaced by `-`, numbered by how many elements got that id so far. The
+    /// same tokens always get the same ids.
+    pub anchors: Vec<String>,
+}
+
+/// Like [`render_html`], adding what `options` asks for.
+///
+/// ```
+/// use papyrus_lib::{render_html_with, tokenize_lossy, HtmlOptions, Span};
+///
+/// let options = HtmlOptions {
+///     source_map: true,
+///     ..Default::default()
+/// };
+/// let rendered = render_html_with(&tokenize_lossy("a&b"), &|_: &_| None, options);
+/// assert_eq!(rendered.html, "a&amp;b");
+/// assert_eq!(rendered.source_map, [(0..7, Span::new(0, 3))]);
+/// ```
+pub fn render_html_with<'a, T, S, R>(tokens: S, resolver: &R, options: HtmlOptions) -> RenderedHtml
+where
+    T: RenderTag,
+    S: TokenSource<'a, T>,
+    R: Resolver<T> + ?Sized,
+{
+    let trace = span!("render_html"; output_len);
+    meter::document("render_html", None);
+    let mut renderer = HtmlRenderer::new(resolver, String::new(), options);
+    walk(tokens, &mut renderer);
+    trace.record("output_len", renderer.out.len());
+    RenderedHtml {
+        html: renderer.out,
+        source_map: renderer.source_map,
+        anchors: renderer.anchors,
+    }
+}
+
+/// Renders nodes as inline HTML like [`render_html`], with spoilers as
+/// `<details class="spoiler"><summary>Spoiler</summary>…</details>` and quotes as
+/// `<blockquote class="quote" data-article-id="…">…</blockquote>`, lists as `<ul>` or `<ol>` of
+/// `<li>`s and tables as `<table>` of `<tr>`s, with `<th>` cells in header rows and `<td>` ones
+/// in others. Conditional blocks are
+/// meant to be [`evaluate`](crate::evaluate)d first, others render their content as it is.
+#[cfg(feature = "tag-blocks")]
+pub fn render_tree_html<R: Resolver + ?Sized>(nodes: &[Node], resolver: &R) -> String {
+    let trace = span!("render_tree_html", nodes = nodes.len(); output_len);
+    meter::document("render_tree_html", None);
+    let mut renderer = HtmlRenderer::new(resolver, String::new(), HtmlOptions::default());
+    renderer.nodes(nodes);
+    trace.record("output_len", renderer.out.len());
+    renderer.out
+}
+
+/// Tokens whose `Display` is their HTML rendering, see [`render_html`].
+///
+/// This lets templates embed rendered markup without an intermediate `String`, the HTML is
+/// written to the formatter as it's rendered. With the `askama` feature it's marked as safe,
+/// so Askama doesn't escape the HTML again.
+///
+/// ```
+/// use papyrus_lib::{Document, Tag};
+///
+/// let doc = Document::parse("hi [user:1] & co").unwrap();
+/// let resolver = |_: &Tag| None;
+/// let rendered = doc.renderable(&resolver);
+/// assert_eq!(format!("<p>{rendered}</p>"), "<p>hi <span class=\"mention unresolved\" data-user-id=\"1\">[user:1]</span> &amp; co</p>");
+/// assert_eq!(rendered.as_plain().to_string(), "hi [user:1] & co");
+/// ```
+pub struct Rendered<'a, R: ?Sized, T = Tag> {
+    tokens: &'a [Token<T>],
+    resolver: &'a R,
+}
+
+impl<'a, T: RenderTag, R: Resolver<T> + ?Sized> Rendered<'a, R, T> {
+    pub fn new(tokens: &'a [Token<T>], resolver: &'a R) -> Self {
+        Self { tokens, resolver }
+    }
+
+    /// The same tokens with their plain text rendering as `Display`, see [`render_plain`].
+    pub fn as_plain(&self) -> RenderedPlain<'a, R, T> {
+        RenderedPlain {
+            tokens: self.tokens,
+            resolver: self.resolver,
+        }
+    }
+}
+
+impl<'a, T: RenderTag, R: Resolver<T> + ?Sized> Display for Rendered<'a, R, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_html(self.tokens, self.resolver, f)
+    }
+}
+
+#[cfg(feature = "askama")]
+impl<'a, T: RenderTag, R: Resolver<T> + ?Sized> askama::filters::HtmlSafe for Rendered<'a, R, T> {}
+
+/// Tokens whose `Display` is their plain text rendering, from [`Rendered::as_plain`].
+pub struct RenderedPlain<'a, R: ?Sized, T = Tag> {
+    tokens: &'a [Token<T>],
+    resolver: &'a R,
+}
+
+impl<'a, T: RenderTag, R: Resolver<T> + ?Sized> Display for RenderedPlain<'a, R, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write_plain(self.tokens, self.resolver, f)
+    }
+}
+
+fn write_html<'a, T, S, R, W>(tokens: S, resolver: &R, out: W) -> core::fmt::Result
+where
+    T: RenderTag,
+    S: TokenSource<'a, T>,
+    R: Resolver<T> + ?Sized,
+    W: Write,
+{
+    let mut renderer = HtmlRenderer::new(resolver, out, HtmlOptions::default());
+    walk(tokens, &mut renderer);
+    renderer.result
+}
+
+/// Renders tokens as plain text, labels prefixed like [`RenderTag::prefix`] says, so
+/// mentions get an `@`.
+pub fn render_plain<'a, T, S, R>(tokens: S, resolver: &R) -> String
+where
+    T: RenderTag,
+    S: TokenSource<'a, T>,
+    R: Resolver<T> + ?Sized,
+{
+    let trace = span!("render_plain"; output_len);
+    meter::document("render_plain", None);
+    let mut out = String::new();
+    write_plain(tokens, resolver, &mut out).unwrap();
+    trace.record("output_len", out.len());
+    out
+}
+
+fn write_plain<'a, T, S, R, W>(tokens: S, resolver: &R, out: W) -> core::fmt::Result
+where
+    T: RenderTag,
+    S: TokenSource<'a, T>,
+    R: Resolver<T> + ?Sized,
+    W: Write,
+{
+    let mut renderer = PlainRenderer {
+        lookups: Lookups::new(resolver),
+        out,
+        result: Ok(()),
+    };
+    walk(tokens, &mut renderer);
+    renderer.result
+}
+
+fn escape_html<W: Write + ?Sized>(out: &mut W, text: &str) -> core::fmt::Result {
+    for c in text.chars() {
+        match c {
+            '&' => out.write_str("&amp;")?,
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            '"' => out.write_str("&quot;")?,
+            '\'' => out.write_str("&#39;")?,
+            c => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+// Escapes everything written through it.
+struct Escaped<'w, W: ?Sized>(&'w mut W);
+
+impl<W: Write + ?Sized> Write for Escaped<'_, W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        escape_html(self.0, s)
+    }
+}
+
+// Counts the bytes written through it.
+struct Counted<'w, W> {
+    out: &'w mut W,
+    len: &'w mut usize,
+}
+
+impl<W: Write> Write for Counted<'_, W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.out.write_str(s)?;
+        *self.len += s.len();
+        Ok(())
+    }
+}
+
+// What the tags of one render resolved to, so each is looked up once unless the resolver
+// doesn't `memoize`.
+pub(crate) struct Lookups<'r, R: ?Sized, T> {
+    resolver: &'r R,
+    memoize: Option<bool>,
+    resolved: BTreeMap<T, Option<Resolved>>,
+}
+
+impl<'r, R: ?Sized, T> Lookups<'r, R, T> {
+    pub(crate) fn new(resolver: &'r R) -> Self {
+        Self {
+            resolver,
+            memoize: None,
+            resolved: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'r, R: Resolver<T> + ?Sized, T: Ord + Clone> Lookups<'r, R, T> {
+    pub(crate) fn resolve(&mut self, tag: &T) -> Option<Resolved> {
+        let resolver = self.resolver;
+        if !*self.memoize.get_or_insert_with(|| resolver.memoize()) {
+            return resolver.resolve(tag);
+        }
+        if let Some(resolved) = self.resolved.get(tag) {
+            return resolved.clone();
+        }
+        let resolved = resolver.resolve(tag);
+        self.resolved.insert(tag.clone(), resolved.clone());
+        resolved
+    }
+}
+
+// Visitors can't fail, so the first error of the writer is kept and everything after it
+// skipped.
+struct HtmlRenderer<'r, R: ?Sized, W, T = Tag> {
+    lookups: Lookups<'r, R, T>,
+    out: W,
+    result: core::fmt::Result,
+    options: HtmlOptions,
+    // What was written to `out`.
+    len: usize,
+    source_map: Vec<(Range<usize>, Span)>,
+    anchors: Vec<String>,
+    // How many anchors each id without its number was given.
+    anchor_counts: BTreeMap<String, usize>,
+}
+
+impl<'r, R: ?Sized, W: Write, T> HtmlRenderer<'r, R, W, T> {
+    fn new(resolver: &'r R, out: W, options: HtmlOptions) -> Self {
+        Self {
+            lookups: Lookups::new(resolver),
+            out,
+            result: Ok(()),
+            options,
+            len: 0,
+            source_map: Vec::new(),
+            anchors: Vec::new(),
+            anchor_counts: BTreeMap::new(),
+        }
+    }
+
+    fn write(&mut self, f: impl FnOnce(&mut Counted<W>) -> core::fmt::Result) {
+        if self.result.is_ok() {
+            self.result = f(&mut Counted {
+                out: &mut self.out,
+                len: &mut self.len,
+            });
+        }
+    }
+
+    // The next id of a tag element. Numbers follow the last `-` and are counted per what's
+    // before it, so ids never collide.
+    fn anchor(&mut self, class: &str, tag: &dyn Display) -> String {
+        let mut id = String::new();
+        write!(id, "{class}-{tag}").unwrap();
+        let id: String = id
+            .chars()
+            .map(|c| match c {
+                c if c.is_ascii_alphanumeric() || c == '_' => c,
+                _ => '-',
+            })
+            .collect();
+        let count = self.anchor_counts.entry(id.clone()).or_insert(0);
+        *count += 1;
+        let anchor = format!("{id}-{count}");
+        self.anchors.push(anchor.clone());
+        anchor
+    }
+
+    // Renders a token with `f`, mapping what it wrote to `span` if the options ask for it.
+    fn token(&mut self, span: Span, f: impl FnOnce(&mut Self)) {
+        let start = self.len;
+        f(self);
+        if self.options.source_map {
+            self.source_map.push((start..self.len, span));
+        }
+    }
+
+    fn tag(&mut self, tag: &T, span: Span)
+    where
+        T: RenderTag,
+        R: Resolver<T>,
+    {
+        let resolved = self.lookups.resolve(tag).or_else(|| tag.fallback());
+        let (class, (attribute, value), prefix) = (tag.class(), tag.data(), tag.prefix());
+        let span_attributes = self.options.span_attributes;
+        let anchor = self.options.anchors.then(|| self.anchor(class, tag));
+        self.write(|out| {
+            let url = resolved.as_ref().and_then(|r| r.url.as_deref());
+            let element = if url.is_some() { "a" } else { "span" };
+            let unresolved = if resolved.is_none() {
+                " unresolved"
+            } else {
+                ""
+            };
+
+            write!(out, "<{element}")?;
+            if let Some(anchor) = &anchor {
+                write!(out, " id=\"{anchor}\"")?;
+            }
+            write!(out, " class=\"{class}{unresolved}\" {attribute}=\"")?;
+            write!(Escaped(&mut *out), "{value}")?;
+            out.write_char('"')?;
+            if span_attributes {
+                write!(out, " data-papyrus-span=\"{}..{}\"", span.start, span.end)?;
+            }
+            if let Some(url) = url {
+                out.write_str(" href=\"")?;
+                escape_html(out, url)?;
+                out.write_char('"')?;
+            }
+            out.write_char('>')?;
+            match &resolved {
+                Some(resolved) => {
+                    out.write_str(prefix)?;
+                    escape_html(out, &resolved.label)?;
+                }
+                None => write!(Escaped(&mut *out), "[{tag}]")?,
+            }
+            write!(out, "</{element}>")
+        });
+    }
+}
+
+#[cfg(feature = "tag-blocks")]
+impl<'r, R: Resolver + ?Sized, W: Write> HtmlRenderer<'r, R, W> {
+    // Keeps the blocks being rendered on a stack rather than recursing, so deep trees can't
+    // overflow the call stack.
+    fn nodes(&mut self, nodes: &[Node]) {
+        let span = Span::default();
+        // Per block, the nodes left in it, its closing tag and whether it's a header row.
+        let mut blocks = vec![(nodes.iter(), "", false)];
+        while let Some((nodes, _, header)) = blocks.last_mut() {
+            let header = *header;
+            let Some(node) = nodes.next() else {
+                let (_, close, _) = blocks.pop().unwrap();
+                self.write(|out| out.write_str(close));
+                continue;
+            };
+            let (tag, children) = match node {
+                Node::Text(text) => {
+                    self.visit_text(text, span);
+                    continue;
+                }
+                Node::Tag(tag) => {
+                    self.tag(tag, span);
+                    continue;
+                }
+                Node::Block { tag, children } => (tag, children),
+                Node::LineBreak => {
+                    self.visit_line_break(span);
+                    continue;
+                }
+                Node::ParagraphBreak => {
+                    self.visit_paragraph_break(span);
+                    continue;
+                }
+                Node::Highlight(text) => {
+                    self.visit_highlight(text, span);
+                    continue;
+                }
+            };
+            let (open, close) = match tag {
+                Tag::If(_) => ("", ""),
+                Tag::Quote(id) => {
+                    self.write(|out| {
+                        write!(out, "<blockquote class=\"quote\" data-article-id=\"{id}\">")
+                    });
+                    ("", "</blockquote>")
+                }
+                Tag::List { ordered: false } => ("<ul>", "</ul>"),
+                Tag::List { ordered: true } => ("<ol>", "</ol>"),
+                Tag::Item => ("<li>", "</li>"),
+                Tag::Table => ("<table>", "</table>"),
+                Tag::Row { .. } => ("<tr>", "</tr>"),
+                Tag::Cell if header => ("<th>", "</th>"),
+                Tag::Cell => ("<td>", "</td>"),
+                _ => (
+                    "<details class=\"spoiler\"><summary>Spoiler</summary>",
+                    "</details>",
+                ),
+            };
+            self.write(|out| out.write_str(open));
+            let header = match tag {
+                Tag::Row { header } => *header,
+                // Conditional blocks only hide or show their content.
+                Tag::If(_) => header,
+                _ => false,
+            };
+            blocks.push((children.iter(), close, header));
+        }
+    }
+}
+
+impl<'r, T, R, W> TokenVisitor<T> for HtmlRenderer<'r, R, W, T>
+where
+    T: RenderTag,
+    R: Resolver<T> + ?Sized,
+    W: Write,
+{
+    fn visit_text(&mut self, text: &str, span: Span) {
+        self.token(span, |r| r.write(|out| escape_html(out, text)));
+    }
+
+    fn visit_tag(&mut self, tag: &T, span: Span) {
+        self.token(span, |r| r.tag(tag, span));
+    }
+
+    fn visit_line_break(&mut self, span: Span) {
+        self.token(span, |r| r.write(|out| out.write_str("<br>\n")));
+    }
+
+    fn visit_paragraph_break(&mut self, span: Span) {
+        self.token(span, |r| r.write(|out| out.write_str("<br>\n<br>\n")));
+    }
+
+    fn visit_highlight(&mut self, text: &str, span: Span) {
+        self.token(span, |r| {
+            r.write(|out| {
+                out.write_str("<mark>")?;
+                escape_html(out, text)?;
+                out.write_str("</mark>")
+            })
+        });
+    }
+}
+
+// Like `HtmlRenderer`, keeps the first error of the writer.
+struct PlainRenderer<'r, R: ?Sized, W, T> {
+    lookups: Lookups<'r, R, T>,
+    out: W,
+    result: core::fmt::Result,
+}
+
+impl<'r, T: RenderTag, R: Resolver<T> + ?Sized, W: Write> TokenVisitor<T>
+    for PlainRenderer<'r, R, W, T>
+{
+    fn visit_text(&mut self, text: &str, _span: Span) {
+        if self.result.is_ok() {
+            self.result = self.out.write_str(text);
+        }
+    }
+
+    fn visit_tag(&mut self, tag: &T, _span: Span) {
+        if self.result.is_ok() {
+            let resolved = self.lookups.resolve(tag);
+            self.result = write_plain_tag(&mut self.out, tag, resolved);
+        }
+    }
+}
+
+// A tag the way `render_plain` writes it, given what it resolved to.
+pub(crate) fn write_plain_tag<T, W>(
+    out: &mut W,
+    tag: &T,
+    resolved: Option<Resolved>,
+) -> core::fmt::Result
+where
+    T: RenderTag,
+    W: Write + ?Sized,
+{
+    match resolved.or_else(|| tag.fallback()) {
+        Some(resolved) => {
+            out.write_str(tag.prefix())?;
+            out.write_str(&resolved.label)
+        }
+        None => write!(out, "[{tag}]"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::*, *};
+
+    fn resolver() -> MapResolver {
+        MapResolver::new()
+            .link(Tag::User(1), "alice", "/u/alice?a=1&b=2")
+            .label(Tag::User(2), "<bob>")
+            .link(Tag::Article(9), "Launch day", "/a/9")
+    }
+
+    #[test]
+    pub fn render_html_tags() {
+        assert_eq!(
+            render_html(
+                &tokenize("hi [user:1] & [user:2], read [article:9]"),
+                &resolver()
+            ),
+            "hi <a class=\"mention\" data-user-id=\"1\" href=\"/u/alice?a=1&amp;b=2\">@alice</a> \
+             &amp; <span class=\"mention\" data-user-id=\"2\">@&lt;bob&gt;</span>, read \
+             <a class=\"article\" data-article-id=\"9\" href=\"/a/9\">Launch day</a>"
+        );
+    }
+
+    #[test]
+    pub fn render_html_unresolved() {
+        assert_eq!(
+            render_html(&tokenize("<[user:3]>"), &resolver()),
+            "&lt;<span class=\"mention unresolved\" data-user-id=\"3\">[user:3]</span>&gt;"
+        );
+    }
+
+    #[test]
+    pub fn render_html_custom_tags() {
+        let ticket = Tag::Custom {
+            name: "ticket".to_string(),
+            value: "A&B-1".to_string(),
+        };
+        let resolver = resolver().link(ticket.clone(), "A&B-1", "/t/1");
+        assert_eq!(
+            render_html(&[Token::Tag(ticket)], &resolver),
+            "<a class=\"tag\" data-tag=\"ticket:A&amp;B-1\" href=\"/t/1\">A&amp;B-1</a>"
+        );
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn render_tree_nested() {
+        let tokens = tokenize("[quote:9]a [spoiler]<[user:1]>[/spoiler][/quote]");
+        assert_eq!(
+            render_tree_html(&parse_tree(&tokens).unwrap(), &resolver()),
+            "<blockquote class=\"quote\" data-article-id=\"9\">a <details class=\"spoiler\">\
+             <summary>Spoiler</summary>&lt;<a class=\"mention\" data-user-id=\"1\" \
+             href=\"/u/alice?a=1&amp;b=2\">@alice</a>&gt;</details></blockquote>"
+        );
+    }
+
+    #[test]
+    pub fn render_html_breaks_and_highlights() {
+        let tokens = tokens!["a", line_break, highlight("<b>"), paragraph_break, "c"];
+        assert_eq!(
+            render_html(&tokens, &resolver()),
+            "a<br>\n<mark>&lt;b&gt;</mark><br>\n<br>\nc"
+        );
+    }
+
+    #[test]
+    pub fn render_html_source_map() {
+        let input = "1 < 2, ask [user:2] & [user:1]";
+        let options = HtmlOptions {
+            source_map: true,
+            span_attributes: true,
+            ..Default::default()
+        };
+        let rendered = render_html_with(&tokenize(input), &resolver(), options);
+        let mapped: Vec<_> = rendered
+            .source_map
+            .iter()
+            .map(|(range, span)| (&rendered.html[range.clone()], &input[span.range()]))
+            .collect();
+        assert_eq!(
+            mapped,
+            [
+                ("1 &lt; 2, ask ", "1 < 2, ask "),
+                (
+                    "<span class=\"mention\" data-user-id=\"2\" data-papyrus-span=\"11..19\">@&lt;bob&gt;</span>",
+                    "[user:2]"
+                ),
+                (" &amp; ", " & "),
+                (
+                    "<a class=\"mention\" data-user-id=\"1\" data-papyrus-span=\"22..30\" href=\"/u/alice?a=1&amp;b=2\">@alice</a>",
+                    "[user:1]"
+                ),
+            ]
+        );
+        assert_eq!(
+            rendered.html,
+            mapped.iter().map(|(html, _)| *html).collect::<String>()
+        );
+        let plain = render_html_with(&tokenize(input), &resolver(), HtmlOptions::default());
+        assert_eq!(plain.html, render_html(&tokenize(input), &resolver()));
+        assert!(plain.source_map.is_empty());
+    }
+
+    #[test]
+    pub fn render_html_anchors() {
+        let tokens = tokenize("[user:5] [article:9] [user:5] [user:1] [user:5]");
+        let options = HtmlOptions {
+            anchors: true,
+            ..Default::default()
+        };
+        let rendered = render_html_with(&tokens, &resolver(), options);
+        assert_eq!(
+            rendered.anchors,
+            [
+                "mention-user-5-1",
+                "article-article-9-1",
+                "mention-user-5-2",
+                "mention-user-1-1",
+                "mention-user-5-3",
+            ]
+        );
+        let ids: Vec<_> = rendered
+            .html
+            .split(" id=\"")
+            .skip(1)
+            .map(|rest| &rest[..rest.find('"').unwrap()])
+            .collect();
+        assert_eq!(ids, rendered.anchors);
+        assert!(rendered
+            .html
+            .starts_with("<span id=\"mention-user-5-1\" class=\"mention unresolved\""));
+        assert_eq!(render_html_with(&tokens, &resolver(), options), rendered);
+    }
+
+    #[test]
+    pub fn render_plain_tags() {
+        assert_eq!(
+            render_plain(
+                &tokenize("hi [user:1], [user:3] read [article:9] <3"),
+                &resolver()
+            ),
+            "hi @alice, [user:3] read Launch day <3"
+        );
+    }
+
+    #[test]
+    pub fn rendered_display() {
+        let tokens = tokenize("hi [user:1] & [user:3]");
+        let resolver = resolver();
+        let rendered = Rendered::new(&tokens, &resolver);
+        assert_eq!(
+            format!("<p>{rendered}</p>"),
+            format!("<p>{}</p>", render_html(&tokens, &resolver))
+        );
+        assert_eq!(
+            rendered.as_plain().to_string(),
+            render_plain(&tokens, &resolver)
+        );
+    }
+
+    #[test]
+    pub fn rendered_writes_as_it_goes() {
+        use core::fmt::Write;
+
+        // Records how the output arrives.
+        #[derive(Default)]
+        struct Counter {
+            writes: usize,
+            len: usize,
+            longest: usize,
+        }
+
+        impl Write for Counter {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                self.writes += 1;
+                self.len += s.len();
+                self.longest = self.longest.max(s.len());
+                Ok(())
+            }
+        }
+
+        let doc = Document::parse("hi [user:1] & [user:3], see [article:9]").unwrap();
+        let resolver = resolver();
+        let html = render_html(doc.tokens(), &resolver);
+        let mut counter = Counter::default();
+        write!(counter, "{}", doc.renderable(&resolver)).unwrap();
+        assert_eq!(counter.len, html.len());
+        // Nothing rendered the whole of it first.
+        assert!(
+            counter.writes > 10 && counter.longest < 20,
+            "{}",
+            counter.longest
+        );
+
+        let plain = render_plain(doc.tokens(), &resolver);
+        let mut counter = Counter::default();
+        write!(counter, "{}", doc.renderable(&resolver).as_plain()).unwrap();
+        assert_eq!(counter.len, plain.len());
+        assert!(counter.writes > 5 && counter.longest < plain.len());
+    }
+
+    // Asked about every tag, like a resolver numbering occurrences would want.
+    struct EveryTime<R>(R);
+
+    impl<R: Resolver> Resolver for EveryTime<R> {
+        fn resolve(&self, tag: &Tag) -> Option<Resolved> {
+            self.0.resolve(tag)
+        }
+
+        fn memoize(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    pub fn render_looks_up_every_tag() {
+        let resolver = EveryTime(RecordingResolver::new(resolver()));
+        render_plain(&tokens![user(1), " ", article(9), " ", user(1)], &resolver);
+        assert_eq!(
+            resolver.0.lookups(),
+            [Tag::User(1), Tag::Article(9), Tag::User(1)]
+        );
+    }
+
+    #[test]
+    pub fn render_looks_up_tags_once() {
+        let tokens = tokens![
+            user(1),
+            " ",
+            user(1),
+            user(1),
+            " ",
+            user(1),
+            article(9),
+            user(1)
+        ];
+        let recording = RecordingResolver::new(resolver());
+        let html = render_html(&tokens, &recording);
+        assert_eq!(recording.lookups(), [Tag::User(1), Tag::Article(9)]);
+        assert_eq!(html, render_html(&tokens, &EveryTime(resolver())));
+
+        // Every render looks up on its own.
+        recording.clear();
+        let plain = render_plain(&tokens, &recording);
+        render_plain(&tokens, &recording);
+        assert_eq!(recording.count(), 4);
+        assert_eq!(plain, "@alice @alice@alice @aliceLaunch day@alice");
+    }
+
+    #[cfg(feature = "askama")]
+    #[test]
+    pub fn rendered_askama_not_escaped_twice() {
+        use askama::Template;
+
+        #[derive(Template)]
+        #[template(source = "<p>{{ body }}</p><p>{{ raw }}</p>", ext = "html")]
+        struct Post<'a> {
+            body: Rendered<'a, dyn Resolver + 'a>,
+            raw: String,
+        }
+
+        let tokens = tokenize("a & [user:1]");
+        let resolver = resolver();
+        let post = Post {
+            body: Rendered::new(&tokens, &resolver as &dyn Resolver),
+            raw: render_html(&tokens, &resolver),
+        };
+        let html = render_html(&tokens, &resolver);
+        assert_eq!(
+            post.render().unwrap(),
+            format!(
+                "<p>{html}</p><p>{}</p>",
+                html.replace('&', "&#38;")
+                    .replace('<', "&#60;")
+                    .replace('>', "&#62;")
+                    .replace('"', "&#34;")
+            )
+        );
+    }
+}
@@ -0,0 +1,102 @@
+#[cfg(feature = "tag-blocks")]
+use crate::{tree::ArchivedBlockKind, BlockKind};
+use crate::{ArchivedTagValue, ArchivedToken, Tag, TokenRef};
+
+impl<'a> From<&'a ArchivedTagValue> for Tag {
+    fn from(tag: &'a ArchivedTagValue) -> Self {
+        match tag {
+            ArchivedTagValue::User(id) => Tag::User(*id as usize),
+            ArchivedTagValue::Article(id) => Tag::Article(*id as usize),
+            ArchivedTagValue::Custom { name, value } => Tag::Custom {
+                name: name.to_string(),
+                value: value.to_string(),
+            },
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::Spoiler => Tag::Spoiler,
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::Quote(id) => Tag::Quote(*id as usize),
+            ArchivedTagValue::UserKey(key) => Tag::UserKey(key.to_string()),
+            ArchivedTagValue::ArticleKey(key) => Tag::ArticleKey(key.to_string()),
+            ArchivedTagValue::Link(url) => Tag::Link(url.to_string()),
+            ArchivedTagValue::Var(name) => Tag::Var(name.to_string()),
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::If(condition) => Tag::If(condition.to_string()),
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::Else => Tag::Else,
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::List { ordered } => Tag::List { ordered: *ordered },
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::Item => Tag::Item,
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::Table => Tag::Table,
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::Row { header } => Tag::Row { header: *header },
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::Cell => Tag::Cell,
+            #[cfg(feature = "tag-blocks")]
+            ArchivedTagValue::Close(kind) => Tag::Close(match kind {
+                ArchivedBlockKind::Spoiler => BlockKind::Spoiler,
+                ArchivedBlockKind::Quote => BlockKind::Quote,
+                ArchivedBlockKind::If => BlockKind::If,
+                ArchivedBlockKind::List => BlockKind::List,
+                ArchivedBlockKind::Item => BlockKind::Item,
+                ArchivedBlockKind::Table => BlockKind::Table,
+                ArchivedBlockKind::Row => BlockKind::Row,
+                ArchivedBlockKind::Cell => BlockKind::Cell,
+            }),
+        }
+    }
+}
+
+impl<'a> From<&'a ArchivedToken> for TokenRef<'a> {
+    fn from(token: &'a ArchivedToken) -> Self {
+        match token {
+            ArchivedToken::Text(text) => TokenRef::Text(text),
+            ArchivedToken::Tag(tag) => TokenRef::Tag(tag.into()),
+            ArchivedToken::LineBreak => TokenRef::LineBreak,
+            ArchivedToken::ParagraphBreak => TokenRef::ParagraphBreak,
+            ArchivedToken::Highlight(text) => TokenRef::Highlight(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn rkyv_render_archived() {
+        let tokens = vec![
+            Token::Text("hi <".to_string()),
+            Token::Tag(Tag::User(1)),
+            Token::LineBreak,
+            Token::Tag(Tag::Article(9)),
+            Token::Highlight("ünï".to_string()),
+        ];
+        let bytes = rkyv::to_bytes::<_, 256>(&tokens).unwrap();
+        let archived = rkyv::check_archived_root::<Vec<Token>>(&bytes).unwrap();
+
+        let resolver = |tag: &Tag| {
+            matches!(tag, Tag::User(1)).then(|| Resolved {
+                label: "alice".to_string(),
+                url: None,
+            })
+        };
+        assert_eq!(
+            render_html(archived.iter(), &resolver),
+            render_html(&tokens, &resolver)
+        );
+    }
+
+    #[test]
+    pub fn rkyv_rejects_invalid_archive() {
+        let tokens = vec![Token::Text("hello".to_string()), Token::Tag(Tag::User(1))];
+        let mut bytes = rkyv::to_bytes::<_, 256>(&tokens).unwrap();
+        assert!(rkyv::check_archived_root::<Vec<Token>>(&bytes[..bytes.len() - 4]).is_err());
+
+        // Corrupt the discriminant of the last token.
+        let last = bytes.len() - 1;
+        bytes.iter_mut().rev().take(24).for_each(|b| *b = 0xff);
+        assert!(rkyv::check_archived_root::<Vec<Token>>(&bytes[..=last]).is_err());
+    }
+}
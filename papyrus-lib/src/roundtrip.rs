@@ -0,0 +1,144 @@
+use alloc::string::{String, ToString};
+
+use crate::{to_source, tokenize_lossy, TokenIter};
+
+/// How much of the input and output [`Divergence`] shows on either side of where they differ.
+const CONTEXT: usize = 16;
+
+/// What serializing the tokens of an input gives back, see [`verify_roundtrip`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RoundtripReport {
+    /// The output is the input.
+    Identical,
+    /// The output is spelled differently but tokenizes the same, like with tags padded by
+    /// whitespace or needlessly escaped text.
+    Reformatted(Divergence),
+    /// The output means something else, like malformed tags the input had turning into
+    /// escaped text.
+    Lossy(Divergence),
+}
+
+/// Where the output of a [`RoundtripReport`] first differs from its input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// The byte offset of the first difference, the same in both strings.
+    pub offset: usize,
+    /// The input around the offset.
+    pub input: String,
+    /// The output around the offset.
+    pub output: String,
+}
+
+impl Divergence {
+    fn between(input: &str, output: &str) -> Self {
+        let mut offset = input
+            .bytes()
+            .zip(output.bytes())
+            .position(|(a, b)| a != b)
+            .unwrap_or(input.len().min(output.len()));
+        // The common prefix ends at a character boundary in both, before the first
+        // character that differs.
+        while !input.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        Self {
+            offset,
+            input: context(input, offset),
+            output: context(output, offset),
+        }
+    }
+}
+
+fn context(s: &str, offset: usize) -> String {
+    let mut start = offset.saturating_sub(CONTEXT);
+    while !s.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (offset + CONTEXT).min(s.len());
+    while !s.is_char_boundary(end) {
+        end += 1;
+    }
+    s[start..end].to_string()
+}
+
+/// Tokenizes the input leniently, serializes the tokens with [`to_source`] and compares the
+/// result with the input.
+///
+/// ```
+/// use papyrus_lib::{verify_roundtrip, RoundtripReport};
+///
+/// assert_eq!(verify_roundtrip("hi [user:1]"), RoundtripReport::Identical);
+/// let RoundtripReport::Reformatted(divergence) = verify_roundtrip("hi [user: 01]") else {
+///     unreachable!()
+/// };
+/// assert_eq!((divergence.offset, divergence.output.as_str()), (9, "hi [user:1]"));
+/// ```
+pub fn verify_roundtrip(input: &str) -> RoundtripReport {
+    let tokens = tokenize_lossy(input);
+    let output = to_source(&tokens);
+    if output == input {
+        return RoundtripReport::Identical;
+    }
+    let divergence = Divergence::between(input, &output);
+    // Tags kept as text by lenient tokenizing are lost even if the output reads back as the
+    // same text.
+    let lossless = TokenIter::new(input).all(|token| token.is_ok());
+    if lossless && tokenize_lossy(&output) == tokens {
+        RoundtripReport::Reformatted(divergence)
+    } else {
+        RoundtripReport::Lossy(divergence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn roundtrip_identical() {
+        for input in [
+            "",
+            "plain",
+            "a [user:1] \\[b\\] [article:22]\n\n[spoiler]c[/spoiler]",
+        ] {
+            assert_eq!(
+                verify_roundtrip(input),
+                RoundtripReport::Identical,
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    pub fn roundtrip_reformatted() {
+        assert_eq!(
+            verify_roundtrip("a long line of text, then [user: 007 ] and more"),
+            RoundtripReport::Reformatted(Divergence {
+                offset: 32,
+                input: "ext, then [user: 007 ] and more".to_string(),
+                output: "ext, then [user:7] and more".to_string(),
+            })
+        );
+        // Backslashes that escape nothing are kept as text, and escaped in the output.
+        let RoundtripReport::Reformatted(divergence) = verify_roundtrip(r"C:\ é\ü") else {
+            panic!("not reformatted");
+        };
+        assert_eq!(divergence.offset, 3);
+        assert_eq!(divergence.output, r"C:\\ é\\ü");
+    }
+
+    #[test]
+    pub fn roundtrip_lossy() {
+        let doc = Document::parse_lossy("see [user:1] and [usr:2]");
+        let input = doc.source();
+        assert_eq!(
+            verify_roundtrip(input),
+            RoundtripReport::Lossy(Divergence {
+                offset: 17,
+                input: "ee [user:1] and [usr:2]".to_string(),
+                output: r"ee [user:1] and \[usr:2\]".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    control::{find_control, strip_controls},
+    ControlChars, Position, TokenizeErr, Tokenizer,
+};
+
+/// What the next token of the input is, as byte ranges into it.
+#[derive(Clone)]
+pub(crate) enum Scanned {
+    LineBreak,
+    ParagraphBreak,
+    /// The range of the tag's body, without brackets.
+    Tag(Range<usize>),
+    /// Whether the text contains escapes, which then have to be removed from its content.
+    Text(Range<usize>, bool),
+}
+
+// The tokenizing state shared by the iterators over strs and bytes. All delimiters are
+// ASCII, so the input can be scanned bytewise and only the ranges of tokens have to be
+// valid UTF-8.
+//
+// The input is passed to every call, so callers can grow it, it only has to keep its
+// prefix up to the offset.
+#[derive(Clone)]
+pub(crate) struct Scanner {
+    offset: usize,
+    position: Position,
+    config: Tokenizer,
+    /// The byte before the offset, if it isn't the start of the input.
+    prev: Option<u8>,
+    /// Whether only blanks are between the start of the line and the offset.
+    line_start: bool,
+}
+
+impl Scanner {
+    pub fn new(config: Tokenizer) -> Self {
+        Self::at(0, config)
+    }
+
+    /// Starts scanning at `offset`, which has to be the start of a token. Positions are
+    /// counted from there.
+    pub fn at(offset: usize, config: Tokenizer) -> Self {
+        Self {
+            offset,
+            position: Position::new(1),
+            config,
+            prev: None,
+            line_start: true,
+        }
+    }
+
+    pub fn config(&self) -> &Tokenizer {
+        &self.config
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Position) {
+        self.position = position;
+    }
+
+    /// Accounts for the first `consumed` bytes of the input being dropped.
+    pub fn rebase(&mut self, consumed: usize) {
+        self.offset -= consumed;
+    }
+
+    /// Returns the next lexeme, together with the position it starts at.
+    pub fn next_lexeme(&mut self, bytes: &[u8]) -> Option<(Scanned, Position)> {
+        let start = self.offset;
+        let lexeme = self.lexeme(bytes)?;
+        // Callers may drop what's before the offset, so what a tag follows is kept.
+        self.prev = Some(bytes[self.offset - 1]);
+        self.line_start = at_line_start(&bytes[start..self.offset], self.line_start);
+        Some(lexeme)
+    }
+
+    // Whether an opening delimiter right after `before`, which follows the offset, starts a
+    // tag.
+    fn opens_tag(&self, before: &[u8]) -> bool {
+        if self.config.line_start_tags && !at_line_start(before, self.line_start) {
+            return false;
+        }
+        let prev = before.last().copied().or(self.prev);
+        // Unless control characters are kept, `ESC [` starts an ANSI escape sequence.
+        if self.config.open == b'['
+            && prev == Some(0x1b)
+            && self.config.options.control_chars != ControlChars::Keep
+        {
+            return false;
+        }
+        match (self.config.boundary, prev) {
+            (None, _) | (_, None) => true,
+            (Some(set), Some(b)) => b.is_ascii_whitespace() || (b < 128 && set & 1 << b != 0),
+        }
+    }
+
+    fn lexeme(&mut self, bytes: &[u8]) -> Option<(Scanned, Position)> {
+        let start = self.offset;
+        let position = self.position;
+        let rest = &bytes[start..];
+        match rest.first()? {
+            b'\n' | b'\r' if self.config.options.emit_breaks && self.line_end(rest) > 0 => {
+                self.offset += self.line_end(rest);
+                self.position = self.position.advanced(b"\n");
+                let lexeme = if self.skip_blank_lines(bytes) {
+                    Scanned::ParagraphBreak
+                } else {
+                    Scanned::LineBreak
+                };
+                return Some((lexeme, position));
+            }
+            &open if open == self.config.open && self.opens_tag(&[]) => {
+                let end = memchr::memchr(self.config.close, rest).unwrap_or(rest.len());
+                let consumed = (end + 1).min(rest.len());
+                self.offset += consumed;
+                self.position = self.config.advance(self.position, &rest[..consumed]);
+                return Some((Scanned::Tag(start + 1..start + end), position));
+            }
+            _ => {}
+        }
+
+        // A backslash escapes the characters that would otherwise be read as markup, any
+        // other backslash is kept as is.
+        let mut end = 0;
+        let mut escaped = false;
+        loop {
+            let options = self.config.options;
+            let next = match (options.emit_breaks, options.normalize_newlines) {
+                (true, false) => memchr::memchr3(self.config.open, b'\\', b'\n', &rest[end..]),
+                (true, true) => rest[end..]
+                    .iter()
+                    .position(|&b| b == self.config.open || matches!(b, b'\\' | b'\n' | b'\r')),
+                (false, _) => memchr::memchr2(self.config.open, b'\\', &rest[end..]),
+            };
+            let Some(next) = next.map(|i| end + i) else {
+                end = rest.len();
+                break;
+            };
+            if rest[next] == self.config.open {
+                if self.opens_tag(&rest[..next]) {
+                    end = next;
+                    break;
+                }
+                end = next + 1;
+                continue;
+            }
+            if matches!(rest[next], b'\n' | b'\r') {
+                end = next;
+                break;
+            }
+            if rest
+                .get(next + 1)
+                .is_some_and(|&c| c == self.config.open || c == self.config.close || c == b'\\')
+            {
+                escaped = true;
+                end = next + 2;
+            } else {
+                end = next + 1;
+            }
+        }
+        self.offset += end;
+        self.position = self.config.advance(self.position, &rest[..end]);
+        Some((Scanned::Text(start..start + end, escaped), position))
+    }
+
+    /// Like [`Self::next_lexeme`], but for input that might still grow: returns `None`
+    /// without consuming anything if the lexeme could continue past the end of `bytes`,
+    /// unless `eof`.
+    pub fn next_complete(&mut self, bytes: &[u8], eof: bool) -> Option<(Scanned, Position)> {
+        let mut scanner = self.clone();
+        let (lexeme, position) = scanner.next_lexeme(bytes)?;
+        // A break takes the blank lines after it, so it's only known to end where something
+        // else starts.
+        let complete = eof
+            || match &lexeme {
+                Scanned::LineBreak | Scanned::ParagraphBreak => bytes[scanner.offset..]
+                    .iter()
+                    .any(|&b| !scanner.is_blank(b)),
+                Scanned::Tag(body) => body.end < bytes.len(),
+                Scanned::Text(..) => scanner.offset < bytes.len(),
+            };
+        if complete {
+            *self = scanner;
+        }
+        complete.then_some((lexeme, position))
+    }
+
+    /// Returns the lexemes at the end of `bytes` after the offset, together with their
+    /// starts. `bytes` has to end at the end of a lexeme.
+    ///
+    /// These are all the lexemes since the last point the scan could start from, so callers
+    /// reading backwards can take them one by one without scanning again.
+    pub fn last_lexemes(&self, bytes: &[u8]) -> Vec<(usize, Scanned)> {
+        // Whether it closed a tag or is text, a `]` is always followed by text that starts
+        // fresh, so scanning forward from there finds the same lexemes as from the start.
+        // Only the first of them might begin earlier, if it's text, in which case it's
+        // scanned from the `]` before, but only up to where it was already scanned.
+        let mut end = bytes.len();
+        let mut tail = None;
+        let mut limit = bytes.len();
+        loop {
+            let close = memchr::memrchr(self.config.close, &bytes[self.offset..limit])
+                .map(|i| self.offset + i);
+            let sync = close.map_or(self.offset, |i| i + 1);
+            let mut scanner = Self::at(sync, self.config.clone());
+            scanner.prev = close.map_or(self.prev, |close| Some(bytes[close]));
+            scanner.line_start = close.is_none() && self.line_start;
+            let mut lexemes = Vec::new();
+            loop {
+                let start = scanner.offset;
+                let Some((lexeme, _)) = scanner.next_lexeme(&bytes[..end]) else {
+                    break;
+                };
+                lexemes.push((start, lexeme));
+            }
+            if let Some(escaped) = tail {
+                match lexemes.last_mut() {
+                    Some((start, Scanned::Text(range, e))) => {
+                        *range = *start..bytes.len();
+                        *e |= escaped;
+                    }
+                    _ => lexemes.push((end, Scanned::Text(end..bytes.len(), escaped))),
+                }
+            }
+            match (close, lexemes.first()) {
+                (Some(close), Some(&(start, Scanned::Text(_, escaped)))) if start == sync => {
+                    if lexemes.len() > 1 {
+                        lexemes.remove(0);
+                        return lexemes;
+                    }
+                    tail = Some(escaped);
+                    end = sync;
+                    limit = close;
+                }
+                (Some(close), None) => limit = close,
+                _ => return lexemes,
+            }
+        }
+    }
+
+    // Consumes the lines following a newline that contain nothing but whitespace,
+    // returning whether there were any.
+    fn skip_blank_lines(&mut self, bytes: &[u8]) -> bool {
+        let mut skipped = false;
+        loop {
+            let rest = &bytes[self.offset..];
+            let blank = rest
+                .iter()
+                .position(|&b| !self.is_blank(b))
+                .unwrap_or(rest.len());
+            let len = self.line_end(&rest[blank..]);
+            if len == 0 {
+                return skipped;
+            }
+            self.offset += blank + len;
+            self.position = self.position.advanced(b"\n");
+            skipped = true;
+        }
+    }
+
+    // Whether the byte can be on a blank line.
+    fn is_blank(&self, b: u8) -> bool {
+        b == b' ' || b == b'\t' || (b == b'\r' && !self.config.options.normalize_newlines)
+    }
+
+    // The length of the newline `bytes` start with, 0 if they don't.
+    fn line_end(&self, bytes: &[u8]) -> usize {
+        match bytes {
+            [b'\n', ..] => 1,
+            [b'\r', b'\n', ..] if self.config.options.normalize_newlines => 2,
+            [b'\r', ..] if self.config.options.normalize_newlines => 1,
+            _ => 0,
+        }
+    }
+
+    /// How many newlines `bytes` contain.
+    pub fn line_ends(&self, bytes: &[u8]) -> usize {
+        match self.config.options.normalize_newlines {
+            true => memchr::memchr2_iter(b'\n', b'\r', bytes)
+                .filter(|&i| bytes[i] == b'\n' || bytes.get(i + 1) != Some(&b'\n'))
+                .count(),
+            false => memchr::memchr_iter(b'\n', bytes).count(),
+        }
+    }
+
+    /// Where the last line of `bytes` starts.
+    pub fn line_start(&self, bytes: &[u8]) -> usize {
+        let last = match self.config.options.normalize_newlines {
+            true => memchr::memrchr2(b'\n', b'\r', bytes),
+            false => memchr::memrchr(b'\n', bytes),
+        };
+        last.map_or(0, |i| i + 1)
+    }
+}
+
+impl Tokenizer {
+    /// The position after `bytes`, which follow `position`, counting lines by the newlines
+    /// of [`TokenizerOptions::normalize_newlines`](crate::TokenizerOptions).
+    pub(crate) fn advance(&self, position: Position, bytes: &[u8]) -> Position {
+        match self.options.normalize_newlines {
+            true => position.advanced_newlines(bytes),
+            false => position.advanced(bytes),
+        }
+    }
+}
+
+// Whether only blanks are between the start of the line and the end of `bytes`, given
+// whether that was the case at their start.
+fn at_line_start(bytes: &[u8], line_start: bool) -> bool {
+    for &b in bytes.iter().rev() {
+        match b {
+            b'\n' => return true,
+            b' ' | b'\t' | b'\r' => {}
+            _ => return false,
+        }
+    }
+    line_start
+}
+
+/// The range of a tag including its delimiters, from that of its body.
+pub(crate) fn tag_range(body: Range<usize>, len: usize) -> Range<usize> {
+    body.start - 1..(body.end + 1).min(len)
+}
+
+/// The content of a text lexeme at `position`, `escaped` if it contains escapes.
+pub(crate) fn text_content<'a>(
+    raw: &'a str,
+    escaped: bool,
+    config: &Tokenizer,
+    position: Position,
+) -> Result<Cow<'a, str>, TokenizeErr> {
+    let mut text = match escaped {
+        true => Cow::Owned(unescape(raw, config)),
+        false => Cow::Borrowed(raw),
+    };
+    if config.options.normalize_newlines && text.contains('\r') {
+        text = Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"));
+    }
+    match config.options.control_chars {
+        ControlChars::Keep => {}
+        ControlChars::Strip if find_control(&text).is_some() => {
+            text = Cow::Owned(strip_controls(&text));
+        }
+        ControlChars::Strip => {}
+        ControlChars::Error => {
+            // Escapes don't change control characters, so the source has them at the same
+            // place.
+            if let Some((i, c)) = find_control(raw) {
+                let position = config.advance(position, &raw.as_bytes()[..i]);
+                return Err(TokenizeErr::ControlChar(position, c));
+            }
+        }
+    }
+    Ok(text)
+}
+
+pub(crate) fn unescape(text: &str, config: &Tokenizer) -> String {
+    let escapable =
+        |c: char| c == char::from(config.open) || c == char::from(config.close) || c == '\\';
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(escaped)) if escapable(escaped) => {
+                chars.next();
+                out.push(escaped);
+            }
+            (c, _) => out.push(c),
+        }
+    }
+    out
+}
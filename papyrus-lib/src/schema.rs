@@ -0,0 +1,128 @@
+use std::borrow::Cow;
+
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+
+use crate::Tag;
+
+// What `FromStr` accepts, but for custom tags and keys.
+#[cfg(feature = "tag-blocks")]
+const TAG_PATTERN: &str = "^((user|article|quote):[0-9]+|link:https?://\\S+|var:[A-Za-z0-9_]+|if:[A-Za-z0-9_]+|spoiler|else|list(:ordered)?|item|table|row(:header)?|cell|/(spoiler|quote|if|list|item|table|row|cell))$";
+#[cfg(not(feature = "tag-blocks"))]
+const TAG_PATTERN: &str = "^((user|article):[0-9]+|link:https?://\\S+|var:[A-Za-z0-9_]+)$";
+
+impl JsonSchema for Tag {
+    fn schema_name() -> Cow<'static, str> {
+        "Tag".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "A tag in its `name:payload` or `name` form, e.g. `user:5`.",
+            "type": "string",
+            "pattern": TAG_PATTERN,
+        })
+    }
+}
+
+// The serialized form of a `ParseIntError`, see `serde_impl::parse_int_err`.
+pub(crate) struct ParseIntErrKind;
+
+impl JsonSchema for ParseIntErrKind {
+    fn schema_name() -> Cow<'static, str> {
+        "ParseIntErrKind".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "enum": ["empty", "invalid_digit", "pos_overflow"],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::*;
+
+    // The names of an externally tagged enum's variants, as listed by its `oneOf`.
+    fn variants(schema: &Value) -> Vec<String> {
+        let mut names = Vec::new();
+        for variant in schema["oneOf"].as_array().unwrap() {
+            // Documented unit variants are a `const` of their own.
+            match (variant["enum"].as_array(), variant["const"].as_str()) {
+                (Some(units), _) => {
+                    names.extend(units.iter().map(|n| n.as_str().unwrap().to_string()))
+                }
+                (None, Some(unit)) => names.push(unit.to_string()),
+                (None, None) => {
+                    names.extend(variant["properties"].as_object().unwrap().keys().cloned())
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+
+    #[test]
+    pub fn schema_tokens() {
+        let schema = schemars::schema_for!(Vec<Token>);
+        let defs = &schema.as_value()["$defs"];
+        assert_eq!(
+            variants(&defs["Token"]),
+            ["Highlight", "LineBreak", "ParagraphBreak", "Tag", "Text"]
+        );
+        assert_eq!(defs["Tag"]["type"], "string");
+        #[cfg(feature = "tag-blocks")]
+        assert_eq!(
+            defs["Tag"]["pattern"],
+            "^((user|article|quote):[0-9]+|link:https?://\\S+|var:[A-Za-z0-9_]+|if:[A-Za-z0-9_]+|spoiler|else|list(:ordered)?|item|table|row(:header)?|cell|/(spoiler|quote|if|list|item|table|row|cell))$"
+        );
+    }
+
+    #[test]
+    pub fn schema_errors() {
+        let schema = schemars::schema_for!((TokenizeErr, SliceError, Span));
+        let defs = &schema.as_value()["$defs"];
+        assert_eq!(
+            variants(&defs["TokenizeErr"]),
+            [
+                "ControlChar",
+                "InvalidUtf8",
+                "TagErr",
+                "TagTooLong",
+                "UnclosedTag"
+            ]
+        );
+        assert_eq!(
+            variants(&defs["TagParseErr"]),
+            [
+                "CaptureNotFound",
+                "CaptureParseErr",
+                "DeprecatedAlias",
+                "InvalidPart",
+                "InvalidPayload",
+                "NoCaptures",
+                "UnknownTag",
+                "UnmatchedClose"
+            ]
+        );
+        assert_eq!(
+            variants(&defs["SliceError"]),
+            ["NotCharBoundary", "OutOfBounds", "SplitsTag"]
+        );
+        assert_eq!(
+            defs["Position"]["required"],
+            serde_json::json!(["line", "column"])
+        );
+        assert_eq!(
+            defs["Span"]["required"],
+            serde_json::json!(["start", "end"])
+        );
+        assert_eq!(
+            defs["ParseIntErrKind"]["enum"],
+            serde_json::json!(["empty", "invalid_digit", "pos_overflow"])
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use alloc::{string::ToString, vec::Vec};
+
+use crate::{Document, Span, Token};
+
+/// Finds every occurrence of `needle` within the text tokens of a document.
+///
+/// Matches never span across tags. The returned spans index the document's source, which
+/// includes any escapes in front of matched brackets.
+pub fn find_text(document: &Document, needle: &str) -> Vec<Span> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    for (token, span) in document.spanned_tokens() {
+        let Token::Text(text) = token else {
+            continue;
+        };
+        let escapes = escapes(&document.source()[span.range()]);
+        let to_source = |offset: usize| {
+            span.start + offset + escapes.iter().take_while(|e| **e < offset).count()
+        };
+        for (offset, _) in text.match_indices(needle) {
+            matches.push(Span::new(
+                to_source(offset),
+                to_source(offset + needle.len()),
+            ));
+        }
+    }
+    matches
+}
+
+/// Turns the text covered by the given source spans into `Token::Highlight`, splitting text
+/// tokens at the span boundaries.
+///
+/// Overlapping and adjacent spans are merged first. Tags are never touched, even when a span
+/// covers them.
+pub fn highlight(document: Document, spans: &[Span]) -> Vec<Token> {
+    let spans = merge_spans(spans);
+    let (source, tokens, token_spans) = document.into_parts();
+
+    let mut out = Vec::new();
+    for (token, token_span) in tokens.into_iter().zip(token_spans) {
+        let Token::Text(text) = token else {
+            out.push(token);
+            continue;
+        };
+
+        let token_source = &source[token_span.range()];
+        let to_content = |offset: usize| {
+            content_offset(
+                token_source,
+                offset.clamp(token_span.start, token_span.end) - token_span.start,
+            )
+        };
+
+        let mut cursor = 0;
+        for span in spans
+            .iter()
+            .filter(|s| s.start < token_span.end && s.end > token_span.start)
+        {
+            let mut start = to_content(span.start).max(cursor);
+            while !text.is_char_boundary(start) {
+                start -= 1;
+            }
+            let mut end = to_content(span.end);
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+            if start > cursor {
+                out.push(Token::Text(text[cursor..start].to_string()));
+            }
+            if end > start {
+                out.push(Token::Highlight(text[start..end].to_string()));
+            }
+            cursor = cursor.max(end);
+        }
+        if cursor < text.len() {
+            out.push(Token::Text(text[cursor..].to_string()));
+        }
+    }
+    out
+}
+
+fn merge_spans(spans: &[Span]) -> Vec<Span> {
+    let mut sorted = spans.to_vec();
+    sorted.sort_by_key(|span| span.start);
+
+    let mut merged: Vec<Span> = Vec::new();
+    for span in sorted {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+// Maps a byte offset into the source of a text token to the matching offset into its content.
+pub(crate) fn content_offset(source: &str, offset: usize) -> usize {
+    let backslashes = escapes(source)
+        .iter()
+        .enumerate()
+        .take_while(|(i, e)| *e + i < offset)
+        .count();
+    offset - backslashes
+}
+
+// Offsets into the text of a token's content at which the source contains an escape.
+fn escapes(source: &str) -> Vec<usize> {
+    let mut escapes = Vec::new();
+    let mut offset = 0;
+    let mut chars = source.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && matches!(chars.clone().next(), Some('[' | ']' | '\\')) {
+            chars.next();
+            escapes.push(offset);
+            offset += 1;
+        } else {
+            offset += c.len_utf8();
+        }
+    }
+    escapes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::text, *};
+
+    fn find<'a>(source: &'a str, needle: &str) -> Vec<&'a str> {
+        let doc = Document::parse(source).unwrap();
+        find_text(&doc, needle)
+            .into_iter()
+            .map(|span| &source[span.range()])
+            .collect()
+    }
+
+    #[test]
+    pub fn find_around_tag() {
+        let doc = Document::parse("hello [user:5] world, hello").unwrap();
+        assert_eq!(
+            find_text(&doc, "hello"),
+            vec![Span::new(0, 5), Span::new(22, 27)]
+        );
+        assert_eq!(find_text(&doc, "world"), vec![Span::new(15, 20)]);
+    }
+
+    #[test]
+    pub fn find_not_across_tag() {
+        assert_eq!(
+            find("hello [user:5] world", "hello  world"),
+            Vec::<&str>::new()
+        );
+        assert_eq!(find("hello [user:5] world", "user"), Vec::<&str>::new());
+        assert_eq!(find("hello [user:5] world", ""), Vec::<&str>::new());
+    }
+
+    fn mark(s: &str) -> Token {
+        Token::Highlight(s.to_string())
+    }
+
+    #[test]
+    pub fn highlight_inside_text() {
+        let doc = Document::parse("a long run of text, [user:1] ok").unwrap();
+        let spans = find_text(&doc, "run");
+        assert_eq!(
+            highlight(doc, &spans),
+            vec![
+                text("a long "),
+                mark("run"),
+                text(" of text, "),
+                Token::Tag(Tag::User(1)),
+                text(" ok")
+            ]
+        );
+    }
+
+    #[test]
+    pub fn highlight_token_start() {
+        let doc = Document::parse(r"[user:1]\[hello\] world").unwrap();
+        let spans = find_text(&doc, "[hello]");
+        assert_eq!(spans, vec![Span::new(8, 17)]);
+        assert_eq!(
+            highlight(doc, &spans),
+            vec![Token::Tag(Tag::User(1)), mark("[hello]"), text(" world")]
+        );
+    }
+
+    #[test]
+    pub fn highlight_merges_spans() {
+        let doc = Document::parse("abcdefgh [user:1] ijkl").unwrap();
+        assert_eq!(
+            highlight(
+                doc,
+                &[
+                    Span::new(3, 5),
+                    Span::new(1, 4),
+                    Span::new(5, 6),
+                    Span::new(7, 20),
+                ]
+            ),
+            vec![
+                text("a"),
+                mark("bcdef"),
+                text("g"),
+                mark("h "),
+                Token::Tag(Tag::User(1)),
+                mark(" ij"),
+                text("kl")
+            ]
+        );
+    }
+
+    #[test]
+    pub fn find_escaped() {
+        assert_eq!(
+            find(r"ünï \[x\] [user:1] a\\\[x\]", "[x]"),
+            vec![r"\[x\]", r"\[x\]"]
+        );
+        assert_eq!(find(r"a \[x\] b", "x"), vec!["x"]);
+    }
+}
@@ -0,0 +1,178 @@
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::Token;
+
+/// Words that end with a period without ending the sentence, lowercase and without their last
+/// period.
+const ABBREVIATIONS: [&str; 10] = [
+    "cf", "dr", "e.g", "etc", "i.e", "mr", "mrs", "ms", "prof", "vs",
+];
+
+/// Splits a token stream into sentences.
+///
+/// A sentence ends after a run of `.`, `?` and `!`, and closing quotes or brackets after it,
+/// followed by whitespace, which stays with the sentence it ends. Periods ending one of a few
+/// abbreviations like `e.g.` don't count. Breaks are whitespace too, and a paragraph break
+/// always ends the sentence. Tags stay where they are, and text is split at the end of
+/// sentences, so joining the sentences gives back the tokens.
+///
+/// ```
+/// use papyrus_lib::{sentences, tokenize_lossy, Tag, Token};
+///
+/// let sentences = sentences(tokenize_lossy("Hi [user:1]. See e.g. the docs!"));
+/// assert_eq!(
+///     sentences,
+///     [
+///         vec![Token::Text("Hi ".into()), Token::Tag(Tag::User(1)), Token::Text(". ".into())],
+///         vec![Token::Text("See e.g. the docs!".into())],
+///     ]
+/// );
+/// ```
+pub fn sentences<I: IntoIterator<Item = Token>>(tokens: I) -> Vec<Vec<Token>> {
+    let mut sentences = Vec::new();
+    let mut sentence = Vec::new();
+    // Whether the last text ended with a terminator, which a break right after makes the end
+    // of the sentence.
+    let mut terminated = false;
+    for token in tokens {
+        match token {
+            Token::Text(text) => {
+                let (ends, rest_terminated) = boundaries(&text);
+                let mut start = 0;
+                for end in ends {
+                    sentence.push(Token::Text(text[start..end].into()));
+                    sentences.push(mem::take(&mut sentence));
+                    start = end;
+                }
+                if start < text.len() {
+                    sentence.push(Token::Text(text[start..].into()));
+                }
+                terminated = rest_terminated;
+            }
+            Token::LineBreak if !terminated => sentence.push(Token::LineBreak),
+            token @ (Token::LineBreak | Token::ParagraphBreak) => {
+                sentence.push(token);
+                sentences.push(mem::take(&mut sentence));
+                terminated = false;
+            }
+            token => {
+                sentence.push(token);
+                terminated = false;
+            }
+        }
+    }
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+    sentences
+}
+
+// Where the sentences ending in the text end, and whether it ends with a terminator that
+// would end one if whitespace followed.
+fn boundaries(text: &str) -> (Vec<usize>, bool) {
+    let mut ends = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if !matches!(c, '.' | '?' | '!') {
+            continue;
+        }
+        let lone_period = c == '.'
+            && chars
+                .peek()
+                .is_none_or(|&(_, c)| !matches!(c, '.' | '?' | '!'));
+        while chars
+            .next_if(|&(_, c)| {
+                matches!(
+                    c,
+                    '.' | '?' | '!' | '"' | '\'' | ')' | ']' | '”' | '’' | '»'
+                )
+            })
+            .is_some()
+        {}
+        let word = text[..i]
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or_default();
+        let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+        if lone_period && ABBREVIATIONS.iter().any(|a| word.eq_ignore_ascii_case(a)) {
+            continue;
+        }
+        match chars.peek() {
+            None => return (ends, true),
+            Some(&(_, c)) if c.is_whitespace() => {
+                while chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {}
+                ends.push(chars.peek().map_or(text.len(), |&(end, _)| end));
+            }
+            Some(_) => {}
+        }
+    }
+    (ends, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn sentences(input: &str) -> Vec<String> {
+        super::sentences(tokenize_lossy(input))
+            .iter()
+            .map(|sentence| to_source(sentence))
+            .collect()
+    }
+
+    #[test]
+    pub fn sentences_with_mentions() {
+        assert_eq!(
+            sentences("As [user:1] said, it works. Really?! Yes (mostly.) Done"),
+            [
+                "As [user:1] said, it works. ",
+                "Really?! ",
+                "Yes (mostly.) ",
+                "Done"
+            ]
+        );
+        assert_eq!(
+            super::sentences(tokenize_lossy("Thanks [user:1]. Bye")),
+            [
+                vec![
+                    Token::Text("Thanks ".into()),
+                    Token::Tag(Tag::User(1)),
+                    Token::Text(". ".into())
+                ],
+                vec![Token::Text("Bye".into())],
+            ]
+        );
+    }
+
+    #[test]
+    pub fn sentences_end_before_tags() {
+        assert_eq!(
+            sentences("See the docs. [user:1] wrote them.[article:2] is next."),
+            ["See the docs. ", "[user:1] wrote them.[article:2] is next."]
+        );
+        let tokens = TokenIter::with_options(
+            "One.\nTwo\nthree\n\nFour",
+            TokenizerOptions {
+                emit_breaks: true,
+                ..Default::default()
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(super::sentences(tokens).len(), 3,);
+    }
+
+    #[test]
+    pub fn sentences_abbreviations() {
+        assert_eq!(
+            sentences("Use a tag, e.g. [user:1], or i.e. this. Mr. Smith vs. Dr. Who..."),
+            [
+                "Use a tag, e.g. [user:1], or i.e. this. ",
+                "Mr. Smith vs. Dr. Who..."
+            ]
+        );
+        assert_eq!(sentences("Pi is 3.14. Ok"), ["Pi is 3.14. ", "Ok"]);
+        assert_eq!(sentences(""), Vec::<String>::new());
+    }
+}
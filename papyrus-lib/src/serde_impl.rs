@@ -0,0 +1,229 @@
+pub(crate) mod parse_int_err {
+    use alloc::string::String;
+    use core::num::{IntErrorKind, ParseIntError};
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    // A `ParseIntError` can't be constructed directly, so it's stored as its kind and
+    // recreated by parsing an input failing the same way.
+    const KINDS: [(&str, &str); 3] = [
+        ("empty", ""),
+        ("invalid_digit", "x"),
+        ("pos_overflow", "999999999999999999999999999999"),
+    ];
+
+    pub fn serialize<S: Serializer>(err: &ParseIntError, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match err.kind() {
+            IntErrorKind::Empty => "empty",
+            IntErrorKind::PosOverflow => "pos_overflow",
+            _ => "invalid_digit",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ParseIntError, D::Error> {
+        let kind = String::deserialize(deserializer)?;
+        let (_, input) = KINDS
+            .iter()
+            .find(|(name, _)| *name == kind)
+            .ok_or_else(|| {
+                D::Error::unknown_variant(&kind, &["empty", "invalid_digit", "pos_overflow"])
+            })?;
+        Ok(input.parse::<usize>().unwrap_err())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::*;
+
+    fn roundtrip<T>(value: &T, expected: serde_json::Value)
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let json = serde_json::to_value(value).unwrap();
+        assert_eq!(json, expected);
+        assert_eq!(&serde_json::from_value::<T>(json).unwrap(), value);
+    }
+
+    #[test]
+    pub fn serde_tokens() {
+        let tokens = vec![
+            Token::Text("hi ".to_string()),
+            Token::Tag(Tag::User(5)),
+            Token::LineBreak,
+            Token::Tag(Tag::Article(9)),
+            Token::ParagraphBreak,
+            Token::Highlight("match".to_string()),
+        ];
+        roundtrip(
+            &tokens,
+            json!([
+                {"Text": "hi "},
+                {"Tag": "user:5"},
+                "LineBreak",
+                {"Tag": "article:9"},
+                "ParagraphBreak",
+                {"Highlight": "match"},
+            ]),
+        );
+        roundtrip(&Span::new(1, 4), json!({"start": 1, "end": 4}));
+    }
+
+    #[test]
+    pub fn serde_tag_string_form() {
+        roundtrip(&Tag::User(5), json!("user:5"));
+        assert_eq!(
+            serde_json::from_value::<Tag>(json!("article: 09")).unwrap(),
+            Tag::Article(9)
+        );
+        let err = serde_json::from_value::<Tag>(json!("poll:1")).unwrap_err();
+        assert_eq!(err.to_string(), "unknown tag [poll:1]");
+    }
+
+    #[test]
+    pub fn serde_errors() {
+        let err = TokenIter::new("\n[unknown]").nth(1).unwrap().unwrap_err();
+        roundtrip(
+            &err,
+            json!({"TagErr": [{"line": 2, "column": 1}, {"UnknownTag": "[unknown]"}]}),
+        );
+
+        let err = TokenIter::new("[user:999999999999999999999999999]")
+            .next()
+            .unwrap()
+            .unwrap_err();
+        roundtrip(
+            &err,
+            json!({"TagErr": [{"line": 1, "column": 1}, {"InvalidPart": {
+                "body": "user:999999999999999999999999999",
+                "span": {"start": 5, "end": 32},
+                "error": {"CaptureParseErr": "pos_overflow"},
+            }}]}),
+        );
+        roundtrip(
+            &TagParseErr::CaptureParseErr("".parse::<usize>().unwrap_err()),
+            json!({"CaptureParseErr": "empty"}),
+        );
+        roundtrip(
+            &SliceError::SplitsTag(Span::new(0, 8)),
+            json!({"SplitsTag": {"start": 0, "end": 8}}),
+        );
+        assert!(serde_json::from_value::<TagParseErr>(json!({"CaptureParseErr": "zero"})).is_err());
+    }
+
+    #[test]
+    pub fn serde_every_error() {
+        let position = Position::new(2).with_column(3);
+        let tag_errors = [
+            TagParseErr::NoCaptures,
+            TagParseErr::CaptureNotFound,
+            TagParseErr::CaptureParseErr("x".parse::<usize>().unwrap_err()),
+            TagParseErr::UnknownTag("[usr:1]".to_string()),
+            TagParseErr::DeprecatedAlias {
+                alias: "u".to_string(),
+                canonical: "user".to_string(),
+            },
+            TagParseErr::InvalidPayload("x".to_string()),
+            TagParseErr::InvalidPart {
+                body: "user:5x".to_string(),
+                span: Span::new(5, 7),
+                error: Box::new(TagParseErr::CaptureParseErr(
+                    "5x".parse::<usize>().unwrap_err(),
+                )),
+            },
+            TagParseErr::UnmatchedClose,
+        ];
+        let errors = tag_errors
+            .iter()
+            .map(|e| TokenizeErr::TagErr(position, e.clone()))
+            .chain([
+                TokenizeErr::InvalidUtf8(position),
+                TokenizeErr::UnclosedTag(position),
+                TokenizeErr::TagTooLong(position),
+                TokenizeErr::ControlChar(position, '\0'),
+            ]);
+        for error in errors {
+            let json = serde_json::to_value(&error).unwrap();
+            assert_eq!(serde_json::from_value::<TokenizeErr>(json).unwrap(), error);
+            let problem = Problem::from(&error);
+            assert_eq!((problem.line, problem.column, problem.span), (2, 3, None));
+            assert_eq!(problem.code, error.code());
+            let json = serde_json::to_value(&problem).unwrap();
+            assert_eq!(serde_json::from_value::<Problem>(json).unwrap(), problem);
+        }
+        for error in tag_errors {
+            let diagnostic = Diagnostic {
+                span: Span::new(4, 9),
+                line: 1,
+                column: 5,
+                severity: Severity::Warning,
+                error,
+            };
+            let json = serde_json::to_value(&diagnostic).unwrap();
+            assert_eq!(
+                serde_json::from_value::<Diagnostic>(json).unwrap(),
+                diagnostic
+            );
+            let problem = Problem::from(&diagnostic);
+            let json = serde_json::to_value(&problem).unwrap();
+            assert_eq!(serde_json::from_value::<Problem>(json).unwrap(), problem);
+        }
+    }
+
+    #[test]
+    pub fn serde_problem_shape() {
+        let tokenizer = Tokenizer::default();
+        let mut problems: Vec<Problem> = tokenizer
+            .validate("hi [user:5x]\n]")
+            .iter()
+            .map(Problem::from)
+            .collect();
+        problems.push(Problem::from(&tokenizer.tokenize("[usr:1]").unwrap_err()));
+        insta::assert_snapshot!(serde_json::to_string_pretty(&problems).unwrap(), @r###"
+        [
+          {
+            "code": "capture_parse_err",
+            "message": "\"5x\": invalid id: invalid digit found in string",
+            "severity": "Error",
+            "line": 1,
+            "column": 4,
+            "span": {
+              "start": 3,
+              "end": 12
+            },
+            "params": {
+              "error": "invalid digit found in string",
+              "part": "5x"
+            }
+          },
+          {
+            "code": "unmatched_close",
+            "message": "closing bracket without a tag opening it",
+            "severity": "Warning",
+            "line": 2,
+            "column": 1,
+            "span": {
+              "start": 13,
+              "end": 14
+            },
+            "params": {}
+          },
+          {
+            "code": "unknown_tag",
+            "message": "unknown tag [usr:1]",
+            "severity": "Error",
+            "line": 1,
+            "column": 1,
+            "span": null,
+            "params": {
+              "tag": "[usr:1]"
+            }
+          }
+        ]
+        "###);
+    }
+}
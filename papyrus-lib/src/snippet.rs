@@ -0,0 +1,157 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::{walk, ParseTag, Span, Tag, TokenSource, TokenVisitor};
+
+/// What's cut off a [`TagContext`] is replaced by.
+const ELLIPSIS: &str = "…";
+
+/// A tag with the text around it, see [`contexts`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TagContext<T = Tag> {
+    pub tag: T,
+    /// The text right before the tag, starting with an ellipsis if it was cut.
+    pub before: String,
+    /// The text right after the tag, ending with an ellipsis if it was cut.
+    pub after: String,
+    /// The span of the tag in the serialized tokens, like [`walk`] gives.
+    pub span: Span,
+}
+
+/// The text around every tag, at most `window` chars on either side.
+///
+/// Only text counts, so the `before` of a tag following another one is empty, as is the
+/// `after` of the first.
+///
+/// ```
+/// use papyrus_lib::{contexts, tokenize_lossy};
+///
+/// let tokens = tokenize_lossy("as [user:1] said in the thread about deploys");
+/// let context = &contexts(&tokens, 10)[0];
+/// assert_eq!((context.before.as_str(), context.after.as_str()), ("as ", " said in t…"));
+/// ```
+pub fn contexts<'a, T, S>(tokens: S, window: usize) -> Vec<TagContext<T>>
+where
+    T: ParseTag,
+    S: TokenSource<'a, T>,
+{
+    let mut pieces = Pieces(Vec::new());
+    walk(tokens, &mut pieces);
+    let pieces = pieces.0;
+
+    let text = |i: Option<usize>| match i.and_then(|i| pieces.get(i)) {
+        Some(Piece::Text(text)) => text.as_str(),
+        _ => "",
+    };
+    let mut contexts = Vec::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        let Piece::Tag(tag, span) = piece else {
+            continue;
+        };
+        contexts.push(TagContext {
+            tag: tag.clone(),
+            before: tail(text(i.checked_sub(1)), window),
+            after: head(text(Some(i + 1)), window),
+            span: *span,
+        });
+    }
+    contexts
+}
+
+// The last `window` chars of the text.
+fn tail(text: &str, window: usize) -> String {
+    let chars = text.chars().count();
+    if chars <= window {
+        return text.into();
+    }
+    let start = text
+        .char_indices()
+        .nth(chars - window)
+        .map_or(text.len(), |(i, _)| i);
+    [ELLIPSIS, &text[start..]].concat()
+}
+
+// The first `window` chars of the text.
+fn head(text: &str, window: usize) -> String {
+    match text.char_indices().nth(window) {
+        Some((end, _)) => [&text[..end], ELLIPSIS].concat(),
+        None => text.into(),
+    }
+}
+
+enum Piece<T> {
+    /// Adjacent text, breaks included, merged.
+    Text(String),
+    Tag(T, Span),
+}
+
+struct Pieces<T>(Vec<Piece<T>>);
+
+impl<T: Clone> TokenVisitor<T> for Pieces<T> {
+    fn visit_text(&mut self, text: &str, _span: Span) {
+        match self.0.last_mut() {
+            Some(Piece::Text(last)) => last.push_str(text),
+            _ => self.0.push(Piece::Text(text.into())),
+        }
+    }
+
+    fn visit_tag(&mut self, tag: &T, span: Span) {
+        self.0.push(Piece::Tag(tag.clone(), span));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn contexts(input: &str, window: usize) -> Vec<(Tag, String, String, Span)> {
+        super::contexts(&Document::parse(input).unwrap(), window)
+            .into_iter()
+            .map(|c| (c.tag, c.before, c.after, c.span))
+            .collect()
+    }
+
+    fn context(tag: Tag, before: &str, after: &str, span: Span) -> (Tag, String, String, Span) {
+        (tag, before.to_string(), after.to_string(), span)
+    }
+
+    #[test]
+    pub fn contexts_mid_sentence() {
+        assert_eq!(
+            contexts("… well, as [user:1] said in the thread about deploys", 8),
+            [context(
+                Tag::User(1),
+                "…ell, as ",
+                " said in…",
+                Span::new(13, 21)
+            )]
+        );
+        // Cut at chars, not bytes.
+        assert_eq!(
+            contexts("äöü [user:1] éé", 2),
+            [context(Tag::User(1), "…ü ", " é…", Span::new(7, 15))]
+        );
+    }
+
+    #[test]
+    pub fn contexts_at_start_and_end() {
+        assert_eq!(
+            contexts("[article:9] is out", 20),
+            [context(Tag::Article(9), "", " is out", Span::new(0, 11))]
+        );
+        assert_eq!(
+            contexts("read [article:9]", 0),
+            [context(Tag::Article(9), "…", "", Span::new(5, 16))]
+        );
+    }
+
+    #[test]
+    pub fn contexts_adjacent_tags() {
+        assert_eq!(
+            contexts("thanks [user:1][user:2]!", 10),
+            [
+                context(Tag::User(1), "thanks ", "", Span::new(7, 15)),
+                context(Tag::User(2), "", "!", Span::new(15, 23)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use alloc::{string::ToString, vec::Vec};
+
+use crate::{normalize, Tag, Token};
+
+/// Maps the `@handle`s and `#topic`s of social markup to tags, see [`convert_social`].
+pub trait HandleResolver {
+    fn user(&self, handle: &str) -> Option<usize>;
+
+    /// Topics have no tag of their own, so they resolve to whatever stands for them, like an
+    /// article about the topic.
+    fn topic(&self, _topic: &str) -> Option<Tag> {
+        None
+    }
+}
+
+/// Replaces `@handle` and `#topic` in text tokens with the tags the resolver maps them to.
+///
+/// Both must start at a word boundary, so `x@y.com` is left alone, and consist of
+/// alphanumerics and `_`. Handles and topics the resolver doesn't know stay text.
+pub fn convert_social<I, R>(tokens: I, resolver: &R) -> Vec<Token>
+where
+    I: IntoIterator<Item = Token>,
+    R: HandleResolver + ?Sized,
+{
+    let mut out = Vec::new();
+    for token in tokens {
+        let Token::Text(text) = token else {
+            out.push(token);
+            continue;
+        };
+
+        let mut cursor = 0;
+        let mut prev = None;
+        for (offset, c) in text.char_indices() {
+            let at_boundary = !prev.is_some_and(|p: char| is_word(p) || p == '@' || p == '#');
+            prev = Some(c);
+            if offset < cursor || !at_boundary || !matches!(c, '@' | '#') {
+                continue;
+            }
+
+            let name = &text[offset + 1..];
+            let name = &name[..name.find(|c| !is_word(c)).unwrap_or(name.len())];
+            if name.is_empty() {
+                continue;
+            }
+            let tag = match c {
+                '@' => resolver.user(name).map(Tag::User),
+                _ => resolver.topic(name),
+            };
+            if let Some(tag) = tag {
+                out.push(Token::Text(text[cursor..offset].to_string()));
+                out.push(Token::Tag(tag));
+                cursor = offset + 1 + name.len();
+            }
+        }
+        out.push(Token::Text(text[cursor..].to_string()));
+    }
+    normalize(out)
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::text, *};
+
+    struct Directory;
+
+    impl HandleResolver for Directory {
+        fn user(&self, handle: &str) -> Option<usize> {
+            ["alice", "bob"]
+                .iter()
+                .position(|h| *h == handle)
+                .map(|i| i + 1)
+        }
+
+        fn topic(&self, topic: &str) -> Option<Tag> {
+            (topic == "rustlang").then_some(Tag::Article(9))
+        }
+    }
+
+    #[test]
+    pub fn social_handles() {
+        assert_eq!(
+            convert_social(vec![text("hi @alice and @bob_, cc @bob")], &Directory),
+            vec![
+                text("hi "),
+                Token::Tag(Tag::User(1)),
+                text(" and @bob_, cc "),
+                Token::Tag(Tag::User(2)),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn social_unresolved() {
+        let tokens = vec![
+            text("@carol says hi "),
+            Token::Tag(Tag::User(1)),
+            text(" @"),
+        ];
+        assert_eq!(convert_social(tokens.clone(), &Directory), tokens);
+    }
+
+    #[test]
+    pub fn social_topic_punctuation() {
+        assert_eq!(
+            convert_social(vec![text("I love #rustlang. #go?")], &Directory),
+            vec![text("I love "), Token::Tag(Tag::Article(9)), text(". #go?")]
+        );
+    }
+
+    #[test]
+    pub fn social_email() {
+        let tokens = vec![text("mail alice@bob.com or @@alice, #@alice")];
+        assert_eq!(convert_social(tokens.clone(), &Directory), tokens);
+    }
+}
@@ -0,0 +1,127 @@
+use alloc::{string::ToString, vec::Vec};
+
+use crate::{normalize, Token};
+
+/// Splits a token stream into paragraphs at blank lines.
+///
+/// A blank line is a `Token::ParagraphBreak` or any whitespace run inside a text token
+/// containing at least two newlines.
+/// Whitespace at the edges of a paragraph is trimmed and empty paragraphs are dropped.
+pub fn paragraphs<I: IntoIterator<Item = Token>>(tokens: I) -> Vec<Vec<Token>> {
+    let mut paragraphs = Vec::new();
+    let mut current = Vec::new();
+
+    for token in tokens {
+        match token {
+            Token::Text(text) => {
+                let mut rest = text.as_str();
+                while let Some((before, after)) = split_blank_line(rest) {
+                    current.push(Token::Text(before.to_string()));
+                    finish_paragraph(&mut paragraphs, &mut current);
+                    rest = after;
+                }
+                current.push(Token::Text(rest.to_string()));
+            }
+            Token::ParagraphBreak => finish_paragraph(&mut paragraphs, &mut current),
+            token => current.push(token),
+        }
+    }
+    finish_paragraph(&mut paragraphs, &mut current);
+
+    paragraphs
+}
+
+fn split_blank_line(s: &str) -> Option<(&str, &str)> {
+    let mut run_start = 0;
+    let mut newlines = 0;
+    for (i, c) in s.char_indices() {
+        if !c.is_whitespace() {
+            newlines = 0;
+            run_start = i + c.len_utf8();
+            continue;
+        }
+        if c == '\n' {
+            newlines += 1;
+        }
+        if newlines == 2 {
+            let run_end = s[i..]
+                .find(|c: char| !c.is_whitespace())
+                .map_or(s.len(), |end| i + end);
+            return Some((&s[..run_start], &s[run_end..]));
+        }
+    }
+    None
+}
+
+fn finish_paragraph(paragraphs: &mut Vec<Vec<Token>>, current: &mut Vec<Token>) {
+    let mut paragraph = normalize(current.drain(..));
+    if let Some(Token::Text(text)) = paragraph.first_mut() {
+        *text = text.trim_start().to_string();
+    }
+    if let Some(Token::Text(text)) = paragraph.last_mut() {
+        *text = text.trim_end().to_string();
+    }
+    let paragraph = normalize(paragraph);
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        testing::{text, tokenize},
+        *,
+    };
+
+    #[test]
+    pub fn paragraphs_blank_line() {
+        assert_eq!(
+            paragraphs(tokenize(
+                "hi [user:1],\nwelcome.\n \nsee [article:2]\n\n\n[user:3] bye"
+            )),
+            vec![
+                vec![text("hi "), Token::Tag(Tag::User(1)), text(",\nwelcome.")],
+                vec![text("see "), Token::Tag(Tag::Article(2))],
+                vec![Token::Tag(Tag::User(3)), text(" bye")],
+            ]
+        );
+    }
+
+    #[test]
+    pub fn paragraphs_single() {
+        assert_eq!(
+            paragraphs(tokenize("one\ntwo [user:1]")),
+            vec![vec![text("one\ntwo "), Token::Tag(Tag::User(1))]]
+        );
+    }
+
+    #[test]
+    pub fn paragraphs_leading_trailing_blank_lines() {
+        assert_eq!(
+            paragraphs(tokenize("\n\n  hello\n\n\n")),
+            vec![vec![text("hello")]]
+        );
+        assert_eq!(paragraphs(tokenize("\n\n")), Vec::<Vec<Token>>::new());
+    }
+
+    #[test]
+    pub fn paragraphs_break_tokens() {
+        let tokens = TokenIter::with_options(
+            "a\nb\n\n[user:1]",
+            TokenizerOptions {
+                emit_breaks: true,
+                ..Default::default()
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(
+            paragraphs(tokens),
+            vec![
+                vec![text("a"), Token::LineBreak, text("b")],
+                vec![Token::Tag(Tag::User(1))],
+            ]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Storing token streams in a database column with sqlx, as `jsonb` in Postgres and as text
+//! in SQLite.
+//!
+//! Tokens are stored in their `serde` form, so custom tags and keys, which don't deserialize
+//! without their tokenizer, can't be read back.
+
+use std::fmt::Display;
+
+use serde_json::Value;
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    types::{Json, Type},
+    Database, Decode, Encode,
+};
+
+use crate::{Tag, TagParseErr, Token};
+
+/// The variants of [`Token`] this version stores and reads.
+const VARIANTS: [&str; 5] = ["Text", "Tag", "LineBreak", "ParagraphBreak", "Highlight"];
+
+/// Tokens as a database value, see the [module](self) docs.
+///
+/// ```no_run
+/// # async fn store(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+/// use papyrus_lib::{sqlx::StoredTokens, tokenize_lossy};
+///
+/// let tokens = StoredTokens(tokenize_lossy("hi [user:1]"));
+/// sqlx::query("INSERT INTO posts (body) VALUES ($1)").bind(&tokens).execute(&pool).await?;
+/// let (body,): (StoredTokens,) = sqlx::query_as("SELECT body FROM posts").fetch_one(&pool).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoredTokens(pub Vec<Token>);
+
+/// Why a stored value isn't tokens this version can read.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The value isn't a JSON array of tokens.
+    Malformed(serde_json::Error),
+    /// The token at the index is of a variant this version doesn't know, like one stored by
+    /// a newer version.
+    UnknownVariant { index: usize, variant: String },
+    /// The tag at the index doesn't parse, like one of a kind this version doesn't have.
+    InvalidTag { index: usize, error: TagParseErr },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed(e) => write!(f, "malformed tokens: {e}"),
+            Self::UnknownVariant { index, variant } => write!(
+                f,
+                "token {index} is a {variant:?}, which this version doesn't know, expected one of {VARIANTS:?}"
+            ),
+            Self::InvalidTag { index, error } => write!(f, "tag of token {index}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Malformed(e) => Some(e),
+            Self::UnknownVariant { .. } => None,
+            Self::InvalidTag { error, .. } => Some(error),
+        }
+    }
+}
+
+impl StoredTokens {
+    /// Reads tokens from their `serde` form, checking each token on its own so an error says
+    /// which one this version can't read.
+    pub fn from_json(value: Value) -> Result<Self, DecodeError> {
+        let Value::Array(values) = value else {
+            return Err(DecodeError::Malformed(serde::de::Error::custom(
+                "expected an array of tokens",
+            )));
+        };
+        let tokens = values.into_iter().enumerate().map(|(index, value)| {
+            let (variant, payload) = match &value {
+                Value::String(variant) => (variant.as_str(), None),
+                Value::Object(object) if object.len() == 1 => {
+                    let (variant, payload) = object.iter().next().unwrap();
+                    (variant.as_str(), Some(payload))
+                }
+                _ => return serde_json::from_value(value).map_err(DecodeError::Malformed),
+            };
+            if !VARIANTS.contains(&variant) {
+                let variant = variant.to_string();
+                return Err(DecodeError::UnknownVariant { index, variant });
+            }
+            if let ("Tag", Some(Value::String(tag))) = (variant, payload) {
+                let tag = tag.parse::<Tag>();
+                return tag
+                    .map(Token::Tag)
+                    .map_err(|error| DecodeError::InvalidTag { index, error });
+            }
+            serde_json::from_value(value).map_err(DecodeError::Malformed)
+        });
+        tokens.collect::<Result<_, _>>().map(Self)
+    }
+}
+
+impl From<Vec<Token>> for StoredTokens {
+    fn from(tokens: Vec<Token>) -> Self {
+        Self(tokens)
+    }
+}
+
+impl From<StoredTokens> for Vec<Token> {
+    fn from(tokens: StoredTokens) -> Self {
+        tokens.0
+    }
+}
+
+impl<DB: Database> Type<DB> for StoredTokens
+where
+    Json<Value>: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <Json<Value> as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <Json<Value> as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'q, DB: Database> Encode<'q, DB> for StoredTokens
+where
+    for<'a> Json<&'a [Token]>: Encode<'q, DB>,
+{
+    fn encode_by_ref(
+        &self,
+        buf: &mut <DB as Database>::ArgumentBuffer<'q>,
+    ) -> Result<IsNull, BoxDynError> {
+        Json(self.0.as_slice()).encode_by_ref(buf)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for StoredTokens
+where
+    Json<Value>: Decode<'r, DB>,
+{
+    fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        let Json(value) = <Json<Value> as Decode<'r, DB>>::decode(value)?;
+        Ok(Self::from_json(value)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::{Connection, SqliteConnection};
+
+    use crate::{sqlx::*, tokenize_lossy, Tag, Token};
+
+    fn tokens() -> StoredTokens {
+        StoredTokens(tokenize_lossy(
+            "hi [user:1]\n\nsee [article:2] [link:https://a.com]",
+        ))
+    }
+
+    #[tokio::test]
+    pub async fn sqlx_sqlite_roundtrip() {
+        let mut db = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE posts (body TEXT NOT NULL)")
+            .execute(&mut db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO posts (body) VALUES (?)")
+            .bind(tokens())
+            .execute(&mut db)
+            .await
+            .unwrap();
+        let (body,): (StoredTokens,) = sqlx::query_as("SELECT body FROM posts")
+            .fetch_one(&mut db)
+            .await
+            .unwrap();
+        assert_eq!(body, tokens());
+
+        sqlx::query("UPDATE posts SET body = '[\"LineBreak\", {\"Embed\": \"x\"}]'")
+            .execute(&mut db)
+            .await
+            .unwrap();
+        let error = sqlx::query_as::<_, (StoredTokens,)>("SELECT body FROM posts")
+            .fetch_one(&mut db)
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains(
+                "token 1 is a \"Embed\", which this version doesn't know, expected one of"
+            ),
+            "{error}"
+        );
+    }
+
+    // Only runs with a Postgres database to connect to.
+    #[tokio::test]
+    pub async fn sqlx_postgres_roundtrip() {
+        let Ok(url) = std::env::var("DATABASE_URL") else {
+            return;
+        };
+        let mut db = sqlx::PgConnection::connect(&url).await.unwrap();
+        let (body,): (StoredTokens,) = sqlx::query_as("SELECT $1::jsonb")
+            .bind(tokens())
+            .fetch_one(&mut db)
+            .await
+            .unwrap();
+        assert_eq!(body, tokens());
+    }
+
+    #[test]
+    pub fn sqlx_decode_errors() {
+        let stored = json!([{ "Text": "a" }, { "Tag": "user:1" }, "ParagraphBreak"]);
+        assert_eq!(
+            StoredTokens::from_json(stored).unwrap().0,
+            [
+                Token::Text("a".to_string()),
+                Token::Tag(Tag::User(1)),
+                Token::ParagraphBreak
+            ]
+        );
+        assert!(matches!(
+            StoredTokens::from_json(json!(["LineBreak", "PageBreak"])),
+            Err(DecodeError::UnknownVariant { index: 1, variant }) if variant == "PageBreak"
+        ));
+        let error = StoredTokens::from_json(json!([{ "Tag": "date:2024-01-01" }])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "tag of token 0: unknown tag [date:2024-01-01]"
+        );
+        assert!(matches!(
+            StoredTokens::from_json(json!({ "Text": "a" })),
+            Err(DecodeError::Malformed(_))
+        ));
+        assert!(matches!(
+            StoredTokens::from_json(json!([{ "Text": 1 }])),
+            Err(DecodeError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,233 @@
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::{RenderTag, Tag, TagKind, Token, TokenRef, TokenSource};
+
+/// Counts the words of a token stream.
+///
+/// A word is a whitespace separated run containing at least one alphanumeric character, so
+/// hyphenated words and numbers like `3.14` count once. Tags render as a name and each counts
+/// as a word of its own, with text right after one, like the `'s` of a possessive, part of it.
+pub fn word_count<'a, I: IntoIterator<Item = &'a Token>>(tokens: I) -> usize {
+    let mut count = 0;
+    // Whether the current whitespace separated run has already been counted.
+    let mut counted = false;
+
+    for token in tokens {
+        match token {
+            Token::Text(text) | Token::Highlight(text) => {
+                for c in text.chars() {
+                    if c.is_whitespace() {
+                        counted = false;
+                    } else if c.is_alphanumeric() && !counted {
+                        count += 1;
+                        counted = true;
+                    }
+                }
+            }
+            Token::Tag(_) => {
+                count += 1;
+                counted = true;
+            }
+            Token::LineBreak | Token::ParagraphBreak => counted = false,
+        }
+    }
+
+    count
+}
+
+/// Estimates how long reading the token stream takes at `wpm` words per minute.
+pub fn reading_time<'a, I: IntoIterator<Item = &'a Token>>(tokens: I, wpm: u32) -> Duration {
+    let words = word_count(tokens) as f64;
+    Duration::from_secs_f64(words * 60.0 / f64::from(wpm.max(1)))
+}
+
+/// How long the labels of tags are, for [`estimate_len`].
+///
+/// ```
+/// use papyrus_lib::{LenModel, Tag, TagKind};
+///
+/// let model = LenModel::new(8)
+///     .kind(TagKind::User, 10)
+///     .exact(|tag: &Tag| matches!(tag, Tag::User(1)).then_some(5));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LenModel<F = fn(&Tag) -> Option<usize>> {
+    default: usize,
+    kinds: Vec<(TagKind, usize)>,
+    exact: Option<F>,
+}
+
+impl LenModel {
+    /// Estimates the label of every tag as `default` chars long.
+    pub fn new(default: usize) -> Self {
+        Self {
+            default,
+            kinds: Vec::new(),
+            exact: None,
+        }
+    }
+}
+
+impl<F: Fn(&Tag) -> Option<usize>> LenModel<F> {
+    /// Estimates the labels of tags of `kind` as `len` chars long, replacing its previous
+    /// estimate.
+    pub fn kind(mut self, kind: TagKind, len: usize) -> Self {
+        self.kinds.retain(|(known, _)| *known != kind);
+        self.kinds.push((kind, len));
+        self
+    }
+
+    /// Asks `exact` for the length of a label first, like from a cache of them, falling back
+    /// to the estimates when it returns `None`.
+    pub fn exact<G: Fn(&Tag) -> Option<usize>>(self, exact: G) -> LenModel<G> {
+        LenModel {
+            default: self.default,
+            kinds: self.kinds,
+            exact: Some(exact),
+        }
+    }
+
+    /// The length of the tag in plain text, with its [`RenderTag::prefix`].
+    pub fn tag_len(&self, tag: &Tag) -> usize {
+        let label = self
+            .exact
+            .as_ref()
+            .and_then(|exact| exact(tag))
+            .unwrap_or_else(|| {
+                let kind = tag.kind();
+                self.kinds
+                    .iter()
+                    .find(|(known, _)| *known == kind)
+                    .map_or(self.default, |(_, len)| *len)
+            });
+        tag.prefix().chars().count() + label
+    }
+}
+
+/// Estimates how many chars [`render_plain`](crate::render_plain) renders the tokens as,
+/// counting the text as it is and taking the length of tags from `model`.
+pub fn estimate_len<'a, S, F>(tokens: S, model: &LenModel<F>) -> usize
+where
+    S: TokenSource<'a>,
+    F: Fn(&Tag) -> Option<usize>,
+{
+    tokens
+        .token_refs()
+        .map(|token| token_len(token, model))
+        .sum()
+}
+
+/// Whether [`estimate_len`] is more than `limit`, reading only as many tokens as it takes
+/// to tell.
+pub fn exceeds<'a, S, F>(tokens: S, limit: usize, model: &LenModel<F>) -> bool
+where
+    S: TokenSource<'a>,
+    F: Fn(&Tag) -> Option<usize>,
+{
+    let mut len = 0;
+    tokens.token_refs().any(|token| {
+        len += token_len(token, model);
+        len > limit
+    })
+}
+
+fn token_len<F: Fn(&Tag) -> Option<usize>>(token: TokenRef<'_>, model: &LenModel<F>) -> usize {
+    match token {
+        TokenRef::Text(text) | TokenRef::Highlight(text) => text.chars().count(),
+        TokenRef::Tag(tag) => model.tag_len(&tag),
+        TokenRef::LineBreak => 1,
+        TokenRef::ParagraphBreak => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{testing::tokenize, *};
+
+    #[test]
+    pub fn word_count_text() {
+        assert_eq!(word_count(&tokenize("")), 0);
+        assert_eq!(
+            word_count(&tokenize(
+                "A well-known fact:\tπ is 3.14 — roughly.\n\u{3000}Ünïcödé  words , too!"
+            )),
+            10
+        );
+    }
+
+    #[test]
+    pub fn word_count_tags() {
+        assert_eq!(
+            word_count(&tokenize(
+                "[user:1] and [user:2] wrote [article:123], see [user:1]'s notes"
+            )),
+            8
+        );
+        assert_eq!(word_count(&tokenize("[user:1][article:2]")), 2);
+        assert_eq!(word_count(&tokenize("cc:[user:1] [user:2][user:3]'s")), 4);
+    }
+
+    #[test]
+    pub fn word_count_breaks() {
+        let tokens = TokenIter::with_options(
+            "one\ntwo\n\nthree",
+            TokenizerOptions {
+                emit_breaks: true,
+                ..Default::default()
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(word_count(&tokens), 3);
+    }
+
+    #[test]
+    pub fn reading_time_wpm() {
+        let tokens = tokenize(&"word ".repeat(450));
+        assert_eq!(reading_time(&tokens, 200), Duration::from_secs(135));
+        assert_eq!(reading_time(&tokenize(""), 200), Duration::ZERO);
+    }
+
+    #[test]
+    pub fn estimate_len_exact() {
+        let resolver = testing::MapResolver::new()
+            .label(Tag::User(1), "alice")
+            .label(Tag::Article(9), "Launch día");
+        let options = TokenizerOptions {
+            emit_breaks: true,
+            ..Default::default()
+        };
+        let input = "hé [user:1] wrote [article:9]\n\nsee [link:https://a.com] 🦀";
+        let tokens = TokenIter::with_options(input, options)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let model = LenModel::new(0).exact(|tag: &Tag| {
+            let resolved = resolver.resolve(tag).or_else(|| tag.fallback())?;
+            Some(resolved.label.chars().count())
+        });
+        let rendered = render_plain(&tokens, &resolver);
+        assert_eq!(estimate_len(&tokens, &model), rendered.chars().count());
+
+        let model = LenModel::new(4).kind(TagKind::User, 10);
+        // 9 chars of text, a mention of 11 and an article of 4.
+        assert_eq!(
+            estimate_len(&tokenize("hi [user:1] and [article:9]!"), &model),
+            24
+        );
+    }
+
+    #[test]
+    pub fn exceeds_stops_early() {
+        let tokens = tokenize(&"some text [user:1] ".repeat(10_000));
+        let model = LenModel::new(6);
+        let read = core::cell::Cell::new(0);
+        let counted = tokens.iter().inspect(|_| read.set(read.get() + 1));
+        assert!(exceeds(counted, 50, &model));
+        assert_eq!(read.get(), 6);
+        assert!(!exceeds(&tokens, estimate_len(&tokens, &model), &model));
+        assert!(exceeds(&tokens, estimate_len(&tokens, &model) - 1, &model));
+    }
+}
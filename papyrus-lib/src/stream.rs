@@ -0,0 +1,116 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::io::AsyncBufRead;
+
+use crate::{PushParser, ReadErr, Token, TokenizerOptions};
+
+/// Tokenizes an async reader incrementally, like [`crate::ReadTokenIter`] does a blocking one.
+///
+/// The reader is only read when the stream is polled and the buffered input holds no
+/// complete token, so a slow consumer slows down reading too.
+pub struct TokenStream<R> {
+    reader: R,
+    parser: PushParser,
+    eof: bool,
+}
+
+impl<R: AsyncBufRead + Unpin> TokenStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, TokenizerOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: TokenizerOptions) -> Self {
+        Self {
+            reader,
+            parser: PushParser::with_options(options),
+            eof: false,
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for TokenStream<R> {
+    type Item = Result<Token, ReadErr>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(token) = this.parser.next_token(this.eof) {
+                return Poll::Ready(Some(token.map_err(ReadErr::Tokenize)));
+            }
+            if this.eof {
+                return Poll::Ready(None);
+            }
+            let mut reader = Pin::new(&mut this.reader);
+            let chunk = match ready!(reader.as_mut().poll_fill_buf(cx)) {
+                Ok(chunk) => chunk,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Poll::Ready(Some(Err(ReadErr::Io(e)))),
+            };
+            let len = chunk.len();
+            this.eof = len == 0;
+            this.parser.push(chunk);
+            reader.consume(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::poll_fn, pin::Pin};
+
+    use futures_core::Stream;
+    use tokio::io::{AsyncWriteExt, BufReader};
+
+    use crate::*;
+
+    async fn collect<S: Stream + Unpin>(mut stream: S) -> Vec<S::Item> {
+        let mut items = Vec::new();
+        while let Some(item) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            items.push(item);
+        }
+        items
+    }
+
+    #[tokio::test]
+    pub async fn stream_matches_sync() {
+        let input = "hi [user:12] wörld 🦀 \\[x\\]\n\n[article: 3] [bad] [user:\n1]\nbye [user:2"
+            .repeat(20)
+            + "]";
+        let expected = TokenIter::new(&*input).collect::<Vec<_>>();
+
+        for seed in 1..=8u64 {
+            // Only a few bytes fit into the pipe, so the writer waits on the stream.
+            let (mut writer, reader) = tokio::io::duplex(8);
+            let bytes = input.clone().into_bytes();
+            let write = tokio::spawn(async move {
+                let mut state = seed;
+                let mut rest = &bytes[..];
+                while !rest.is_empty() {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    let len = (state % 13 + 1).min(rest.len() as u64) as usize;
+                    writer.write_all(&rest[..len]).await.unwrap();
+                    rest = &rest[len..];
+                }
+            });
+            let tokens = collect(TokenStream::new(BufReader::new(reader))).await;
+            write.await.unwrap();
+            let tokens = tokens
+                .into_iter()
+                .map(|t| {
+                    t.map_err(|e| match e {
+                        ReadErr::Tokenize(e) => e,
+                        ReadErr::Io(e) => panic!("{e}"),
+                    })
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(tokens, expected, "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::Display;
+use std::collections::HashMap;
+
+use crate::{normalize, visit::TokenRef, Span, Tag, Token};
+
+/// The variables [`substitute`] has no value for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingVar {
+    /// The name of each variable tag without a value, and its span in the serialized
+    /// tokens, like [`walk`](crate::walk) gives, in order.
+    pub missing: Vec<(String, Span)>,
+}
+
+impl Display for MissingVar {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("missing variables")?;
+        for (i, (name, span)) in self.missing.iter().enumerate() {
+            let separator = if i == 0 { ":" } else { "," };
+            write!(f, "{separator} {name} at {}..{}", span.start, span.end)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingVar {}
+
+/// Replaces every [`Tag::Var`] with a text token of its value, merged with the text around it.
+///
+/// Values are text as they are, so tags in them stay text. Fails with all variables without
+/// a value.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use papyrus_lib::{substitute, tokenize_lossy, Token};
+///
+/// let values = HashMap::from([("name".to_string(), "Ann".to_string())]);
+/// assert_eq!(
+///     substitute(tokenize_lossy("hi [var:name]!"), &values),
+///     Ok(vec![Token::Text("hi Ann!".to_string())])
+/// );
+/// ```
+pub fn substitute<I: IntoIterator<Item = Token>>(
+    tokens: I,
+    values: &HashMap<String, String>,
+) -> Result<Vec<Token>, MissingVar> {
+    let mut out = Vec::new();
+    let mut missing = Vec::new();
+    let mut offset = 0;
+    for token in tokens {
+        let start = offset;
+        offset += TokenRef::from(&token).source_len();
+        match token {
+            Token::Tag(Tag::Var(name)) => match values.get(&name) {
+                Some(value) => out.push(Token::Text(value.clone())),
+                None => missing.push((name, Span::new(start, offset))),
+            },
+            token => out.push(token),
+        }
+    }
+    match missing.is_empty() {
+        true => Ok(normalize(out)),
+        false => Err(MissingVar { missing }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::*;
+
+    fn values() -> HashMap<String, String> {
+        HashMap::from([
+            ("first_name".to_string(), "Ann".to_string()),
+            ("order_id".to_string(), "[user:1]".to_string()),
+        ])
+    }
+
+    #[test]
+    pub fn substitute_all() {
+        let tokens = Tokenizer::default()
+            .tokenize("Hi [var:first_name], [user:2] shipped [var: order_id ].")
+            .unwrap();
+        let substituted = substitute(tokens, &values()).unwrap();
+        assert_eq!(
+            substituted,
+            [
+                Token::Text("Hi Ann, ".to_string()),
+                Token::Tag(Tag::User(2)),
+                Token::Text(" shipped [user:1].".to_string()),
+            ]
+        );
+        assert_eq!(
+            to_source(&substituted),
+            r"Hi Ann, [user:2] shipped \[user:1\]."
+        );
+    }
+
+    #[test]
+    pub fn substitute_missing() {
+        let tokens = tokenize_lossy("[var:first_name] [var:last_name], [var:city]");
+        let e = substitute(tokens, &values()).unwrap_err();
+        assert_eq!(
+            e.missing,
+            [
+                ("last_name".to_string(), Span::new(17, 32)),
+                ("city".to_string(), Span::new(34, 44)),
+            ]
+        );
+        assert_eq!(
+            e.to_string(),
+            "missing variables: last_name at 17..32, city at 34..44"
+        );
+        assert_eq!(
+            "var:first name".parse::<Tag>(),
+            Err(TagParseErr::InvalidPayload("first name".to_string()))
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use core::iter::FusedIterator;
+
+use crate::{
+    scan::{tag_range, Scanned, Scanner},
+    BorrowedToken, Span, Tag, TokenizeErr, Tokenizer,
+};
+
+/// Finds the tags of the input with their spans, like a [`TokenIter`](crate::TokenIter)
+/// keeping only the tags and errors, without making tokens of the text in between.
+///
+/// For questions only tags answer, like whether a document links a private article, over
+/// many documents. Stopping early, like [`Iterator::any`] does, skips the rest of the input.
+///
+/// ```
+/// use papyrus_lib::{scan_tags, Span, Tag};
+///
+/// let input = "a long post, see [article:7] and [user:2]";
+/// assert_eq!(scan_tags(input).next(), Some(Ok((Tag::Article(7), Span::new(17, 28)))));
+/// assert!(scan_tags(input).any(|tag| matches!(tag, Ok((Tag::User(_), _)))));
+/// ```
+pub fn scan_tags(input: &str) -> TagScan<'_> {
+    Tokenizer::default().scan_tags(input)
+}
+
+/// The iterator returned by [`scan_tags`] and [`Tokenizer::scan_tags`].
+#[derive(Clone)]
+pub struct TagScan<'a> {
+    source: &'a str,
+    scanner: Scanner,
+}
+
+impl<'a> TagScan<'a> {
+    pub(crate) fn new(source: &'a str, scanner: Scanner) -> Self {
+        Self { source, scanner }
+    }
+}
+
+impl Iterator for TagScan<'_> {
+    type Item = Result<(Tag, Span), TokenizeErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let source = self.source;
+        loop {
+            let start = self.scanner.offset();
+            // Text is only skipped, its content is never unescaped.
+            let (Scanned::Tag(body), position) = self.scanner.next_lexeme(source.as_bytes())?
+            else {
+                continue;
+            };
+            let span = Span::new(start, self.scanner.offset());
+            let raw = &source[tag_range(body, source.len())];
+            match self.scanner.config().tag(raw, position) {
+                Ok(BorrowedToken::Tag(tag)) => return Some(Ok((tag, span))),
+                // Unknown tags kept as text.
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl FusedIterator for TagScan<'_> {}
+
+/// Counts the tags of the input, failing at the first malformed one like
+/// [`Tokenizer::tokenize`] does, without making tokens.
+///
+/// ```
+/// use papyrus_lib::count_tags;
+///
+/// assert_eq!(count_tags("[user:1] and [article:2]"), Ok(2));
+/// assert!(count_tags("[user:x]").is_err());
+/// ```
+pub fn count_tags(input: &str) -> Result<usize, TokenizeErr> {
+    scan_tags(input).try_fold(0, |count, tag| tag.map(|_| count + 1))
+}
+
+/// Whether the input has a tag that parses, stopping at the first.
+pub fn has_any_tag(input: &str) -> bool {
+    scan_tags(input).any(|tag| tag.is_ok())
+}
+
+/// Counts the tokens [`tokenize_lossy`](crate::tokenize_lossy) makes of the input, without
+/// making them.
+pub fn count_tokens(input: &str) -> usize {
+    let mut scanner = Scanner::new(Tokenizer::default());
+    let mut count = 0;
+    let mut after_text = false;
+    while let Some((lexeme, position)) = scanner.next_lexeme(input.as_bytes()) {
+        let text = match lexeme {
+            Scanned::LineBreak | Scanned::ParagraphBreak => false,
+            Scanned::Text(..) => true,
+            Scanned::Tag(body) => {
+                let raw = &input[tag_range(body, input.len())];
+                // Malformed tags are kept as text.
+                !matches!(
+                    scanner.config().tag::<Tag>(raw, position),
+                    Ok(BorrowedToken::Tag(_))
+                )
+            }
+        };
+        if !(text && after_text) {
+            count += 1;
+        }
+        after_text = text;
+    }
+    count
+}
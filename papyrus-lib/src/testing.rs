@@ -0,0 +1,360 @@
+//! Doubles and helpers for the tests of code that renders documents.
+//!
+//! ```
+//! use papyrus_lib::{render_plain, testing::MapResolver, tokens, Tag};
+//!
+//! let resolver = MapResolver::new()
+//!     .link(Tag::User(5), "alice", "/u/alice")
+//!     .label(Tag::Article(9), "Launch day");
+//! let tokens = tokens!["hi ", user(5), ", read ", article(9)];
+//! assert_eq!(render_plain(&tokens, &resolver), "hi @alice, read Launch day");
+//! ```
+
+use alloc::{string::String, vec::Vec};
+use core::cell::RefCell;
+
+use crate::{dump::write_token, Resolved, Resolver, Tag, Token, TokenIter, TokenizeErr};
+
+/// A resolver knowing a fixed set of tags, everything else is unresolved.
+#[derive(Clone, Debug, Default)]
+pub struct MapResolver {
+    entries: Vec<(Tag, Resolved)>,
+}
+
+impl MapResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves `tag` to `label` linking to `url`, replacing what it resolved to before.
+    pub fn link(self, tag: Tag, label: &str, url: &str) -> Self {
+        self.insert(tag, label, Some(url))
+    }
+
+    /// Resolves `tag` to `label` without a link.
+    pub fn label(self, tag: Tag, label: &str) -> Self {
+        self.insert(tag, label, None)
+    }
+
+    fn insert(mut self, tag: Tag, label: &str, url: Option<&str>) -> Self {
+        let resolved = Resolved {
+            label: label.into(),
+            url: url.map(String::from),
+        };
+        self.entries.retain(|(known, _)| *known != tag);
+        self.entries.push((tag, resolved));
+        self
+    }
+}
+
+impl FromIterator<(Tag, Resolved)> for MapResolver {
+    fn from_iter<I: IntoIterator<Item = (Tag, Resolved)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |resolver, (tag, resolved)| {
+                resolver.insert(tag, &resolved.label, resolved.url.as_deref())
+            })
+    }
+}
+
+impl Resolver for MapResolver {
+    fn resolve(&self, tag: &Tag) -> Option<Resolved> {
+        self.entries
+            .iter()
+            .find(|(known, _)| known == tag)
+            .map(|(_, resolved)| resolved.clone())
+    }
+}
+
+/// Wraps a resolver, recording every tag it's asked to resolve.
+///
+/// ```
+/// use papyrus_lib::{render_html, testing::{MapResolver, RecordingResolver}, tokens, Tag};
+///
+/// let resolver = RecordingResolver::new(MapResolver::new());
+/// render_html(&tokens![user(1), " and ", user(2), " and ", user(1)], &resolver);
+/// assert_eq!(resolver.lookups(), [Tag::User(1), Tag::User(2)]);
+/// ```
+#[derive(Debug, Default)]
+pub struct RecordingResolver<R> {
+    inner: R,
+    lookups: RefCell<Vec<Tag>>,
+}
+
+impl<R> RecordingResolver<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            lookups: RefCell::default(),
+        }
+    }
+
+    /// The tags looked up so far, in order.
+    pub fn lookups(&self) -> Vec<Tag> {
+        self.lookups.borrow().clone()
+    }
+
+    pub fn count(&self) -> usize {
+        self.lookups.borrow().len()
+    }
+
+    pub fn clear(&self) {
+        self.lookups.borrow_mut().clear();
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Resolver> Resolver for RecordingResolver<R> {
+    fn resolve(&self, tag: &Tag) -> Option<Resolved> {
+        self.lookups.borrow_mut().push(tag.clone());
+        self.inner.resolve(tag)
+    }
+
+    fn memoize(&self) -> bool {
+        self.inner.memoize()
+    }
+}
+
+/// Tokenizes input that's known to be valid, panicking if it isn't.
+#[track_caller]
+pub fn tokenize(input: &str) -> Vec<Token> {
+    TokenIter::new(input).collect::<Result<_, _>>().unwrap()
+}
+
+/// A text token.
+pub fn text(text: &str) -> Token {
+    Token::Text(text.into())
+}
+
+/// Builds a `Vec<Token>`, from text and `user(id)`, `article(id)`, `highlight(text)`,
+/// `line_break` and `paragraph_break`.
+///
+/// ```
+/// use papyrus_lib::{tokens, Tag, Token};
+///
+/// assert_eq!(
+///     tokens!["hi ", user(5), line_break],
+///     vec![
+///         Token::Text("hi ".to_string()),
+///         Token::Tag(Tag::User(5)),
+///         Token::LineBreak,
+///     ]
+/// );
+/// ```
+#[macro_export]
+macro_rules! tokens {
+    ($($item:tt $(($($arg:tt)*))?),* $(,)?) => {
+        $crate::__private::vec![$($crate::__token!($item $(($($arg)*))?)),*]
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __token {
+    (user($id:expr)) => {
+        $crate::Token::Tag($crate::Tag::User($id))
+    };
+    (article($id:expr)) => {
+        $crate::Token::Tag($crate::Tag::Article($id))
+    };
+    (highlight($text:expr)) => {
+        $crate::Token::Highlight($crate::__private::String::from($text))
+    };
+    (line_break) => {
+        $crate::Token::LineBreak
+    };
+    (paragraph_break) => {
+        $crate::Token::ParagraphBreak
+    };
+    ($text:expr) => {
+        $crate::Token::Text($crate::__private::String::from($text))
+    };
+}
+
+/// Tokenizes the input and asserts it gives the expected tokens and errors, written like the
+/// lines of [`Document::dump`](crate::Document::dump) without spans.
+///
+/// Tags are their name and payload, like `user 1`, `spoiler` or `/spoiler`, and errors are
+/// `err` with what went wrong in snake case and the position, like `err unknown_tag @ line 2`.
+/// Options for the tokenizer go between the input and the expected tokens.
+///
+/// On a mismatch, the panic shows the expected and actual tokens side by side.
+///
+/// ```
+/// use papyrus_lib::{assert_tokens, TokenizerOptions};
+///
+/// assert_tokens!("hi [user:1]\n[nope]", [text "hi ", user 1, text "\n", err unknown_tag @ line 2]);
+/// assert_tokens!(
+///     "a\n\n[article:2]b",
+///     TokenizerOptions { emit_breaks: true, ..Default::default() },
+///     [text "a", paragraph_break, article 2, text "b"]
+/// );
+/// ```
+#[macro_export]
+macro_rules! assert_tokens {
+    ($input:expr, [$($expected:tt)*] $(,)?) => {
+        $crate::assert_tokens!($input, $crate::TokenizerOptions::default(), [$($expected)*])
+    };
+    ($input:expr, $options:expr, [$($expected:tt)*] $(,)?) => {{
+        let input = $input;
+        $crate::testing::check_tokens(
+            input,
+            $crate::TokenIter::with_options(input, $options),
+            $crate::__expected_tokens!([] $($expected)*),
+        )
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __expected_tokens {
+    ([$($done:expr),*]) => {
+        $crate::__private::vec![$($done),*]
+    };
+    ([$($done:expr),*] , $($rest:tt)*) => {
+        $crate::__expected_tokens!([$($done),*] $($rest)*)
+    };
+    ([$($done:expr),*] text $text:tt $($rest:tt)*) => {
+        $crate::__expected_tokens!(
+            [$($done,)* $crate::__private::format!("text \"{}\"", $text.escape_debug())]
+            $($rest)*
+        )
+    };
+    ([$($done:expr),*] highlight $text:tt $($rest:tt)*) => {
+        $crate::__expected_tokens!(
+            [$($done,)* $crate::__private::format!("highlight \"{}\"", $text.escape_debug())]
+            $($rest)*
+        )
+    };
+    ([$($done:expr),*] err $kind:ident @ line $line:tt $($rest:tt)*) => {
+        $crate::__expected_tokens!(
+            [$($done,)* $crate::__private::format!("err {} @ line {}", stringify!($kind), $line)]
+            $($rest)*
+        )
+    };
+    ([$($done:expr),*] / $name:ident $($rest:tt)*) => {
+        $crate::__expected_tokens!(
+            [$($done,)* $crate::__private::String::from(concat!("/", stringify!($name)))]
+            $($rest)*
+        )
+    };
+    ([$($done:expr),*] $name:ident , $($rest:tt)*) => {
+        $crate::__expected_tokens!(
+            [$($done,)* $crate::__private::String::from(stringify!($name))]
+            , $($rest)*
+        )
+    };
+    ([$($done:expr),*] $name:ident) => {
+        $crate::__expected_tokens!([$($done,)* $crate::__private::String::from(stringify!($name))])
+    };
+    ([$($done:expr),*] $name:ident $payload:tt $($rest:tt)*) => {
+        $crate::__expected_tokens!(
+            [$($done,)* $crate::__private::format!("{} {}", stringify!($name), $payload)]
+            $($rest)*
+        )
+    };
+}
+
+/// What [`assert_tokens!`](crate::assert_tokens) runs, with the expected lines already written.
+#[doc(hidden)]
+#[track_caller]
+pub fn check_tokens(
+    input: &str,
+    tokens: impl IntoIterator<Item = Result<Token, TokenizeErr>>,
+    expected: Vec<String>,
+) {
+    let actual: Vec<String> = tokens
+        .into_iter()
+        .map(|token| {
+            let mut line = String::new();
+            match token {
+                Ok(token) => write_token(&mut line, &token),
+                Err(e) => line = alloc::format!("err {} @ {}", e.code(), e.position()),
+            }
+            line
+        })
+        .collect();
+    if actual == expected {
+        return;
+    }
+    let width = expected.iter().map(|line| line.chars().count()).max();
+    let width = width.unwrap_or(0).max("expected".len());
+    let mut table = alloc::format!("  {:width$} | actual\n", "expected");
+    for i in 0..expected.len().max(actual.len()) {
+        let left = expected.get(i).map_or("", String::as_str);
+        let right = actual.get(i).map_or("", String::as_str);
+        let marker = if expected.get(i) == actual.get(i) {
+            ' '
+        } else {
+            '>'
+        };
+        table.push_str(&alloc::format!("{marker} {left:width$} | {right}\n"));
+    }
+    panic!("tokens of {input:?} differ:\n{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::*, *};
+
+    #[test]
+    pub fn map_resolver_replaces() {
+        let resolver =
+            MapResolver::new()
+                .label(Tag::User(1), "old")
+                .link(Tag::User(1), "new", "/u/1");
+        assert_eq!(
+            resolver.resolve(&Tag::User(1)),
+            Some(Resolved {
+                label: "new".to_string(),
+                url: Some("/u/1".to_string()),
+            })
+        );
+        assert_eq!(resolver.resolve(&Tag::Article(1)), None);
+    }
+
+    #[test]
+    pub fn tokens_macro() {
+        let name = String::from("x");
+        assert_eq!(
+            tokens![
+                "a",
+                user(1 + 1),
+                article(3),
+                highlight("b"),
+                paragraph_break,
+                name
+            ],
+            vec![
+                Token::Text("a".to_string()),
+                Token::Tag(Tag::User(2)),
+                Token::Tag(Tag::Article(3)),
+                Token::Highlight("b".to_string()),
+                Token::ParagraphBreak,
+                Token::Text("x".to_string()),
+            ]
+        );
+        let empty: Vec<Token> = tokens![];
+        assert!(empty.is_empty());
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn assert_tokens_blocks_and_errors() {
+        assert_tokens!(
+            "[quote:2]hi[/quote] [link:https://a.com]\n[article:x]",
+            [quote 2, text "hi", /quote, text " ", link "https://a.com", text "\n", err unknown_tag @ line 2],
+        );
+        assert_tokens!("", []);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "tokens of \"a[user:1]\" differ:\n  expected | actual\n  text \"a\" | text \"a\"\n> user 2   | user 1\n"
+    )]
+    pub fn assert_tokens_shows_diff() {
+        assert_tokens!("a[user:1]", [text "a", user 2]);
+    }
+}
@@ -0,0 +1,1223 @@
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+};
+
+use crate::{
+    canonical, complete, handler, meter, normalize,
+    scan::Scanner,
+    trace::{debug, span},
+    validate, BorrowedToken, ControlChars, Diagnostic, Flow, Lexemes, ParseTag, PartialTag,
+    Position, ScanHandler, Tag, TagParseErr, TagScan, Token, TokenIter, TokenizeErr,
+    TokenizerOptions,
+};
+
+type Parser = dyn Fn(&str) -> Result<String, TagParseErr> + Send + Sync;
+
+#[derive(Default)]
+struct Parsers(Vec<(String, Box<Parser>)>);
+
+impl Parsers {
+    fn get(&self, name: &str) -> Option<&Parser> {
+        self.0
+            .iter()
+            .find(|(known, _)| known == name)
+            .map(|(_, parser)| &**parser)
+    }
+}
+
+impl Debug for Parsers {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set()
+            .entries(self.0.iter().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+/// What the payload of a tag has to look like, see [`TokenizerBuilder::payload_pattern`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PayloadKind {
+    /// Digits, like the ids of users and articles are by default.
+    Integer,
+    /// A ULID, 26 chars of Crockford's base 32 like `01ARZ3NDEKTSV4RRFFQ69G5FAV`.
+    Ulid,
+    /// Lowercase ASCII letters and digits, with single dashes between them.
+    Slug,
+}
+
+impl PayloadKind {
+    pub fn matches(self, payload: &str) -> bool {
+        match self {
+            Self::Integer => !payload.is_empty() && payload.bytes().all(|b| b.is_ascii_digit()),
+            // The first char is at most 7, or the 128 bits would overflow.
+            Self::Ulid => {
+                payload.len() == 26
+                    && payload.starts_with(|c: char| ('0'..='7').contains(&c))
+                    && payload
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() && !b"IiLlOoUu".contains(&b))
+            }
+            Self::Slug => {
+                !payload.is_empty()
+                    && payload
+                        .split('-')
+                        .all(|part| !part.is_empty() && part.bytes().all(is_slug_byte))
+            }
+        }
+    }
+}
+
+fn is_slug_byte(b: u8) -> bool {
+    b.is_ascii_lowercase() || b.is_ascii_digit()
+}
+
+enum Pattern {
+    Kind(PayloadKind),
+    Custom(Box<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl Pattern {
+    fn matches(&self, payload: &str) -> bool {
+        match self {
+            Self::Kind(kind) => kind.matches(payload),
+            Self::Custom(matches) => matches(payload),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Patterns(Vec<(String, Pattern)>);
+
+impl Patterns {
+    fn get(&self, name: &str) -> Option<&Pattern> {
+        self.0
+            .iter()
+            .find(|(known, _)| known == name)
+            .map(|(_, pattern)| pattern)
+    }
+}
+
+impl Debug for Patterns {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut map = f.debug_map();
+        for (name, pattern) in &self.0 {
+            match pattern {
+                Pattern::Kind(kind) => map.entry(name, kind),
+                Pattern::Custom(_) => map.entry(name, &"custom"),
+            };
+        }
+        map.finish()
+    }
+}
+
+type UnknownTagHook = dyn Fn(&str, Position) -> UnknownTagAction + Send + Sync;
+
+// The custom tags and hooks of a tokenizer. Closures can't be compared, so tokenizers only
+// have the same ones if they share them.
+#[derive(Debug)]
+struct Shared<P: ?Sized>(Option<Arc<P>>);
+
+impl<P: ?Sized> Default for Shared<P> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<P: ?Sized> Clone for Shared<P> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<P: ?Sized> PartialEq for Shared<P> {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+}
+
+impl<P: ?Sized> Eq for Shared<P> {}
+
+impl<P: ?Sized> Hash for Shared<P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0
+            .as_ref()
+            .map(|p| Arc::as_ptr(p) as *const ())
+            .hash(state);
+    }
+}
+
+// Closures aren't `Debug`, so only whether there is one is shown.
+struct Hook(Box<UnknownTagHook>);
+
+impl Debug for Hook {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegisterError {
+    /// The name is one of the built-in tags, which always take priority.
+    Builtin(String),
+    Duplicate(String),
+    /// The name is empty or contains a colon or whitespace, so no tag could match it.
+    InvalidName(String),
+    /// An alias or payload pattern is for a name that's neither built-in nor registered.
+    UnknownTarget(String),
+    /// The built-in tag has no payload, or one referring to another tag's, like quotes.
+    FixedPayload(String),
+}
+
+impl Display for RegisterError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Builtin(name) => write!(f, "{name:?} is a built-in tag"),
+            Self::Duplicate(name) => write!(f, "{name:?} is already registered"),
+            Self::InvalidName(name) => write!(f, "{name:?} isn't a valid tag name"),
+            Self::UnknownTarget(name) => write!(f, "{name:?} isn't a known tag"),
+            Self::FixedPayload(name) => write!(f, "the payload of {name:?} can't be changed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RegisterError {}
+
+/// What happens to tags with a name the tokenizer doesn't know.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum UnknownTags {
+    /// Fails with [`TagParseErr::UnknownTag`].
+    #[default]
+    Reject,
+    /// Keeps them as text, exactly as written.
+    Text,
+}
+
+/// What the hook set with [`TokenizerBuilder::on_unknown_tag`] decides for a tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnknownTagAction {
+    /// Fails with [`TagParseErr::UnknownTag`].
+    Error,
+    /// Keeps the tag as text, exactly as written.
+    AsText,
+    /// Puts a token in place of the tag. A [`Token::Highlight`] becomes text, as the
+    /// tokenizer never produces those, and a tag the tag type can't hold, see
+    /// [`ParseTag::from_tag`], fails like [`UnknownTagAction::Error`].
+    Replace(Token),
+}
+
+/// A tokenizer configuration, built once and used for any number of inputs.
+///
+/// Custom tags are shared between clones, so cloning it is cheap.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tokenizer {
+    pub(crate) options: TokenizerOptions,
+    pub(crate) open: u8,
+    pub(crate) close: u8,
+    unknown_tags: UnknownTags,
+    max_tag_len: Option<usize>,
+    /// The ASCII bytes besides whitespace a tag may follow, as a bitset, if tags have to
+    /// start at word boundaries.
+    pub(crate) boundary: Option<u128>,
+    pub(crate) line_start_tags: bool,
+    custom: Shared<Parsers>,
+    on_unknown_tag: Shared<Hook>,
+    payloads: Shared<Patterns>,
+    /// Pairs of an alias and the name it stands for.
+    aliases: Arc<Vec<(String, String)>>,
+    deprecate_aliases: bool,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self {
+            options: TokenizerOptions::default(),
+            open: b'[',
+            close: b']',
+            unknown_tags: UnknownTags::default(),
+            max_tag_len: None,
+            boundary: None,
+            line_start_tags: false,
+            custom: Shared::default(),
+            on_unknown_tag: Shared::default(),
+            payloads: Shared::default(),
+            aliases: Arc::default(),
+            deprecate_aliases: false,
+        }
+    }
+}
+
+impl From<TokenizerOptions> for Tokenizer {
+    fn from(options: TokenizerOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+}
+
+impl Tokenizer {
+    pub fn builder() -> TokenizerBuilder {
+        TokenizerBuilder::default()
+    }
+
+    pub fn iter<'a>(&self, input: &'a str) -> TokenIter<'a> {
+        self.iter_as(input)
+    }
+
+    /// Like [`Tokenizer::iter`], but parsing tags as `T` instead of [`Tag`].
+    pub fn iter_as<'a, T: ParseTag>(&self, input: &'a str) -> TokenIter<'a, T> {
+        TokenIter::resume(input, Scanner::new(self.clone()))
+    }
+
+    /// Finds only the tags of the input, see [`scan_tags`](crate::scan_tags).
+    pub fn scan_tags<'a>(&self, input: &'a str) -> TagScan<'a> {
+        TagScan::new(input, Scanner::new(self.clone()))
+    }
+
+    /// Splits the input into lexemes, see [`lex`](crate::lex).
+    pub fn lex<'a>(&self, input: &'a str) -> Lexemes<'a> {
+        Lexemes::new(input, Scanner::new(self.clone()))
+    }
+
+    /// Finds the tag being typed at the cursor, see
+    /// [`completion_context`](crate::completion_context).
+    pub fn completion_context<'a>(&self, input: &'a str, cursor: usize) -> Option<PartialTag<'a>> {
+        complete::partial_tag(self, input, cursor)
+    }
+
+    /// Rewrites the tags of the input in their canonical form, see [`format`](crate::format).
+    pub fn format(&self, input: &str) -> Result<String, TokenizeErr> {
+        canonical::format_with(self, input)
+    }
+
+    /// Whether [`Tokenizer::format`] would leave the input as it is.
+    pub fn check_format(&self, input: &str) -> Result<bool, TokenizeErr> {
+        Ok(self.format(input)? == input)
+    }
+
+    /// Passes every token to `handler`, see [`scan`](crate::scan).
+    pub fn scan<H: ScanHandler + ?Sized>(&self, input: &str, handler: &mut H) -> Flow {
+        handler::drive(self, input, handler)
+    }
+
+    /// Finds every malformed tag of the input, see [`validate`](crate::validate), and with
+    /// [`TokenizerBuilder::deprecate_aliases`] every alias used.
+    pub fn validate(&self, input: &str) -> Vec<Diagnostic> {
+        validate::diagnostics(self, input)
+    }
+
+    /// Tokenizes all of the input, merging adjacent text, like that of tags kept as text.
+    pub fn tokenize(&self, input: &str) -> Result<Vec<Token>, TokenizeErr> {
+        self.tokenize_as(input)
+    }
+
+    /// Like [`Tokenizer::tokenize`], but parsing tags as `T` instead of [`Tag`].
+    pub fn tokenize_as<T: ParseTag>(&self, input: &str) -> Result<Vec<Token<T>>, TokenizeErr> {
+        let trace = span!("tokenize", input_len = input.len(); tokens, errors);
+        meter::document("tokenize", Some(input.len()));
+        let tokens = self.iter_as(input).collect::<Result<Vec<_>, _>>();
+        match &tokens {
+            Ok(tokens) => trace.record("tokens", tokens.len()),
+            Err(error) => {
+                trace.record("errors", 1);
+                meter::tokenize_err(error);
+                debug!(error = %error, "tokenizing failed");
+            }
+        }
+        let tokens = tokens.map(normalize);
+        if let Ok(tokens) = &tokens {
+            meter::tokens(tokens);
+        }
+        tokens
+    }
+
+    // Turns a tag, including its delimiters, into its token.
+    pub(crate) fn tag<'a, T: ParseTag>(
+        &self,
+        raw: &'a str,
+        position: Position,
+    ) -> Result<BorrowedToken<'a, T>, TokenizeErr> {
+        let closed = raw.len() > 1 && raw.as_bytes()[raw.len() - 1] == self.close;
+        let body = &raw[1..raw.len() - usize::from(closed)];
+        if self.max_tag_len.is_some_and(|max| body.len() > max) {
+            return Err(TokenizeErr::TagTooLong(position));
+        }
+        let source_body = body;
+        let canonical = self.resolve_alias(body);
+        let body = canonical.as_deref().unwrap_or(body);
+        let tag = match self.patterned_tag(body) {
+            Some(tag) => tag,
+            None => match T::parse_tag(body) {
+                Err(TagParseErr::UnknownTag(e)) => self
+                    .custom_tag(body)
+                    .unwrap_or(Err(TagParseErr::UnknownTag(e))),
+                tag => tag,
+            },
+        };
+        let action = match tag {
+            Ok(tag) => return Ok(BorrowedToken::Tag(tag)),
+            Err(TagParseErr::UnknownTag(_)) => match &self.on_unknown_tag.0 {
+                Some(hook) => (hook.0)(body, position),
+                None if self.unknown_tags == UnknownTags::Text => UnknownTagAction::AsText,
+                None => UnknownTagAction::Error,
+            },
+            // Aliases only replace the name, so parts of the payload keep their distance to
+            // the end.
+            Err(e) => {
+                let e = e.moved_into(source_body, |start| start + source_body.len() - body.len());
+                return Err(TokenizeErr::TagErr(position, e));
+            }
+        };
+        let unknown = || TokenizeErr::TagErr(position, TagParseErr::UnknownTag(raw.into()));
+        match action {
+            UnknownTagAction::Error => Err(unknown()),
+            UnknownTagAction::AsText => Ok(BorrowedToken::Text(Cow::Borrowed(raw))),
+            UnknownTagAction::Replace(token) => Ok(match token {
+                Token::Text(text) | Token::Highlight(text) => BorrowedToken::Text(Cow::Owned(text)),
+                Token::Tag(tag) => BorrowedToken::Tag(T::from_tag(tag).ok_or_else(unknown)?),
+                Token::LineBreak => BorrowedToken::LineBreak,
+                Token::ParagraphBreak => BorrowedToken::ParagraphBreak,
+            }),
+        }
+    }
+
+    // The body with an aliased name replaced by the one it stands for, also in closers.
+    fn resolve_alias(&self, body: &str) -> Option<String> {
+        let (name, payload) = split_name(body);
+        let canonical = self.alias_of(name)?;
+        let slash = if body.trim_start().starts_with('/') {
+            "/"
+        } else {
+            ""
+        };
+        Some(format!("{slash}{canonical}{payload}"))
+    }
+
+    fn alias_of(&self, name: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias == name)
+            .map(|(_, canonical)| &**canonical)
+    }
+
+    // The alias and canonical name of a tag using a deprecated alias.
+    pub(crate) fn deprecated_alias<'a>(&'a self, body: &'a str) -> Option<(&'a str, &'a str)> {
+        if !self.deprecate_aliases {
+            return None;
+        }
+        let name = split_name(body).0;
+        Some((name, self.alias_of(name)?))
+    }
+
+    // Checks the payload against the tag's pattern. `None` if it has none, or the tag parses
+    // like it would without one, like numeric ids and custom tags do.
+    fn patterned_tag<T: ParseTag>(&self, body: &str) -> Option<Result<T, TagParseErr>> {
+        let (name, payload) = body.trim().split_once(':')?;
+        let pattern = self.payloads.0.as_ref()?.get(name)?;
+        let payload = payload.trim();
+        if !pattern.matches(payload) {
+            return Some(Err(TagParseErr::InvalidPayload(payload.into())));
+        }
+        let key: fn(String) -> Tag = match name {
+            "user" => Tag::UserKey,
+            "article" => Tag::ArticleKey,
+            _ => return None,
+        };
+        if matches!(pattern, Pattern::Kind(PayloadKind::Integer))
+            || payload.parse::<usize>().is_ok()
+        {
+            return None;
+        }
+        let unknown = || TagParseErr::UnknownTag(format!("[{body}]"));
+        Some(T::from_tag(key(payload.into())).ok_or_else(unknown))
+    }
+
+    // `None` if the name isn't registered, or `T` can't hold custom tags.
+    fn custom_tag<T: ParseTag>(&self, body: &str) -> Option<Result<T, TagParseErr>> {
+        let (name, payload) = body.trim().split_once(':')?;
+        let parser = self.custom.0.as_ref()?.get(name)?;
+        let payload = payload.trim_start();
+        match parser(payload) {
+            Ok(value) => T::from_custom(name.to_string(), value).map(Ok),
+            Err(e) => {
+                let offset = payload.as_ptr() as usize - body.as_ptr() as usize;
+                Some(Err(e.moved_into(body, |start| start + offset)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TokenizerBuilder {
+    tokenizer: Tokenizer,
+    custom: Parsers,
+    payloads: Patterns,
+    aliases: Vec<(String, String)>,
+}
+
+impl TokenizerBuilder {
+    /// Emit break tokens, see [`TokenizerOptions::emit_breaks`].
+    pub fn emit_breaks(mut self, emit_breaks: bool) -> Self {
+        self.tokenizer.options.emit_breaks = emit_breaks;
+        self
+    }
+
+    /// Reads every line ending as `\n`, see [`TokenizerOptions::normalize_newlines`].
+    pub fn normalize_newlines(mut self, normalize_newlines: bool) -> Self {
+        self.tokenizer.options.normalize_newlines = normalize_newlines;
+        self
+    }
+
+    /// Keeps, strips or rejects control characters in text, see [`ControlChars`].
+    pub fn control_chars(mut self, control_chars: ControlChars) -> Self {
+        self.tokenizer.options.control_chars = control_chars;
+        self
+    }
+
+    /// Uses `open` and `close` instead of brackets around tags, and escapes them instead.
+    ///
+    /// # Panics
+    ///
+    /// If they're the same, or either isn't ASCII punctuation or is a backslash.
+    pub fn delimiters(mut self, open: char, close: char) -> Self {
+        let valid = |c: char| c.is_ascii_punctuation() && c != '\\';
+        assert!(
+            valid(open) && valid(close) && open != close,
+            "invalid tag delimiters {open:?} and {close:?}"
+        );
+        self.tokenizer.open = open as u8;
+        self.tokenizer.close = close as u8;
+        self
+    }
+
+    pub fn unknown_tags(mut self, unknown_tags: UnknownTags) -> Self {
+        self.tokenizer.unknown_tags = unknown_tags;
+        self
+    }
+
+    /// Fails with [`TokenizeErr::TagTooLong`] on tags with a body longer than `max` bytes.
+    pub fn max_tag_len(mut self, max: usize) -> Self {
+        self.tokenizer.max_tag_len = Some(max);
+        self
+    }
+
+    /// Only starts tags at the start of the input or after whitespace or one of the ASCII
+    /// characters of `punctuation`, keeping other opening brackets as text, so
+    /// `array[index:0]` isn't read as a tag.
+    ///
+    /// ```
+    /// use papyrus_lib::{Tag, Token, Tokenizer};
+    ///
+    /// let tokenizer = Tokenizer::builder().word_boundary("([").build();
+    /// assert_eq!(
+    ///     tokenizer.tokenize("a[i:0] ([user:1])"),
+    ///     Ok(vec![
+    ///         Token::Text("a[i:0] (".to_string()),
+    ///         Token::Tag(Tag::User(1)),
+    ///         Token::Text(")".to_string()),
+    ///     ])
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `punctuation` isn't ASCII.
+    pub fn word_boundary(mut self, punctuation: &str) -> Self {
+        assert!(
+            punctuation.is_ascii(),
+            "non-ASCII boundary in {punctuation:?}"
+        );
+        let set = punctuation.bytes().fold(0, |set, b| set | 1 << b);
+        self.tokenizer.boundary = Some(set);
+        self
+    }
+
+    /// Only starts tags at the start of a line, after nothing but spaces and tabs, keeping
+    /// other opening brackets as text, for tags that are directives.
+    pub fn line_start_tags(mut self, line_start_tags: bool) -> Self {
+        self.tokenizer.line_start_tags = line_start_tags;
+        self
+    }
+
+    /// Calls `hook` with the body and position of tags of names no parser knows, and handles
+    /// them like it decides, instead of like [`TokenizerBuilder::unknown_tags`] says.
+    pub fn on_unknown_tag<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str, Position) -> UnknownTagAction + Send + Sync + 'static,
+    {
+        self.tokenizer.on_unknown_tag = Shared(Some(Arc::new(Hook(Box::new(hook)))));
+        self
+    }
+
+    /// Parses tags named `name` with `parser`, which gets what follows the colon and returns
+    /// the value of the [`Tag::Custom`].
+    pub fn register<F>(mut self, name: &str, parser: F) -> Result<Self, RegisterError>
+    where
+        F: Fn(&str) -> Result<String, TagParseErr> + Send + Sync + 'static,
+    {
+        self.check_name(name)?;
+        self.custom.0.push((name.into(), Box::new(parser)));
+        Ok(self)
+    }
+
+    /// Parses tags named `alias` like those named `canonical`, a built-in or registered name,
+    /// so `alias("member", "user")` makes `[member:1]` a [`Tag::User`]. Aliases of block tags
+    /// also stand for them in closers, like `[/hidden]` for `[/spoiler]`.
+    pub fn alias(mut self, alias: &str, canonical: &str) -> Result<Self, RegisterError> {
+        if !(is_builtin(canonical) || self.custom.get(canonical).is_some()) {
+            return Err(RegisterError::UnknownTarget(canonical.into()));
+        }
+        self.check_name(alias)?;
+        self.aliases.push((alias.into(), canonical.into()));
+        Ok(self)
+    }
+
+    /// Only accepts payloads of `kind` for the built-in or registered tag `name`, failing with
+    /// [`TagParseErr::InvalidPayload`] on others.
+    ///
+    /// Users and articles with ids that aren't numbers become [`Tag::UserKey`]s and
+    /// [`Tag::ArticleKey`]s, while custom tags are parsed by their parser after the check.
+    ///
+    /// ```
+    /// use papyrus_lib::{PayloadKind, Tag, Token, Tokenizer};
+    ///
+    /// let tokenizer = Tokenizer::builder()
+    ///     .payload_pattern("article", PayloadKind::Ulid)
+    ///     .unwrap()
+    ///     .build();
+    /// assert_eq!(
+    ///     tokenizer.tokenize("[article:01ARZ3NDEKTSV4RRFFQ69G5FAV]"),
+    ///     Ok(vec![Token::Tag(Tag::ArticleKey("01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string()))])
+    /// );
+    /// assert!(tokenizer.tokenize("[article:12]").is_err());
+    /// ```
+    pub fn payload_pattern(self, name: &str, kind: PayloadKind) -> Result<Self, RegisterError> {
+        self.pattern(name, Pattern::Kind(kind))
+    }
+
+    /// Like [`TokenizerBuilder::payload_pattern`], but accepting the payloads `matches`
+    /// accepts.
+    pub fn payload_with<F>(self, name: &str, matches: F) -> Result<Self, RegisterError>
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.pattern(name, Pattern::Custom(Box::new(matches)))
+    }
+
+    fn pattern(mut self, name: &str, pattern: Pattern) -> Result<Self, RegisterError> {
+        match name {
+            "user" | "article" => {}
+            name if is_builtin(name) => return Err(RegisterError::FixedPayload(name.into())),
+            name if self.custom.get(name).is_some() => {}
+            name => return Err(RegisterError::UnknownTarget(name.into())),
+        }
+        if self.payloads.get(name).is_some() {
+            return Err(RegisterError::Duplicate(name.into()));
+        }
+        self.payloads.0.push((name.into(), pattern));
+        Ok(self)
+    }
+
+    /// Reports every use of an alias as a warning in [`Tokenizer::validate`].
+    pub fn deprecate_aliases(mut self, deprecate: bool) -> Self {
+        self.tokenizer.deprecate_aliases = deprecate;
+        self
+    }
+
+    // Whether `name` could name another tag.
+    fn check_name(&self, name: &str) -> Result<(), RegisterError> {
+        if name.is_empty() || name.contains(|c: char| c == ':' || c.is_whitespace()) {
+            return Err(RegisterError::InvalidName(name.into()));
+        }
+        if is_builtin(name) {
+            return Err(RegisterError::Builtin(name.into()));
+        }
+        if self.custom.get(name).is_some() || self.aliases.iter().any(|(alias, _)| alias == name) {
+            return Err(RegisterError::Duplicate(name.into()));
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Tokenizer {
+        let custom = (!self.custom.0.is_empty()).then(|| Arc::new(self.custom));
+        let payloads = (!self.payloads.0.is_empty()).then(|| Arc::new(self.payloads));
+        Tokenizer {
+            custom: Shared(custom),
+            payloads: Shared(payloads),
+            aliases: Arc::new(self.aliases),
+            ..self.tokenizer
+        }
+    }
+}
+
+// The names of the built-in tags, with or without the `tag-blocks` feature.
+pub(crate) const BUILTIN_NAMES: [&str; 13] = [
+    "user", "article", "spoiler", "quote", "link", "var", "if", "else", "list", "item", "table",
+    "row", "cell",
+];
+
+fn is_builtin(name: &str) -> bool {
+    BUILTIN_NAMES.contains(&name)
+}
+
+// Splits a tag body into its name, without the slash of a closer, and the rest from the colon.
+fn split_name(body: &str) -> (&str, &str) {
+    let (name, payload) = body.split_at(body.find(':').unwrap_or(body.len()));
+    let name = name.trim();
+    (name.strip_prefix('/').unwrap_or(name), payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::text, *};
+
+    #[test]
+    pub fn tokenizers_diverge() {
+        let strict = Tokenizer::builder().build();
+        let lenient = Tokenizer::builder()
+            .delimiters('{', '}')
+            .unknown_tags(UnknownTags::Text)
+            .build();
+        let input = r"{user:1} [bug:2] {bug:3} \{x\} \[y\]";
+        assert_eq!(
+            strict.tokenize(input),
+            Err(TokenizeErr::TagErr(
+                Position::new(1).with_column(10),
+                TagParseErr::UnknownTag("[bug:2]".to_string())
+            ))
+        );
+        assert_eq!(
+            lenient.tokenize(input),
+            Ok(vec![
+                Token::Tag(Tag::User(1)),
+                text(r" [bug:2] {bug:3} {x} \[y\]"),
+            ])
+        );
+        assert_eq!(strict.tokenize(r"\{x\}"), Ok(vec![text(r"\{x\}")]));
+    }
+
+    #[test]
+    pub fn tokenizer_limits() {
+        let tokenizer = Tokenizer::builder()
+            .max_tag_len(8)
+            .emit_breaks(true)
+            .build();
+        assert_eq!(
+            tokenizer.tokenize("[article:1]\n[user:1]"),
+            Err(TokenizeErr::TagTooLong(Position::new(1)))
+        );
+        assert_eq!(
+            tokenizer.tokenize("a\n[user:1]"),
+            Ok(vec![text("a"), Token::LineBreak, Token::Tag(Tag::User(1))])
+        );
+        assert_eq!(
+            tokenizer.iter("[x]").collect::<Vec<_>>(),
+            TokenIter::new("[x]").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    pub fn tokenizer_word_boundary() {
+        let tokenizer = Tokenizer::builder().word_boundary("(").build();
+        assert_eq!(
+            tokenizer.tokenize("array[index:0]"),
+            Ok(vec![text("array[index:0]")])
+        );
+        assert!(Tokenizer::default().tokenize("array[index:0]").is_err());
+        assert_eq!(
+            tokenizer.tokenize("hello [user:1]"),
+            Ok(vec![text("hello "), Token::Tag(Tag::User(1))])
+        );
+        assert_eq!(
+            tokenizer.tokenize("([user:1])[user:2]\t[user:3]"),
+            Ok(vec![
+                text("("),
+                Token::Tag(Tag::User(1)),
+                text(")[user:2]\t"),
+                Token::Tag(Tag::User(3)),
+            ])
+        );
+        let input = "[user:1]x[user:2] é[user:3]\n[user:4] \\[y]";
+        let forward = tokenizer.iter(input).borrowed().collect::<Vec<_>>();
+        let mut backward = tokenizer.iter(input).borrowed().rev().collect::<Vec<_>>();
+        backward.reverse();
+        assert_eq!(forward, backward);
+        assert_eq!(forward.len(), 4);
+    }
+
+    #[test]
+    pub fn tokenizer_line_start_tags() {
+        let tokenizer = Tokenizer::builder().line_start_tags(true).build();
+        let input = "[user:1] wrote [article:2]\n  \t[article:3]\nsee [x] for\n[user:4][user:5]";
+        assert_eq!(
+            tokenizer.tokenize(input),
+            Ok(vec![
+                Token::Tag(Tag::User(1)),
+                text(" wrote [article:2]\n  \t"),
+                Token::Tag(Tag::Article(3)),
+                text("\nsee [x] for\n"),
+                Token::Tag(Tag::User(4)),
+                text("[user:5]"),
+            ])
+        );
+        let forward = tokenizer.iter(input).borrowed().collect::<Vec<_>>();
+        let mut backward = tokenizer.iter(input).borrowed().rev().collect::<Vec<_>>();
+        backward.reverse();
+        assert_eq!(forward, backward);
+
+        let tokenizer = Tokenizer::builder()
+            .line_start_tags(true)
+            .emit_breaks(true)
+            .build();
+        assert_eq!(
+            tokenizer.tokenize("a [user:1]\n\n [user:2]"),
+            Ok(vec![
+                text("a [user:1]"),
+                Token::ParagraphBreak,
+                text(" "),
+                Token::Tag(Tag::User(2)),
+            ])
+        );
+    }
+
+    #[test]
+    pub fn tokenizer_shared() {
+        fn assert_shareable<T: Send + Sync + 'static>() {}
+        assert_shareable::<Tokenizer>();
+        let tokenizer = std::sync::Arc::new(Tokenizer::default());
+        let worker = std::thread::spawn({
+            let tokenizer = tokenizer.clone();
+            move || tokenizer.tokenize("[user:1]")
+        });
+        assert_eq!(worker.join().unwrap(), tokenizer.tokenize("[user:1]"));
+    }
+
+    fn ticket(payload: &str) -> Result<String, TagParseErr> {
+        let (project, number) = payload
+            .split_once('-')
+            .filter(|(project, _)| project.chars().all(|c| c.is_ascii_uppercase()))
+            .ok_or(TagParseErr::NoCaptures)?;
+        let number = number.parse::<u32>().map_err(|e| {
+            TagParseErr::invalid_part(payload, number, TagParseErr::CaptureParseErr(e))
+        })?;
+        Ok(format!("{project}-{number}"))
+    }
+
+    #[test]
+    pub fn tokenizer_payload_patterns() {
+        let integers = Tokenizer::default();
+        let ulids = Tokenizer::builder()
+            .payload_pattern("article", PayloadKind::Ulid)
+            .unwrap()
+            .build();
+        let input = "[article: 01ARZ3NDEKTSV4RRFFQ69G5FAV] [article:12]";
+        assert!(integers.tokenize(input).is_err());
+        assert_eq!(
+            ulids.tokenize(input),
+            Err(TokenizeErr::TagErr(
+                Position::new(1).with_column(39),
+                TagParseErr::InvalidPayload("12".to_string())
+            ))
+        );
+        assert_eq!(
+            integers.tokenize("[article:12]"),
+            Ok(vec![Token::Tag(Tag::Article(12))])
+        );
+        let tag = Tag::ArticleKey("01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string());
+        assert_eq!(
+            ulids.tokenize(&input[..37]),
+            Ok(vec![Token::Tag(tag.clone())])
+        );
+        assert_eq!(tag.kind(), TagKind::Article);
+        assert_eq!(tag.to_string(), "article:01ARZ3NDEKTSV4RRFFQ69G5FAV");
+
+        let topics = Tokenizer::builder()
+            .register("topic", |slug| Ok(slug.to_string()))
+            .unwrap()
+            .payload_pattern("topic", PayloadKind::Slug)
+            .unwrap()
+            .payload_with("user", |name| {
+                name.starts_with('@') || name.parse::<usize>().is_ok()
+            })
+            .unwrap()
+            .build();
+        assert_eq!(
+            topics.tokenize("[topic:rust-lang] [user:@ann] [user:3]"),
+            Ok(vec![
+                Token::Tag(Tag::Custom {
+                    name: "topic".to_string(),
+                    value: "rust-lang".to_string(),
+                }),
+                text(" "),
+                Token::Tag(Tag::UserKey("@ann".to_string())),
+                text(" "),
+                Token::Tag(Tag::User(3)),
+            ])
+        );
+        assert!(topics.tokenize("[topic:Rust--lang]").is_err());
+
+        let builder = || Tokenizer::builder().payload_pattern("user", PayloadKind::Slug);
+        assert!(builder().is_ok());
+        assert_eq!(
+            builder()
+                .unwrap()
+                .payload_pattern("user", PayloadKind::Ulid)
+                .err(),
+            Some(RegisterError::Duplicate("user".to_string()))
+        );
+        assert_eq!(
+            Tokenizer::builder()
+                .payload_pattern("quote", PayloadKind::Ulid)
+                .err(),
+            Some(RegisterError::FixedPayload("quote".to_string()))
+        );
+        assert_eq!(
+            Tokenizer::builder()
+                .payload_pattern("topic", PayloadKind::Slug)
+                .err(),
+            Some(RegisterError::UnknownTarget("topic".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn tokenizer_custom_tags() {
+        let tokenizer = Tokenizer::builder()
+            .register("ticket", ticket)
+            .unwrap()
+            .build();
+        assert_eq!(
+            tokenizer.tokenize("[user:1] fixed [ticket: PROJ-0123]"),
+            Ok(vec![
+                Token::Tag(Tag::User(1)),
+                text(" fixed "),
+                Token::Tag(Tag::Custom {
+                    name: "ticket".to_string(),
+                    value: "PROJ-123".to_string(),
+                }),
+            ])
+        );
+        assert_eq!(
+            tokenizer.tokenize("ok\n[ticket:proj-1]"),
+            Err(TokenizeErr::TagErr(
+                Position::new(2),
+                TagParseErr::NoCaptures
+            ))
+        );
+        let err = tokenizer.tokenize("ok\n[ticket: PROJ-x]").unwrap_err();
+        assert!(matches!(
+            &err,
+            TokenizeErr::TagErr(_, TagParseErr::InvalidPart { body, span, .. })
+                if body == "ticket: PROJ-x" && *span == Span::new(13, 14)
+        ));
+        assert_eq!(
+            err.invalid_part(),
+            Some((Position::new(2).with_column(15), "x"))
+        );
+        assert_eq!(
+            tokenizer.tokenize("[sku:1]"),
+            Err(TokenizeErr::TagErr(
+                Position::new(1),
+                TagParseErr::UnknownTag("[sku:1]".to_string())
+            ))
+        );
+        assert_eq!(
+            Tokenizer::default().tokenize("[ticket:PROJ-1]"),
+            Tokenizer::builder().build().tokenize("[ticket:PROJ-1]")
+        );
+    }
+
+    #[test]
+    pub fn tokenizer_register_errors() {
+        let builder = Tokenizer::builder().register("ticket", ticket).unwrap();
+        assert_eq!(
+            builder.register("ticket", ticket).unwrap_err(),
+            RegisterError::Duplicate("ticket".to_string())
+        );
+        assert_eq!(
+            Tokenizer::builder().register("user", ticket).unwrap_err(),
+            RegisterError::Builtin("user".to_string())
+        );
+        assert_eq!(
+            Tokenizer::builder().register("a:b", ticket).unwrap_err(),
+            RegisterError::InvalidName("a:b".to_string())
+        );
+    }
+
+    #[test]
+    pub fn tokenizer_unknown_tag_hook() {
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let tokenizer = Tokenizer::builder()
+            .unknown_tags(UnknownTags::Text)
+            .register("ticket", ticket)
+            .unwrap()
+            .on_unknown_tag({
+                let seen = seen.clone();
+                move |raw: &str, position: Position| {
+                    seen.lock().unwrap().push((raw.to_string(), position));
+                    match raw.split_once(':').map(|(name, _)| name.trim()) {
+                        Some("poll") => UnknownTagAction::Replace(Token::Text("(poll)".into())),
+                        Some("wip") => UnknownTagAction::AsText,
+                        _ => UnknownTagAction::Error,
+                    }
+                }
+            })
+            .build();
+        assert_eq!(
+            tokenizer.tokenize("[user:1] [ticket:A-1]\n[ poll :7] [wip:x]"),
+            Ok(vec![
+                Token::Tag(Tag::User(1)),
+                text(" "),
+                Token::Tag(Tag::Custom {
+                    name: "ticket".to_string(),
+                    value: "A-1".to_string(),
+                }),
+                text("\n(poll) [wip:x]"),
+            ])
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                (" poll :7".to_string(), Position::new(2)),
+                ("wip:x".to_string(), Position::new(2).with_column(12)),
+            ]
+        );
+        assert_eq!(
+            tokenizer.tokenize("[wip:x]\n[poll:7]"),
+            Ok(vec![text("[wip:x]\n(poll)")])
+        );
+        assert_eq!(
+            tokenizer.tokenize("ok\n[bug:2]"),
+            Err(TokenizeErr::TagErr(
+                Position::new(2),
+                TagParseErr::UnknownTag("[bug:2]".to_string())
+            ))
+        );
+        let err = tokenizer.tokenize("[user:1x]").unwrap_err();
+        assert!(matches!(
+            &err,
+            TokenizeErr::TagErr(_, TagParseErr::InvalidPart { span, error, .. })
+                if *span == Span::new(5, 7) && matches!(**error, TagParseErr::CaptureParseErr(_))
+        ));
+        assert_eq!(
+            err.invalid_part(),
+            Some((Position::new(1).with_column(7), "1x"))
+        );
+    }
+
+    #[test]
+    pub fn tokenizer_unknown_tag_replaced_by_tag() {
+        let tokenizer = Tokenizer::builder()
+            .on_unknown_tag(|_, _| UnknownTagAction::Replace(Token::Tag(Tag::User(0))))
+            .build();
+        assert_eq!(
+            tokenizer.tokenize("[poll:7]"),
+            Ok(vec![Token::Tag(Tag::User(0))])
+        );
+        assert!(matches!(
+            tokenizer.tokenize_as::<Shop>("[poll:7]"),
+            Err(TokenizeErr::TagErr(_, TagParseErr::UnknownTag(_)))
+        ));
+    }
+
+    #[test]
+    pub fn tokenizer_aliases() {
+        let tokenizer = Tokenizer::builder()
+            .register("ticket", ticket)
+            .unwrap()
+            .alias("member", "user")
+            .unwrap()
+            .alias("issue", "ticket")
+            .unwrap()
+            .build();
+        assert_eq!(
+            tokenizer.tokenize("[ member : 1] [issue:PROJ-2] [user:3]"),
+            Ok(vec![
+                Token::Tag(Tag::User(1)),
+                text(" "),
+                Token::Tag(Tag::Custom {
+                    name: "ticket".to_string(),
+                    value: "PROJ-2".to_string(),
+                }),
+                text(" "),
+                Token::Tag(Tag::User(3)),
+            ])
+        );
+        assert!(matches!(
+            tokenizer.tokenize("[member:x1]"),
+            Err(TokenizeErr::TagErr(_, TagParseErr::UnknownTag(tag))) if tag == "[member:x1]"
+        ));
+        assert!(Tokenizer::default().tokenize("[member:1]").is_err());
+        let err = tokenizer.tokenize("[issue:PROJ-x] [user:3]").unwrap_err();
+        assert_eq!(
+            err.invalid_part(),
+            Some((Position::new(1).with_column(13), "x"))
+        );
+    }
+
+    #[cfg(feature = "tag-blocks")]
+    #[test]
+    pub fn tokenizer_aliases_without_payload() {
+        let tokenizer = Tokenizer::builder()
+            .alias("hidden", "spoiler")
+            .unwrap()
+            .alias("otherwise", "else")
+            .unwrap()
+            .build();
+        assert_eq!(
+            tokenizer.tokenize("[hidden]a[ /hidden ][if:x][otherwise][/if]"),
+            Ok(vec![
+                Token::Tag(Tag::Spoiler),
+                text("a"),
+                Token::Tag(Tag::Close(BlockKind::Spoiler)),
+                Token::Tag(Tag::If("x".to_string())),
+                Token::Tag(Tag::Else),
+                Token::Tag(Tag::Close(BlockKind::If)),
+            ])
+        );
+        assert!(tokenizer.tokenize("[/otherwise]").is_err());
+    }
+
+    #[test]
+    pub fn tokenizer_alias_errors() {
+        let builder = Tokenizer::builder()
+            .register("ticket", ticket)
+            .unwrap()
+            .alias("member", "user")
+            .unwrap();
+        assert_eq!(
+            Tokenizer::builder().alias("user", "article").unwrap_err(),
+            RegisterError::Builtin("user".to_string())
+        );
+        assert_eq!(
+            Tokenizer::builder()
+                .register("ticket", ticket)
+                .unwrap()
+                .alias("ticket", "user")
+                .unwrap_err(),
+            RegisterError::Duplicate("ticket".to_string())
+        );
+        assert_eq!(
+            builder.alias("member", "article").unwrap_err(),
+            RegisterError::Duplicate("member".to_string())
+        );
+        let builder = Tokenizer::builder().alias("member", "user").unwrap();
+        assert_eq!(
+            builder.register("member", ticket).unwrap_err(),
+            RegisterError::Duplicate("member".to_string())
+        );
+        assert_eq!(
+            Tokenizer::builder().alias("member", "person").unwrap_err(),
+            RegisterError::UnknownTarget("person".to_string())
+        );
+        assert_eq!(
+            Tokenizer::builder().alias("m m", "user").unwrap_err(),
+            RegisterError::InvalidName("m m".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid tag delimiters")]
+    pub fn tokenizer_rejects_backslash() {
+        Tokenizer::builder().delimiters('\\', ']');
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Shop {
+        Sku(String),
+        Order(u32),
+    }
+
+    impl core::fmt::Display for Shop {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            match self {
+                Shop::Sku(sku) => write!(f, "sku:{sku}"),
+                Shop::Order(id) => write!(f, "order:{id}"),
+            }
+        }
+    }
+
+    impl ParseTag for Shop {
+        fn parse_tag(body: &str) -> Result<Self, TagParseErr> {
+            match body.split_once(':') {
+                Some(("sku", sku)) => Ok(Shop::Sku(sku.trim().to_string())),
+                Some(("order", id)) => id
+                    .trim()
+                    .parse()
+                    .map(Shop::Order)
+                    .map_err(TagParseErr::CaptureParseErr),
+                _ => Err(TagParseErr::UnknownTag(format!("[{body}]"))),
+            }
+        }
+    }
+
+    impl RenderTag for Shop {
+        fn class(&self) -> &str {
+            match self {
+                Shop::Sku(_) => "sku",
+                Shop::Order(_) => "order",
+            }
+        }
+
+        fn data(&self) -> (&str, &dyn core::fmt::Display) {
+            match self {
+                Shop::Sku(sku) => ("data-sku", sku),
+                Shop::Order(id) => ("data-order", id),
+            }
+        }
+
+        fn prefix(&self) -> &str {
+            "#"
+        }
+    }
+
+    #[test]
+    pub fn tokenizer_custom_tag_type() {
+        let tokenizer = Tokenizer::builder().unknown_tags(UnknownTags::Text).build();
+        let tokens = tokenizer
+            .tokenize_as::<Shop>("[sku: A-1] in [order:7], [user:1]")
+            .unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Tag(Shop::Sku("A-1".to_string())),
+                Token::Text(" in ".to_string()),
+                Token::Tag(Shop::Order(7)),
+                Token::Text(", [user:1]".to_string()),
+            ]
+        );
+        assert!(matches!(
+            Tokenizer::default().tokenize_as::<Shop>("[user:1]"),
+            Err(TokenizeErr::TagErr(_, TagParseErr::UnknownTag(_)))
+        ));
+
+        let resolver = |tag: &Shop| {
+            matches!(tag, Shop::Order(_)).then(|| Resolved {
+                label: tag.to_string(),
+                url: None,
+            })
+        };
+        assert_eq!(
+            render_html(&tokens, &resolver),
+            "<span class=\"sku unresolved\" data-sku=\"A-1\">[sku:A-1]</span> in \
+             <span class=\"order\" data-order=\"7\">#order:7</span>, [user:1]"
+        );
+        assert_eq!(
+            render_plain(&tokens, &resolver),
+            "[sku:A-1] in #order:7, [user:1]"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// Spans and events of the `tracing` feature, which compile to nothing without it.
+
+/// An entered span, left when dropped.
+pub(crate) struct Traced(#[cfg(feature = "tracing")] pub(crate) tracing::span::EnteredSpan);
+
+impl Traced {
+    pub(crate) fn record(&self, _field: &'static str, _value: usize) {
+        #[cfg(feature = "tracing")]
+        self.0.record(_field, _value);
+    }
+}
+
+/// Enters a debug span named `$name` with the given fields, and empty fields for those
+/// recorded later with [`Traced::record`].
+macro_rules! span {
+    ($name:literal $(, $field:ident = $value:expr)* $(; $($later:ident),*)?) => {{
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::Traced(
+            tracing::debug_span!(
+                $name,
+                $($field = $value,)*
+                $($($later = tracing::field::Empty,)*)?
+            )
+            .entered(),
+        );
+        #[cfg(not(feature = "tracing"))]
+        let span = {
+            $(let _ = &$value;)*
+            crate::trace::Traced()
+        };
+        span
+    }};
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+pub(crate) use {debug, span};
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::fmt::{format::FmtSpan, MakeWriter};
+
+    use crate::*;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MakeWriter<'_> for Captured {
+        type Writer = Self;
+
+        fn make_writer(&self) -> Self {
+            self.clone()
+        }
+    }
+
+    fn traced(f: impl FnOnce()) -> String {
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(captured.clone())
+            .with_max_level(tracing::Level::DEBUG)
+            .with_span_events(FmtSpan::CLOSE)
+            .without_time()
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        let output = captured.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    pub fn trace_spans() {
+        let output = traced(|| {
+            Tokenizer::default().tokenize("hi [user:1]").unwrap();
+            Tokenizer::default().tokenize("[bad]").unwrap_err();
+            validate("[usr:1] [article:x]");
+            render_html(&tokenize_lossy("a [b] [user:2]"), &|_: &Tag| None);
+        });
+        let lines = |name: &str| {
+            output
+                .lines()
+                .filter(|line| line.contains(&format!(" {name}{{")) && line.ends_with(" close"))
+                .collect::<Vec<_>>()
+        };
+        let tokenize = lines("tokenize");
+        assert!(tokenize[0].contains("input_len=11 tokens=2"), "{output}");
+        assert!(tokenize[1].contains("input_len=5 errors=1"), "{output}");
+        assert!(
+            lines("validate")[0].contains("errors=2 warnings=0"),
+            "{output}"
+        );
+        assert!(
+            lines("parse_lossy")[0].contains("tokens=2 errors=1"),
+            "{output}"
+        );
+        assert!(output.contains("kept malformed tag as text"), "{output}");
+        assert!(lines("render_html")[0].contains("output_len="), "{output}");
+    }
+}
@@ -0,0 +1,695 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{any::type_name, mem};
+
+use crate::{
+    provenance::{per_token, realign},
+    Tag, Token, TracedToken,
+};
+
+/// Merges adjacent text tokens and drops empty ones.
+pub fn normalize<T, I: IntoIterator<Item = Token<T>>>(tokens: I) -> Vec<Token<T>> {
+    // Collecting a `Vec` reuses its allocation.
+    let mut tokens = tokens.into_iter().collect();
+    normalize_in_place(&mut tokens);
+    tokens
+}
+
+fn normalize_in_place<T>(tokens: &mut Vec<Token<T>>) {
+    let mut len: usize = 0;
+    for read in 0..tokens.len() {
+        match mem::replace(&mut tokens[read], Token::LineBreak) {
+            Token::Text(text) if text.is_empty() => {}
+            Token::Text(text) => match len.checked_sub(1).map(|last| &mut tokens[last]) {
+                Some(Token::Text(last)) => last.push_str(&text),
+                _ => {
+                    tokens[len] = Token::Text(text);
+                    len += 1;
+                }
+            },
+            token => {
+                tokens[len] = token;
+                len += 1;
+            }
+        }
+    }
+    tokens.truncate(len);
+}
+
+/// Iterates over the tags of a token stream, skipping text.
+pub fn tags<'a, I: IntoIterator<Item = &'a Token>>(tokens: I) -> impl Iterator<Item = &'a Tag> {
+    tokens.into_iter().filter_map(Token::as_tag)
+}
+
+/// Applies `f` to the content of every text token, passing tags through untouched.
+///
+/// The result is normalized, so text mapped to an empty string disappears.
+pub fn map_text<I, F>(tokens: I, mut f: F) -> Vec<Token>
+where
+    I: IntoIterator<Item = Token>,
+    F: FnMut(String) -> String,
+{
+    normalize(tokens.into_iter().map(|token| match token {
+        Token::Text(text) => Token::Text(f(text)),
+        token => token,
+    }))
+}
+
+/// Composes text into Unicode NFC, so text typed with decomposed accents compares equal to
+/// the precomposed one. Tags are left as they are.
+#[cfg(feature = "unicode-normalization")]
+pub fn nfc<I: IntoIterator<Item = Token>>(tokens: I) -> Vec<Token> {
+    use unicode_normalization::UnicodeNormalization;
+
+    map_text(tokens, |text| text.nfc().collect())
+}
+
+/// Turns breaks and highlights into text, so only text and tags are left.
+pub fn flatten<I: IntoIterator<Item = Token>>(tokens: I) -> Vec<Token> {
+    normalize(tokens.into_iter().map(flatten_token))
+}
+
+fn flatten_token(token: Token) -> Token {
+    match token {
+        Token::LineBreak => Token::Text("\n".into()),
+        Token::ParagraphBreak => Token::Text("\n\n".into()),
+        Token::Highlight(text) => Token::Text(text),
+        token => token,
+    }
+}
+
+/// Replaces the mentions of `users`, like deleted ones, with `placeholder` text.
+pub fn redact<I: IntoIterator<Item = Token>>(
+    tokens: I,
+    users: &[usize],
+    placeholder: &str,
+) -> Vec<Token> {
+    normalize(
+        tokens
+            .into_iter()
+            .map(|token| redact_token(token, users, placeholder)),
+    )
+}
+
+fn redact_token(token: Token, users: &[usize], placeholder: &str) -> Token {
+    match token {
+        Token::Tag(Tag::User(id)) if users.contains(&id) => Token::Text(placeholder.into()),
+        token => token,
+    }
+}
+
+/// Turns `http` and `https` urls in text into [`Tag::Link`]s, splitting the text around them.
+///
+/// Urls start at a word boundary and end before whitespace, brackets or quotes. Trailing
+/// punctuation isn't part of them, nor are closing parentheses without an opening one in the
+/// url, so `(see https://a.com/x_(y)).` links `https://a.com/x_(y)`. Only text is looked at,
+/// so running it again changes nothing.
+pub fn autolink<I: IntoIterator<Item = Token>>(tokens: I) -> Vec<Token> {
+    let mut out = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Text(text) => link_text(&text, &mut out),
+            token => out.push(token),
+        }
+    }
+    normalize(out)
+}
+
+fn link_text(text: &str, out: &mut Vec<Token>) {
+    let mut linked = 0;
+    let mut search = 0;
+    while let Some(start) = text[search..].find("http").map(|i| search + i) {
+        search = start + "http".len();
+        let at_boundary = !text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric);
+        let Some(rest) = ["https://", "http://"]
+            .into_iter()
+            .find(|scheme| text[start..].starts_with(scheme))
+        else {
+            continue;
+        };
+        let end = start + url_len(&text[start..]);
+        if !at_boundary || end <= start + rest.len() {
+            continue;
+        }
+        out.push(Token::Text(text[linked..start].into()));
+        out.push(Token::Tag(Tag::Link(text[start..end].into())));
+        linked = end;
+        search = end;
+    }
+    out.push(Token::Text(text[linked..].into()));
+}
+
+// The length of the url at the start of `text`.
+fn url_len(text: &str) -> usize {
+    let mut url = text
+        .find(|c: char| c.is_whitespace() || c.is_control() || "<>\"'[]\\`".contains(c))
+        .map_or(text, |end| &text[..end]);
+    loop {
+        let unbalanced = |url: &str| url.matches('(').count() < url.matches(')').count();
+        url = match url.chars().next_back() {
+            Some('.' | ',' | ':' | ';' | '!' | '?') => &url[..url.len() - 1],
+            Some(')') if unbalanced(url) => &url[..url.len() - 1],
+            _ => return url.len(),
+        };
+    }
+}
+
+/// Which rules [`typography_with`] applies, all of them by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TypographyOptions {
+    /// Curls straight quotes, opening them at the start, after whitespace or after opening
+    /// brackets and quotes, and closing them elsewhere, so apostrophes become `’`.
+    pub quotes: bool,
+    /// Turns `--` into an em dash.
+    pub dashes: bool,
+    /// Turns `...` into an ellipsis.
+    pub ellipses: bool,
+}
+
+impl Default for TypographyOptions {
+    fn default() -> Self {
+        Self {
+            quotes: true,
+            dashes: true,
+            ellipses: true,
+        }
+    }
+}
+
+/// Like [`typography_with`], applying every rule.
+///
+/// ```
+/// use papyrus_lib::{to_source, typography, Document};
+///
+/// let doc = Document::parse(r#""don't" -- see [user:1]..."#).unwrap();
+/// assert_eq!(to_source(&typography(doc.into_tokens())), "“don’t” — see [user:1]…");
+/// ```
+pub fn typography<I: IntoIterator<Item = Token>>(tokens: I) -> Vec<Token> {
+    typography_with(tokens, TypographyOptions::default())
+}
+
+/// Typesets quotes, dashes and ellipses in text, leaving tags as they are.
+///
+/// A tag reads like a word, so a quote after it closes, and breaks like whitespace.
+pub fn typography_with<I>(tokens: I, options: TypographyOptions) -> Vec<Token>
+where
+    I: IntoIterator<Item = Token>,
+{
+    // Whether a quote here would open.
+    let mut opens = true;
+    normalize(
+        tokens
+            .into_iter()
+            .map(|token| typeset_token(token, options, &mut opens)),
+    )
+}
+
+fn typeset_token(token: Token, options: TypographyOptions, opens: &mut bool) -> Token {
+    match &token {
+        Token::Text(text) => return Token::Text(typeset(text, options, opens)),
+        Token::LineBreak | Token::ParagraphBreak => *opens = true,
+        Token::Tag(_) | Token::Highlight(_) => *opens = false,
+    }
+    token
+}
+
+fn typeset(text: &str, options: TypographyOptions, opens: &mut bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let (typeset, len) = match c {
+            '"' if options.quotes => (if *opens { '“' } else { '”' }, 1),
+            '\'' if options.quotes => (if *opens { '‘' } else { '’' }, 1),
+            '-' if options.dashes && rest.starts_with("--") => ('—', 2),
+            '.' if options.ellipses && rest.starts_with("...") => ('…', 3),
+            c => (c, c.len_utf8()),
+        };
+        out.push(typeset);
+        *opens = typeset.is_whitespace() || "([{“‘—".contains(typeset);
+        rest = &rest[len..];
+    }
+    out
+}
+
+/// A pass over a token stream, see [`Pipeline`].
+///
+/// Passes get the tokens by value, so they can reuse their allocation.
+pub trait Transform {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token>;
+
+    /// Like [`Transform::apply`], recording the pass in the origin of the tokens it changes or
+    /// makes, see [`Pipeline::apply_traced`].
+    ///
+    /// Unless a pass does it itself, its tokens are matched up with the ones it got by
+    /// equality, in order, and the others are taken as made by it.
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        realign(tokens, type_name::<Self>(), |tokens| self.apply(tokens))
+    }
+
+    /// What the pass is called when inspecting a [`Pipeline`].
+    fn name(&self) -> &str {
+        type_name::<Self>()
+    }
+}
+
+impl<F: Fn(Vec<Token>) -> Vec<Token>> Transform for F {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        self(tokens)
+    }
+}
+
+/// [`normalize`] as a [`Transform`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Normalize;
+
+impl Transform for Normalize {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        normalize(tokens)
+    }
+
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        per_token(tokens, "normalize", |token, out| out.push(token))
+    }
+
+    fn name(&self) -> &str {
+        "normalize"
+    }
+}
+
+/// [`flatten`] as a [`Transform`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Flatten;
+
+impl Transform for Flatten {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        flatten(tokens)
+    }
+
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        per_token(tokens, "flatten", |token, out| {
+            out.push(flatten_token(token))
+        })
+    }
+
+    fn name(&self) -> &str {
+        "flatten"
+    }
+}
+
+/// [`autolink`] as a [`Transform`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Autolink;
+
+impl Transform for Autolink {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        autolink(tokens)
+    }
+
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        per_token(tokens, "autolink", |token, out| match token {
+            Token::Text(text) => link_text(&text, out),
+            token => out.push(token),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "autolink"
+    }
+}
+
+/// [`nfc`] as a [`Transform`].
+#[cfg(feature = "unicode-normalization")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Nfc;
+
+#[cfg(feature = "unicode-normalization")]
+impl Transform for Nfc {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        nfc(tokens)
+    }
+
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        use unicode_normalization::UnicodeNormalization;
+
+        per_token(tokens, "nfc", |token, out| match token {
+            Token::Text(text) => out.push(Token::Text(text.nfc().collect())),
+            token => out.push(token),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "nfc"
+    }
+}
+
+/// [`redact`] as a [`Transform`].
+#[derive(Clone, Debug, Default)]
+pub struct Redact {
+    pub users: Vec<usize>,
+    pub placeholder: String,
+}
+
+impl Transform for Redact {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        redact(tokens, &self.users, &self.placeholder)
+    }
+
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        per_token(tokens, "redact", |token, out| {
+            out.push(redact_token(token, &self.users, &self.placeholder))
+        })
+    }
+
+    fn name(&self) -> &str {
+        "redact"
+    }
+}
+
+/// [`typography_with`] as a [`Transform`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Typography(pub TypographyOptions);
+
+impl Transform for Typography {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        typography_with(tokens, self.0)
+    }
+
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        let mut opens = true;
+        per_token(tokens, "typography", |token, out| {
+            out.push(typeset_token(token, self.0, &mut opens))
+        })
+    }
+
+    fn name(&self) -> &str {
+        "typography"
+    }
+}
+
+/// [`map_text`] as a [`Transform`].
+#[derive(Clone, Copy, Debug)]
+pub struct MapText<F>(pub F);
+
+impl<F: Fn(String) -> String> Transform for MapText<F> {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        map_text(tokens, &self.0)
+    }
+
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        per_token(tokens, "map_text", |token, out| match token {
+            Token::Text(text) => out.push(Token::Text(self.0(text))),
+            token => out.push(token),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "map_text"
+    }
+}
+
+/// Passes run one after another, in the order they were added.
+///
+/// ```
+/// use papyrus_lib::{Document, MapText, Normalize, Pipeline, Redact, Token};
+///
+/// let pipeline = Pipeline::new()
+///     .then(Redact {
+///         users: vec![2],
+///         placeholder: "someone".to_string(),
+///     })
+///     .then(MapText(|text: String| text.to_uppercase()))
+///     .then(Normalize);
+/// assert_eq!(pipeline.names().collect::<Vec<_>>(), ["redact", "map_text", "normalize"]);
+///
+/// let doc = Document::parse("thanks [user:2]!").unwrap();
+/// assert_eq!(
+///     pipeline.apply(doc.tokens().to_vec()),
+///     [Token::Text("THANKS SOMEONE!".to_string())]
+/// );
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Transform + Send + Sync>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pass running after all the others.
+    pub fn then<T: Transform + Send + Sync + 'static>(mut self, transform: T) -> Self {
+        self.stages.push(Box::new(transform));
+        self
+    }
+
+    /// The names of the passes, in the order they run.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.stages.iter().map(|stage| stage.name())
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        self.stages
+            .iter()
+            .fold(tokens, |tokens, stage| stage.apply(tokens))
+    }
+
+    /// Like [`Pipeline::apply`], with each pass recording itself in the [`Origin`] of the
+    /// tokens it changes or makes, for finding out where a token came from.
+    ///
+    /// [`Document::traced_tokens`](crate::Document::traced_tokens) gives the tokens to start
+    /// with. [`Pipeline::apply`] doesn't pay for any of it.
+    ///
+    /// [`Origin`]: crate::Origin
+    pub fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        self.stages
+            .iter()
+            .fold(tokens, |tokens, stage| stage.apply_traced(tokens))
+    }
+}
+
+impl Transform for Pipeline {
+    fn apply(&self, tokens: Vec<Token>) -> Vec<Token> {
+        Pipeline::apply(self, tokens)
+    }
+
+    fn apply_traced(&self, tokens: Vec<TracedToken>) -> Vec<TracedToken> {
+        Pipeline::apply_traced(self, tokens)
+    }
+
+    fn name(&self) -> &str {
+        "pipeline"
+    }
+}
+
+impl core::fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::tokenize, *};
+
+    fn typeset(s: &str) -> String {
+        to_source(&typography(tokenize(s)))
+    }
+
+    #[test]
+    pub fn typography_quotes_across_tags() {
+        assert_eq!(typeset(r#""see [user:1]""#), "“see [user:1]”");
+        assert_eq!(
+            typeset(r#"he said "[user:1]", then left"#),
+            "he said “[user:1]”, then left"
+        );
+        assert_eq!(
+            typeset("'[article:2]' ([user:1]'s)"),
+            "‘[article:2]’ ([user:1]’s)"
+        );
+        assert_eq!(
+            typography(vec![
+                Token::Text("a\"".to_string()),
+                Token::LineBreak,
+                Token::Text("\"b\"".to_string()),
+            ]),
+            [
+                Token::Text("a”".to_string()),
+                Token::LineBreak,
+                Token::Text("“b”".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn typography_apostrophes() {
+        assert_eq!(typeset("don't"), "don’t");
+        assert_eq!(typeset("'it's fine,' she said"), "‘it’s fine,’ she said");
+        assert_eq!(typeset(r#""don't," he wrote"#), "“don’t,” he wrote");
+    }
+
+    #[test]
+    pub fn typography_dashes_and_ellipses() {
+        assert_eq!(typeset("wait -- what... ok"), "wait — what… ok");
+        assert_eq!(typeset("a----b ...."), "a——b ….");
+        // Payloads stay as they are.
+        let link = "[link:https://a.com/it's--y...] -- \"[user:1]\"";
+        assert_eq!(
+            typeset(link),
+            "[link:https://a.com/it's--y...] — “[user:1]”"
+        );
+
+        let options = TypographyOptions {
+            quotes: false,
+            ellipses: false,
+            ..TypographyOptions::default()
+        };
+        let tokens = typography_with(tokenize("\"a\" -- b..."), options);
+        assert_eq!(tokens, [Token::Text("\"a\" — b...".to_string())]);
+        assert_eq!(Typography(options).apply(tokenize("--")), tokenize("—"));
+    }
+
+    #[test]
+    pub fn map_text_uppercase() {
+        assert_eq!(
+            map_text(tokenize("hey [user:5], look"), |t| t.to_uppercase()),
+            vec![
+                Token::Text("HEY ".to_string()),
+                Token::Tag(Tag::User(5)),
+                Token::Text(", LOOK".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    pub fn map_text_drops_empty() {
+        assert_eq!(
+            map_text(tokenize("  [user:5]  [article:1]"), |t| t
+                .trim()
+                .to_string()),
+            vec![Token::Tag(Tag::User(5)), Token::Tag(Tag::Article(1))]
+        );
+    }
+
+    #[test]
+    pub fn map_text_merges_neighbours() {
+        let tokens = vec![
+            Token::Text("a".to_string()),
+            Token::Text("b".to_string()),
+            Token::Tag(Tag::User(1)),
+        ];
+        let mapped = map_text(tokens, |t| t.repeat(2));
+        assert_eq!(
+            mapped,
+            vec![Token::Text("aabb".to_string()), Token::Tag(Tag::User(1))]
+        );
+        assert_eq!(tags(&mapped).collect::<Vec<_>>(), vec![&Tag::User(1)]);
+    }
+
+    #[test]
+    pub fn flatten_and_redact() {
+        let tokens = vec![
+            Token::Text("by ".to_string()),
+            Token::Tag(Tag::User(1)),
+            Token::LineBreak,
+            Token::Highlight("and".to_string()),
+            Token::Text(" ".to_string()),
+            Token::Tag(Tag::User(2)),
+        ];
+        assert_eq!(
+            redact(flatten(tokens), &[1], "[deleted]"),
+            vec![
+                Token::Text("by [deleted]\nand ".to_string()),
+                Token::Tag(Tag::User(2)),
+            ]
+        );
+    }
+
+    fn link(url: &str) -> Token {
+        Token::Tag(Tag::Link(url.to_string()))
+    }
+
+    #[test]
+    pub fn autolink_boundaries() {
+        let tokens = autolink(tokenize("Go to https://example.com/a?b=1. Or (see https://en.wikipedia.org/wiki/Rust_(language))!"));
+        assert_eq!(
+            tokens,
+            [
+                Token::Text("Go to ".to_string()),
+                link("https://example.com/a?b=1"),
+                Token::Text(". Or (see ".to_string()),
+                link("https://en.wikipedia.org/wiki/Rust_(language)"),
+                Token::Text(")!".to_string()),
+            ]
+        );
+        assert_eq!(
+            autolink(tokenize(
+                "(http://a.com), xhttp://b.com and http:// [user:1]"
+            )),
+            [
+                Token::Text("(".to_string()),
+                link("http://a.com"),
+                Token::Text("), xhttp://b.com and http:// ".to_string()),
+                Token::Tag(Tag::User(1)),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn autolink_twice() {
+        let tokens = autolink(tokenize("https://a.com, https://b.com\nhttps://c.com"));
+        assert_eq!(
+            tokens,
+            [
+                link("https://a.com"),
+                Token::Text(", ".to_string()),
+                link("https://b.com"),
+                Token::Text("\n".to_string()),
+                link("https://c.com"),
+            ]
+        );
+        assert_eq!(autolink(tokens.clone()), tokens);
+        let source = to_source(&tokens);
+        assert_eq!(tokenize(&source), tokens);
+        assert_eq!(
+            render_html(&tokens[..2], &|_: &Tag| None),
+            "<a class=\"link\" data-url=\"https://a.com\" href=\"https://a.com\">https://a.com</a>, "
+        );
+    }
+
+    #[test]
+    pub fn pipeline_matches_composition() {
+        let tokens = tokenize("hi  [user:1]\n\n[user:2] [article:3]");
+        let shout = |text: String| text.to_uppercase();
+        let pipeline = Pipeline::new()
+            .then(Redact {
+                users: vec![2],
+                placeholder: "someone".to_string(),
+            })
+            .then(MapText(shout))
+            .then(Flatten);
+        assert_eq!(
+            pipeline.apply(tokens.clone()),
+            flatten(map_text(redact(tokens.clone(), &[2], "someone"), shout))
+        );
+        assert_eq!(pipeline.len(), 3);
+        assert_eq!(
+            format!("{pipeline:?}"),
+            "[\"redact\", \"map_text\", \"flatten\"]"
+        );
+
+        let empty = Pipeline::new();
+        assert!(empty.is_empty());
+        assert_eq!(empty.apply(tokens.clone()), tokens);
+    }
+}
@@ -0,0 +1,1024 @@
+use alloc::{format, string::String, vec::Vec};
+use core::{fmt::Display, mem, slice};
+
+use crate::{normalize, Span, Tag, Token, TokenCursor, TokenRef, TokenSource};
+
+/// What a block tag is, named like its tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes)
+)]
+pub enum BlockKind {
+    Spoiler,
+    Quote,
+    If,
+    List,
+    Item,
+    Table,
+    Row,
+    Cell,
+}
+
+impl BlockKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockKind::Spoiler => "spoiler",
+            BlockKind::Quote => "quote",
+            BlockKind::If => "if",
+            BlockKind::List => "list",
+            BlockKind::Item => "item",
+            BlockKind::Table => "table",
+            BlockKind::Row => "row",
+            BlockKind::Cell => "cell",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "spoiler" => Some(BlockKind::Spoiler),
+            "quote" => Some(BlockKind::Quote),
+            "if" => Some(BlockKind::If),
+            "list" => Some(BlockKind::List),
+            "item" => Some(BlockKind::Item),
+            "table" => Some(BlockKind::Table),
+            "row" => Some(BlockKind::Row),
+            "cell" => Some(BlockKind::Cell),
+            _ => None,
+        }
+    }
+}
+
+impl Tag {
+    /// The kind of block the tag opens, `None` for inline tags and closers.
+    pub fn opens(&self) -> Option<BlockKind> {
+        match self {
+            Tag::Spoiler => Some(BlockKind::Spoiler),
+            Tag::Quote(_) => Some(BlockKind::Quote),
+            Tag::If(_) => Some(BlockKind::If),
+            Tag::List { .. } => Some(BlockKind::List),
+            Tag::Item => Some(BlockKind::Item),
+            Tag::Table => Some(BlockKind::Table),
+            Tag::Row { .. } => Some(BlockKind::Row),
+            Tag::Cell => Some(BlockKind::Cell),
+            _ => None,
+        }
+    }
+
+    /// The kind of block the tag has to be right inside, if it does.
+    pub fn parent(&self) -> Option<BlockKind> {
+        match self {
+            Tag::Item => Some(BlockKind::List),
+            Tag::Row { .. } => Some(BlockKind::Table),
+            Tag::Cell => Some(BlockKind::Row),
+            _ => None,
+        }
+    }
+}
+
+/// A token, or a block of them, see [`parse_tree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Text(String),
+    /// An inline tag.
+    Tag(Tag),
+    /// What's between a block tag and its closer. The children of an [`Tag::If`] block
+    /// include the [`Tag::Else`] it may have.
+    Block {
+        tag: Tag,
+        children: Vec<Node>,
+    },
+    LineBreak,
+    ParagraphBreak,
+    Highlight(String),
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        // Dropping the children in turn would take stack per level, so the blocks below are
+        // taken apart here, and each is dropped with no children left.
+        let Node::Block { children, .. } = self else {
+            return;
+        };
+        let mut nodes = mem::take(children);
+        while let Some(mut node) = nodes.pop() {
+            if let Node::Block { children, .. } = &mut node {
+                nodes.append(children);
+            }
+        }
+    }
+}
+
+/// Why block tags don't nest, with the spans of the tags in the serialized tokens, like
+/// [`walk`] gives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreeError {
+    /// A closer without any block open.
+    Unopened { close: BlockKind, span: Span },
+    /// A closer of another kind than the innermost open block, like in
+    /// `[spoiler][quote:1][/spoiler]`.
+    Mismatched {
+        open: Tag,
+        open_span: Span,
+        close: BlockKind,
+        span: Span,
+    },
+    /// A block still open at the end of the tokens.
+    Unclosed { open: Tag, span: Span },
+    /// An `[else]` not right inside an `[if]` block, or a second one in the same block.
+    DanglingElse { span: Span },
+    /// A tag not right inside the kind of block it belongs in, like an `[item]` outside a
+    /// `[list]`, see [`Tag::parent`].
+    Misplaced {
+        tag: Tag,
+        parent: BlockKind,
+        span: Span,
+    },
+    /// A block opened with `limit` blocks open already, see [`TreeOptions::max_depth`].
+    TooDeep { span: Span, limit: usize },
+    /// An `[if]` block on a condition [`evaluate`] was given no value for.
+    UnknownCondition { name: String, span: Span },
+}
+
+impl Display for TreeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Unopened { close, span } => {
+                write!(f, "[/{}] at {} closes nothing", close.name(), span.start)
+            }
+            Self::Mismatched {
+                open,
+                open_span,
+                close,
+                span,
+            } => write!(
+                f,
+                "[/{}] at {} doesn't close [{open}] at {}",
+                close.name(),
+                span.start,
+                open_span.start
+            ),
+            Self::Unclosed { open, span } => {
+                write!(f, "[{open}] at {} is never closed", span.start)
+            }
+            Self::DanglingElse { span } => {
+                write!(
+                    f,
+                    "[else] at {} doesn't belong to an [if] block",
+                    span.start
+                )
+            }
+            Self::Misplaced { tag, parent, span } => write!(
+                f,
+                "[{tag}] at {} isn't in a [{}] block",
+                span.start,
+                parent.name()
+            ),
+            Self::TooDeep { span, limit } => {
+                write!(f, "block at {} is nested deeper than {limit}", span.start)
+            }
+            Self::UnknownCondition { name, span } => {
+                write!(f, "[if:{name}] at {} has an unknown condition", span.start)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TreeError {}
+
+/// How [`parse_tree_with`] nests blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TreeOptions {
+    /// The most blocks open at once, 32 by default.
+    pub max_depth: usize,
+    /// Keeps block tags past `max_depth` as text instead of failing with
+    /// [`TreeError::TooDeep`].
+    pub lenient: bool,
+}
+
+impl Default for TreeOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            lenient: false,
+        }
+    }
+}
+
+/// Nests the tokens between block tags and their closers into [`Node::Block`]s, with the
+/// default [`TreeOptions`].
+///
+/// ```
+/// use papyrus_lib::{parse_tree, Document, Node, Tag};
+///
+/// let doc = Document::parse("[spoiler]it was [user:1][/spoiler]").unwrap();
+/// assert_eq!(
+///     parse_tree(doc.tokens()),
+///     Ok(vec![Node::Block {
+///         tag: Tag::Spoiler,
+///         children: vec![Node::Text("it was ".to_string()), Node::Tag(Tag::User(1))],
+///     }])
+/// );
+/// ```
+pub fn parse_tree<'a, S: TokenSource<'a>>(tokens: S) -> Result<Vec<Node>, TreeError> {
+    parse_tree_with(tokens, TreeOptions::default())
+}
+
+/// Like [`parse_tree`], with the given options.
+///
+/// ```
+/// use papyrus_lib::{parse_tree_with, tokenize_lossy, Node, Tag, TreeOptions};
+///
+/// let options = TreeOptions {
+///     max_depth: 1,
+///     lenient: true,
+/// };
+/// let tokens = tokenize_lossy("[spoiler][spoiler]a[/spoiler][/spoiler]");
+/// assert_eq!(
+///     parse_tree_with(&tokens, options),
+///     Ok(vec![Node::Block {
+///         tag: Tag::Spoiler,
+///         children: vec![Node::Text("[spoiler]a[/spoiler]".to_string())],
+///     }])
+/// );
+/// ```
+pub fn parse_tree_with<'a, S: TokenSource<'a>>(
+    tokens: S,
+    options: TreeOptions,
+) -> Result<Vec<Node>, TreeError> {
+    let mut cursor = TokenCursor::new(tokens.token_refs().map(TokenRef::into_owned));
+    // The children of the innermost open block, or the top level nodes.
+    let mut nodes = Vec::new();
+    // The open blocks, with the nodes of the block around each.
+    let mut open: Vec<(Tag, Span, Vec<Node>)> = Vec::new();
+    // The blocks opened past the depth limit and kept as text.
+    let mut flattened: Vec<(Tag, Span)> = Vec::new();
+    loop {
+        if let Some(text) = cursor.eat_text() {
+            push_text(&mut nodes, text);
+            continue;
+        }
+        let Some(token) = cursor.bump() else {
+            break;
+        };
+        let innermost = open.last().and_then(|(tag, _, _)| tag.opens());
+        let node = match token {
+            Token::Tag(tag)
+                if !flattened.is_empty()
+                    && (tag.opens().is_some() || matches!(tag, Tag::Else | Tag::Close(_))) =>
+            {
+                let (tag, span) = (tag.clone(), cursor.span());
+                if let Tag::Close(close) = tag {
+                    let (open, open_span) = flattened.pop().unwrap();
+                    if open.opens() != Some(close) {
+                        return Err(TreeError::Mismatched {
+                            open,
+                            open_span,
+                            close,
+                            span,
+                        });
+                    }
+                } else if tag.opens().is_some() {
+                    flattened.push((tag.clone(), span));
+                }
+                push_text(&mut nodes, &format!("[{tag}]"));
+                continue;
+            }
+            Token::Tag(Tag::Close(close)) => {
+                let (close, span) = (*close, cursor.span());
+                let Some((open, open_span, outer)) = open.pop() else {
+                    return Err(TreeError::Unopened { close, span });
+                };
+                if open.opens() != Some(close) {
+                    return Err(TreeError::Mismatched {
+                        open,
+                        open_span,
+                        close,
+                        span,
+                    });
+                }
+                let mut children = mem::replace(&mut nodes, outer);
+                if let Tag::List { .. } | Tag::Table | Tag::Row { .. } = open {
+                    // Only the items, rows and cells are rendered, not what separates them.
+                    children.retain(|child| match child {
+                        Node::Text(text) => !text.trim().is_empty(),
+                        Node::LineBreak | Node::ParagraphBreak => false,
+                        _ => true,
+                    });
+                }
+                Node::Block {
+                    tag: open,
+                    children,
+                }
+            }
+            Token::Tag(Tag::Else) => {
+                let in_if = matches!(open.last(), Some((Tag::If(_), _, _)));
+                if !in_if || nodes.contains(&Node::Tag(Tag::Else)) {
+                    return Err(TreeError::DanglingElse {
+                        span: cursor.span(),
+                    });
+                }
+                Node::Tag(Tag::Else)
+            }
+            Token::Tag(tag) if tag.parent().is_some_and(|parent| innermost != Some(parent)) => {
+                return Err(TreeError::Misplaced {
+                    tag: tag.clone(),
+                    parent: tag.parent().unwrap(),
+                    span: cursor.span(),
+                });
+            }
+            Token::Tag(tag) if tag.opens().is_some() && open.len() >= options.max_depth => {
+                let (tag, span) = (tag.clone(), cursor.span());
+                if !options.lenient {
+                    let limit = options.max_depth;
+                    return Err(TreeError::TooDeep { span, limit });
+                }
+                push_text(&mut nodes, &format!("[{tag}]"));
+                flattened.push((tag, span));
+                continue;
+            }
+            Token::Tag(tag) if tag.opens().is_some() => {
+                let tag = tag.clone();
+                open.push((tag, cursor.span(), mem::take(&mut nodes)));
+                continue;
+            }
+            Token::Tag(tag) => Node::Tag(tag.clone()),
+            // Text was eaten above.
+            Token::Text(_) => continue,
+            Token::LineBreak => Node::LineBreak,
+            Token::ParagraphBreak => Node::ParagraphBreak,
+            Token::Highlight(text) => Node::Highlight(text.clone()),
+        };
+        nodes.push(node);
+    }
+    match open.into_iter().next() {
+        Some((open, span, _)) => Err(TreeError::Unclosed { open, span }),
+        None => Ok(nodes),
+    }
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    match nodes.last_mut() {
+        Some(Node::Text(last)) => last.push_str(text),
+        _ => nodes.push(Node::Text(text.into())),
+    }
+}
+
+/// Replaces every [`Tag::If`] block with its children before its [`Tag::Else`] if
+/// `condition` gives true for its name, and with those after it if false.
+///
+/// Names `condition` gives `None` for fail with [`TreeError::UnknownCondition`], with the span
+/// of the first such `[if]` in the serialized nodes. That's its span in the tokens they were
+/// parsed from, unless whitespace between list items or table rows was dropped before it.
+///
+/// ```
+/// use papyrus_lib::{evaluate, parse_tree, tokenize_lossy, Node, Span, TreeError};
+///
+/// let tree = parse_tree(&tokenize_lossy("[if:premium]Thanks![else]Upgrade?[/if]")).unwrap();
+/// let condition = |name: &str| (name == "premium").then_some(false);
+/// assert_eq!(evaluate(tree.clone(), &condition), Ok(vec![Node::Text("Upgrade?".into())]));
+/// assert_eq!(
+///     evaluate(tree, &|_: &str| None),
+///     Err(TreeError::UnknownCondition { name: "premium".into(), span: Span::new(0, 12) })
+/// );
+/// ```
+pub fn evaluate<F: Fn(&str) -> Option<bool> + ?Sized>(
+    nodes: Vec<Node>,
+    condition: &F,
+) -> Result<Vec<Node>, TreeError> {
+    // Per block, the nodes left in it, its tag, and the length of its source after them, with
+    // the children evaluated so far in `outs`. An if block's branch has no tag, it goes into
+    // the block around it. `offset` is where the next node starts in the serialized nodes.
+    let mut blocks = vec![(nodes.into_iter(), None, 0)];
+    let mut outs = vec![Vec::new()];
+    let mut offset = 0;
+    while let Some((nodes, _, _)) = blocks.last_mut() {
+        let Some(mut node) = nodes.next() else {
+            let (_, tag, rest) = blocks.pop().unwrap();
+            offset += rest;
+            if let Some(tag) = tag {
+                let children = outs.pop().unwrap();
+                outs.last_mut().unwrap().push(Node::Block { tag, children });
+            }
+            continue;
+        };
+        let out = outs.last_mut().unwrap();
+        if !matches!(node, Node::Block { .. }) {
+            offset += source_len(slice::from_ref(&node));
+        }
+        match &mut node {
+            Node::Block {
+                tag: tag @ Tag::If(_),
+                children,
+            } => {
+                let Tag::If(name) = &*tag else { unreachable!() };
+                let span = Span::new(offset, offset + tag_len(tag));
+                let Some(holds) = condition(name) else {
+                    let name = name.clone();
+                    return Err(TreeError::UnknownCondition { name, span });
+                };
+                let close = tag_len(&Tag::Close(BlockKind::If));
+                let (otherwise, else_len) =
+                    match children.iter().position(|n| *n == Node::Tag(Tag::Else)) {
+                        Some(i) => (children.split_off(i).split_off(1), tag_len(&Tag::Else)),
+                        None => (Vec::new(), 0),
+                    };
+                let (skipped, branch, rest) = if holds {
+                    let rest = else_len + source_len(&otherwise) + close;
+                    (0, mem::take(children), rest)
+                } else {
+                    (source_len(children) + else_len, otherwise, close)
+                };
+                offset = span.end + skipped;
+                blocks.push((branch.into_iter(), None, rest));
+            }
+            Node::Block { tag, children } => {
+                offset += tag_len(tag);
+                let rest = tag.opens().map_or(0, |kind| tag_len(&Tag::Close(kind)));
+                blocks.push((mem::take(children).into_iter(), Some(tag.clone()), rest));
+                outs.push(Vec::new());
+            }
+            // Dropped branches may leave text next to text.
+            Node::Text(text) => match out.last_mut() {
+                Some(Node::Text(last)) => last.push_str(text),
+                _ => out.push(Node::Text(mem::take(text))),
+            },
+            _ => out.push(node),
+        }
+    }
+    Ok(outs.pop().unwrap())
+}
+
+// The length of the nodes serialized like their tokens, with blocks between their tag and its
+// closer.
+fn source_len(nodes: &[Node]) -> usize {
+    let mut len = 0;
+    let mut stack = vec![nodes.iter()];
+    while let Some(nodes) = stack.last_mut() {
+        let Some(node) = nodes.next() else {
+            stack.pop();
+            continue;
+        };
+        len += match node {
+            Node::Text(text) => TokenRef::<Tag>::Text(text).source_len(),
+            Node::Highlight(text) => TokenRef::<Tag>::Highlight(text).source_len(),
+            Node::Tag(tag) => tag_len(tag),
+            Node::LineBreak => TokenRef::<Tag>::LineBreak.source_len(),
+            Node::ParagraphBreak => TokenRef::<Tag>::ParagraphBreak.source_len(),
+            Node::Block { tag, children } => {
+                stack.push(children.iter());
+                tag_len(tag) + tag.opens().map_or(0, |kind| tag_len(&Tag::Close(kind)))
+            }
+        };
+    }
+    len
+}
+
+fn tag_len(tag: &Tag) -> usize {
+    tag.to_string().len() + 2
+}
+
+/// What [`unwrap_blocks`] does with a block.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Unwrap {
+    /// Keeps its children, without the block.
+    #[default]
+    Keep,
+    /// Drops it along with its children.
+    Drop,
+    /// Replaces it with the text.
+    Placeholder(String),
+}
+
+/// What [`unwrap_blocks`] does with blocks of each kind, [`Unwrap::Keep`] unless set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnwrapPolicy {
+    kinds: Vec<(BlockKind, Unwrap)>,
+}
+
+impl UnwrapPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, kind: BlockKind, unwrap: Unwrap) -> Self {
+        self.kinds.retain(|(k, _)| *k != kind);
+        self.kinds.push((kind, unwrap));
+        self
+    }
+
+    pub fn get(&self, kind: BlockKind) -> &Unwrap {
+        self.kinds
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map_or(&Unwrap::Keep, |(_, unwrap)| unwrap)
+    }
+}
+
+/// Flattens nodes back into tokens without their blocks, doing with each block what `policy`
+/// says for its kind, for text that shouldn't have structure, like excerpts.
+///
+/// Blocks inside kept ones are unwrapped by their own rule, inline tags are left as they are.
+/// [`Tag::If`] blocks are meant to be [`evaluate`]d first, kept ones keep both branches and
+/// their [`Tag::Else`].
+///
+/// ```
+/// use papyrus_lib::{parse_tree, tokenize_lossy, unwrap_blocks, BlockKind, Token, Unwrap, UnwrapPolicy};
+///
+/// let tree = parse_tree(&tokenize_lossy("[quote:1]no[/quote]so [spoiler]yes[/spoiler]")).unwrap();
+/// let policy = UnwrapPolicy::new().set(BlockKind::Quote, Unwrap::Drop);
+/// assert_eq!(unwrap_blocks(&tree, &policy), [Token::Text("so yes".to_string())]);
+/// ```
+pub fn unwrap_blocks(nodes: &[Node], policy: &UnwrapPolicy) -> Vec<Token> {
+    let mut out = Vec::new();
+    // The nodes left in each kept block.
+    let mut blocks = vec![nodes.iter()];
+    while let Some(nodes) = blocks.last_mut() {
+        let Some(node) = nodes.next() else {
+            blocks.pop();
+            continue;
+        };
+        out.push(match node {
+            Node::Block { tag, children } => {
+                match tag.opens().map_or(&Unwrap::Keep, |kind| policy.get(kind)) {
+                    Unwrap::Keep => blocks.push(children.iter()),
+                    Unwrap::Drop => {}
+                    Unwrap::Placeholder(text) => out.push(Token::Text(text.clone())),
+                }
+                continue;
+            }
+            Node::Text(text) => Token::Text(text.clone()),
+            Node::Tag(tag) => Token::Tag(tag.clone()),
+            Node::LineBreak => Token::LineBreak,
+            Node::ParagraphBreak => Token::ParagraphBreak,
+            Node::Highlight(text) => Token::Highlight(text.clone()),
+        });
+    }
+    normalize(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn tree(s: &str) -> Result<Vec<Node>, TreeError> {
+        parse_tree(&Document::parse(s).unwrap().tokens().to_vec())
+    }
+
+    fn text(s: &str) -> Node {
+        Node::Text(s.to_string())
+    }
+
+    #[test]
+    pub fn tree_nests_blocks() {
+        assert_eq!(
+            tree("a [quote:3]b [spoiler]c[/spoiler][/quote] [spoiler][/spoiler]"),
+            Ok(vec![
+                text("a "),
+                Node::Block {
+                    tag: Tag::Quote(3),
+                    children: vec![
+                        text("b "),
+                        Node::Block {
+                            tag: Tag::Spoiler,
+                            children: vec![text("c")],
+                        },
+                    ],
+                },
+                text(" "),
+                Node::Block {
+                    tag: Tag::Spoiler,
+                    children: vec![],
+                },
+            ])
+        );
+    }
+
+    #[test]
+    pub fn tree_block_with_inline_tags() {
+        assert_eq!(
+            tree("[spoiler]by [user:1] in [article:2][/spoiler]"),
+            Ok(vec![Node::Block {
+                tag: Tag::Spoiler,
+                children: vec![
+                    text("by "),
+                    Node::Tag(Tag::User(1)),
+                    text(" in "),
+                    Node::Tag(Tag::Article(2)),
+                ],
+            }])
+        );
+    }
+
+    #[test]
+    pub fn tree_errors() {
+        assert_eq!(
+            tree("[spoiler][quote:1][/spoiler][/quote]"),
+            Err(TreeError::Mismatched {
+                open: Tag::Quote(1),
+                open_span: Span::new(9, 18),
+                close: BlockKind::Spoiler,
+                span: Span::new(18, 28),
+            })
+        );
+        assert_eq!(
+            tree("a [/quote]"),
+            Err(TreeError::Unopened {
+                close: BlockKind::Quote,
+                span: Span::new(2, 10),
+            })
+        );
+        let error = tree("[quote:1] [spoiler]a[/spoiler]").unwrap_err();
+        assert_eq!(
+            error,
+            TreeError::Unclosed {
+                open: Tag::Quote(1),
+                span: Span::new(0, 9),
+            }
+        );
+        assert_eq!(error.to_string(), "[quote:1] at 0 is never closed");
+        assert!(Document::parse("[/user]").is_err());
+    }
+
+    fn evaluated(s: &str, known: &[&str]) -> Vec<Node> {
+        evaluate(tree(s).unwrap(), &|name: &str| Some(known.contains(&name))).unwrap()
+    }
+
+    #[test]
+    pub fn evaluate_true_branch() {
+        let input = "a [if:beta]b [user:1][else]c[/if] d";
+        assert_eq!(
+            evaluated(input, &["beta"]),
+            [text("a b "), Node::Tag(Tag::User(1)), text(" d")]
+        );
+        assert_eq!(evaluated("a [if:beta]b[/if]", &["beta"]), [text("a b")]);
+    }
+
+    #[test]
+    pub fn evaluate_false_branch() {
+        assert_eq!(
+            evaluated("a [if:beta]b [user:1][else]c[/if] d", &[]),
+            [text("a c d")]
+        );
+        assert_eq!(evaluated("a [if:beta]b[/if] d", &[]), [text("a  d")]);
+    }
+
+    #[test]
+    pub fn evaluate_nested() {
+        let input =
+            "[if:a][if:b]ab[else]a[/if][spoiler][if:b]b[/if][/spoiler][else][if:b]b[/if][/if]";
+        let spoiler = |children| Node::Block {
+            tag: Tag::Spoiler,
+            children,
+        };
+        assert_eq!(
+            evaluated(input, &["a", "b"]),
+            [text("ab"), spoiler(vec![text("b")])]
+        );
+        assert_eq!(evaluated(input, &["a"]), [text("a"), spoiler(vec![])]);
+        assert_eq!(evaluated(input, &["b"]), [text("b")]);
+        assert_eq!(evaluated(input, &[]), []);
+    }
+
+    #[test]
+    pub fn evaluate_unknown_condition() {
+        let input =
+            "a [if:beta]b\n[else][quote:1]\\[[/quote][/if][spoiler]\\\\[if:gamma]x[/if][/spoiler]";
+        let condition = |name: &str| (name == "beta").then_some(false);
+        let error = evaluate(tree(input).unwrap(), &condition).unwrap_err();
+        assert_eq!(
+            error,
+            TreeError::UnknownCondition {
+                name: "gamma".to_string(),
+                span: Span::new(54, 64),
+            }
+        );
+        assert_eq!(&to_source(&tokenize_lossy(input))[54..64], "[if:gamma]");
+        assert_eq!(
+            error.to_string(),
+            "[if:gamma] at 54 has an unknown condition"
+        );
+
+        let condition = |name: &str| (name == "gamma").then_some(true);
+        assert_eq!(
+            evaluate(tree(input).unwrap(), &condition),
+            Err(TreeError::UnknownCondition {
+                name: "beta".to_string(),
+                span: Span::new(2, 11),
+            })
+        );
+    }
+
+    fn list(ordered: bool, items: Vec<Vec<Node>>) -> Node {
+        let items = items.into_iter().map(|children| Node::Block {
+            tag: Tag::Item,
+            children,
+        });
+        Node::Block {
+            tag: Tag::List { ordered },
+            children: items.collect(),
+        }
+    }
+
+    fn html(nodes: &[Node]) -> String {
+        render_tree_html(nodes, &|_: &Tag| None)
+    }
+
+    #[test]
+    pub fn tree_lists() {
+        let input = "[list]\n  [item]first[/item]\n  [item]second [/item]\n[/list]";
+        let nodes = tree(input).unwrap();
+        assert_eq!(
+            nodes,
+            [list(
+                false,
+                vec![vec![text("first")], vec![text("second ")]]
+            )]
+        );
+        assert_eq!(html(&nodes), "<ul><li>first</li><li>second </li></ul>");
+
+        let nodes = tree("[list:ordered][item]a[/item] [item]b[/item][/list]").unwrap();
+        assert_eq!(nodes, [list(true, vec![vec![text("a")], vec![text("b")]])]);
+        assert_eq!(html(&nodes), "<ol><li>a</li><li>b</li></ol>");
+        assert_eq!(
+            "list:numbered".parse::<Tag>(),
+            Err(TagParseErr::InvalidPayload("numbered".to_string()))
+        );
+    }
+
+    #[test]
+    pub fn tree_list_item_with_tag() {
+        let nodes = tree("[list][item]by [user:3][/item][/list]").unwrap();
+        assert_eq!(
+            nodes,
+            [list(
+                false,
+                vec![vec![text("by "), Node::Tag(Tag::User(3))]]
+            )]
+        );
+        assert_eq!(
+            html(&nodes),
+            "<ul><li>by <span class=\"mention unresolved\" data-user-id=\"3\">[user:3]</span></li></ul>"
+        );
+    }
+
+    fn block(tag: Tag, children: Vec<Node>) -> Node {
+        Node::Block { tag, children }
+    }
+
+    #[test]
+    pub fn tree_tables() {
+        let input = "[table]\n[row][cell]A[/cell][cell]B[/cell][/row]\n[row] [cell]C[/cell][cell]D[/cell][/row]\n[/table]";
+        let row = |a, b| {
+            block(
+                Tag::Row { header: false },
+                vec![
+                    block(Tag::Cell, vec![text(a)]),
+                    block(Tag::Cell, vec![text(b)]),
+                ],
+            )
+        };
+        let nodes = tree(input).unwrap();
+        assert_eq!(
+            nodes,
+            [block(Tag::Table, vec![row("A", "B"), row("C", "D")])]
+        );
+        assert_eq!(
+            html(&nodes),
+            "<table><tr><td>A</td><td>B</td></tr><tr><td>C</td><td>D</td></tr></table>"
+        );
+    }
+
+    #[test]
+    pub fn tree_table_header() {
+        let input =
+            "[table][row:header][cell]Name[/cell][/row][row][cell]by [user:3][/cell][/row][/table]";
+        let nodes = tree(input).unwrap();
+        assert_eq!(
+            nodes,
+            [block(
+                Tag::Table,
+                vec![
+                    block(
+                        Tag::Row { header: true },
+                        vec![block(Tag::Cell, vec![text("Name")])]
+                    ),
+                    block(
+                        Tag::Row { header: false },
+                        vec![block(Tag::Cell, vec![text("by "), Node::Tag(Tag::User(3))])]
+                    ),
+                ]
+            )]
+        );
+        assert_eq!(
+            html(&nodes),
+            "<table><tr><th>Name</th></tr><tr><td>by <span class=\"mention unresolved\" data-user-id=\"3\">[user:3]</span></td></tr></table>"
+        );
+    }
+
+    #[test]
+    pub fn tree_misplaced_table_parts() {
+        assert_eq!(
+            tree("[table][cell]a[/cell][/table]"),
+            Err(TreeError::Misplaced {
+                tag: Tag::Cell,
+                parent: BlockKind::Row,
+                span: Span::new(7, 13),
+            })
+        );
+        let error = tree("[row:header][/row]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[row:header] at 0 isn't in a [table] block"
+        );
+    }
+
+    #[test]
+    pub fn tree_item_outside_list() {
+        let error = tree("a [item]b[/item]").unwrap_err();
+        assert_eq!(
+            error,
+            TreeError::Misplaced {
+                tag: Tag::Item,
+                parent: BlockKind::List,
+                span: Span::new(2, 8)
+            }
+        );
+        assert_eq!(error.to_string(), "[item] at 2 isn't in a [list] block");
+        assert_eq!(
+            tree("[list][item][item][/item][/item][/list]"),
+            Err(TreeError::Misplaced {
+                tag: Tag::Item,
+                parent: BlockKind::List,
+                span: Span::new(12, 18)
+            })
+        );
+    }
+
+    fn nested(depth: usize) -> String {
+        "[spoiler]".repeat(depth) + "x" + &"[/spoiler]".repeat(depth)
+    }
+
+    #[test]
+    pub fn tree_max_depth() {
+        let options = TreeOptions {
+            max_depth: 3,
+            ..Default::default()
+        };
+        assert!(parse_tree_with(&tokenize_lossy(&nested(3)), options).is_ok());
+        let error = parse_tree_with(&tokenize_lossy(&nested(4)), options).unwrap_err();
+        assert_eq!(
+            error,
+            TreeError::TooDeep {
+                span: Span::new(27, 36),
+                limit: 3
+            }
+        );
+        assert_eq!(error.to_string(), "block at 27 is nested deeper than 3");
+        assert!(matches!(
+            tree(&nested(33)),
+            Err(TreeError::TooDeep { limit: 32, .. })
+        ));
+    }
+
+    #[test]
+    pub fn tree_max_depth_lenient() {
+        let options = TreeOptions {
+            max_depth: 1,
+            lenient: true,
+        };
+        let tokens = tokenize_lossy("[quote:1][list][item]a[/item][/list] [user:2][/quote]");
+        assert_eq!(
+            parse_tree_with(&tokens, options),
+            Ok(vec![Node::Block {
+                tag: Tag::Quote(1),
+                children: vec![
+                    text("[list][item]a[/item][/list] "),
+                    Node::Tag(Tag::User(2))
+                ],
+            }])
+        );
+        let tokens = tokenize_lossy("[spoiler][quote:1][/spoiler][/spoiler]");
+        assert!(matches!(
+            parse_tree_with(&tokens, options),
+            Err(TreeError::Mismatched { .. })
+        ));
+    }
+
+    #[test]
+    pub fn tree_deeply_nested() {
+        let depth = 1_000_000;
+        let input = nested(depth);
+        let tokens = tokenize_lossy(&input);
+        assert!(matches!(
+            parse_tree(&tokens),
+            Err(TreeError::TooDeep { .. })
+        ));
+        let lenient = TreeOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let nodes = parse_tree_with(&tokens, lenient).unwrap();
+        assert_eq!(evaluate(nodes, &|_: &str| Some(true)).unwrap().len(), 1);
+
+        let unlimited = TreeOptions {
+            max_depth: usize::MAX,
+            lenient: false,
+        };
+        let nodes = parse_tree_with(&tokens, unlimited).unwrap();
+        let html = html(&nodes);
+        assert!(html.starts_with("<details class=\"spoiler\"><summary>Spoiler</summary><details"));
+        assert_eq!(html.matches("</details>").count(), depth);
+        // Evaluating, unwrapping and dropping the tree don't take stack per level either.
+        let nodes = evaluate(nodes, &|_: &str| Some(true)).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(
+            unwrap_blocks(&nodes, &UnwrapPolicy::new()),
+            [Token::Text("x".to_string())]
+        );
+        drop(nodes);
+
+        let input = "[if:a]".repeat(depth) + "x" + &"[/if]".repeat(depth);
+        let nodes = parse_tree_with(&tokenize_lossy(&input), unlimited).unwrap();
+        assert_eq!(evaluate(nodes, &|_: &str| Some(true)), Ok(vec![text("x")]));
+    }
+
+    #[test]
+    pub fn tree_dangling_else() {
+        let error = tree("a [else] b").unwrap_err();
+        assert_eq!(
+            error,
+            TreeError::DanglingElse {
+                span: Span::new(2, 8)
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "[else] at 2 doesn't belong to an [if] block"
+        );
+        assert_eq!(
+            tree("[if:a][spoiler][else][/spoiler][/if]"),
+            Err(TreeError::DanglingElse {
+                span: Span::new(15, 21)
+            })
+        );
+        assert_eq!(
+            tree("[if:a]x[else]y[else]z[/if]"),
+            Err(TreeError::DanglingElse {
+                span: Span::new(14, 20)
+            })
+        );
+    }
+
+    #[test]
+    pub fn unwrap_spoiler_keeps_mentions() {
+        let tree = tree("see [spoiler]it was [user:1]![/spoiler] ok").unwrap();
+        assert_eq!(
+            unwrap_blocks(&tree, &UnwrapPolicy::new()),
+            [
+                Token::Text("see it was ".to_string()),
+                Token::Tag(Tag::User(1)),
+                Token::Text("! ok".to_string()),
+            ]
+        );
+        let policy =
+            UnwrapPolicy::new().set(BlockKind::Spoiler, Unwrap::Placeholder("[…]".to_string()));
+        assert_eq!(
+            unwrap_blocks(&tree, &policy),
+            [Token::Text("see […] ok".to_string())]
+        );
+    }
+
+    #[test]
+    pub fn unwrap_drops_quotes() {
+        let tree = tree("[quote:2]you said [user:3][/quote]\nI disagree").unwrap();
+        let policy = UnwrapPolicy::new().set(BlockKind::Quote, Unwrap::Drop);
+        assert_eq!(
+            unwrap_blocks(&tree, &policy),
+            [Token::Text("\nI disagree".to_string())]
+        );
+    }
+
+    #[test]
+    pub fn unwrap_nested_blocks() {
+        let tree = tree(
+            "a [spoiler]b [quote:1]c [user:1][/quote] d [spoiler][user:2][/spoiler][/spoiler] e",
+        )
+        .unwrap();
+        let policy = UnwrapPolicy::new()
+            .set(BlockKind::Spoiler, Unwrap::Drop)
+            .set(BlockKind::Spoiler, Unwrap::Keep)
+            .set(BlockKind::Quote, Unwrap::Placeholder("(quote)".to_string()));
+        assert_eq!(policy.get(BlockKind::Spoiler), &Unwrap::Keep);
+        assert_eq!(
+            unwrap_blocks(&tree, &policy),
+            [
+                Token::Text("a b (quote) d ".to_string()),
+                Token::Tag(Tag::User(2)),
+                Token::Text(" e".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use alloc::{string::ToString, vec::Vec};
+use core::fmt::Display;
+
+use crate::{meter, trace::span, Span, TagParseErr, Token, TokenizeErr, Tokenizer};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum Severity {
+    /// The tag doesn't parse.
+    Error,
+    /// The tag parses, but shouldn't be written like that anymore.
+    Warning,
+}
+
+/// A malformed tag found by [`validate`], or a questionable one found by
+/// [`Tokenizer::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Diagnostic {
+    /// The source of the tag, including its brackets, or of the bracket of a
+    /// [`TagParseErr::UnmatchedClose`].
+    pub span: Span,
+    /// The 1-based line of the tag's start.
+    pub line: usize,
+    /// The 1-based column of the tag's start, in chars.
+    pub column: usize,
+    pub severity: Severity,
+    pub error: TagParseErr,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        if self.severity == Severity::Warning {
+            f.write_str("warning: ")?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+/// Finds every malformed tag of the input, instead of stopping at the first one like
+/// [`crate::Document::parse`] does.
+///
+/// Closing brackets in text that aren't escaped get a [`TagParseErr::UnmatchedClose`]
+/// warning, as they're likely what's left of a tag.
+pub fn validate(input: &str) -> Vec<Diagnostic> {
+    Tokenizer::default().validate(input)
+}
+
+pub(crate) fn diagnostics(tokenizer: &Tokenizer, input: &str) -> Vec<Diagnostic> {
+    let trace = span!("validate", input_len = input.len(); errors, warnings);
+    meter::document("validate", Some(input.len()));
+    let mut diagnostics = Vec::new();
+    let mut iter = tokenizer.iter(input);
+    let mut push = |span: Span, severity, error| {
+        let before = &input[..span.start];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        diagnostics.push(Diagnostic {
+            span,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            severity,
+            error,
+        });
+    };
+    loop {
+        let start = iter.byte_offset();
+        let token = iter.next();
+        let span = Span::new(start, iter.byte_offset());
+        match token {
+            None => break,
+            Some(Err(TokenizeErr::TagErr(_, error))) => {
+                meter::error(error.code());
+                push(span, Severity::Error, error)
+            }
+            Some(Ok(Token::Tag(_))) => {
+                // Without its delimiters, the body starts with the name.
+                let raw = &input[span.start + 1..span.end];
+                let body = raw.strip_suffix(char::from(tokenizer.close)).unwrap_or(raw);
+                if let Some((alias, canonical)) = tokenizer.deprecated_alias(body) {
+                    let error = TagParseErr::DeprecatedAlias {
+                        alias: alias.to_string(),
+                        canonical: canonical.to_string(),
+                    };
+                    push(span, Severity::Warning, error);
+                }
+            }
+            Some(Ok(Token::Text(_))) => {
+                for offset in unmatched_closes(tokenizer, &input[span.range()]) {
+                    let span = Span::new(span.start + offset, span.start + offset + 1);
+                    push(span, Severity::Warning, TagParseErr::UnmatchedClose);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    trace.record("errors", errors);
+    trace.record("warnings", diagnostics.len() - errors);
+    diagnostics
+}
+
+// The offsets of the closing delimiters in the source of a text token that aren't escaped.
+// Opening ones always start a tag, so none of these has one.
+fn unmatched_closes<'a>(tokenizer: &Tokenizer, text: &'a str) -> impl Iterator<Item = usize> + 'a {
+    let (open, close) = (tokenizer.open, tokenizer.close);
+    let mut bytes = text.bytes().enumerate();
+    core::iter::from_fn(move || loop {
+        match bytes.next()? {
+            (_, b'\\') => {
+                if let Some((_, c)) = bytes.clone().next() {
+                    if c == open || c == close || c == b'\\' {
+                        bytes.next();
+                    }
+                }
+            }
+            (offset, c) if c == close => return Some(offset),
+            _ => {}
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    pub fn validate_positions() {
+        let input = "fine [user:1]\nbad [usr:2] ü[article:x]\n[user:3]";
+        let diagnostics = validate(input);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (&input[d.span.range()], d.line, d.column))
+                .collect::<Vec<_>>(),
+            vec![("[usr:2]", 2, 5), ("[article:x]", 2, 14)]
+        );
+        assert_eq!(diagnostics[0].to_string(), "2:5: unknown tag [usr:2]");
+        assert!(validate("all [user:1] good").is_empty());
+    }
+
+    #[test]
+    pub fn validate_deprecated_aliases() {
+        let input = "[member:1] [user:2]\n  [member:x]\n[member:3]";
+        let tokenizer = |deprecate| {
+            Tokenizer::builder()
+                .alias("member", "user")
+                .unwrap()
+                .deprecate_aliases(deprecate)
+                .build()
+        };
+        let diagnostics = tokenizer(true).validate(input);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (&input[d.span.range()], d.line, d.column, d.severity))
+                .collect::<Vec<_>>(),
+            vec![
+                ("[member:1]", 1, 1, Severity::Warning),
+                ("[member:x]", 2, 3, Severity::Error),
+                ("[member:3]", 3, 1, Severity::Warning),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "1:1: warning: member is deprecated, use user"
+        );
+        assert_eq!(tokenizer(false).validate(input).len(), 1);
+    }
+
+    #[test]
+    pub fn validate_unmatched_closes() {
+        let warnings = |input: &str| {
+            validate(input)
+                .into_iter()
+                .map(|d| (d.span, d.line, d.column, d.severity, d.error))
+                .collect::<Vec<_>>()
+        };
+        let unmatched = |start, line, column| {
+            (
+                Span::new(start, start + 1),
+                line,
+                column,
+                Severity::Warning,
+                TagParseErr::UnmatchedClose,
+            )
+        };
+        assert_eq!(warnings("done ] here"), [unmatched(5, 1, 6)]);
+        assert_eq!(
+            warnings("hi [user:1]\nthanks user:5] and ]"),
+            [unmatched(25, 2, 14), unmatched(31, 2, 20)]
+        );
+        let diagnostic = &validate("a]")[0];
+        assert_eq!(
+            diagnostic.to_string(),
+            "1:2: warning: closing bracket without a tag opening it"
+        );
+        assert_eq!(diagnostic.message().severity, Severity::Warning);
+
+        assert!(warnings(r"escaped \] and \\\] [user:1]").is_empty());
+        assert_eq!(warnings(r"not escaped \\] here"), [unmatched(14, 1, 15)]);
+        let braces = Tokenizer::builder().delimiters('{', '}').build();
+        assert!(braces.validate("a ] {user:1}").is_empty());
+        assert_eq!(braces.validate("a } b").len(), 1);
+    }
+}
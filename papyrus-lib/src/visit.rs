@@ -0,0 +1,244 @@
+use alloc::string::ToString;
+
+use crate::{BorrowedToken, ParseTag, Span, Tag, Token};
+
+/// A borrowed view of a token, so token streams don't have to be made of owned [`Token`]s to
+/// be walked or rendered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenRef<'a, T = Tag> {
+    Text(&'a str),
+    Tag(T),
+    LineBreak,
+    ParagraphBreak,
+    Highlight(&'a str),
+}
+
+impl<'a, T: ParseTag> TokenRef<'a, T> {
+    // The length of the token's serialized form, see [`crate::to_source`].
+    pub(crate) fn source_len(&self) -> usize {
+        match self {
+            TokenRef::Text(text) | TokenRef::Highlight(text) => {
+                text.len() + text.matches(['[', ']', '\\']).count()
+            }
+            TokenRef::Tag(tag) => tag.to_string().len() + 2,
+            TokenRef::LineBreak => 1,
+            TokenRef::ParagraphBreak => 2,
+        }
+    }
+}
+
+impl<'a, T> TokenRef<'a, T> {
+    pub fn into_owned(self) -> Token<T> {
+        match self {
+            TokenRef::Text(text) => Token::Text(text.into()),
+            TokenRef::Tag(tag) => Token::Tag(tag),
+            TokenRef::LineBreak => Token::LineBreak,
+            TokenRef::ParagraphBreak => Token::ParagraphBreak,
+            TokenRef::Highlight(text) => Token::Highlight(text.into()),
+        }
+    }
+}
+
+impl<'a, T> From<TokenRef<'a, T>> for Token<T> {
+    fn from(token: TokenRef<'a, T>) -> Self {
+        token.into_owned()
+    }
+}
+
+impl<'a, T: Clone> From<&'a Token<T>> for TokenRef<'a, T> {
+    fn from(token: &'a Token<T>) -> Self {
+        match token {
+            Token::Text(text) => TokenRef::Text(text),
+            Token::Tag(tag) => TokenRef::Tag(tag.clone()),
+            Token::LineBreak => TokenRef::LineBreak,
+            Token::ParagraphBreak => TokenRef::ParagraphBreak,
+            Token::Highlight(text) => TokenRef::Highlight(text),
+        }
+    }
+}
+
+impl<'a, T: Clone> From<&'a BorrowedToken<'_, T>> for TokenRef<'a, T> {
+    fn from(token: &'a BorrowedToken<'_, T>) -> Self {
+        match token {
+            BorrowedToken::Text(text) => TokenRef::Text(text),
+            BorrowedToken::Tag(tag) => TokenRef::Tag(tag.clone()),
+            BorrowedToken::LineBreak => TokenRef::LineBreak,
+            BorrowedToken::ParagraphBreak => TokenRef::ParagraphBreak,
+        }
+    }
+}
+
+/// Anything yielding token views, like `&[Token]` or an iterator over archived tokens.
+pub trait TokenSource<'a, T = Tag> {
+    fn token_refs(self) -> impl Iterator<Item = TokenRef<'a, T>>;
+}
+
+impl<'a, T, I> TokenSource<'a, T> for I
+where
+    I: IntoIterator,
+    I::Item: Into<TokenRef<'a, T>>,
+{
+    fn token_refs(self) -> impl Iterator<Item = TokenRef<'a, T>> {
+        self.into_iter().map(Into::into)
+    }
+}
+
+/// Callbacks for walking a token stream, see [`walk`].
+///
+/// Every method has a default, so implementors only handle what they care about. Tag kinds
+/// fall back to [`TokenVisitor::visit_tag`] and breaks and highlights to
+/// [`TokenVisitor::visit_text`], which keeps implementors working when new kinds are added.
+///
+/// Visitors of other tag types than [`Tag`] only get [`TokenVisitor::visit_tag`], unless
+/// their [`ParseTag::accept`] calls other methods.
+pub trait TokenVisitor<T = Tag> {
+    fn visit_text(&mut self, _text: &str, _span: Span) {}
+
+    fn visit_tag(&mut self, _tag: &T, _span: Span) {}
+
+    fn visit_user(&mut self, id: usize, span: Span)
+    where
+        T: From<Tag>,
+    {
+        self.visit_tag(&Tag::User(id).into(), span)
+    }
+
+    fn visit_article(&mut self, id: usize, span: Span)
+    where
+        T: From<Tag>,
+    {
+        self.visit_tag(&Tag::Article(id).into(), span)
+    }
+
+    fn visit_line_break(&mut self, span: Span) {
+        self.visit_text("\n", span)
+    }
+
+    fn visit_paragraph_break(&mut self, span: Span) {
+        self.visit_text("\n\n", span)
+    }
+
+    fn visit_highlight(&mut self, text: &str, span: Span) {
+        self.visit_text(text, span)
+    }
+}
+
+/// Walks tokens that weren't read from a source, which are given the spans of their
+/// serialized form.
+pub fn walk<'a, T, S, V>(tokens: S, visitor: &mut V)
+where
+    T: ParseTag,
+    S: TokenSource<'a, T>,
+    V: TokenVisitor<T> + ?Sized,
+{
+    let mut offset = 0;
+    for token in tokens.token_refs() {
+        let start = offset;
+        offset += token.source_len();
+        visit(token, Span::new(start, offset), visitor);
+    }
+}
+
+pub fn walk_spanned<'a, T, I, V>(tokens: I, visitor: &mut V)
+where
+    T: ParseTag + 'a,
+    I: IntoIterator<Item = (&'a Token<T>, Span)>,
+    V: TokenVisitor<T> + ?Sized,
+{
+    for (token, span) in tokens {
+        visit(token.into(), span, visitor);
+    }
+}
+
+fn visit<T, V>(token: TokenRef<T>, span: Span, visitor: &mut V)
+where
+    T: ParseTag,
+    V: TokenVisitor<T> + ?Sized,
+{
+    match token {
+        TokenRef::Text(text) => visitor.visit_text(text, span),
+        TokenRef::Tag(tag) => tag.accept(visitor, span),
+        TokenRef::LineBreak => visitor.visit_line_break(span),
+        TokenRef::ParagraphBreak => visitor.visit_paragraph_break(span),
+        TokenRef::Highlight(text) => visitor.visit_highlight(text, span),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[derive(Default)]
+    struct Counter {
+        text: usize,
+        tags: usize,
+        users: usize,
+    }
+
+    impl TokenVisitor for Counter {
+        fn visit_text(&mut self, _text: &str, _span: Span) {
+            self.text += 1;
+        }
+
+        fn visit_tag(&mut self, _tag: &Tag, _span: Span) {
+            self.tags += 1;
+        }
+
+        fn visit_user(&mut self, _id: usize, _span: Span) {
+            self.users += 1;
+        }
+    }
+
+    #[derive(Default)]
+    struct Collector(Vec<(String, Span)>);
+
+    impl TokenVisitor for Collector {
+        fn visit_text(&mut self, text: &str, span: Span) {
+            self.0.push((text.to_string(), span));
+        }
+
+        fn visit_tag(&mut self, tag: &Tag, span: Span) {
+            self.0.push((format!("{tag:?}"), span));
+        }
+    }
+
+    #[test]
+    pub fn walk_counting() {
+        let doc = Document::parse("a [user:1] b [article:2][user:3] c").unwrap();
+        let mut counter = Counter::default();
+        doc.walk(&mut counter);
+        assert_eq!((counter.text, counter.tags, counter.users), (3, 1, 2));
+    }
+
+    #[test]
+    pub fn walk_collecting() {
+        let doc = Document::parse(r"\[a\] [user:1] [article: 02]").unwrap();
+        let mut collector = Collector::default();
+        doc.walk(&mut collector);
+        assert_eq!(
+            collector.0,
+            vec![
+                ("[a] ".to_string(), Span::new(0, 6)),
+                ("User(1)".to_string(), Span::new(6, 14)),
+                (" ".to_string(), Span::new(14, 15)),
+                ("Article(2)".to_string(), Span::new(15, 28)),
+            ]
+        );
+
+        let mut collector = Collector::default();
+        walk(doc.tokens(), &mut collector);
+        assert_eq!(
+            collector
+                .0
+                .iter()
+                .map(|(_, span)| *span)
+                .collect::<Vec<_>>(),
+            vec![
+                Span::new(0, 6),
+                Span::new(6, 14),
+                Span::new(14, 15),
+                Span::new(15, 26)
+            ]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! A language server for papyrus markup, with diagnostics of malformed tags, hovers on tags
+//! and document symbols for blocks.
+//!
+//! Documents are synced in full on every change.
+
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{DocumentSymbolRequest, HoverRequest, Request as _},
+    DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentSymbolParams, DocumentSymbolResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, SymbolInformation, SymbolKind,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use papyrus_lib::{validate, BlockKind, Document, Severity, Span, Tag, TagKind, Token};
+
+/// Serves `connection` until the client shuts the server down.
+pub fn run(connection: &Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut documents = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(respond(&documents, request).into())?;
+            }
+            Message::Notification(notification) => {
+                if let Some(params) = sync(&mut documents, notification) {
+                    let publish = Notification::new(PublishDiagnostics::METHOD.into(), params);
+                    connection.sender.send(publish.into())?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+// Updates the documents, returning the diagnostics of the one that changed.
+fn sync(
+    documents: &mut HashMap<Url, String>,
+    notification: Notification,
+) -> Option<PublishDiagnosticsParams> {
+    let (uri, version) = match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params = notification
+                .extract::<DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                .ok()?;
+            let document = params.text_document;
+            documents.insert(document.uri.clone(), document.text);
+            (document.uri, Some(document.version))
+        }
+        DidChangeTextDocument::METHOD => {
+            let mut params = notification
+                .extract::<DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)
+                .ok()?;
+            // With full sync, the last change is the whole text.
+            let text = params.content_changes.pop()?.text;
+            let document = params.text_document;
+            documents.insert(document.uri.clone(), text);
+            (document.uri, Some(document.version))
+        }
+        DidCloseTextDocument::METHOD => {
+            let params = notification
+                .extract::<DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                .ok()?;
+            documents.remove(&params.text_document.uri);
+            return Some(PublishDiagnosticsParams::new(
+                params.text_document.uri,
+                Vec::new(),
+                None,
+            ));
+        }
+        _ => return None,
+    };
+    let text = &documents[&uri];
+    let diagnostics = validate(text)
+        .into_iter()
+        .map(|diagnostic| lsp_types::Diagnostic {
+            range: range(text, diagnostic.span),
+            severity: Some(match diagnostic.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+            }),
+            source: Some("papyrus".into()),
+            message: diagnostic.error.to_string(),
+            ..lsp_types::Diagnostic::default()
+        })
+        .collect();
+    Some(PublishDiagnosticsParams::new(uri, diagnostics, version))
+}
+
+fn respond(documents: &HashMap<Url, String>, request: Request) -> Response {
+    let id = request.id.clone();
+    match request.method.as_str() {
+        HoverRequest::METHOD => match request.extract::<HoverParams>(HoverRequest::METHOD) {
+            Ok((id, params)) => {
+                let position = params.text_document_position_params;
+                let hover = documents
+                    .get(&position.text_document.uri)
+                    .and_then(|text| hover(text, position.position));
+                Response::new_ok(id, hover)
+            }
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        },
+        DocumentSymbolRequest::METHOD => {
+            match request.extract::<DocumentSymbolParams>(DocumentSymbolRequest::METHOD) {
+                Ok((id, params)) => {
+                    let uri = params.text_document.uri;
+                    let symbols = documents.get(&uri).map(|text| symbols(&uri, text));
+                    Response::new_ok(id, symbols)
+                }
+                Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+            }
+        }
+        method => Response::new_err(
+            id,
+            ErrorCode::MethodNotFound as i32,
+            format!("unsupported request {method}"),
+        ),
+    }
+}
+
+fn hover(text: &str, position: Position) -> Option<Hover> {
+    let document = Document::parse_lossy(text);
+    let (Token::Tag(tag), span) = document.token_at(offset(text, position)?)? else {
+        return None;
+    };
+    let source = tag.to_string();
+    let value = match (
+        tag.kind(),
+        source.split_once(':').map(|(_, payload)| payload),
+    ) {
+        (TagKind::Block(kind), _) if matches!(tag, Tag::Close(_)) => {
+            format!("closes a {} block", kind.name())
+        }
+        (TagKind::Block(_), None) if matches!(tag, Tag::Else) => {
+            "starts the else branch of an if block".to_string()
+        }
+        (TagKind::Block(BlockKind::Item), None) => "opens a list item block".to_string(),
+        (TagKind::Block(kind), None) => format!("opens a {} block", kind.name()),
+        (TagKind::Block(BlockKind::If), Some(condition)) => {
+            format!("opens an if block on `{condition}`")
+        }
+        (TagKind::Block(BlockKind::List), Some(_)) => "opens an ordered list block".to_string(),
+        (TagKind::Block(BlockKind::Row), Some(_)) => "opens a header row block".to_string(),
+        (TagKind::Block(kind), Some(id)) => {
+            format!("opens a {} block of article `{id}`", kind.name())
+        }
+        (TagKind::User, Some(id)) => format!("mention of user `{id}`"),
+        (TagKind::Article, Some(id)) => format!("article `{id}`"),
+        (TagKind::Link, Some(url)) => format!("link to `{url}`"),
+        (TagKind::Var, Some(name)) => format!("variable `{name}`"),
+        (TagKind::Custom(name), Some(value)) => format!("custom tag `{name}` of `{value}`"),
+        _ => format!("`{source}`"),
+    };
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(range(text, span)),
+    })
+}
+
+// There are no section tags, so the blocks are what's listed.
+#[allow(deprecated)]
+fn symbols(uri: &Url, text: &str) -> DocumentSymbolResponse {
+    let document = Document::parse_lossy(text);
+    let symbols = document
+        .tokens()
+        .iter()
+        .zip(document.spans())
+        .filter_map(|(token, span)| match token {
+            Token::Tag(tag) if tag.opens().is_some() => Some(SymbolInformation {
+                name: tag.to_string(),
+                kind: SymbolKind::NAMESPACE,
+                tags: None,
+                deprecated: None,
+                location: lsp_types::Location::new(uri.clone(), range(text, *span)),
+                container_name: None,
+            }),
+            _ => None,
+        })
+        .collect();
+    DocumentSymbolResponse::Flat(symbols)
+}
+
+fn range(text: &str, span: Span) -> Range {
+    Range::new(position(text, span.start), position(text, span.end))
+}
+
+// LSP positions count UTF-16 code units from the start of the line.
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+fn offset(text: &str, position: Position) -> Option<usize> {
+    let line_start = match position.line {
+        0 => 0,
+        line => text.match_indices('\n').nth(line as usize - 1)?.0 + 1,
+    };
+    let line = &text[line_start..];
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize || c == '\n' {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
@@ -0,0 +1,149 @@
+use std::thread;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, Notification as _,
+        PublishDiagnostics,
+    },
+    request::{DocumentSymbolRequest, HoverRequest, Initialize, Request as _, Shutdown},
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, DocumentSymbolParams,
+    DocumentSymbolResponse, Hover, HoverContents, HoverParams, InitializeParams, InitializeResult,
+    Position, PublishDiagnosticsParams, Range, TextDocumentContentChangeEvent,
+    TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, Url,
+    VersionedTextDocumentIdentifier,
+};
+use serde_json::Value;
+
+struct Client {
+    connection: Connection,
+    next_id: i32,
+}
+
+impl Client {
+    fn request<P: serde::Serialize>(&mut self, method: &str, params: P) -> Value {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let request = Request::new(id.clone(), method.into(), params);
+        self.connection.sender.send(request.into()).unwrap();
+        match self.connection.receiver.recv().unwrap() {
+            Message::Response(Response {
+                id: response_id,
+                result: Some(result),
+                ..
+            }) if response_id == id => result,
+            message => panic!("unexpected {message:?}"),
+        }
+    }
+
+    fn notify<P: serde::Serialize>(&self, method: &str, params: P) {
+        let notification = Notification::new(method.into(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    fn diagnostics(&self) -> PublishDiagnosticsParams {
+        match self.connection.receiver.recv().unwrap() {
+            Message::Notification(n) if n.method == PublishDiagnostics::METHOD => {
+                serde_json::from_value(n.params).unwrap()
+            }
+            message => panic!("unexpected {message:?}"),
+        }
+    }
+}
+
+#[test]
+pub fn lsp_session() {
+    let (server, client) = Connection::memory();
+    let server = thread::spawn(move || papyrus_lsp::run(&server).unwrap());
+    let mut client = Client {
+        connection: client,
+        next_id: 0,
+    };
+
+    let init: InitializeResult =
+        serde_json::from_value(client.request(Initialize::METHOD, InitializeParams::default()))
+            .unwrap();
+    assert!(init.capabilities.hover_provider.is_some());
+    client.notify(Initialized::METHOD, serde_json::json!({}));
+
+    let uri = Url::parse("file:///notes.txt").unwrap();
+    // `🦀` is two UTF-16 code units, so the bad tag starts at character 4.
+    let text = "🦀 [user:1]\n🦀 [usr:2] [spoiler]x[/spoiler]";
+    client.notify(
+        DidOpenTextDocument::METHOD,
+        DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "papyrus".into(), 1, text.into()),
+        },
+    );
+    let published = client.diagnostics();
+    assert_eq!(published.version, Some(1));
+    assert_eq!(published.diagnostics.len(), 1);
+    assert_eq!(
+        published.diagnostics[0].range,
+        Range::new(Position::new(1, 3), Position::new(1, 10))
+    );
+    assert_eq!(published.diagnostics[0].message, "unknown tag [usr:2]");
+
+    let hover = |client: &mut Client, line, character| {
+        let value = client.request(
+            HoverRequest::METHOD,
+            HoverParams {
+                text_document_position_params: TextDocumentPositionParams::new(
+                    TextDocumentIdentifier::new(uri.clone()),
+                    Position::new(line, character),
+                ),
+                work_done_progress_params: Default::default(),
+            },
+        );
+        serde_json::from_value::<Option<Hover>>(value).unwrap()
+    };
+    let Some(Hover {
+        contents: HoverContents::Markup(markup),
+        range,
+    }) = hover(&mut client, 0, 5)
+    else {
+        panic!("no hover on the mention");
+    };
+    assert_eq!(markup.value, "mention of user `1`");
+    assert_eq!(
+        range,
+        Some(Range::new(Position::new(0, 3), Position::new(0, 11)))
+    );
+    assert_eq!(hover(&mut client, 0, 1), None);
+
+    let symbols = client.request(
+        DocumentSymbolRequest::METHOD,
+        DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        },
+    );
+    let Some(DocumentSymbolResponse::Flat(symbols)) = serde_json::from_value(symbols).unwrap()
+    else {
+        panic!("no symbols");
+    };
+    assert_eq!(
+        symbols.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+        ["spoiler"]
+    );
+
+    client.notify(
+        DidChangeTextDocument::METHOD,
+        DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "fixed [user:2]".into(),
+            }],
+        },
+    );
+    let published = client.diagnostics();
+    assert_eq!(published.version, Some(2));
+    assert!(published.diagnostics.is_empty());
+
+    client.request(Shutdown::METHOD, ());
+    client.notify(Exit::METHOD, ());
+    server.join().unwrap();
+}
@@ -0,0 +1,138 @@
+use papyrus_lib::{Tag, Token, TokenIter};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Ident, LitStr};
+
+/// Tokenizes a markup literal at compile time and expands to the `Vec<Token>` it describes.
+///
+/// Malformed tags are reported as compile errors. A tag payload of the form `{name}`
+/// interpolates the id from the variable `name`, e.g. `papyrus!("[user:{uid}] hi")`.
+#[proc_macro]
+pub fn papyrus(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    expand(&lit)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(lit: &LitStr) -> syn::Result<TokenStream> {
+    let (source, mut interpolations) = extract_interpolations(lit)?;
+
+    let mut tokens = Vec::new();
+    for token in TokenIter::new(&source) {
+        let token = token
+            .map_err(|e| syn::Error::new(lit.span(), format!("malformed papyrus markup: {e}")))?;
+        tokens.push(match token {
+            Token::Text(text) => quote! {
+                ::papyrus_lib::Token::Text(::papyrus_lib::__private::String::from(#text))
+            },
+            Token::Tag(tag) => {
+                let interpolation = interpolations.remove(0);
+                let id = |id: usize| match &interpolation {
+                    Some(ident) => quote!(#ident),
+                    None => quote!(#id),
+                };
+                let tag = match tag {
+                    Tag::User(user) => {
+                        let id = id(user);
+                        quote!(::papyrus_lib::Tag::User(#id))
+                    }
+                    Tag::Article(article) => {
+                        let id = id(article);
+                        quote!(::papyrus_lib::Tag::Article(#id))
+                    }
+                    Tag::Quote(article) => {
+                        let id = id(article);
+                        quote!(::papyrus_lib::Tag::Quote(#id))
+                    }
+                    Tag::Spoiler => quote!(::papyrus_lib::Tag::Spoiler),
+                    Tag::Link(url) => quote! {
+                        ::papyrus_lib::Tag::Link(::papyrus_lib::__private::String::from(#url))
+                    },
+                    Tag::Var(name) => quote! {
+                        ::papyrus_lib::Tag::Var(::papyrus_lib::__private::String::from(#name))
+                    },
+                    Tag::If(condition) => quote! {
+                        ::papyrus_lib::Tag::If(::papyrus_lib::__private::String::from(#condition))
+                    },
+                    Tag::Else => quote!(::papyrus_lib::Tag::Else),
+                    Tag::List { ordered } => quote!(::papyrus_lib::Tag::List { ordered: #ordered }),
+                    Tag::Item => quote!(::papyrus_lib::Tag::Item),
+                    Tag::Table => quote!(::papyrus_lib::Tag::Table),
+                    Tag::Row { header } => quote!(::papyrus_lib::Tag::Row { header: #header }),
+                    Tag::Cell => quote!(::papyrus_lib::Tag::Cell),
+                    Tag::Close(kind) => {
+                        let kind = format_ident!("{kind:?}");
+                        quote!(::papyrus_lib::Tag::Close(::papyrus_lib::BlockKind::#kind))
+                    }
+                    Tag::Custom { .. } | Tag::UserKey(_) | Tag::ArticleKey(_) => {
+                        unreachable!("tokenized without custom tags or payload patterns")
+                    }
+                    tag => {
+                        let message = format!("[{tag}] isn't supported in papyrus markup literals");
+                        return Err(syn::Error::new(lit.span(), message));
+                    }
+                };
+                quote!(::papyrus_lib::Token::Tag(#tag))
+            }
+            Token::LineBreak => quote!(::papyrus_lib::Token::LineBreak),
+            Token::ParagraphBreak => quote!(::papyrus_lib::Token::ParagraphBreak),
+            Token::Highlight(text) => quote! {
+                ::papyrus_lib::Token::Highlight(::papyrus_lib::__private::String::from(#text))
+            },
+        });
+    }
+
+    Ok(quote! {
+        ::papyrus_lib::__private::vec![#(#tokens),*]
+    })
+}
+
+// Replaces every `{name}` tag payload with a placeholder id the tokenizer accepts and returns,
+// per tag in source order, the variable that has to be interpolated into it.
+fn extract_interpolations(lit: &LitStr) -> syn::Result<(String, Vec<Option<Ident>>)> {
+    let value = lit.value();
+    let mut source = String::with_capacity(value.len());
+    let mut interpolations = Vec::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                source.push(c);
+                if let Some(&escaped @ ('[' | ']' | '\\')) = chars.peek() {
+                    source.push(escaped);
+                    chars.next();
+                }
+            }
+            '[' => {
+                let body = chars.by_ref().take_while(|c| *c != ']').collect::<String>();
+                let interpolation = match (body.find('{'), body.strip_suffix('}')) {
+                    (Some(start), Some(rest)) => {
+                        let name = &rest[start + 1..];
+                        let ident = syn::parse_str::<Ident>(name).map_err(|_| {
+                            syn::Error::new(
+                                lit.span(),
+                                format!("`{name}` is not a valid variable name to interpolate"),
+                            )
+                        })?;
+                        source.push('[');
+                        source.push_str(&body[..start]);
+                        source.push_str("0]");
+                        Some(ident)
+                    }
+                    _ => {
+                        source.push('[');
+                        source.push_str(&body);
+                        source.push(']');
+                        None
+                    }
+                };
+                interpolations.push(interpolation);
+            }
+            c => source.push(c),
+        }
+    }
+
+    Ok((source, interpolations))
+}
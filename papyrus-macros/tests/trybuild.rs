@@ -0,0 +1,7 @@
+#[test]
+pub fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass.rs");
+    t.compile_fail("tests/ui/unknown_tag.rs");
+    t.compile_fail("tests/ui/bad_interpolation.rs");
+}
@@ -0,0 +1,5 @@
+use papyrus_macros::papyrus;
+
+fn main() {
+    let _ = papyrus!("[user:{1uid}]");
+}
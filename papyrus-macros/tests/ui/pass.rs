@@ -0,0 +1,22 @@
+use papyrus_lib::{Tag, Token};
+use papyrus_macros::papyrus;
+
+fn main() {
+    assert_eq!(
+        papyrus!("[user:0] welcome to [article:1]"),
+        vec![
+            Token::Tag(Tag::User(0)),
+            Token::Text(" welcome to ".to_string()),
+            Token::Tag(Tag::Article(1)),
+        ]
+    );
+
+    let uid = 42;
+    assert_eq!(
+        papyrus!(r"[user:{uid}] hi {not interpolated} \[user:{uid}\]"),
+        vec![
+            Token::Tag(Tag::User(42)),
+            Token::Text(" hi {not interpolated} [user:{uid}]".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,31 @@
+//! Uses the library from a `no_std` crate, to check it still builds without `std`.
+//!
+//! Building it for the host links `std` anyway, build it for a target without one instead:
+//! `cargo build -p papyrus_no_std --target thumbv7em-none-eabihf`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+
+use papyrus_lib::{binary, render_plain, tags, Resolved, Tag, Token, TokenIter, TokenizeErr};
+use papyrus_macros::papyrus;
+
+pub fn mentions(input: &str) -> Result<Vec<usize>, TokenizeErr> {
+    let tokens = TokenIter::new(input).collect::<Result<Vec<_>, _>>()?;
+    Ok(tags(&tokens)
+        .filter_map(|tag| match tag {
+            Tag::User(id) => Some(*id),
+            _ => None,
+        })
+        .collect())
+}
+
+pub fn plain(input: &[Token]) -> String {
+    render_plain(input, &|_: &Tag| None::<Resolved>)
+}
+
+pub fn greeting(user: usize) -> Vec<u8> {
+    binary::encode(&papyrus!("hi [user:{user}]!"))
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use papyrus_lib::{Diagnostic, Document, Resolved, Severity, Span, Tag, Token, TokenizeErr};
+use pyo3::{create_exception, exceptions::PyValueError, prelude::*, types::PyDict};
+
+create_exception!(
+    papyrus,
+    ParseError,
+    PyValueError,
+    "Raised on malformed markup, with the `line`, `column` and `span` of the first malformed tag."
+);
+
+/// Tokenizes the input into `{"kind", "payload", "span"}` dicts.
+///
+/// Kinds are `text`, `line_break`, `paragraph_break` or the name of a tag, like `user`,
+/// whose payload is what follows its colon, `None` if it has none. Spans are char offsets, so
+/// `text[start:end]` is the token's source.
+#[pyfunction]
+fn tokenize<'py>(py: Python<'py>, text: &str) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let document = parse(py, text)?;
+    let chars = Chars::new(text);
+    document
+        .tokens()
+        .iter()
+        .zip(document.spans())
+        .map(|(token, span)| {
+            let (kind, payload) = match token {
+                Token::Text(text) | Token::Highlight(text) => ("text".into(), Some(text.clone())),
+                Token::Tag(tag) => {
+                    let tag = tag.to_string();
+                    match tag.split_once(':') {
+                        Some((name, payload)) => (name.into(), Some(payload.into())),
+                        None => (tag, None),
+                    }
+                }
+                Token::LineBreak => ("line_break".into(), None),
+                Token::ParagraphBreak => ("paragraph_break".into(), None),
+            };
+            let dict = PyDict::new(py);
+            dict.set_item("kind", kind)?;
+            dict.set_item("payload", payload)?;
+            dict.set_item("span", chars.span(*span))?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+/// Lists every malformed tag of the input as `{"line", "column", "span", "severity",
+/// "message"}` dicts, with spans in chars.
+#[pyfunction]
+fn validate<'py>(py: Python<'py>, text: &str) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let chars = Chars::new(text);
+    papyrus_lib::validate(text)
+        .iter()
+        .map(|diagnostic| {
+            let dict = PyDict::new(py);
+            dict.set_item("line", diagnostic.line)?;
+            dict.set_item("column", diagnostic.column)?;
+            dict.set_item("span", chars.span(diagnostic.span))?;
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            dict.set_item("severity", severity)?;
+            dict.set_item("message", diagnostic.error.to_string())?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+/// Renders the input as HTML, looking tags up in `resolver`, a dict from tags like `"user:5"`
+/// to `{"label", "url"}` dicts, where the url is optional.
+#[pyfunction]
+#[pyo3(signature = (text, resolver = None))]
+fn render_html(
+    py: Python<'_>,
+    text: &str,
+    resolver: Option<HashMap<String, HashMap<String, Option<String>>>>,
+) -> PyResult<String> {
+    let mut entries = HashMap::new();
+    for (tag, mut entry) in resolver.unwrap_or_default() {
+        let Some(Some(label)) = entry.remove("label") else {
+            return Err(PyValueError::new_err(format!("{tag} has no label")));
+        };
+        let url = entry.remove("url").flatten();
+        entries.insert(tag, Resolved { label, url });
+    }
+    let document = parse(py, text)?;
+    let resolver = |tag: &Tag| entries.get(&tag.to_string()).cloned();
+    Ok(papyrus_lib::render_html(document.tokens(), &resolver))
+}
+
+fn parse(py: Python<'_>, text: &str) -> PyResult<Document> {
+    Document::parse(text).map_err(|e| parse_error(py, text, &e))
+}
+
+// Positions only have the line, so the rest comes from the diagnostic of the tag.
+fn parse_error(py: Python<'_>, text: &str, error: &TokenizeErr) -> PyErr {
+    let diagnostics = papyrus_lib::validate(text);
+    let diagnostic = diagnostics.iter().find(|d| d.severity == Severity::Error);
+    let err = ParseError::new_err(diagnostic.map_or(error.to_string(), Diagnostic::to_string));
+    let value = err.value(py);
+    let set = value
+        .setattr("line", diagnostic.map(|d| d.line))
+        .and_then(|()| value.setattr("column", diagnostic.map(|d| d.column)))
+        .and_then(|()| value.setattr("span", diagnostic.map(|d| Chars::new(text).span(d.span))));
+    match set {
+        Ok(()) => err,
+        Err(e) => e,
+    }
+}
+
+// Turns byte offsets into char offsets, which Python strings are indexed by.
+struct Chars(Vec<usize>);
+
+impl Chars {
+    fn new(text: &str) -> Self {
+        let mut offsets = vec![0; text.len() + 1];
+        for (chars, (start, c)) in text.char_indices().enumerate() {
+            offsets[start + 1..=start + c.len_utf8()].fill(chars + 1);
+        }
+        Self(offsets)
+    }
+
+    fn span(&self, span: Span) -> (usize, usize) {
+        (self.0[span.start], self.0[span.end])
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "papyrus")]
+fn papyrus_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(tokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(validate, m)?)?;
+    m.add_function(wrap_pyfunction!(render_html, m)?)?;
+    m.add("ParseError", m.py().get_type::<ParseError>())?;
+    Ok(())
+}
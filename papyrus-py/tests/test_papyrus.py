@@ -0,0 +1,73 @@
+import json
+from pathlib import Path
+
+import pytest
+
+import papyrus
+
+# The fixtures of the papyrus binary's tests.
+FIXTURES = Path(__file__).parents[2] / "tests" / "fixtures"
+
+
+def fixture(name):
+    return (FIXTURES / name).read_text()
+
+
+def test_tokenize():
+    text = fixture("valid.txt")
+    tokens = papyrus.tokenize(text)
+    assert tokens == [
+        {"kind": "text", "payload": "Thanks ", "span": (0, 7)},
+        {"kind": "user", "payload": "1", "span": (7, 15)},
+        {"kind": "text", "payload": ", see ", "span": (15, 21)},
+        {"kind": "article", "payload": "9", "span": (21, 32)},
+        {"kind": "text", "payload": "!\n", "span": (32, 34)},
+    ]
+    for token in tokens[1::2]:
+        start, end = token["span"]
+        assert text[start:end] == f"[{token['kind']}:{token['payload']}]"
+
+
+def test_tokenize_spans_in_chars():
+    text = "für [user:1] [spoiler]"
+    assert [token["span"] for token in papyrus.tokenize(text)] == [
+        (0, 4),
+        (4, 12),
+        (12, 13),
+        (13, 22),
+    ]
+    assert papyrus.tokenize(text)[-1]["payload"] is None
+
+
+def test_validate():
+    assert papyrus.validate(fixture("valid.txt")) == []
+    diagnostics = papyrus.validate(fixture("invalid.txt"))
+    assert [(d["line"], d["column"], d["severity"]) for d in diagnostics] == [
+        (2, 5, "error"),
+        (2, 17, "error"),
+    ]
+    assert diagnostics[0]["message"] == "unknown tag [usr:2]"
+
+
+def test_parse_error():
+    text = fixture("invalid.txt")
+    with pytest.raises(papyrus.ParseError) as error:
+        papyrus.tokenize(text)
+    assert isinstance(error.value, ValueError)
+    assert (error.value.line, error.value.column) == (2, 5)
+    start, end = error.value.span
+    assert text[start:end] == "[usr:2]"
+    assert str(error.value) == "2:5: unknown tag [usr:2]"
+
+
+def test_render_html():
+    resolver = json.loads(fixture("resolvers.json"))
+    assert papyrus.render_html(fixture("valid.txt"), resolver) == (
+        'Thanks <a class="mention" data-user-id="1" href="/u/alice">@alice</a>, see '
+        '<span class="article" data-article-id="9">Launch day</span>!\n'
+    )
+    assert papyrus.render_html("[user:2]") == (
+        '<span class="mention unresolved" data-user-id="2">[user:2]</span>'
+    )
+    with pytest.raises(ValueError):
+        papyrus.render_html("[user:1]", {"user:1": {"url": "/u/alice"}})
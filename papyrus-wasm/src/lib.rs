@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use papyrus_lib::{Document, Resolved, Tag};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// Tokenizes the input, resolving to the tokens in their serde form, e.g.
+/// `[{"Text":"hi "},{"Tag":"user:5"}]`.
+#[wasm_bindgen]
+pub fn tokenize(input: &str) -> Result<JsValue, JsError> {
+    let document = Document::parse(input).map_err(|e| JsError::new(&e.to_string()))?;
+    to_js(document.tokens())
+}
+
+/// Resolves to a `{start, end, line, column, message}` object for every malformed tag.
+#[wasm_bindgen]
+pub fn validate(input: &str) -> Result<JsValue, JsError> {
+    let diagnostics = papyrus_lib::validate(input)
+        .into_iter()
+        .map(|d| JsDiagnostic {
+            start: d.span.start,
+            end: d.span.end,
+            line: d.line,
+            column: d.column,
+            message: d.error.to_string(),
+        })
+        .collect::<Vec<_>>();
+    to_js(&diagnostics)
+}
+
+/// Renders the input as HTML, looking tags up in `resolver_map`, an object from tags like
+/// `"user:5"` to `{label, url}`.
+#[wasm_bindgen]
+pub fn render_html(input: &str, resolver_map: JsValue) -> Result<String, JsError> {
+    let resolver_map: HashMap<String, Entry> = if resolver_map.is_undefined() {
+        HashMap::new()
+    } else {
+        serde_wasm_bindgen::from_value(resolver_map).map_err(|e| JsError::new(&e.to_string()))?
+    };
+    let document = Document::parse(input).map_err(|e| JsError::new(&e.to_string()))?;
+
+    let resolver = |tag: &Tag| {
+        resolver_map.get(&tag.to_string()).map(|entry| Resolved {
+            label: entry.label.clone(),
+            url: entry.url.clone(),
+        })
+    };
+    Ok(papyrus_lib::render_html(document.tokens(), &resolver))
+}
+
+fn to_js<T: Serialize + ?Sized>(value: &T) -> Result<JsValue, JsError> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[derive(Serialize)]
+struct JsDiagnostic {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    label: String,
+    url: Option<String>,
+}
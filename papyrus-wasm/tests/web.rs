@@ -0,0 +1,37 @@
+#![cfg(target_arch = "wasm32")]
+
+use papyrus_wasm::*;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+fn json(value: &JsValue) -> String {
+    js_sys::JSON::stringify(value).unwrap().into()
+}
+
+#[wasm_bindgen_test]
+pub fn tokenize_json() {
+    assert_eq!(
+        json(&tokenize("hi [user:5]").unwrap()),
+        r#"[{"Text":"hi "},{"Tag":"user:5"}]"#
+    );
+    assert!(tokenize("[usr:5]").is_err());
+}
+
+#[wasm_bindgen_test]
+pub fn validate_diagnostics() {
+    assert_eq!(
+        json(&validate("ok\n [usr:5]").unwrap()),
+        r#"[{"start":4,"end":11,"line":2,"column":2,"message":"unknown tag [usr:5]"}]"#
+    );
+}
+
+#[wasm_bindgen_test]
+pub fn render_with_map() {
+    let map = js_sys::JSON::parse(r#"{"user:1":{"label":"alice","url":"/u/alice"}}"#).unwrap();
+    assert_eq!(
+        render_html("hi [user:1]", map).unwrap(),
+        "hi <a class=\"mention\" data-user-id=\"1\" href=\"/u/alice\">@alice</a>"
+    );
+    assert!(render_html("[usr:1]", JsValue::UNDEFINED).is_err());
+    assert!(render_html("hi", JsValue::from_str("not a map")).is_err());
+}
@@ -0,0 +1,3 @@
+//! The papyrus markup, re-exported from [`papyrus_lib`], which is its only implementation.
+
+pub use papyrus_lib::*;
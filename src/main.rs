@@ -1 +1,163 @@
-fn main() {}
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use papyrus::{render_html, render_plain, validate, Document, Resolved, Severity, Tag};
+use serde::Deserialize;
+
+/// Tokenizes, validates and renders papyrus markup, from a file or stdin.
+#[derive(Parser)]
+#[command(name = "papyrus")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the tokens of the input, one per line.
+    Tokenize {
+        file: Option<PathBuf>,
+        /// Prints the tokens as a JSON array instead.
+        #[arg(long)]
+        json: bool,
+        /// Keeps malformed tags as text instead of failing.
+        #[arg(long)]
+        lenient: bool,
+    },
+    /// Lists the malformed tags of the input, failing if there are any.
+    Validate { file: Option<PathBuf> },
+    /// Renders the input.
+    Render {
+        file: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Format::Html)]
+        format: Format,
+        /// A JSON object mapping tags like `user:1` to a `label` and an optional `url`.
+        #[arg(long)]
+        resolver: Option<PathBuf>,
+        /// Keeps malformed tags as text instead of failing.
+        #[arg(long)]
+        lenient: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Html,
+    Plain,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    label: String,
+    url: Option<String>,
+}
+
+// Failures of the input are reported with exit code 1, those of reading it with 2.
+enum Error {
+    Input(String),
+    Io(String),
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Error::Input(message)) => {
+            eprintln!("{message}");
+            ExitCode::from(1)
+        }
+        Err(Error::Io(message)) => {
+            eprintln!("papyrus: {message}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Tokenize {
+            file,
+            json,
+            lenient,
+        } => {
+            let doc = parse(file.as_deref(), lenient)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(doc.tokens()).unwrap());
+            } else {
+                for token in doc.tokens() {
+                    println!("{token:?}");
+                }
+            }
+        }
+        Command::Validate { file } => {
+            let input = read(file.as_deref())?;
+            let name = name(file.as_deref());
+            let diagnostics = validate(&input);
+            for diagnostic in &diagnostics {
+                println!("{name}:{diagnostic}");
+            }
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            if errors > 0 {
+                return Err(Error::Input(format!("{name}: {errors} malformed tags")));
+            }
+        }
+        Command::Render {
+            file,
+            format,
+            resolver,
+            lenient,
+        } => {
+            let entries: HashMap<String, Entry> = match &resolver {
+                Some(path) => {
+                    let json = fs::read_to_string(path)
+                        .map_err(|e| Error::Io(format!("{}: {e}", path.display())))?;
+                    serde_json::from_str(&json)
+                        .map_err(|e| Error::Io(format!("{}: {e}", path.display())))?
+                }
+                None => HashMap::new(),
+            };
+            let resolver = |tag: &Tag| {
+                entries.get(&tag.to_string()).map(|entry| Resolved {
+                    label: entry.label.clone(),
+                    url: entry.url.clone(),
+                })
+            };
+            let doc = parse(file.as_deref(), lenient)?;
+            match format {
+                Format::Html => print!("{}", render_html(doc.tokens(), &resolver)),
+                Format::Plain => print!("{}", render_plain(doc.tokens(), &resolver)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn name(file: Option<&Path>) -> String {
+    file.map_or("<stdin>".into(), |file| file.display().to_string())
+}
+
+fn read(file: Option<&Path>) -> Result<String, Error> {
+    let mut input = String::new();
+    match file {
+        Some(file) => fs::read_to_string(file).map(|read| input = read),
+        None => io::stdin().read_to_string(&mut input).map(drop),
+    }
+    .map_err(|e| Error::Io(format!("{}: {e}", name(file))))?;
+    Ok(input)
+}
+
+fn parse(file: Option<&Path>, lenient: bool) -> Result<Document, Error> {
+    let input = read(file)?;
+    if lenient {
+        return Ok(Document::parse_lossy(&input));
+    }
+    Document::parse(&input).map_err(|e| Error::Input(format!("{}: {e}", name(file))))
+}
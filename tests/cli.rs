@@ -0,0 +1,101 @@
+use assert_cmd::Command;
+
+fn papyrus() -> Command {
+    let mut command = Command::cargo_bin("papyrus").unwrap();
+    command.current_dir("tests/fixtures");
+    command
+}
+
+#[test]
+pub fn cli_tokenize() {
+    papyrus()
+        .args(["tokenize", "valid.txt"])
+        .assert()
+        .success()
+        .stdout(
+            "Text(\"Thanks \")\nTag(User(1))\nText(\", see \")\nTag(Article(9))\nText(\"!\\n\")\n",
+        );
+
+    let output = papyrus()
+        .args(["tokenize", "--json"])
+        .write_stdin("hi [user:1]")
+        .output()
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([{ "Text": "hi " }, { "Tag": "user:1" }])
+    );
+}
+
+#[test]
+pub fn cli_validate() {
+    papyrus()
+        .args(["validate", "valid.txt"])
+        .assert()
+        .success()
+        .stdout("");
+    papyrus()
+        .args(["validate", "invalid.txt"])
+        .assert()
+        .code(1)
+        .stdout("invalid.txt:2:5: unknown tag [usr:2]\ninvalid.txt:2:17: unknown tag [article:x]\n")
+        .stderr("invalid.txt: 2 malformed tags\n");
+    papyrus().args(["validate", "missing.txt"]).assert().code(2);
+}
+
+#[test]
+pub fn cli_validate_warnings() {
+    let warning = "<stdin>:1:3: warning: closing bracket without a tag opening it\n";
+    papyrus()
+        .arg("validate")
+        .write_stdin("a ] b")
+        .assert()
+        .success()
+        .stdout(warning)
+        .stderr("");
+    papyrus()
+        .arg("validate")
+        .write_stdin("a ] [usr:1]")
+        .assert()
+        .code(1)
+        .stdout(format!("{warning}<stdin>:1:5: unknown tag [usr:1]\n"))
+        .stderr("<stdin>: 1 malformed tags\n");
+}
+
+#[test]
+pub fn cli_render() {
+    papyrus()
+        .args(["render", "valid.txt", "--resolver", "resolvers.json"])
+        .assert()
+        .success()
+        .stdout(
+            "Thanks <a class=\"mention\" data-user-id=\"1\" href=\"/u/alice\">@alice</a>, see \
+             <span class=\"article\" data-article-id=\"9\">Launch day</span>!\n",
+        );
+    papyrus()
+        .args([
+            "render",
+            "--format",
+            "plain",
+            "--resolver",
+            "resolvers.json",
+        ])
+        .write_stdin("[user:1] and [user:2]")
+        .assert()
+        .success()
+        .stdout("@alice and [user:2]");
+}
+
+#[test]
+pub fn cli_lenient() {
+    papyrus()
+        .args(["render", "invalid.txt", "--format", "plain"])
+        .assert()
+        .code(1);
+    papyrus()
+        .args(["render", "invalid.txt", "--format", "plain", "--lenient"])
+        .assert()
+        .success()
+        .stdout("fine [user:1]\nbad [usr:2] and [article:x]\n");
+}